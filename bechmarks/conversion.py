@@ -0,0 +1,34 @@
+"""
+Per-insert cost of converting keys of each type into tree elements. Every
+key is inserted into an empty tree so the tree itself adds a constant cost.
+"""
+
+import random
+import time
+
+from tree_collections.tree_collections import PyBTreeMap
+
+N = 1_000_000
+
+key_fns = {
+    "int": lambda: random.randint(0, 100_000_000),
+    "float": random.random,
+    "str": lambda: str(random.random()),
+    "bool": lambda: random.random() < 0.5,
+    "2-tuple": lambda: (random.randint(0, 100), random.random()),
+    "3-tuple": lambda: (random.randint(0, 100), random.random(), "a"),
+    "list": lambda: [random.randint(0, 100), random.random()],
+    "None": lambda: None,
+}
+
+for name, key_fn in key_fns.items():
+    keys = [key_fn() for _ in range(N)]
+    tree = PyBTreeMap()
+
+    t0 = time.perf_counter()
+    for key in keys:
+        tree.insert(key, None)
+        tree.clear()
+    t = time.perf_counter() - t0
+
+    print(f"{name}: {t / N * 1e9:.0f} ns per insert")
@@ -66,20 +66,29 @@ def test_tree_collections(key_fn, value_fn):
     return time.time() - t0
 
 
-ts = {
-    test_sortedcollections: [],
-    test_tree_collections: [],
-}
+def test_tree_collections_typed(key_fn, value_fn):
+    # keys of a single type are stored natively
+    key_type = type(key_fn())
+    t0 = time.time()
+    tree = tree_collections.TreeDict(key_type=key_type)
+    for _ in range(N):
+        tree[key_fn()] = value_fn()
+
+    return time.time() - t0
+
+
+test_fns = [test_sortedcollections, test_tree_collections, test_tree_collections_typed]
+ts = {test_fn: [] for test_fn in test_fns}
 
 for key_fn, value_fn in combinations:
-    for test_fn in [test_sortedcollections, test_tree_collections]:
+    for test_fn in test_fns:
         t = test_fn(key_fn, value_fn)
         ts[test_fn].append((key_fn, value_fn, t))
         print(
             f"[{test_fn.__name__}]({key_fn.__name__}, {value_fn.__name__}): {t:.3f} seconds"
         )
 
-for test_fn in [test_sortedcollections, test_tree_collections]:
+for test_fn in test_fns:
     t = sum(t for _, _, t in ts[test_fn])
     print(f"{test_fn.__name__}: {t:.3f} seconds")
 
@@ -89,6 +98,7 @@ import matplotlib.pyplot as plt
 fig, ax = plt.subplots()
 
 times_tree_collections = [t for _, _, t in ts[test_tree_collections]]
+times_tree_collections_typed = [t for _, _, t in ts[test_tree_collections_typed]]
 times_sortedcollections = [t for _, _, t in ts[test_sortedcollections]]
 # remove 'random_' prefix
 labels = [
@@ -97,16 +107,22 @@ labels = [
 ]
 
 x = np.arange(len(labels))  # the label locations
-width = 0.35  # the width of the bars
+width = 0.25  # the width of the bars
 
 rects1 = ax.bar(
-    x - width / 2,
+    x - width,
     times_tree_collections,
     width,
     label="BTreeMap",
 )
 rects2 = ax.bar(
-    x + width / 2,
+    x,
+    times_tree_collections_typed,
+    width,
+    label="BTreeMap (key_type)",
+)
+rects3 = ax.bar(
+    x + width,
     times_sortedcollections,
     width,
     label="SortedDict",
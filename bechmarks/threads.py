@@ -0,0 +1,31 @@
+"""
+Throughput of bulk construction and serialization from several threads.
+Native keys are sorted and encoded with the GIL released, so the threads
+run in parallel.
+"""
+
+import random
+import time
+from concurrent.futures import ThreadPoolExecutor
+
+import tree_collections
+
+N = 1_000_000
+N_TASKS = 8
+
+
+def build_and_encode(keys):
+    tree = tree_collections.TreeDict(zip(keys, keys))
+    data = tree.to_bytes()
+    tree_collections.TreeDict.from_bytes(data)
+
+
+inputs = [[random.randint(0, 100_000_000) for _ in range(N)] for _ in range(N_TASKS)]
+
+for n_threads in [1, 2, 4, 8]:
+    with ThreadPoolExecutor(n_threads) as executor:
+        t0 = time.time()
+        list(executor.map(build_and_encode, inputs))
+        t = time.time() - t0
+
+    print(f"{n_threads} threads: {t:.3f} seconds, {N_TASKS / t:.2f} trees/second")
@@ -0,0 +1,114 @@
+//! Bytes per entry of a `BTreeMap` keyed by `Elem`, against the layout it had
+//! before strings were stored inline and tuples in boxed slices.
+//!
+//!     cargo bench --bench memory
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tree_collections::elem::Elem;
+
+const N: usize = 100_000;
+
+/// Counts the bytes currently allocated.
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// `Elem` as it was laid out before, without the `PyObj` variant.
+#[allow(dead_code)]
+#[derive(PartialEq, PartialOrd)]
+enum LegacyElem {
+    Float(f64),
+    Int(i64),
+    String(String),
+    TwoTuple(Box<LegacyElem>, Box<LegacyElem>),
+    Tuple(Vec<LegacyElem>),
+    Vec(Vec<LegacyElem>),
+    PyNone,
+}
+
+impl Eq for LegacyElem {}
+
+#[allow(clippy::derive_ord_xor_partial_ord)]
+impl Ord for LegacyElem {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // the benchmark keys are never NaN
+        self.partial_cmp(other).unwrap()
+    }
+}
+
+/// Bytes allocated per entry by a map of `n` keys built with `key`.
+fn bytes_per_entry<K: Ord>(key: impl Fn(usize) -> K) -> f64 {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let map = (0..N).map(|i| (key(i), i)).collect::<BTreeMap<_, _>>();
+    let after = ALLOCATED.load(Ordering::Relaxed);
+    drop(map);
+    (after - before) as f64 / N as f64
+}
+
+fn report(name: &str, legacy: f64, compact: f64) {
+    println!(
+        "{name:<12} {legacy:>8.1} {compact:>8.1} {:>7.1}%",
+        (compact - legacy) / legacy * 100.0
+    );
+}
+
+fn main() {
+    println!(
+        "size_of: legacy {} bytes, compact {} bytes\n",
+        std::mem::size_of::<LegacyElem>(),
+        std::mem::size_of::<Elem>()
+    );
+    println!(
+        "{:<12} {:>8} {:>8} {:>8}",
+        "key", "legacy", "compact", "change"
+    );
+
+    report(
+        "int",
+        bytes_per_entry(|i| LegacyElem::Int(i as i64)),
+        bytes_per_entry(|i| Elem::Int(i as i64)),
+    );
+    report(
+        "short str",
+        bytes_per_entry(|i| LegacyElem::String(format!("key{i:08}"))),
+        bytes_per_entry(|i| Elem::String(format!("key{i:08}").into())),
+    );
+    report(
+        "long str",
+        bytes_per_entry(|i| LegacyElem::String(format!("a longer key {i:08}"))),
+        bytes_per_entry(|i| Elem::String(format!("a longer key {i:08}").into())),
+    );
+    report(
+        "pair",
+        bytes_per_entry(|i| {
+            let a = LegacyElem::Int(i as i64);
+            let b = LegacyElem::String(format!("{i}"));
+            LegacyElem::TwoTuple(Box::new(a), Box::new(b))
+        }),
+        bytes_per_entry(|i| Elem::pair(Elem::Int(i as i64), Elem::String(format!("{i}").into()))),
+    );
+    report(
+        "tuple",
+        bytes_per_entry(|i| {
+            LegacyElem::Tuple((0..3).map(|j| LegacyElem::Int((i + j) as i64)).collect())
+        }),
+        bytes_per_entry(|i| Elem::tuple((0..3).map(|j| Elem::Int((i + j) as i64)).collect())),
+    );
+}
@@ -0,0 +1,198 @@
+use crate::elem::Elem;
+use crate::number::{self, Sum};
+#[cfg(feature = "python")]
+use pyo3::{exceptions, prelude::*, types::PyString};
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+
+// Aggregates a `PersistentMap` maintains over its values. Every node keeps
+// the summary of its subtree, so the aggregate of any key range is combined
+// from O(log n) nodes. `count` needs no summary since the nodes already keep
+// the size of their subtrees.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AggregateKind {
+    Sum,
+    Min,
+    Max,
+    Count,
+}
+
+#[cfg(feature = "python")]
+impl FromPyObject<'_> for AggregateKind {
+    fn extract_bound(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let name = ob.downcast::<PyString>()?.to_cow()?;
+        match name.as_ref() {
+            "sum" => Ok(AggregateKind::Sum),
+            "min" => Ok(AggregateKind::Min),
+            "max" => Ok(AggregateKind::Max),
+            "count" => Ok(AggregateKind::Count),
+            other => Err(PyErr::new::<exceptions::PyValueError, _>(format!(
+                "aggregate must be 'sum', 'min', 'max' or 'count', got '{other}'"
+            ))),
+        }
+    }
+}
+
+impl AggregateKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AggregateKind::Sum => "sum",
+            AggregateKind::Min => "min",
+            AggregateKind::Max => "max",
+            AggregateKind::Count => "count",
+        }
+    }
+
+    /// Fails unless `value` can be aggregated, `count` accepts any value while
+    /// the other kinds require an int or a float.
+    pub fn check(&self, value: &Elem) -> Result<(), NotANumber> {
+        match (self, value) {
+            (AggregateKind::Count, _) | (_, Elem::Int(_) | Elem::Float(_)) => Ok(()),
+            _ => Err(NotANumber(*self)),
+        }
+    }
+}
+
+/// A value given to an aggregate of the kind it holds that needs a number.
+#[derive(Debug)]
+pub struct NotANumber(pub AggregateKind);
+
+impl fmt::Display for NotANumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "values of a '{}' aggregate map must be int or float",
+            self.0.name()
+        )
+    }
+}
+
+impl std::error::Error for NotANumber {}
+
+#[cfg(feature = "python")]
+impl From<NotANumber> for PyErr {
+    fn from(err: NotANumber) -> PyErr {
+        PyErr::new::<exceptions::PyTypeError, _>(err.to_string())
+    }
+}
+
+/// The aggregate of a subtree, kept in its root.
+#[derive(Clone)]
+pub enum Summary {
+    Sum(Box<Sum>),
+    // the entry holding the min or max value
+    Extreme(Arc<(Elem, Elem)>),
+}
+
+/// The aggregate of a key range.
+pub enum Aggregate {
+    Sum(Sum),
+    // `None` for an empty range
+    Extreme(Option<Elem>),
+    Count(usize),
+}
+
+#[cfg(feature = "python")]
+impl Aggregate {
+    pub fn to_object(&self, py: Python<'_>) -> PyResult<PyObject> {
+        let output = match self {
+            Aggregate::Sum(sum) => match sum.to_int() {
+                Some(x) => x.into_pyobject(py)?.into_any().unbind(),
+                None => sum.to_f64().into_pyobject(py)?.into_any().unbind(),
+            },
+            Aggregate::Extreme(Some(x)) => x.to_pyobject(py),
+            Aggregate::Extreme(None) => py.None(),
+            Aggregate::Count(x) => x.into_pyobject(py)?.into_any().unbind(),
+        };
+        Ok(output)
+    }
+}
+
+/// Combines the summaries of subtrees and single entries, given in key
+/// order, into the summary of a node or the aggregate of a range.
+pub struct Fold {
+    kind: AggregateKind,
+    sum: Sum,
+    extreme: Option<Arc<(Elem, Elem)>>,
+    count: usize,
+}
+
+impl Fold {
+    pub fn new(kind: AggregateKind) -> Self {
+        Fold {
+            kind,
+            sum: Sum::default(),
+            extreme: None,
+            count: 0,
+        }
+    }
+
+    /// Adds a subtree of `len` entries.
+    pub fn subtree(&mut self, summary: Option<&Summary>, len: usize) {
+        self.count += len;
+        match summary {
+            Some(Summary::Sum(sum)) => self.sum.merge(sum),
+            Some(Summary::Extreme(entry)) => self.extreme(entry),
+            None => (),
+        }
+    }
+
+    pub fn entry(&mut self, entry: &Arc<(Elem, Elem)>) {
+        self.count += 1;
+        match (self.kind, &entry.1) {
+            (AggregateKind::Sum, Elem::Int(x)) => self.sum.add_int(*x),
+            (AggregateKind::Sum, Elem::Float(x)) => self.sum.add_float(*x),
+            (AggregateKind::Min | AggregateKind::Max, _) => self.extreme(entry),
+            _ => (),
+        }
+    }
+
+    /// Keeps the first of equal values, a NaN wins over any other value so
+    /// that the result doesn't depend on the shape of the tree.
+    fn extreme(&mut self, entry: &Arc<(Elem, Elem)>) {
+        let current = match &self.extreme {
+            Some(current) => current,
+            None => {
+                self.extreme = Some(entry.clone());
+                return;
+            }
+        };
+        let ord = match (current.1.number(), entry.1.number()) {
+            (Some(a), Some(b)) => number::cmp(a, b),
+            _ => None,
+        };
+        let replace = match (ord, self.kind) {
+            (None, _) => !is_nan(&current.1),
+            (Some(Ordering::Greater), AggregateKind::Min) => true,
+            (Some(Ordering::Less), AggregateKind::Max) => true,
+            _ => false,
+        };
+        if replace {
+            self.extreme = Some(entry.clone());
+        }
+    }
+
+    pub fn into_summary(self) -> Option<Summary> {
+        match self.kind {
+            AggregateKind::Sum => Some(Summary::Sum(Box::new(self.sum))),
+            AggregateKind::Min | AggregateKind::Max => self.extreme.map(Summary::Extreme),
+            AggregateKind::Count => None,
+        }
+    }
+
+    pub fn finish(self) -> Aggregate {
+        match self.kind {
+            AggregateKind::Sum => Aggregate::Sum(self.sum),
+            AggregateKind::Min | AggregateKind::Max => {
+                Aggregate::Extreme(self.extreme.map(|entry| entry.1.clone()))
+            }
+            AggregateKind::Count => Aggregate::Count(self.count),
+        }
+    }
+}
+
+fn is_nan(value: &Elem) -> bool {
+    matches!(value, Elem::Float(x) if x.is_nan())
+}
@@ -0,0 +1,692 @@
+use crate::calendar::{self, AwareDateTime};
+use crate::elem::Elem;
+use crate::number::{Decimal, Fraction};
+#[cfg(feature = "python")]
+use pyo3::{exceptions, prelude::*};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// Binary format for trees of native `Elem` variants, readable without Python.
+//
+// header (20 bytes, little endian):
+//   magic b"TCOL" | version: u16 | kind: u8 | reserved: u8 | count: u64 | crc32: u32
+// body, `count` records of:
+//   map: key, value    set: elem    seq: elem, multiplicity (varint)
+// elems are a tag byte followed by their payload:
+//   0 None | 1 Int (zigzag varint) | 2 Float (f64) | 3 String (varint length, utf-8)
+//   4 2-tuple (2 elems) | 5 Tuple (varint length, elems) | 6 List (varint length, elems)
+//   7 Bool (u8) | 8 Bytes (varint length, bytes) | 9 Date (zigzag varint ordinal)
+//   10 DateTime (zigzag varint microseconds) | 11 AwareDateTime (zigzag varint utc
+//   microseconds, zigzag varint offset seconds) | 12 Decimal (varint length, utf-8)
+//   13 Fraction (zigzag varint numerator, varint denominator)
+//
+// Version 2 added tags 7 to 13, version 1 files are read as they were.
+//
+// The checksum covers the body, writers stream the body and patch it into the
+// header afterwards so memory use doesn't depend on the size of the tree.
+
+const MAGIC: &[u8; 4] = b"TCOL";
+const VERSION: u16 = 2;
+const HEADER_LEN: u64 = 20;
+const CRC_OFFSET: u64 = 16;
+// bounds the recursion on nested tuples and lists in untrusted input
+const MAX_DEPTH: usize = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    Map = 1,
+    Set = 2,
+    Seq = 3,
+}
+
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Map => "map",
+            Kind::Set => "set",
+            Kind::Seq => "seq",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    /// The input is not a valid tree in this format.
+    Invalid(String),
+    /// The tree holds an element the format can't represent.
+    Unserializable(String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::Io(err) => err.fmt(f),
+            FormatError::Invalid(msg) | FormatError::Unserializable(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<io::Error> for FormatError {
+    fn from(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            return FormatError::Invalid("unexpected end of input".to_string());
+        }
+        FormatError::Io(err)
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<FormatError> for PyErr {
+    fn from(err: FormatError) -> PyErr {
+        match err {
+            FormatError::Io(err) => err.into(),
+            FormatError::Invalid(msg) => PyErr::new::<exceptions::PyValueError, _>(msg),
+            FormatError::Unserializable(msg) => PyErr::new::<exceptions::PyTypeError, _>(msg),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, FormatError>;
+
+pub fn write_map<'a, W: Write + Seek>(
+    inner: W,
+    entries: impl IntoIterator<Item = (&'a Elem, &'a Elem), IntoIter: ExactSizeIterator>,
+) -> Result<W> {
+    let entries = entries.into_iter();
+    write_tree(inner, Kind::Map, entries.len(), |w| {
+        for (key, value) in entries {
+            w.write_elem(key)?;
+            w.write_elem(value)?;
+        }
+        Ok(())
+    })
+}
+
+pub fn write_set<W: Write + Seek>(inner: W, btree_set: &BTreeSet<Elem>) -> Result<W> {
+    write_tree(inner, Kind::Set, btree_set.len(), |w| {
+        btree_set.iter().try_for_each(|x| w.write_elem(x))
+    })
+}
+
+pub fn write_seq<W: Write + Seek>(inner: W, btree_map: &BTreeMap<Elem, usize>) -> Result<W> {
+    write_tree(inner, Kind::Seq, btree_map.len(), |w| {
+        for (elem, count) in btree_map.iter() {
+            w.write_elem(elem)?;
+            w.write_varint(*count as u64)?;
+        }
+        Ok(())
+    })
+}
+
+pub fn read_map<R: Read>(inner: R) -> Result<BTreeMap<Elem, Elem>> {
+    read_tree(inner, Kind::Map, |r, count| {
+        let mut items = Vec::with_capacity(count.min(1 << 16) as usize);
+        for _ in 0..count {
+            let key = r.read_elem(0)?;
+            let value = r.read_elem(0)?;
+            items.push((key, value));
+        }
+        Ok(items.into_iter().collect())
+    })
+}
+
+pub fn read_set<R: Read>(inner: R) -> Result<BTreeSet<Elem>> {
+    read_tree(inner, Kind::Set, |r, count| {
+        let mut items = Vec::with_capacity(count.min(1 << 16) as usize);
+        for _ in 0..count {
+            items.push(r.read_elem(0)?);
+        }
+        Ok(items.into_iter().collect())
+    })
+}
+
+/// Returns the multiplicity of every element along with their total.
+pub fn read_seq<R: Read>(inner: R) -> Result<(BTreeMap<Elem, usize>, usize)> {
+    read_tree(inner, Kind::Seq, |r, count| {
+        let mut items = Vec::with_capacity(count.min(1 << 16) as usize);
+        let mut length = 0usize;
+        for _ in 0..count {
+            let elem = r.read_elem(0)?;
+            let count = r.read_varint()? as usize;
+            if count == 0 {
+                return Err(FormatError::Invalid(
+                    "seq element with no occurrences".to_string(),
+                ));
+            }
+            length = length
+                .checked_add(count)
+                .ok_or_else(|| FormatError::Invalid("seq length overflows".to_string()))?;
+            items.push((elem, count));
+        }
+        Ok((items.into_iter().collect(), length))
+    })
+}
+
+/// Appends the encoding of a single elem to `buf`, for stores built on this format.
+pub fn encode_elem(buf: &mut Vec<u8>, elem: &Elem) -> Result<()> {
+    let mut writer = Writer {
+        inner: buf,
+        crc: Crc32::new(),
+    };
+    writer.write_elem(elem)
+}
+
+/// Decodes a single elem from the front of `bytes` and advances past it.
+pub fn decode_elem(bytes: &mut &[u8]) -> Result<Elem> {
+    let mut reader = Reader {
+        inner: bytes,
+        crc: Crc32::new(),
+    };
+    reader.read_elem(0)
+}
+
+/// Streams a tree to `path` with one of the `write_*` functions, removing the
+/// partially written file if it fails.
+pub fn save<P: AsRef<Path>>(
+    path: P,
+    write: impl FnOnce(BufWriter<File>) -> Result<BufWriter<File>>,
+) -> Result<()> {
+    let path = path.as_ref();
+    let output = File::create(path)
+        .map_err(FormatError::from)
+        .and_then(|file| write(BufWriter::new(file)))
+        .and_then(|writer| writer.into_inner().map_err(|err| err.into_error().into()))
+        .and_then(|file| file.sync_all().map_err(FormatError::from));
+
+    if output.is_err() {
+        let _ = fs::remove_file(path);
+    }
+    output
+}
+
+pub fn open<P: AsRef<Path>>(path: P) -> Result<BufReader<File>> {
+    Ok(BufReader::new(File::open(path)?))
+}
+
+fn write_tree<W: Write + Seek>(
+    mut inner: W,
+    kind: Kind,
+    count: usize,
+    body: impl FnOnce(&mut Writer<W>) -> Result<()>,
+) -> Result<W> {
+    let start = inner.stream_position()?;
+    inner.write_all(MAGIC)?;
+    inner.write_all(&VERSION.to_le_bytes())?;
+    inner.write_all(&[kind as u8, 0])?;
+    inner.write_all(&(count as u64).to_le_bytes())?;
+    inner.write_all(&0u32.to_le_bytes())?;
+
+    let mut writer = Writer {
+        inner,
+        crc: Crc32::new(),
+    };
+    body(&mut writer)?;
+    let Writer { mut inner, crc } = writer;
+
+    let end = inner.stream_position()?;
+    inner.seek(SeekFrom::Start(start + CRC_OFFSET))?;
+    inner.write_all(&crc.finish().to_le_bytes())?;
+    inner.seek(SeekFrom::Start(end))?;
+
+    Ok(inner)
+}
+
+fn read_tree<R: Read, T>(
+    mut inner: R,
+    kind: Kind,
+    body: impl FnOnce(&mut Reader<R>, u64) -> Result<T>,
+) -> Result<T> {
+    let mut header = [0u8; HEADER_LEN as usize];
+    inner.read_exact(&mut header)?;
+
+    if &header[..4] != MAGIC {
+        return Err(FormatError::Invalid(
+            "not a tree_collections file".to_string(),
+        ));
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if !(1..=VERSION).contains(&version) {
+        return Err(FormatError::Invalid(format!(
+            "unsupported format version {version}, expected at most {VERSION}"
+        )));
+    }
+    if header[6] != kind as u8 {
+        let found = [Kind::Map, Kind::Set, Kind::Seq]
+            .into_iter()
+            .find(|x| *x as u8 == header[6])
+            .map_or("unknown", |x| x.name());
+        return Err(FormatError::Invalid(format!(
+            "expected a {}, found a {found}",
+            kind.name()
+        )));
+    }
+    let count = u64::from_le_bytes(header[8..16].try_into().unwrap());
+    let checksum = u32::from_le_bytes(header[16..20].try_into().unwrap());
+
+    let mut reader = Reader {
+        inner,
+        crc: Crc32::new(),
+    };
+    let output = body(&mut reader, count)?;
+
+    if reader.crc.finish() != checksum {
+        return Err(FormatError::Invalid("checksum mismatch".to_string()));
+    }
+    if reader.inner.read(&mut [0u8])? != 0 {
+        return Err(FormatError::Invalid("trailing data after tree".to_string()));
+    }
+
+    Ok(output)
+}
+
+struct Writer<W: Write> {
+    inner: W,
+    crc: Crc32,
+}
+
+impl<W: Write> Writer<W> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.crc.update(bytes);
+        self.inner.write_all(bytes)?;
+        Ok(())
+    }
+
+    fn write_varint(&mut self, mut x: u64) -> Result<()> {
+        let mut buf = [0u8; 10];
+        let mut len = 0;
+        while x >= 0x80 {
+            buf[len] = (x as u8) | 0x80;
+            x >>= 7;
+            len += 1;
+        }
+        buf[len] = x as u8;
+        self.write_bytes(&buf[..=len])
+    }
+
+    fn write_zigzag(&mut self, x: i64) -> Result<()> {
+        self.write_varint(((x << 1) ^ (x >> 63)) as u64)
+    }
+
+    fn write_str(&mut self, bytes: &[u8]) -> Result<()> {
+        self.write_varint(bytes.len() as u64)?;
+        self.write_bytes(bytes)
+    }
+
+    fn write_elems(&mut self, tag: u8, elems: &[Elem]) -> Result<()> {
+        self.write_bytes(&[tag])?;
+        self.write_varint(elems.len() as u64)?;
+        elems.iter().try_for_each(|x| self.write_elem(x))
+    }
+
+    fn write_elem(&mut self, elem: &Elem) -> Result<()> {
+        match elem {
+            Elem::PyNone => self.write_bytes(&[0]),
+            Elem::Int(x) => {
+                self.write_bytes(&[1])?;
+                self.write_zigzag(*x)
+            }
+            Elem::Float(x) => {
+                self.write_bytes(&[2])?;
+                self.write_bytes(&x.to_le_bytes())
+            }
+            Elem::String(s) => {
+                self.write_bytes(&[3])?;
+                self.write_str(s.as_bytes())
+            }
+            Elem::TwoTuple(pair) => {
+                self.write_bytes(&[4])?;
+                self.write_elem(&pair[0])?;
+                self.write_elem(&pair[1])
+            }
+            Elem::Tuple(v) => self.write_elems(5, v),
+            Elem::Vec(v) => self.write_elems(6, v),
+            Elem::Bool(x) => self.write_bytes(&[7, *x as u8]),
+            Elem::Bytes(b) => {
+                self.write_bytes(&[8])?;
+                self.write_str(b)
+            }
+            Elem::Date(x) => {
+                self.write_bytes(&[9])?;
+                self.write_zigzag(*x as i64)
+            }
+            Elem::DateTime(x) => {
+                self.write_bytes(&[10])?;
+                self.write_zigzag(*x)
+            }
+            Elem::AwareDateTime(x) => {
+                self.write_bytes(&[11])?;
+                self.write_zigzag(x.utc)?;
+                self.write_zigzag(x.offset as i64)
+            }
+            Elem::Decimal(x) => {
+                self.write_bytes(&[12])?;
+                self.write_str(x.to_string().as_bytes())
+            }
+            Elem::Fraction(x) => {
+                self.write_bytes(&[13])?;
+                self.write_zigzag(x.num())?;
+                self.write_varint(x.den() as u64)
+            }
+            #[cfg(feature = "python")]
+            Elem::PyObj(obj) => Err(unserializable(obj)),
+        }
+    }
+}
+
+/// The error for a `PyObj`, which no format can store, naming its type.
+#[cfg(feature = "python")]
+pub(crate) fn unserializable(obj: &PyObject) -> FormatError {
+    let name = Python::with_gil(|py| {
+        obj.bind(py)
+            .get_type()
+            .name()
+            .map(|x| x.to_string())
+            .unwrap_or_default()
+    });
+    FormatError::Unserializable(format!(
+        "cannot serialize an element of type '{name}', only int, float, str, \
+         tuple, list, None, bool, bytes, date, datetime with a fixed offset, \
+         Decimal and Fraction are supported"
+    ))
+}
+
+struct Reader<R: Read> {
+    inner: R,
+    crc: Crc32,
+}
+
+impl<R: Read> Reader<R> {
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<()> {
+        self.inner.read_exact(buf)?;
+        self.crc.update(buf);
+        Ok(())
+    }
+
+    fn read_varint(&mut self) -> Result<u64> {
+        let mut x = 0u64;
+        for shift in (0..64).step_by(7) {
+            let mut byte = [0u8];
+            self.read_bytes(&mut byte)?;
+            x |= ((byte[0] & 0x7f) as u64) << shift;
+            if byte[0] & 0x80 == 0 {
+                return Ok(x);
+            }
+        }
+        Err(FormatError::Invalid("varint is too long".to_string()))
+    }
+
+    fn read_zigzag(&mut self) -> Result<i64> {
+        let x = self.read_varint()?;
+        Ok(((x >> 1) as i64) ^ -((x & 1) as i64))
+    }
+
+    fn read_str(&mut self) -> Result<Vec<u8>> {
+        let len = self.read_varint()?;
+        let mut buf = Vec::with_capacity(len.min(1 << 16) as usize);
+        (&mut self.inner).take(len).read_to_end(&mut buf)?;
+        if buf.len() as u64 != len {
+            return Err(FormatError::Invalid("unexpected end of input".to_string()));
+        }
+        self.crc.update(&buf);
+        Ok(buf)
+    }
+
+    fn read_elems(&mut self, depth: usize) -> Result<Vec<Elem>> {
+        let len = self.read_varint()?;
+        let mut elems = Vec::with_capacity(len.min(1 << 16) as usize);
+        for _ in 0..len {
+            elems.push(self.read_elem(depth + 1)?);
+        }
+        Ok(elems)
+    }
+
+    fn read_elem(&mut self, depth: usize) -> Result<Elem> {
+        if depth > MAX_DEPTH {
+            return Err(FormatError::Invalid(
+                "elements are nested too deeply".to_string(),
+            ));
+        }
+
+        let mut tag = [0u8];
+        self.read_bytes(&mut tag)?;
+        match tag[0] {
+            0 => Ok(Elem::PyNone),
+            1 => Ok(Elem::Int(self.read_zigzag()?)),
+            2 => {
+                let mut buf = [0u8; 8];
+                self.read_bytes(&mut buf)?;
+                Ok(Elem::Float(f64::from_le_bytes(buf)))
+            }
+            3 => {
+                let s = String::from_utf8(self.read_str()?)
+                    .map_err(|_| FormatError::Invalid("string is not valid utf-8".to_string()))?;
+                Ok(Elem::String(s.into()))
+            }
+            4 => {
+                let a = self.read_elem(depth + 1)?;
+                let b = self.read_elem(depth + 1)?;
+                Ok(Elem::pair(a, b))
+            }
+            5 => Ok(Elem::tuple(self.read_elems(depth)?)),
+            6 => Ok(Elem::list(self.read_elems(depth)?)),
+            7 => {
+                let mut buf = [0u8];
+                self.read_bytes(&mut buf)?;
+                match buf[0] {
+                    0 | 1 => Ok(Elem::Bool(buf[0] == 1)),
+                    _ => Err(invalid("bool")),
+                }
+            }
+            8 => Ok(Elem::bytes(self.read_str()?)),
+            9 => {
+                let x = i32::try_from(self.read_zigzag()?).ok();
+                match x.filter(|&x| calendar::is_valid_date(x)) {
+                    Some(x) => Ok(Elem::Date(x)),
+                    None => Err(invalid("date")),
+                }
+            }
+            10 => {
+                let x = self.read_zigzag()?;
+                match calendar::is_valid_datetime(x) {
+                    true => Ok(Elem::DateTime(x)),
+                    false => Err(invalid("datetime")),
+                }
+            }
+            11 => {
+                let utc = self.read_zigzag()?;
+                let offset = i32::try_from(self.read_zigzag()?).ok();
+                let offset = offset.filter(|&x| calendar::is_valid_offset(x));
+                let micros = offset.and_then(|x| (x as i64).checked_mul(1_000_000));
+                let local = micros.and_then(|x| utc.checked_add(x));
+                match (offset, local) {
+                    (Some(offset), Some(local)) if calendar::is_valid_datetime(local) => {
+                        Ok(Elem::AwareDateTime(Box::new(AwareDateTime { utc, offset })))
+                    }
+                    _ => Err(invalid("datetime")),
+                }
+            }
+            12 => {
+                let s = String::from_utf8(self.read_str()?).ok();
+                match s.as_deref().and_then(Decimal::parse) {
+                    Some(x) => Ok(Elem::Decimal(Box::new(x))),
+                    None => Err(invalid("decimal")),
+                }
+            }
+            13 => {
+                let num = self.read_zigzag()?;
+                let den = i64::try_from(self.read_varint()?).ok();
+                match den.and_then(|den| Fraction::new(num, den)) {
+                    Some(x) => Ok(Elem::Fraction(Box::new(x))),
+                    None => Err(invalid("fraction")),
+                }
+            }
+            tag => Err(FormatError::Invalid(format!("unknown element tag {tag}"))),
+        }
+    }
+}
+
+fn invalid(kind: &str) -> FormatError {
+    FormatError::Invalid(format!("invalid {kind}"))
+}
+
+// CRC-32 (IEEE), as used by zlib and png
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xedb8_8320
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    pub fn new() -> Self {
+        Crc32 { state: !0 }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            let index = (self.state ^ *byte as u32) & 0xff;
+            self.state = (self.state >> 8) ^ Crc32::TABLE[index as usize];
+        }
+    }
+
+    pub fn finish(&self) -> u32 {
+        !self.state
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Crc32::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn sample_map() -> BTreeMap<Elem, Elem> {
+        let values = vec![
+            Elem::PyNone,
+            Elem::Int(-3),
+            Elem::Float(f64::NAN),
+            Elem::String("héllo".into()),
+            Elem::pair(Elem::Int(1), Elem::PyNone),
+            Elem::tuple(vec![Elem::Int(1), Elem::Int(2), Elem::Int(3)]),
+            Elem::list(vec![Elem::list(vec![]), Elem::Float(0.5)]),
+            Elem::Bool(true),
+            Elem::bytes(vec![0, 255, 7]),
+            Elem::Date(738_000),
+            Elem::DateTime(63_800_000_000_123_456),
+            Elem::AwareDateTime(Box::new(AwareDateTime {
+                utc: 63_800_000_000_000_000,
+                offset: -19_800,
+            })),
+            Elem::Decimal(Box::new(Decimal::parse("-1.50E+7").unwrap())),
+            Elem::Fraction(Box::new(Fraction::new(-2, 6).unwrap())),
+        ];
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| (Elem::Int(i as i64 * 1_000_000_007 - 3), x))
+            .collect()
+    }
+
+    fn to_bytes(btree_map: &BTreeMap<Elem, Elem>) -> Vec<u8> {
+        write_map(Cursor::new(Vec::new()), btree_map)
+            .unwrap()
+            .into_inner()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let btree_map = sample_map();
+        let output = read_map(&to_bytes(&btree_map)[..]).unwrap();
+
+        assert_eq!(output.len(), btree_map.len());
+        for ((a, x), (b, y)) in output.iter().zip(btree_map.iter()) {
+            assert_eq!(a, b);
+            match (x, y) {
+                (Elem::Float(x), Elem::Float(y)) => assert_eq!(x.to_bits(), y.to_bits()),
+                (x, y) => assert_eq!(x, y),
+            }
+        }
+
+        let btree_set = (0..1000).map(|x| Elem::Int(x * x)).collect::<BTreeSet<_>>();
+        let bytes = write_set(Cursor::new(Vec::new()), &btree_set)
+            .unwrap()
+            .into_inner();
+        assert_eq!(read_set(&bytes[..]).unwrap(), btree_set);
+
+        let seq = BTreeMap::from([(Elem::Int(1), 3), (Elem::Int(2), 1)]);
+        let bytes = write_seq(Cursor::new(Vec::new()), &seq)
+            .unwrap()
+            .into_inner();
+        assert_eq!(read_seq(&bytes[..]).unwrap(), (seq, 4));
+    }
+
+    fn is_invalid<T>(output: Result<T>) -> bool {
+        matches!(output, Err(FormatError::Invalid(_)))
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        let bytes = to_bytes(&sample_map());
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(is_invalid(read_map(&corrupted[..])));
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(is_invalid(read_map(truncated)));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(is_invalid(read_map(&trailing[..])));
+
+        assert!(is_invalid(read_set(&bytes[..])));
+        assert!(is_invalid(read_map(&b"nope"[..])));
+
+        // a utc time that overflows once the offset is added
+        let mut aware = &[
+            11, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 2,
+        ][..];
+        assert!(is_invalid(decode_elem(&mut aware)));
+    }
+
+    #[test]
+    fn test_versions() {
+        let btree_map = BTreeMap::from([(Elem::Int(1), Elem::String("one".into()))]);
+        let mut bytes = to_bytes(&btree_map);
+        assert_eq!(bytes[4..6], 2u16.to_le_bytes());
+
+        bytes[4..6].copy_from_slice(&1u16.to_le_bytes());
+        assert_eq!(read_map(&bytes[..]).unwrap(), btree_map);
+
+        bytes[4..6].copy_from_slice(&3u16.to_le_bytes());
+        assert!(is_invalid(read_map(&bytes[..])));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::elem::{self, Elem};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyMapping, PySequence, PyTuple};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Converts a mapping or an iterable of `(key, value)` tuples into `Elem` pairs.
+pub fn extract_pairs(input: PyObject, py: Python) -> PyResult<Vec<(Elem, Elem)>> {
+    extract_pairs_with(input, py, |key| key.extract::<Elem>())
+}
+
+/// Like `extract_pairs`, with the keys converted by `extract_key`.
+pub fn extract_pairs_with<K>(
+    input: PyObject,
+    py: Python,
+    extract_key: impl Fn(&Bound<'_, PyAny>) -> PyResult<K>,
+) -> PyResult<Vec<(K, Elem)>> {
+    let input = input.bind(py);
+    // a mapping iterates over its keys, the pairs are its items
+    let input = match input.downcast::<PyMapping>() {
+        Ok(input) => input.items()?.into_any(),
+        Err(_) => input.clone(),
+    };
+
+    let mut items = Vec::with_capacity(len_hint(&input));
+    for x in input.try_iter()? {
+        let x = x?;
+        let x = x.downcast::<PyTuple>()?;
+        let (key, value) = match (x.get_item(0), x.get_item(1)) {
+            (Ok(key), Ok(value)) => (key, value),
+            _ => {
+                return Err(PyErr::new::<exceptions::PyTypeError, _>(
+                    "iterable of tuples must contain two elements",
+                ))
+            }
+        };
+        let elem_key = extract_key(&key)?;
+        let elem_value = value.extract::<Elem>()?;
+        items.push((elem_key, elem_value));
+    }
+
+    Ok(items)
+}
+
+/// Converts any iterable into `Elem`s.
+pub fn extract_elems(input: PyObject, py: Python) -> PyResult<Vec<Elem>> {
+    extract_elems_with(input, py, |x| x.extract::<Elem>())
+}
+
+/// Like `extract_elems`, with each item converted by `extract`.
+pub fn extract_elems_with<T>(
+    input: PyObject,
+    py: Python,
+    extract: impl Fn(&Bound<'_, PyAny>) -> PyResult<T>,
+) -> PyResult<Vec<T>> {
+    let input = input.bind(py);
+    let mut items = Vec::with_capacity(len_hint(input));
+    for x in input.try_iter()? {
+        items.push(extract(&x?)?);
+    }
+
+    Ok(items)
+}
+
+/// The length of `input` when it's a sequence, to size the output up front.
+fn len_hint(input: &Bound<'_, PyAny>) -> usize {
+    input
+        .downcast::<PySequence>()
+        .ok()
+        .and_then(|x| x.len().ok())
+        .unwrap_or(0)
+}
+
+/// Runs `f` on `items` with the GIL released when every key is native, so
+/// that other Python threads can run while a large batch is sorted or built
+/// into a tree. Keys wrapping Python objects need the GIL to be compared, so
+/// for them `f` runs with the GIL held throughout.
+pub fn allow_threads_if_native<T: Send, R: Send>(
+    py: Python,
+    items: Vec<T>,
+    key: impl Fn(&T) -> &Elem,
+    f: impl Send + FnOnce(Vec<T>) -> R,
+) -> R {
+    if items.iter().all(|item| key(item).is_native()) {
+        elem::allow_threads(py, || f(items))
+    } else {
+        let _attached = elem::attach(py);
+        f(items)
+    }
+}
+
+/// Pairs each item with its position in the input and sorts them by key, so a
+/// batch can be applied to the tree in key order while its results are still
+/// returned in input order. Equal keys keep their relative input order.
+pub fn sort_batch<T, K: Ord>(items: Vec<T>, key: impl Fn(&T) -> &K) -> Vec<(usize, T)> {
+    let mut batch = items.into_iter().enumerate().collect::<Vec<_>>();
+    batch.sort_by(|a, b| key(&a.1).cmp(key(&b.1)));
+    batch
+}
+
+/// Checks that `items` are in non-decreasing order, raising a ValueError that
+/// points to the first out-of-order element otherwise.
+pub fn check_sorted<T, K: Ord>(items: &[T], key: impl Fn(&T) -> &K) -> PyResult<()> {
+    for (i, pair) in items.windows(2).enumerate() {
+        if key(&pair[0]) > key(&pair[1]) {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(format!(
+                "input is not sorted: element at index {} is out of order",
+                i + 1
+            )));
+        }
+    }
+    Ok(())
+}
+
+// The std collections build trees from an iterator by sorting the input and
+// then filling the nodes bottom-up. The sort detects already sorted runs, so
+// for sorted input the whole construction is O(n) instead of O(n log n).
+
+/// Builds a map with the same result as inserting `items` one by one: the
+/// first key of a run of equal keys is kept along with the last value.
+pub fn build_map<K: Ord, V>(items: Vec<(K, V)>) -> BTreeMap<K, V> {
+    BTreeMap::from_iter(build_entries(items))
+}
+
+/// The entries of `build_map` as a vec with strictly increasing keys.
+pub fn build_entries<K: Ord, V>(mut items: Vec<(K, V)>) -> Vec<(K, V)> {
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut deduped: Vec<(K, V)> = Vec::with_capacity(items.len());
+    for (key, value) in items {
+        match deduped.last_mut() {
+            Some(last) if last.0 == key => last.1 = value,
+            _ => deduped.push((key, value)),
+        }
+    }
+
+    deduped
+}
+
+/// Builds a set with the same result as inserting `items` one by one: the
+/// first element of a run of equal elements is kept.
+pub fn build_set(mut items: Vec<Elem>) -> BTreeSet<Elem> {
+    items.sort();
+    items.dedup();
+
+    BTreeSet::from_iter(items)
+}
+
+/// Builds the element counts of a seq along with its total length, keeping
+/// the first element of a run of equal elements.
+pub fn build_seq(mut items: Vec<Elem>) -> (BTreeMap<Elem, usize>, usize) {
+    let length = items.len();
+    items.sort();
+
+    let mut counts: Vec<(Elem, usize)> = Vec::with_capacity(items.len());
+    for elem in items {
+        match counts.last_mut() {
+            Some(last) if last.0 == elem => last.1 += 1,
+            _ => counts.push((elem, 1)),
+        }
+    }
+
+    (BTreeMap::from_iter(counts), length)
+}
+
+/// Builds the element counts of a seq from `(element, count)` pairs, such as
+/// a pickled state, rejecting repeated elements and zero counts.
+pub fn build_counts(mut items: Vec<(Elem, usize)>) -> PyResult<(BTreeMap<Elem, usize>, usize)> {
+    let invalid = |msg| Err(PyErr::new::<exceptions::PyValueError, _>(msg));
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if items.iter().any(|(_, count)| *count == 0) {
+        return invalid("invalid state: element counts must be positive");
+    }
+    if items.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+        return invalid("invalid state: an element is listed more than once");
+    }
+    let Some(length) = items
+        .iter()
+        .try_fold(0usize, |length, (_, count)| length.checked_add(*count))
+    else {
+        return invalid("invalid state: element counts overflow");
+    };
+
+    Ok((BTreeMap::from_iter(items), length))
+}
+
+/// Builds the values of a multimap along with its total length. Values of
+/// equal keys keep their input order and the first of the equal keys is kept.
+pub fn build_multimap(mut items: Vec<(Elem, Elem)>) -> (BTreeMap<Elem, Vec<Elem>>, usize) {
+    let length = items.len();
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut grouped: Vec<(Elem, Vec<Elem>)> = Vec::with_capacity(items.len());
+    for (key, value) in items {
+        match grouped.last_mut() {
+            Some(last) if last.0 == key => last.1.push(value),
+            _ => grouped.push((key, vec![value])),
+        }
+    }
+
+    (BTreeMap::from_iter(grouped), length)
+}
@@ -0,0 +1,246 @@
+//! Dates and times of the `Date`, `DateTime` and `AwareDateTime` variants.
+//! Dates are Python's `date.toordinal()`, day 1 being 0001-01-01 of the
+//! proleptic Gregorian calendar, and times count microseconds from midnight
+//! of day 1. Text is ISO 8601 as written by `isoformat()`.
+
+pub const MICROS_PER_DAY: i64 = 86_400_000_000;
+const MICROS_PER_SECOND: i64 = 1_000_000;
+const MAX_ORDINAL: i32 = 3_652_059; // 9999-12-31
+
+/// A `datetime` with a `datetime.timezone` fixed offset. Equal to another when
+/// they are the same instant, whatever their offsets.
+#[derive(Clone, Debug)]
+pub struct AwareDateTime {
+    /// The instant in UTC.
+    pub utc: i64,
+    /// Seconds east of UTC, strictly within a day.
+    pub offset: i32,
+}
+
+impl AwareDateTime {
+    pub fn local(&self) -> i64 {
+        self.utc + self.offset as i64 * MICROS_PER_SECOND
+    }
+}
+
+const DAYS_BEFORE_MONTH: [i32; 13] = [0, 0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+
+fn is_leap(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The ordinal of a date, `None` if it doesn't exist or is outside years
+/// 1 to 9999 like Python's.
+pub fn ordinal(year: i32, month: u8, day: u8) -> Option<i32> {
+    if !(1..=9999).contains(&year) || !(1..=12).contains(&month) {
+        return None;
+    }
+    if day == 0 || day > days_in_month(year, month) {
+        return None;
+    }
+    let y = year - 1;
+    let leap_day = (month > 2 && is_leap(year)) as i32;
+    Some(
+        y * 365 + y / 4 - y / 100
+            + y / 400
+            + DAYS_BEFORE_MONTH[month as usize]
+            + leap_day
+            + day as i32,
+    )
+}
+
+/// The year, month and day of a valid ordinal.
+pub fn from_ordinal(ordinal: i32) -> (i32, u8, u8) {
+    // whole 400, 100, 4 and 1 year cycles, as `date.fromordinal()` does
+    let n = ordinal - 1;
+    let (n400, n) = (n / 146_097, n % 146_097);
+    let (n100, n) = (n / 36_524, n % 36_524);
+    let (n4, n) = (n / 1_461, n % 1_461);
+    let (n1, n) = (n / 365, n % 365);
+    let year = n400 * 400 + n100 * 100 + n4 * 4 + n1 + 1;
+    if n1 == 4 || n100 == 4 {
+        // the last day of a leap year
+        return (year - 1, 12, 31);
+    }
+
+    let leap = is_leap(year);
+    let day_of_year = n + 1;
+    let mut month = 12;
+    while DAYS_BEFORE_MONTH[month] + (month > 2 && leap) as i32 >= day_of_year {
+        month -= 1;
+    }
+    let day = day_of_year - DAYS_BEFORE_MONTH[month] - (month > 2 && leap) as i32;
+    (year, month as u8, day as u8)
+}
+
+pub fn is_valid_date(ordinal: i32) -> bool {
+    (1..=MAX_ORDINAL).contains(&ordinal)
+}
+
+pub fn is_valid_datetime(micros: i64) -> bool {
+    (0..MAX_ORDINAL as i64 * MICROS_PER_DAY).contains(&micros)
+}
+
+pub fn is_valid_offset(offset: i32) -> bool {
+    offset.unsigned_abs() < 86_400
+}
+
+/// Splits a valid time into its ordinal and its hour, minute, second and
+/// microsecond.
+pub fn split(micros: i64) -> (i32, [u32; 4]) {
+    let (days, time) = (micros / MICROS_PER_DAY, micros % MICROS_PER_DAY);
+    let seconds = time / MICROS_PER_SECOND;
+    let fields = [
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        time % MICROS_PER_SECOND,
+    ];
+    (days as i32 + 1, fields.map(|x| x as u32))
+}
+
+/// The time of a date and time of day, `None` if any field is out of range.
+pub fn join(ordinal: i32, hour: u8, minute: u8, second: u8, micro: u32) -> Option<i64> {
+    if hour > 23 || minute > 59 || second > 59 || micro > 999_999 {
+        return None;
+    }
+    let seconds = hour as i64 * 3600 + minute as i64 * 60 + second as i64;
+    Some((ordinal as i64 - 1) * MICROS_PER_DAY + seconds * MICROS_PER_SECOND + micro as i64)
+}
+
+pub fn format_date(ordinal: i32) -> String {
+    let (year, month, day) = from_ordinal(ordinal);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// `isoformat()` of a naive `datetime`, or of an aware one at `offset`.
+pub fn format_datetime(micros: i64, offset: Option<i32>) -> String {
+    let (ordinal, [hour, minute, second, micro]) = split(micros);
+    let mut s = format!("{}T{hour:02}:{minute:02}:{second:02}", format_date(ordinal));
+    if micro != 0 {
+        s += &format!(".{micro:06}");
+    }
+    if let Some(offset) = offset {
+        let sign = if offset < 0 { '-' } else { '+' };
+        let offset = offset.unsigned_abs();
+        s += &format!("{sign}{:02}:{:02}", offset / 3600, offset / 60 % 60);
+        if offset % 60 != 0 {
+            s += &format!(":{:02}", offset % 60);
+        }
+    }
+    s
+}
+
+fn number<T: std::str::FromStr>(s: &str, digits: usize) -> Option<T> {
+    if s.len() != digits || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+pub fn parse_date(s: &str) -> Option<i32> {
+    let mut parts = s.split('-');
+    let year = number(parts.next()?, 4)?;
+    let month = number(parts.next()?, 2)?;
+    let day = number(parts.next()?, 2)?;
+    if parts.next().is_some() {
+        return None;
+    }
+    ordinal(year, month, day)
+}
+
+/// Parses the output of `format_datetime`, the time in the local time of the
+/// offset along with the offset if there is one.
+pub fn parse_datetime(s: &str) -> Option<(i64, Option<i32>)> {
+    let (date, time) = s.split_once('T')?;
+    let ordinal = parse_date(date)?;
+
+    let (time, offset) = match time.find(['+', '-']) {
+        Some(i) => {
+            let sign = if &time[i..=i] == "-" { -1 } else { 1 };
+            let fields = time[i + 1..]
+                .split(':')
+                .map(|x| number::<i32>(x, 2))
+                .collect::<Option<Vec<_>>>()?;
+            let offset = match fields[..] {
+                [hours, minutes] if minutes < 60 => hours * 3600 + minutes * 60,
+                [hours, minutes, seconds] if minutes < 60 && seconds < 60 => {
+                    hours * 3600 + minutes * 60 + seconds
+                }
+                _ => return None,
+            };
+            if !is_valid_offset(offset) {
+                return None;
+            }
+            (&time[..i], Some(sign * offset))
+        }
+        None => (time, None),
+    };
+
+    let (time, micro) = match time.split_once('.') {
+        Some((time, micro)) => (time, number(micro, 6)?),
+        None => (time, 0),
+    };
+    let mut fields = time.split(':').map(|x| number::<u8>(x, 2));
+    let (hour, minute, second) = (fields.next()??, fields.next()??, fields.next()??);
+    if fields.next().is_some() {
+        return None;
+    }
+    Some((join(ordinal, hour, minute, second, micro)?, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordinal() {
+        assert_eq!(ordinal(1, 1, 1), Some(1));
+        assert_eq!(ordinal(2000, 3, 1), Some(730_180));
+        assert_eq!(ordinal(9999, 12, 31), Some(MAX_ORDINAL));
+        assert_eq!(ordinal(1900, 2, 29), None);
+        assert_eq!(ordinal(0, 1, 1), None);
+
+        for n in (1..=MAX_ORDINAL)
+            .step_by(97)
+            .chain([59, 60, 365, 366, 1461, 146_097])
+        {
+            let (year, month, day) = from_ordinal(n);
+            assert_eq!(ordinal(year, month, day), Some(n));
+        }
+    }
+
+    #[test]
+    fn test_iso() {
+        let date = ordinal(2024, 2, 29).unwrap();
+        assert_eq!(format_date(date), "2024-02-29");
+        assert_eq!(parse_date("2024-02-29"), Some(date));
+        assert_eq!(parse_date("2023-02-29"), None);
+
+        let time = join(date, 13, 5, 9, 250).unwrap();
+        for (s, offset) in [
+            ("2024-02-29T13:05:09.000250", None),
+            ("2024-02-29T13:05:09.000250+00:00", Some(0)),
+            ("2024-02-29T13:05:09.000250-05:30", Some(-19_800)),
+            ("2024-02-29T13:05:09.000250+01:00:30", Some(3630)),
+        ] {
+            assert_eq!(format_datetime(time, offset), s);
+            assert_eq!(parse_datetime(s), Some((time, offset)));
+        }
+        assert_eq!(
+            format_datetime(join(1, 0, 0, 0, 0).unwrap(), None),
+            "0001-01-01T00:00:00"
+        );
+        assert_eq!(parse_datetime("2024-02-29T24:00:00"), None);
+        assert_eq!(parse_datetime("2024-02-29T10:00:00+24:00"), None);
+    }
+}
@@ -0,0 +1,955 @@
+use crate::binary::{self, Crc32, FormatError, Result};
+use crate::elem::Elem;
+use memmap2::MmapMut;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::ops::Bound;
+use std::path::Path;
+
+// B+ tree stored in a memory mapped page file.
+//
+// Pages 0 and 1 hold two copies of the meta record (root page, length, free
+// list), the one with the highest valid generation is current. Pages reachable
+// from the current meta are never written: updates copy each node they touch
+// to a page that is free in the committed tree, and `flush()` syncs those pages
+// before writing the next meta to the other slot. A crash at any point leaves
+// either the old or the new tree intact.
+//
+// node pages:     kind: u8 | count: u16 | entries
+//   leaf          (key, value) elems in the binary module's encoding
+//   internal      child: u64, then (key, child: u64) with children holding keys >= key
+// free list page: kind: u8 | count: u16 | next: u64 | ids: u64...
+
+pub const PAGE_SIZE: usize = 4096;
+/// Largest encoded key and value pair, so every split leaves two nodes that fit.
+pub const MAX_ENTRY_SIZE: usize = PAGE_SIZE / 4 - 16;
+
+const MAGIC: &[u8; 4] = b"TCBT";
+const VERSION: u16 = 1;
+const LEAF: u8 = 0;
+const INTERNAL: u8 = 1;
+const FREE_LIST: u8 = 2;
+const NODE_HEADER: usize = 3;
+const FREE_LIST_HEADER: usize = 11;
+const FREE_LIST_CAPACITY: usize = (PAGE_SIZE - FREE_LIST_HEADER) / 8;
+// page id 0 is a meta page, so it doubles as "no page"
+const NONE: u64 = 0;
+
+enum Node {
+    Leaf { keys: Vec<Elem>, values: Vec<Elem> },
+    Internal { keys: Vec<Elem>, children: Vec<u64> },
+}
+
+impl Node {
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(PAGE_SIZE);
+        match self {
+            Node::Leaf { keys, values } => {
+                buf.push(LEAF);
+                buf.extend_from_slice(&(keys.len() as u16).to_le_bytes());
+                for (key, value) in keys.iter().zip(values) {
+                    binary::encode_elem(&mut buf, key)?;
+                    binary::encode_elem(&mut buf, value)?;
+                }
+            }
+            Node::Internal { keys, children } => {
+                buf.push(INTERNAL);
+                buf.extend_from_slice(&(keys.len() as u16).to_le_bytes());
+                buf.extend_from_slice(&children[0].to_le_bytes());
+                for (key, child) in keys.iter().zip(&children[1..]) {
+                    binary::encode_elem(&mut buf, key)?;
+                    buf.extend_from_slice(&child.to_le_bytes());
+                }
+            }
+        }
+        Ok(buf)
+    }
+
+    fn decode(mut page: &[u8]) -> Result<Node> {
+        let kind = page[0];
+        let count = u16::from_le_bytes([page[1], page[2]]) as usize;
+        page = &page[NODE_HEADER..];
+
+        match kind {
+            LEAF => {
+                let mut keys = Vec::with_capacity(count);
+                let mut values = Vec::with_capacity(count);
+                for _ in 0..count {
+                    keys.push(binary::decode_elem(&mut page)?);
+                    values.push(binary::decode_elem(&mut page)?);
+                }
+                Ok(Node::Leaf { keys, values })
+            }
+            INTERNAL => {
+                let mut keys = Vec::with_capacity(count);
+                let mut children = Vec::with_capacity(count + 1);
+                children.push(read_u64(&mut page)?);
+                for _ in 0..count {
+                    keys.push(binary::decode_elem(&mut page)?);
+                    children.push(read_u64(&mut page)?);
+                }
+                Ok(Node::Internal { keys, children })
+            }
+            _ => Err(corrupted()),
+        }
+    }
+
+    /// Splits off the upper half by encoded size, returning it with its first key.
+    fn split(&mut self) -> Result<(Elem, Node)> {
+        let sizes = match self {
+            Node::Leaf { keys, values } => keys
+                .iter()
+                .zip(values.iter())
+                .map(|(key, value)| entry_size(key, value))
+                .collect::<Result<Vec<_>>>()?,
+            Node::Internal { keys, .. } => keys
+                .iter()
+                .map(|key| Ok(elem_size(key)? + 8))
+                .collect::<Result<Vec<_>>>()?,
+        };
+        let half = sizes.iter().sum::<usize>() / 2;
+        let mut mid = 0;
+        let mut size = 0;
+        while mid < sizes.len() && size < half {
+            size += sizes[mid];
+            mid += 1;
+        }
+
+        match self {
+            Node::Leaf { keys, values } => {
+                let mid = mid.clamp(1, keys.len() - 1);
+                let right_keys = keys.split_off(mid);
+                let right_values = values.split_off(mid);
+                let separator = right_keys[0].clone();
+                let right = Node::Leaf {
+                    keys: right_keys,
+                    values: right_values,
+                };
+                Ok((separator, right))
+            }
+            Node::Internal { keys, children } => {
+                let mid = mid.clamp(1, keys.len() - 2);
+                let right_keys = keys.split_off(mid + 1);
+                let right_children = children.split_off(mid + 1);
+                let separator = keys.pop().unwrap();
+                let right = Node::Internal {
+                    keys: right_keys,
+                    children: right_children,
+                };
+                Ok((separator, right))
+            }
+        }
+    }
+
+    /// Appends `right`, which follows this node under `separator` in the parent.
+    fn merge(&mut self, separator: Elem, right: Node) {
+        match (self, right) {
+            (
+                Node::Leaf { keys, values },
+                Node::Leaf {
+                    keys: right_keys,
+                    values: right_values,
+                },
+            ) => {
+                keys.extend(right_keys);
+                values.extend(right_values);
+            }
+            (
+                Node::Internal { keys, children },
+                Node::Internal {
+                    keys: right_keys,
+                    children: right_children,
+                },
+            ) => {
+                keys.push(separator);
+                keys.extend(right_keys);
+                children.extend(right_children);
+            }
+            _ => unreachable!("siblings are at the same depth"),
+        }
+    }
+}
+
+fn elem_size(elem: &Elem) -> Result<usize> {
+    let mut buf = Vec::new();
+    binary::encode_elem(&mut buf, elem)?;
+    Ok(buf.len())
+}
+
+fn entry_size(key: &Elem, value: &Elem) -> Result<usize> {
+    Ok(elem_size(key)? + elem_size(value)?)
+}
+
+fn read_u64(bytes: &mut &[u8]) -> Result<u64> {
+    if bytes.len() < 8 {
+        return Err(corrupted());
+    }
+    let (head, tail) = bytes.split_at(8);
+    *bytes = tail;
+    Ok(u64::from_le_bytes(head.try_into().unwrap()))
+}
+
+fn corrupted() -> FormatError {
+    FormatError::Invalid("page file is corrupted".to_string())
+}
+
+#[derive(Clone, Copy)]
+struct Meta {
+    generation: u64,
+    root: u64,
+    len: u64,
+    page_count: u64,
+    free_list: u64,
+}
+
+impl Meta {
+    const SIZE: usize = 52;
+
+    fn encode(&self) -> [u8; Meta::SIZE] {
+        let mut buf = [0u8; Meta::SIZE];
+        buf[..4].copy_from_slice(MAGIC);
+        buf[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        let fields = [
+            self.generation,
+            self.root,
+            self.len,
+            self.page_count,
+            self.free_list,
+        ];
+        for (i, field) in fields.iter().enumerate() {
+            buf[8 + i * 8..16 + i * 8].copy_from_slice(&field.to_le_bytes());
+        }
+        let mut crc = Crc32::new();
+        crc.update(&buf[..48]);
+        buf[48..].copy_from_slice(&crc.finish().to_le_bytes());
+        buf
+    }
+
+    /// Returns `None` for a torn or foreign meta record.
+    fn decode(page: &[u8]) -> Option<Meta> {
+        let mut crc = Crc32::new();
+        crc.update(&page[..48]);
+        let valid = &page[..4] == MAGIC
+            && page[4..6] == VERSION.to_le_bytes()
+            && page[48..52] == crc.finish().to_le_bytes();
+        if !valid {
+            return None;
+        }
+
+        let field = |i: usize| u64::from_le_bytes(page[8 + i * 8..16 + i * 8].try_into().unwrap());
+        Some(Meta {
+            generation: field(0),
+            root: field(1),
+            len: field(2),
+            page_count: field(3),
+            free_list: field(4),
+        })
+    }
+}
+
+/// Separator and page of the new right sibling when a node was split.
+type Split = Option<(Elem, u64)>;
+
+struct CachedNode {
+    node: Node,
+    dirty: bool,
+    last_used: u64,
+}
+
+pub struct DiskBTree {
+    file: File,
+    mmap: MmapMut,
+    meta: Meta,
+    /// Slot of the committed meta record.
+    meta_slot: usize,
+    cache: HashMap<u64, CachedNode>,
+    cache_size: usize,
+    tick: u64,
+    /// Pages allocated since the last flush, these can be written in place.
+    fresh: HashSet<u64>,
+    /// Pages that are free in the committed tree.
+    free: Vec<u64>,
+    /// Pages of the committed tree replaced since, free after the next flush.
+    pending: Vec<u64>,
+    /// Pages holding the committed free list.
+    free_list_pages: Vec<u64>,
+    modified: bool,
+}
+
+impl DiskBTree {
+    /// Opens the page file at `path`, creating an empty one if it's missing.
+    pub fn open<P: AsRef<Path>>(path: P, cache_size: usize) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let created = file.metadata()?.len() == 0;
+        if created {
+            file.set_len(2 * PAGE_SIZE as u64)?;
+        }
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+
+        let (meta, meta_slot) = if created {
+            let meta = Meta {
+                generation: 1,
+                root: NONE,
+                len: 0,
+                page_count: 2,
+                free_list: NONE,
+            };
+            mmap[..Meta::SIZE].copy_from_slice(&meta.encode());
+            mmap.flush()?;
+            (meta, 0)
+        } else {
+            if mmap.len() < 2 * PAGE_SIZE {
+                return Err(FormatError::Invalid(
+                    "not a tree_collections page file".to_string(),
+                ));
+            }
+            let metas = [
+                Meta::decode(&mmap[..PAGE_SIZE]),
+                Meta::decode(&mmap[PAGE_SIZE..2 * PAGE_SIZE]),
+            ];
+            match metas {
+                [Some(a), Some(b)] if b.generation > a.generation => (b, 1),
+                [Some(a), _] => (a, 0),
+                [None, Some(b)] => (b, 1),
+                [None, None] => {
+                    return Err(FormatError::Invalid(
+                        "not a tree_collections page file".to_string(),
+                    ))
+                }
+            }
+        };
+        if meta.page_count as usize * PAGE_SIZE > mmap.len() {
+            return Err(corrupted());
+        }
+
+        let mut tree = DiskBTree {
+            file,
+            mmap,
+            meta,
+            meta_slot,
+            cache: HashMap::new(),
+            cache_size: cache_size.max(16),
+            tick: 0,
+            fresh: HashSet::new(),
+            free: Vec::new(),
+            pending: Vec::new(),
+            free_list_pages: Vec::new(),
+            modified: false,
+        };
+        tree.read_free_list()?;
+
+        Ok(tree)
+    }
+
+    pub fn len(&self) -> usize {
+        self.meta.len as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meta.len == 0
+    }
+
+    pub fn get(&mut self, key: &Elem) -> Result<Option<Elem>> {
+        let mut id = self.meta.root;
+        while id != NONE {
+            match self.node(id)? {
+                Node::Leaf { keys, values } => {
+                    return Ok(keys
+                        .binary_search(key)
+                        .ok()
+                        .map(|index| values[index].clone()));
+                }
+                Node::Internal { keys, children } => {
+                    id = children[keys.partition_point(|x| x <= key)];
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the previous value for `key`, if any.
+    pub fn insert(&mut self, key: Elem, value: Elem) -> Result<Option<Elem>> {
+        if entry_size(&key, &value)? > MAX_ENTRY_SIZE {
+            return Err(FormatError::Invalid(format!(
+                "entry is too large for a page, the limit is {MAX_ENTRY_SIZE} bytes"
+            )));
+        }
+        // nodes are taken out of the cache on the way down, look the key up
+        // first so a key that can't be compared fails before anything moves
+        self.get(&key)?;
+        self.modified = true;
+
+        if self.meta.root == NONE {
+            let id = self.allocate()?;
+            let node = Node::Leaf {
+                keys: vec![key],
+                values: vec![value],
+            };
+            self.put(id, node);
+            self.meta.root = id;
+            self.meta.len = 1;
+            return Ok(None);
+        }
+
+        let (root, old, split) = self.insert_at(self.meta.root, key, value)?;
+        self.meta.root = root;
+        if let Some((separator, right)) = split {
+            let id = self.allocate()?;
+            let node = Node::Internal {
+                keys: vec![separator],
+                children: vec![root, right],
+            };
+            self.put(id, node);
+            self.meta.root = id;
+        }
+        if old.is_none() {
+            self.meta.len += 1;
+        }
+
+        Ok(old)
+    }
+
+    /// Returns the removed value, leaving the file untouched if `key` is missing.
+    pub fn remove(&mut self, key: &Elem) -> Result<Option<Elem>> {
+        if self.get(key)?.is_none() {
+            return Ok(None);
+        }
+        self.modified = true;
+
+        let (root, old) = self.remove_at(self.meta.root, key)?;
+        self.meta.root = root;
+        self.meta.len -= 1;
+
+        // shrink the tree while the root has a single child or no keys
+        loop {
+            let id = self.meta.root;
+            let replacement = match self.node(id)? {
+                Node::Internal { children, .. } if children.len() == 1 => children[0],
+                Node::Leaf { keys, .. } if keys.is_empty() => NONE,
+                _ => break,
+            };
+            self.release(id);
+            self.meta.root = replacement;
+            if replacement == NONE {
+                break;
+            }
+        }
+
+        Ok(Some(old))
+    }
+
+    /// Returns up to `limit` entries in key order, from `start` up to but
+    /// excluding `stop`.
+    pub fn range(
+        &mut self,
+        start: Bound<&Elem>,
+        stop: Option<&Elem>,
+        limit: usize,
+    ) -> Result<Vec<(Elem, Elem)>> {
+        let mut output = Vec::new();
+        if self.meta.root != NONE {
+            self.collect_range(self.meta.root, start, stop, limit, &mut output)?;
+        }
+        Ok(output)
+    }
+
+    /// Makes every change so far durable, a crash afterwards reopens to this state.
+    pub fn flush(&mut self) -> Result<()> {
+        if !self.modified {
+            return Ok(());
+        }
+
+        let dirty = self
+            .cache
+            .iter()
+            .filter(|(_, x)| x.dirty)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in dirty {
+            self.write_back(id)?;
+        }
+
+        let (free_list, pages) = self.write_free_list()?;
+        if let Err(err) = self.commit(free_list) {
+            pages.into_iter().for_each(|id| self.release(id));
+            return Err(err);
+        }
+
+        // pages of the previous tree are only free now that its meta is gone
+        self.free.append(&mut self.pending);
+        self.free.append(&mut self.free_list_pages);
+        self.free_list_pages = pages;
+        self.fresh.clear();
+        self.modified = false;
+
+        Ok(())
+    }
+
+    /// Syncs the pages written so far, then switches to the tree they hold by
+    /// writing its meta to the other slot.
+    fn commit(&mut self, free_list: u64) -> Result<()> {
+        self.mmap.flush()?;
+
+        let meta = Meta {
+            generation: self.meta.generation + 1,
+            free_list,
+            ..self.meta
+        };
+        let slot = 1 - self.meta_slot;
+        let offset = slot * PAGE_SIZE;
+        self.mmap[offset..offset + Meta::SIZE].copy_from_slice(&meta.encode());
+        self.mmap.flush_range(offset, PAGE_SIZE)?;
+
+        self.meta = meta;
+        self.meta_slot = slot;
+        Ok(())
+    }
+
+    fn insert_at(&mut self, id: u64, key: Elem, value: Elem) -> Result<(u64, Option<Elem>, Split)> {
+        let mut node = self.take(id)?;
+        let id = self.make_fresh(id)?;
+
+        let old = match &mut node {
+            Node::Leaf { keys, values } => match keys.binary_search(&key) {
+                Ok(index) => Some(std::mem::replace(&mut values[index], value)),
+                Err(index) => {
+                    keys.insert(index, key);
+                    values.insert(index, value);
+                    None
+                }
+            },
+            Node::Internal { keys, children } => {
+                let index = keys.partition_point(|x| *x <= key);
+                let (child, old, split) = self.insert_at(children[index], key, value)?;
+                children[index] = child;
+                if let Some((separator, right)) = split {
+                    keys.insert(index, separator);
+                    children.insert(index + 1, right);
+                }
+                old
+            }
+        };
+
+        let split = if node.encode()?.len() > PAGE_SIZE {
+            let (separator, right) = node.split()?;
+            let right_id = self.allocate()?;
+            self.put(right_id, right);
+            Some((separator, right_id))
+        } else {
+            None
+        };
+        self.put(id, node);
+
+        Ok((id, old, split))
+    }
+
+    fn remove_at(&mut self, id: u64, key: &Elem) -> Result<(u64, Elem)> {
+        let mut node = self.take(id)?;
+        let id = self.make_fresh(id)?;
+
+        let old = match &mut node {
+            Node::Leaf { keys, values } => {
+                let index = keys.binary_search(key).map_err(|_| corrupted())?;
+                keys.remove(index);
+                values.remove(index)
+            }
+            Node::Internal { keys, children } => {
+                let index = keys.partition_point(|x| x <= key);
+                let (child, old) = self.remove_at(children[index], key)?;
+                children[index] = child;
+                self.rebalance(keys, children, index)?;
+                old
+            }
+        };
+        self.put(id, node);
+
+        Ok((id, old))
+    }
+
+    /// Merges the child at `index` into a sibling if it's under a quarter full
+    /// and they fit in one page together.
+    fn rebalance(
+        &mut self,
+        keys: &mut Vec<Elem>,
+        children: &mut Vec<u64>,
+        index: usize,
+    ) -> Result<()> {
+        if children.len() < 2 {
+            return Ok(());
+        }
+        let child = self.node(children[index])?;
+        let empty = matches!(child, Node::Leaf { keys, .. } if keys.is_empty());
+        let size = child.encode()?.len();
+        if size >= PAGE_SIZE / 4 && !empty {
+            return Ok(());
+        }
+
+        let left = if index > 0 { index - 1 } else { index };
+        let (left_id, right_id) = (children[left], children[left + 1]);
+        let left_size = self.node(left_id)?.encode()?.len();
+        let right_size = self.node(right_id)?.encode()?.len();
+        let separator_size = elem_size(&keys[left])? + 8;
+        if !empty && left_size + right_size + separator_size > PAGE_SIZE {
+            return Ok(());
+        }
+
+        let right = self.take(right_id)?;
+        self.release(right_id);
+        let mut node = self.take(left_id)?;
+        let left_id = self.make_fresh(left_id)?;
+        node.merge(keys.remove(left), right);
+        self.put(left_id, node);
+        children[left] = left_id;
+        children.remove(left + 1);
+
+        Ok(())
+    }
+
+    fn collect_range(
+        &mut self,
+        id: u64,
+        start: Bound<&Elem>,
+        stop: Option<&Elem>,
+        limit: usize,
+        output: &mut Vec<(Elem, Elem)>,
+    ) -> Result<bool> {
+        let children = match self.node(id)? {
+            Node::Leaf { keys, values } => {
+                let first = match start {
+                    Bound::Included(start) => keys.partition_point(|x| x < start),
+                    Bound::Excluded(start) => keys.partition_point(|x| x <= start),
+                    Bound::Unbounded => 0,
+                };
+                for (key, value) in keys[first..].iter().zip(&values[first..]) {
+                    if output.len() >= limit || stop.is_some_and(|stop| key >= stop) {
+                        return Ok(true);
+                    }
+                    output.push((key.clone(), value.clone()));
+                }
+                return Ok(false);
+            }
+            Node::Internal { keys, children } => {
+                let first = match start {
+                    Bound::Included(start) | Bound::Excluded(start) => {
+                        keys.partition_point(|x| x <= start)
+                    }
+                    Bound::Unbounded => 0,
+                };
+                children[first..].to_vec()
+            }
+        };
+
+        for child in children {
+            if self.collect_range(child, start, stop, limit, output)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn page(&self, id: u64) -> &[u8] {
+        let offset = id as usize * PAGE_SIZE;
+        &self.mmap[offset..offset + PAGE_SIZE]
+    }
+
+    fn page_mut(&mut self, id: u64) -> &mut [u8] {
+        let offset = id as usize * PAGE_SIZE;
+        &mut self.mmap[offset..offset + PAGE_SIZE]
+    }
+
+    fn node(&mut self, id: u64) -> Result<&Node> {
+        if !self.cache.contains_key(&id) {
+            let node = self.load(id)?;
+            self.cache_insert(id, node, false)?;
+        }
+        self.tick += 1;
+        let cached = self.cache.get_mut(&id).unwrap();
+        cached.last_used = self.tick;
+        Ok(&cached.node)
+    }
+
+    fn load(&self, id: u64) -> Result<Node> {
+        if id < 2 || id >= self.meta.page_count {
+            return Err(corrupted());
+        }
+        Node::decode(self.page(id))
+    }
+
+    /// Removes a node from the cache for updating, `put` stores it back.
+    fn take(&mut self, id: u64) -> Result<Node> {
+        match self.cache.remove(&id) {
+            Some(cached) => Ok(cached.node),
+            None => self.load(id),
+        }
+    }
+
+    fn put(&mut self, id: u64, node: Node) {
+        // a failed write back leaves the node cached and dirty, flush retries it
+        let _ = self.cache_insert(id, node, true);
+    }
+
+    fn cache_insert(&mut self, id: u64, node: Node, dirty: bool) -> Result<()> {
+        self.tick += 1;
+        let cached = CachedNode {
+            node,
+            dirty,
+            last_used: self.tick,
+        };
+        self.cache.insert(id, cached);
+
+        if self.cache.len() > self.cache_size {
+            self.evict(id)?;
+        }
+        Ok(())
+    }
+
+    /// Evicts the least recently used half of the cache, except `keep`.
+    fn evict(&mut self, keep: u64) -> Result<()> {
+        let mut ids = self
+            .cache
+            .iter()
+            .filter(|(id, _)| **id != keep)
+            .map(|(id, x)| (x.last_used, *id))
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        for (_, id) in ids.into_iter().take(self.cache.len() - self.cache_size / 2) {
+            self.write_back(id)?;
+            self.cache.remove(&id);
+        }
+        Ok(())
+    }
+
+    /// Writes a dirty node to its page, which is fresh and so not part of the
+    /// committed tree.
+    fn write_back(&mut self, id: u64) -> Result<()> {
+        let cached = match self.cache.get_mut(&id) {
+            Some(cached) if cached.dirty => cached,
+            _ => return Ok(()),
+        };
+        let bytes = cached.node.encode()?;
+        cached.dirty = false;
+
+        let page = self.page_mut(id);
+        page[..bytes.len()].copy_from_slice(&bytes);
+        page[bytes.len()..].fill(0);
+        Ok(())
+    }
+
+    /// Returns a page that can be written without touching the committed tree.
+    fn make_fresh(&mut self, id: u64) -> Result<u64> {
+        if self.fresh.contains(&id) {
+            return Ok(id);
+        }
+        self.pending.push(id);
+        self.allocate()
+    }
+
+    fn release(&mut self, id: u64) {
+        self.cache.remove(&id);
+        if self.fresh.remove(&id) {
+            self.free.push(id);
+        } else {
+            self.pending.push(id);
+        }
+    }
+
+    fn allocate(&mut self) -> Result<u64> {
+        let id = match self.free.pop() {
+            Some(id) => id,
+            None => {
+                let id = self.meta.page_count;
+                self.meta.page_count += 1;
+                self.reserve(self.meta.page_count as usize)?;
+                id
+            }
+        };
+        self.fresh.insert(id);
+        Ok(id)
+    }
+
+    /// Grows the file and remaps it so it holds at least `pages` pages.
+    fn reserve(&mut self, pages: usize) -> Result<()> {
+        if pages * PAGE_SIZE <= self.mmap.len() {
+            return Ok(());
+        }
+        let capacity = (self.mmap.len() / PAGE_SIZE * 2).max(pages).max(16);
+        self.file.set_len((capacity * PAGE_SIZE) as u64)?;
+        self.mmap = unsafe { MmapMut::map_mut(&self.file)? };
+        Ok(())
+    }
+
+    fn read_free_list(&mut self) -> Result<()> {
+        let mut id = self.meta.free_list;
+        while id != NONE {
+            if id < 2
+                || id >= self.meta.page_count
+                || self.free_list_pages.len() as u64 > self.meta.page_count
+            {
+                return Err(corrupted());
+            }
+            let page = self.page(id);
+            if page[0] != FREE_LIST {
+                return Err(corrupted());
+            }
+            let count = u16::from_le_bytes([page[1], page[2]]) as usize;
+            let mut bytes = &page[3..];
+            let next = read_u64(&mut bytes)?;
+            let ids = (0..count.min(FREE_LIST_CAPACITY))
+                .map(|_| read_u64(&mut bytes))
+                .collect::<Result<Vec<_>>>()?;
+            self.free.extend(ids);
+            self.free_list_pages.push(id);
+            id = next;
+        }
+        Ok(())
+    }
+
+    /// Writes the free list of the tree about to be committed to fresh pages,
+    /// returning the first page and all of them.
+    fn write_free_list(&mut self) -> Result<(u64, Vec<u64>)> {
+        let mut pages = Vec::new();
+        let count = |tree: &Self| tree.free.len() + tree.pending.len() + tree.free_list_pages.len();
+        while pages.len() * FREE_LIST_CAPACITY < count(self) {
+            pages.push(self.allocate()?);
+        }
+        let ids = self
+            .free
+            .iter()
+            .chain(&self.pending)
+            .chain(&self.free_list_pages)
+            .copied()
+            .collect::<Vec<_>>();
+
+        let mut chunks = ids.chunks(FREE_LIST_CAPACITY).collect::<Vec<_>>();
+        chunks.resize(pages.len(), &[]);
+
+        let mut next = NONE;
+        for (id, chunk) in pages.iter().zip(chunks).rev() {
+            let page = self.page_mut(*id);
+            page.fill(0);
+            page[0] = FREE_LIST;
+            page[1..3].copy_from_slice(&(chunk.len() as u16).to_le_bytes());
+            page[3..11].copy_from_slice(&next.to_le_bytes());
+            for (j, free) in chunk.iter().enumerate() {
+                let offset = FREE_LIST_HEADER + j * 8;
+                page[offset..offset + 8].copy_from_slice(&free.to_le_bytes());
+            }
+            next = *id;
+        }
+
+        Ok((next, pages))
+    }
+}
+
+impl Drop for DiskBTree {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}-{}.tcbt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn value(i: i64) -> Elem {
+        Elem::String(format!("value {i:>40}").into())
+    }
+
+    #[test]
+    fn test_disk_btree() {
+        let path = temp_path("test_disk_btree");
+        let mut expected = BTreeMap::new();
+
+        {
+            let mut tree = DiskBTree::open(&path, 32).unwrap();
+            // deterministic pseudo-random updates, enough to split internal nodes
+            let mut seed: i64 = 7;
+            for i in 0..20000 {
+                seed = (seed * 1103515245 + 12345) % 2147483648;
+                let key = seed % 5000;
+                if seed % 4 == 0 {
+                    let old = tree.remove(&Elem::Int(key)).unwrap();
+                    assert_eq!(old, expected.remove(&key).map(value));
+                } else {
+                    let old = tree.insert(Elem::Int(key), value(i)).unwrap();
+                    assert_eq!(old, expected.insert(key, i).map(value));
+                }
+                if i % 5000 == 0 {
+                    tree.flush().unwrap();
+                }
+            }
+            assert_eq!(tree.len(), expected.len());
+        }
+
+        let mut tree = DiskBTree::open(&path, 32).unwrap();
+        assert_eq!(tree.len(), expected.len());
+        let entries = tree.range(Bound::Unbounded, None, usize::MAX).unwrap();
+        let expected_entries = expected
+            .iter()
+            .map(|(key, i)| (Elem::Int(*key), value(*i)))
+            .collect::<Vec<_>>();
+        assert_eq!(entries, expected_entries);
+
+        let entries = tree
+            .range(Bound::Excluded(&Elem::Int(100)), Some(&Elem::Int(200)), 10)
+            .unwrap();
+        assert!(entries.len() <= 10);
+        assert!(entries
+            .iter()
+            .all(|(key, _)| *key > Elem::Int(100) && *key < Elem::Int(200)));
+
+        // emptying the tree puts every node page back on the free list
+        let pages = tree.meta.page_count;
+        for key in expected.keys() {
+            tree.remove(&Elem::Int(*key)).unwrap();
+        }
+        tree.flush().unwrap();
+        for i in 0..1000 {
+            tree.insert(Elem::Int(i), value(i)).unwrap();
+        }
+        tree.flush().unwrap();
+        assert_eq!(tree.meta.page_count, pages);
+
+        drop(tree);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_unflushed_changes_are_dropped_on_crash() {
+        let path = temp_path("test_crash");
+
+        let mut tree = DiskBTree::open(&path, 16).unwrap();
+        for i in 0..2000 {
+            tree.insert(Elem::Int(i), value(i)).unwrap();
+        }
+        tree.flush().unwrap();
+        for i in 0..2000 {
+            tree.insert(Elem::Int(i), Elem::PyNone).unwrap();
+        }
+        // skips the flush in drop, as if the process died here
+        std::mem::forget(tree);
+
+        let mut tree = DiskBTree::open(&path, 16).unwrap();
+        assert_eq!(tree.len(), 2000);
+        for i in (0..2000).step_by(97) {
+            assert_eq!(tree.get(&Elem::Int(i)).unwrap(), Some(value(i)));
+        }
+
+        drop(tree);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,80 +1,512 @@
+#[cfg(feature = "python")]
+use pyo3::marker::Ungil;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyo3::sync::GILOnceCell;
+#[cfg(feature = "python")]
+use pyo3::types::{
+    PyBool, PyBytes, PyDate, PyDateAccess, PyDateTime, PyDelta, PyDeltaAccess, PyFloat, PyInt,
+    PyList, PyString, PyTimeAccess, PyTuple, PyType, PyTzInfo, PyTzInfoAccess,
+};
+#[cfg(feature = "python")]
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "python")]
+use std::collections::hash_map::DefaultHasher;
+#[cfg(feature = "python")]
+use std::convert::Infallible;
+#[cfg(feature = "python")]
+use std::hash::{Hash, Hasher};
+
+#[cfg(feature = "python")]
+use crate::calendar;
+use crate::calendar::AwareDateTime;
+use crate::number::{self, Decimal, Fraction, Number};
+use crate::small_str::SmallStr;
+use std::cmp::Ordering;
+
+/// The items of a `Tuple` or `Vec`, boxed twice so that the pointer is thin.
+pub type Elems = Box<Box<[Elem]>>;
 
 // static mut GLOBALS: HashMap<>
 
+// Without the `python` feature there's no `PyObj` variant and every comparison
+// is plain Rust. Every variant fits in 8 bytes next to the tag, or 15 for an
+// inline string, so an `Elem` takes 16 bytes. `bool`, `bytes`, `date`,
+// `datetime`, `Decimal` and `Fraction` values that can't be stored exactly
+// (a NaN, a `datetime` with `fold=1` or a `tzinfo` other than a fixed
+// `timezone`, a `Fraction` beyond `i64`) stay `PyObj`.
 #[derive(Debug)]
 pub enum Elem {
     Float(f64),
     Int(i64),
-    String(String),
-    TwoTuple(Box<Elem>, Box<Elem>),
-    Tuple(Vec<Elem>),
-    Vec(Vec<Elem>),
+    String(SmallStr),
+    TwoTuple(Box<[Elem; 2]>),
+    Tuple(Elems),
+    Vec(Elems),
+    Bool(bool),
+    Bytes(Box<Box<[u8]>>),
+    /// Ordinal of a `date`.
+    Date(i32),
+    /// Microseconds of a naive `datetime`.
+    DateTime(i64),
+    AwareDateTime(Box<AwareDateTime>),
+    Decimal(Box<Decimal>),
+    Fraction(Box<Fraction>),
+    #[cfg(feature = "python")]
     PyObj(PyObject),
     PyNone,
 }
 
+impl Clone for Elem {
+    fn clone(&self) -> Self {
+        match self {
+            Elem::Float(x) => Elem::Float(*x),
+            Elem::Int(x) => Elem::Int(*x),
+            Elem::String(s) => Elem::String(s.clone()),
+            Elem::TwoTuple(pair) => Elem::TwoTuple(pair.clone()),
+            Elem::Tuple(v) => Elem::Tuple(v.clone()),
+            Elem::Vec(v) => Elem::Vec(v.clone()),
+            Elem::Bool(x) => Elem::Bool(*x),
+            Elem::Bytes(b) => Elem::Bytes(b.clone()),
+            Elem::Date(x) => Elem::Date(*x),
+            Elem::DateTime(x) => Elem::DateTime(*x),
+            Elem::AwareDateTime(x) => Elem::AwareDateTime(x.clone()),
+            Elem::Decimal(x) => Elem::Decimal(x.clone()),
+            Elem::Fraction(x) => Elem::Fraction(x.clone()),
+            // `Py` clones need an attached thread, `clone_ref` avoids this
+            #[cfg(feature = "python")]
+            Elem::PyObj(obj) => Elem::PyObj(with_py(|py| obj.clone_ref(py))),
+            Elem::PyNone => Elem::PyNone,
+        }
+    }
+}
+
+impl Elem {
+    pub fn pair(a: Elem, b: Elem) -> Elem {
+        Elem::TwoTuple(Box::new([a, b]))
+    }
+
+    pub fn tuple(items: Vec<Elem>) -> Elem {
+        Elem::Tuple(Box::new(items.into_boxed_slice()))
+    }
+
+    pub fn list(items: Vec<Elem>) -> Elem {
+        Elem::Vec(Box::new(items.into_boxed_slice()))
+    }
+
+    pub fn bytes(data: Vec<u8>) -> Elem {
+        Elem::Bytes(Box::new(data.into_boxed_slice()))
+    }
+
+    /// The number an `int`, `float`, `bool`, `Decimal` or `Fraction` stands
+    /// for, `bool` as an `int`.
+    pub fn number(&self) -> Option<Number<'_>> {
+        match self {
+            Elem::Int(x) => Some(Number::Int(*x)),
+            Elem::Bool(x) => Some(Number::Int(*x as i64)),
+            Elem::Float(x) => Some(Number::Float(*x)),
+            Elem::Decimal(x) => Some(Number::Decimal(x)),
+            Elem::Fraction(x) => Some(Number::Fraction(x)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+static DECIMAL: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+#[cfg(feature = "python")]
+static FRACTION: GILOnceCell<Py<PyType>> = GILOnceCell::new();
+
+#[cfg(feature = "python")]
 fn elem2pyobject(elem: &Elem, py: Python<'_>) -> PyObject {
     match elem {
-        Elem::Float(x) => x.to_object(py),
-        Elem::Int(x) => x.to_object(py),
-        Elem::String(s) => s.to_object(py),
-        Elem::TwoTuple(a, b) => {
-            let a = a.to_pyobject(py);
-            let b = b.to_pyobject(py);
-            (a, b).to_object(py)
+        Elem::Float(x) => PyFloat::new(py, *x).into_any().unbind(),
+        Elem::Int(x) => PyInt::new(py, *x).into_any().unbind(),
+        Elem::String(s) => PyString::new(py, s).into_any().unbind(),
+        Elem::TwoTuple(pair) => {
+            let items = pair.each_ref().map(|x| x.to_pyobject(py));
+            PyTuple::new(py, items).unwrap().into_any().unbind()
         }
         Elem::Tuple(v) => {
-            let v = v.iter().map(|x| x.to_pyobject(py)).collect::<Vec<_>>();
-            v.to_object(py)
+            let items = v.iter().map(|x| x.to_pyobject(py));
+            PyTuple::new(py, items).unwrap().into_any().unbind()
         }
         Elem::Vec(v) => {
-            let v = v.iter().map(|x| x.to_pyobject(py)).collect::<Vec<_>>();
-            v.to_object(py)
+            let items = v.iter().map(|x| x.to_pyobject(py));
+            PyList::new(py, items).unwrap().into_any().unbind()
+        }
+        Elem::Bool(x) => PyBool::new(py, *x).to_owned().into_any().unbind(),
+        Elem::Bytes(b) => PyBytes::new(py, b).into_any().unbind(),
+        Elem::Date(x) => {
+            let (year, month, day) = calendar::from_ordinal(*x);
+            PyDate::new(py, year, month, day)
+                .unwrap()
+                .into_any()
+                .unbind()
         }
-        Elem::PyObj(obj) => obj.clone(),
+        Elem::DateTime(x) => datetime2pyobject(py, *x, None).unwrap(),
+        Elem::AwareDateTime(x) => {
+            let offset = PyDelta::new(py, 0, x.offset, 0, true).unwrap();
+            let tzinfo = PyTzInfo::fixed_offset(py, offset).unwrap();
+            datetime2pyobject(py, x.local(), Some(&tzinfo)).unwrap()
+        }
+        Elem::Decimal(x) => {
+            let decimal = DECIMAL.import(py, "decimal", "Decimal").unwrap();
+            decimal.call1((x.to_string(),)).unwrap().unbind()
+        }
+        Elem::Fraction(x) => {
+            let fraction = FRACTION.import(py, "fractions", "Fraction").unwrap();
+            fraction.call1((x.num(), x.den())).unwrap().unbind()
+        }
+        Elem::PyObj(obj) => obj.clone_ref(py),
         Elem::PyNone => py.None(),
     }
 }
 
-fn pyobject2elem(ob: &PyAny) -> PyResult<Elem> {
-    let type_name = ob.get_type().name().unwrap();
+#[cfg(feature = "python")]
+fn datetime2pyobject(
+    py: Python<'_>,
+    micros: i64,
+    tzinfo: Option<&Bound<'_, PyTzInfo>>,
+) -> PyResult<PyObject> {
+    let (ordinal, [hour, minute, second, micro]) = calendar::split(micros);
+    let (year, month, day) = calendar::from_ordinal(ordinal);
+    let (hour, minute, second) = (hour as u8, minute as u8, second as u8);
+    let datetime = PyDateTime::new(py, year, month, day, hour, minute, second, micro, tzinfo)?;
+    Ok(datetime.into_any().unbind())
+}
+
+#[cfg(feature = "python")]
+fn datetime2elem(x: &Bound<'_, PyDateTime>) -> PyResult<Option<Elem>> {
+    let py = x.py();
+    if x.get_fold() {
+        return Ok(None);
+    }
+    let ordinal = calendar::ordinal(x.get_year(), x.get_month(), x.get_day());
+    let time = (
+        x.get_hour(),
+        x.get_minute(),
+        x.get_second(),
+        x.get_microsecond(),
+    );
+    let local = calendar::join(ordinal.unwrap(), time.0, time.1, time.2, time.3).unwrap();
+
+    let Some(tzinfo) = x.get_tzinfo() else {
+        return Ok(Some(Elem::DateTime(local)));
+    };
+    // only fixed offsets without a name of their own, `timezone.utc` included
+    if !tzinfo.get_type().is(PyTzInfo::utc(py)?.get_type()) {
+        return Ok(None);
+    }
+    let offset = tzinfo.call_method1("utcoffset", (py.None(),))?;
+    let offset = offset.downcast::<PyDelta>()?;
+    let unnamed = PyTzInfo::fixed_offset(py, offset)?;
+    if offset.get_microseconds() != 0 || tzinfo.str()?.to_cow()? != unnamed.str()?.to_cow()? {
+        return Ok(None);
+    }
+
+    let offset = offset.get_days() * 86_400 + offset.get_seconds();
+    let utc = local - offset as i64 * 1_000_000;
+    Ok(Some(Elem::AwareDateTime(Box::new(AwareDateTime {
+        utc,
+        offset,
+    }))))
+}
+
+/// The native element a `PyObj` compares equal to, if any, for hashing. That
+/// covers subclasses of the native types, such as `IntEnum`, and the values
+/// `pyobject2elem` leaves as `PyObj`, such as `zoneinfo` datetimes.
+#[cfg(feature = "python")]
+fn equivalent_elem(ob: &Bound<'_, PyAny>) -> PyResult<Option<Elem>> {
+    let py = ob.py();
+    let elem = if let Ok(x) = ob.downcast::<PyBool>() {
+        Elem::Bool(x.is_true())
+    } else if ob.is_instance_of::<PyInt>() {
+        match ob.extract::<i64>() {
+            Ok(x) => Elem::Int(x),
+            Err(_) => return Ok(None),
+        }
+    } else if let Ok(x) = ob.downcast::<PyFloat>() {
+        Elem::Float(x.value())
+    } else if let Ok(s) = ob.downcast::<PyString>() {
+        Elem::String(s.to_str()?.into())
+    } else if let Ok(b) = ob.downcast::<PyBytes>() {
+        Elem::bytes(b.as_bytes().to_vec())
+    } else if let Ok(t) = ob.downcast::<PyTuple>() {
+        let Ok(items) = t
+            .iter()
+            .map(|x| pyobject2elem(&x))
+            .collect::<PyResult<Vec<_>>>()
+        else {
+            return Ok(None);
+        };
+        match <[Elem; 2]>::try_from(items) {
+            Ok([a, b]) => Elem::pair(a, b),
+            Err(items) => Elem::tuple(items),
+        }
+    } else if let Ok(l) = ob.downcast::<PyList>() {
+        let Ok(items) = l
+            .iter()
+            .map(|x| pyobject2elem(&x))
+            .collect::<PyResult<Vec<_>>>()
+        else {
+            return Ok(None);
+        };
+        Elem::list(items)
+    } else if let Ok(x) = ob.downcast::<PyDateTime>() {
+        let ordinal = calendar::ordinal(x.get_year(), x.get_month(), x.get_day()).unwrap();
+        let (hour, minute) = (x.get_hour(), x.get_minute());
+        let (second, micro) = (x.get_second(), x.get_microsecond());
+        let local = calendar::join(ordinal, hour, minute, second, micro).unwrap();
+        // any tzinfo, compared by the instant like the native variant
+        match ob.call_method0("utcoffset")?.downcast_into::<PyDelta>() {
+            Ok(offset) => {
+                let seconds = offset.get_days() as i64 * 86_400 + offset.get_seconds() as i64;
+                let utc = local - seconds * 1_000_000 - offset.get_microseconds() as i64;
+                Elem::AwareDateTime(Box::new(AwareDateTime { utc, offset: 0 }))
+            }
+            Err(_) => Elem::DateTime(local),
+        }
+    } else if let Ok(x) = ob.downcast::<PyDate>() {
+        Elem::Date(calendar::ordinal(x.get_year(), x.get_month(), x.get_day()).unwrap())
+    } else if ob.is_instance(DECIMAL.import(py, "decimal", "Decimal")?)? {
+        match Decimal::parse(ob.str()?.to_str()?) {
+            Some(x) => Elem::Decimal(Box::new(x)),
+            None => return Ok(None),
+        }
+    } else if ob.is_instance(FRACTION.import(py, "fractions", "Fraction")?)? {
+        let num = ob.getattr("numerator")?.extract::<i64>();
+        let den = ob.getattr("denominator")?.extract::<i64>();
+        match (num, den) {
+            (Ok(num), Ok(den)) => Elem::Fraction(Box::new(Fraction::new(num, den).unwrap())),
+            _ => return Ok(None),
+        }
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(elem))
+}
 
-    if type_name == "float" {
-        Ok(Elem::Float(ob.extract::<f64>()?))
-    } else if type_name == "int" {
+// Only the exact builtin types are converted, instances of subclasses such as
+// `IntEnum` are kept as `PyObj` so they come back with their type.
+#[cfg(feature = "python")]
+fn pyobject2elem(ob: &Bound<'_, PyAny>) -> PyResult<Elem> {
+    if ob.is_exact_instance_of::<PyInt>() {
         Ok(Elem::Int(ob.extract::<i64>()?))
-    } else if type_name == "str" {
-        Ok(Elem::String(ob.extract::<String>()?))
-    } else if type_name == "tuple" {
-        if let Ok((a, b)) = ob.extract::<(Elem, Elem)>() {
-            Ok(Elem::TwoTuple(Box::new(a), Box::new(b)))
+    } else if let Ok(x) = ob.downcast_exact::<PyFloat>() {
+        Ok(Elem::Float(x.value()))
+    } else if let Ok(s) = ob.downcast_exact::<PyString>() {
+        Ok(Elem::String(s.to_str()?.into()))
+    } else if let Ok(t) = ob.downcast_exact::<PyTuple>() {
+        if t.len() == 2 {
+            let a = pyobject2elem(&t.get_item(0)?)?;
+            let b = pyobject2elem(&t.get_item(1)?)?;
+            Ok(Elem::pair(a, b))
         } else {
-            Ok(Elem::Tuple(ob.extract::<Vec<Elem>>()?))
+            let items = t.iter().map(|x| pyobject2elem(&x));
+            Ok(Elem::tuple(items.collect::<PyResult<_>>()?))
         }
-    } else if type_name == "list" {
-        Ok(Elem::Vec(ob.extract::<Vec<Elem>>()?))
-    } else if type_name == "NoneType" {
+    } else if let Ok(l) = ob.downcast_exact::<PyList>() {
+        let items = l.iter().map(|x| pyobject2elem(&x));
+        Ok(Elem::list(items.collect::<PyResult<_>>()?))
+    } else if ob.is_none() {
         Ok(Elem::PyNone)
+    } else if let Ok(x) = ob.downcast_exact::<PyBool>() {
+        Ok(Elem::Bool(x.is_true()))
+    } else if let Ok(b) = ob.downcast_exact::<PyBytes>() {
+        Ok(Elem::bytes(b.as_bytes().to_vec()))
+    } else if let Ok(x) = ob.downcast_exact::<PyDateTime>() {
+        Ok(datetime2elem(x)?.unwrap_or_else(|| Elem::PyObj(ob.clone().unbind())))
+    } else if let Ok(x) = ob.downcast_exact::<PyDate>() {
+        let ordinal = calendar::ordinal(x.get_year(), x.get_month(), x.get_day());
+        Ok(Elem::Date(ordinal.unwrap()))
+    } else if ob
+        .get_type()
+        .is(DECIMAL.import(ob.py(), "decimal", "Decimal")?)
+    {
+        match Decimal::parse(ob.str()?.to_str()?) {
+            Some(x) => Ok(Elem::Decimal(Box::new(x))),
+            None => Ok(Elem::PyObj(ob.clone().unbind())),
+        }
+    } else if ob
+        .get_type()
+        .is(FRACTION.import(ob.py(), "fractions", "Fraction")?)
+    {
+        let num = ob.getattr("numerator")?.extract::<i64>();
+        let den = ob.getattr("denominator")?.extract::<i64>();
+        match (num, den) {
+            (Ok(num), Ok(den)) => Ok(Elem::Fraction(Box::new(Fraction::new(num, den).unwrap()))),
+            _ => Ok(Elem::PyObj(ob.clone().unbind())),
+        }
     } else {
-        Ok(Elem::PyObj(ob.to_object(ob.py())))
+        Ok(Elem::PyObj(ob.clone().unbind()))
     }
 }
 
+#[cfg(feature = "python")]
 impl Elem {
     pub fn to_pyobject(&self, py: Python<'_>) -> PyObject {
         elem2pyobject(self, py)
     }
+
+    pub fn clone_ref(&self, py: Python<'_>) -> Elem {
+        match self {
+            Elem::Float(x) => Elem::Float(*x),
+            Elem::Int(x) => Elem::Int(*x),
+            Elem::String(s) => Elem::String(s.clone()),
+            Elem::TwoTuple(pair) => {
+                Elem::TwoTuple(Box::new(pair.each_ref().map(|x| x.clone_ref(py))))
+            }
+            Elem::Tuple(v) => Elem::Tuple(Box::new(v.iter().map(|x| x.clone_ref(py)).collect())),
+            Elem::Vec(v) => Elem::Vec(Box::new(v.iter().map(|x| x.clone_ref(py)).collect())),
+            Elem::PyObj(obj) => Elem::PyObj(obj.clone_ref(py)),
+            x => x.clone(),
+        }
+    }
+
+    /// Whether the element holds no `PyObj`, so that comparing it never calls
+    /// back into Python and can happen with the GIL released.
+    pub fn is_native(&self) -> bool {
+        match self {
+            Elem::TwoTuple(pair) => pair.iter().all(Elem::is_native),
+            Elem::Tuple(v) | Elem::Vec(v) => v.iter().all(Elem::is_native),
+            Elem::PyObj(_) => false,
+            _ => true,
+        }
+    }
+
+    /// Copies the contents, `PyObj` payloads are deep copied with `memo`.
+    pub fn deepcopy(&self, py: Python<'_>, memo: &Bound<'_, PyAny>) -> PyResult<Elem> {
+        match self {
+            Elem::TwoTuple(pair) => {
+                let [a, b] = &**pair;
+                Ok(Elem::pair(a.deepcopy(py, memo)?, b.deepcopy(py, memo)?))
+            }
+            Elem::Tuple(v) => Ok(Elem::Tuple(Box::new(
+                v.iter()
+                    .map(|x| x.deepcopy(py, memo))
+                    .collect::<PyResult<_>>()?,
+            ))),
+            Elem::Vec(v) => Ok(Elem::Vec(Box::new(
+                v.iter()
+                    .map(|x| x.deepcopy(py, memo))
+                    .collect::<PyResult<_>>()?,
+            ))),
+            Elem::PyObj(obj) => {
+                let deepcopy = py.import("copy")?.getattr("deepcopy")?;
+                Ok(Elem::PyObj(deepcopy.call1((obj, memo))?.unbind()))
+            }
+            x => Ok(x.clone()),
+        }
+    }
+
+    /// Feeds the contents into `state`, consistently with `PartialEq` so that
+    /// equal numbers of any type hash alike. A `PyObj` that can equal a native
+    /// element hashes as that element, any other uses the Python `hash()`.
+    pub fn hash_into<H: Hasher>(&self, py: Python<'_>, state: &mut H) -> PyResult<()> {
+        if let Some(n) = self.number() {
+            number::hash(n, state);
+            return Ok(());
+        }
+        match self {
+            Elem::String(s) => {
+                2u8.hash(state);
+                s.hash(state);
+            }
+            Elem::TwoTuple(pair) => {
+                3u8.hash(state);
+                for x in pair.iter() {
+                    x.hash_into(py, state)?;
+                }
+            }
+            Elem::Tuple(v) | Elem::Vec(v) => {
+                let tag: u8 = if matches!(self, Elem::Tuple(_)) { 4 } else { 5 };
+                tag.hash(state);
+                v.len().hash(state);
+                for x in v.iter() {
+                    x.hash_into(py, state)?;
+                }
+            }
+            Elem::PyObj(obj) => match equivalent_elem(obj.bind(py))? {
+                Some(x) => x.hash_into(py, state)?,
+                None => {
+                    6u8.hash(state);
+                    obj.bind(py).hash()?.hash(state);
+                }
+            },
+            Elem::PyNone => 7u8.hash(state),
+            Elem::Bytes(b) => {
+                10u8.hash(state);
+                b.hash(state);
+            }
+            Elem::Date(x) => {
+                11u8.hash(state);
+                x.hash(state);
+            }
+            Elem::DateTime(x) => {
+                12u8.hash(state);
+                x.hash(state);
+            }
+            Elem::AwareDateTime(x) => {
+                13u8.hash(state);
+                x.utc.hash(state);
+            }
+            Elem::Int(_)
+            | Elem::Float(_)
+            | Elem::Bool(_)
+            | Elem::Decimal(_)
+            | Elem::Fraction(_) => {
+                unreachable!("numbers are hashed above")
+            }
+        }
+        Ok(())
+    }
 }
 
-impl IntoPy<PyObject> for Elem {
-    fn into_py(self, py: Python<'_>) -> PyObject {
-        elem2pyobject(&self, py)
+/// Combines per-item hashes so that the result doesn't depend on their order,
+/// the output is never -1 which Python reserves for errors.
+#[cfg(feature = "python")]
+pub fn hash_unordered(hashes: impl Iterator<Item = u64>) -> isize {
+    let (mut sum, mut len) = (0u64, 0usize);
+    for x in hashes {
+        sum = sum.wrapping_add(x);
+        len += 1;
+    }
+
+    let mut state = DefaultHasher::new();
+    (sum, len).hash(&mut state);
+    match state.finish() as isize {
+        -1 => -2,
+        x => x,
     }
 }
 
+#[cfg(feature = "python")]
+impl<'py> IntoPyObject<'py> for &Elem {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = Infallible;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        Ok(elem2pyobject(self, py).into_bound(py))
+    }
+}
+
+#[cfg(feature = "python")]
+impl<'py> IntoPyObject<'py> for Elem {
+    type Target = PyAny;
+    type Output = Bound<'py, PyAny>;
+    type Error = Infallible;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        (&self).into_pyobject(py)
+    }
+}
+
+#[cfg(feature = "python")]
 impl FromPyObject<'_> for Elem {
-    fn extract(ob: &PyAny) -> PyResult<Self> {
+    fn extract_bound(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
         pyobject2elem(ob)
     }
 }
@@ -87,23 +519,38 @@ impl PartialEq for Elem {
             (Elem::Int(a), Elem::Int(b)) => a == b,
             // String
             (Elem::String(a), Elem::String(b)) => a == b,
-            // Numbers: Mixed types
-            (Elem::Float(a), Elem::Int(b)) => *a == *b as f64,
-            (Elem::Int(a), Elem::Float(b)) => *a as f64 == *b as f64,
             // TwoTuple
-            (Elem::TwoTuple(a1, a2), Elem::TwoTuple(b1, b2)) => a1 == b1 && a2 == b2,
+            (Elem::TwoTuple(a), Elem::TwoTuple(b)) => a == b,
             // Tuple
             (Elem::Tuple(a), Elem::Tuple(b)) => a == b,
+            (Elem::TwoTuple(a), Elem::Tuple(b)) => a[..] == b[..],
+            (Elem::Tuple(a), Elem::TwoTuple(b)) => a[..] == b[..],
             // Vec
             (Elem::Vec(a), Elem::Vec(b)) => a == b,
+            // Bytes
+            (Elem::Bytes(a), Elem::Bytes(b)) => a == b,
+            // Dates and times, aware ones by instant
+            (Elem::Date(a), Elem::Date(b)) => a == b,
+            (Elem::DateTime(a), Elem::DateTime(b)) => a == b,
+            (Elem::AwareDateTime(a), Elem::AwareDateTime(b)) => a.utc == b.utc,
             // PyObjects
+            #[cfg(feature = "python")]
             (Elem::PyObj(a), Elem::PyObj(b)) => {
-                Python::with_gil(|py| -> PyResult<bool> { pyobject_eq(py, a, b) }).unwrap()
+                with_py(|py| pyobject_eq(py, a, b)).unwrap_or_else(|err| cmp_failed(err.into()))
+            }
+            #[cfg(feature = "python")]
+            (Elem::PyObj(_), _) | (_, Elem::PyObj(_)) => {
+                with_py(|py| pyobject_eq(py, &self.to_pyobject(py), &other.to_pyobject(py)))
+                    .unwrap_or_else(|err| cmp_failed(err.into()))
             }
             // PyNone
             (Elem::PyNone, Elem::PyNone) => true,
-            // All other cases
-            _ => false,
+            // Numbers: Mixed types
+            (a, b) => match (a.number(), b.number()) {
+                (Some(a), Some(b)) => number::cmp(a, b) == Some(Ordering::Equal),
+                // All other cases
+                _ => false,
+            },
         }
     }
 }
@@ -111,98 +558,260 @@ impl PartialEq for Elem {
 impl Eq for Elem {}
 
 impl PartialOrd for Elem {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match (self, other) {
-            // Numbers: Same type
-            (Elem::Float(a), Elem::Float(b)) => a.partial_cmp(b),
-            (Elem::Int(a), Elem::Int(b)) => a.partial_cmp(b),
-            // Strings
-            (Elem::String(a), Elem::String(b)) => a.partial_cmp(b),
-            // Numbers: Mixed types
-            (Elem::Int(a), Elem::Float(b)) => (*a as f64).partial_cmp(b),
-            (Elem::Float(a), Elem::Int(b)) => a.partial_cmp(&(*b as f64)),
-            // TwoTuple
-            (Elem::TwoTuple(a1, a2), Elem::TwoTuple(b1, b2)) => match a1.partial_cmp(b1) {
-                Some(std::cmp::Ordering::Equal) => a2.partial_cmp(b2),
-                x => x,
-            },
-            // Tuple
-            (Elem::Tuple(a), Elem::Tuple(b)) => a.partial_cmp(b),
-            // Vec
-            (Elem::Vec(a), Elem::Vec(b)) => a.partial_cmp(b),
-            // PyObjects
-            (Elem::PyObj(a), Elem::PyObj(b)) => {
-                Python::with_gil(|py| pyobject_partial_cmp(py, a, b)).unwrap()
-            }
-            (a, b) => panic!("Comparison not supported: {a:?} == {b:?}"),
-        }
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Elem {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    /// Elements that can't be ordered unwind to the enclosing `catch_cmp`,
+    /// which raises the error, and panic outside of one.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.try_cmp(other).unwrap_or_else(|err| cmp_failed(err))
+    }
+}
+
+/// Why two elements can't be ordered.
+#[derive(Debug)]
+pub enum CmpError {
+    /// Values Python can't order, such as a `str` and an `int` or a naive and
+    /// an aware `datetime`, described by their types.
+    Types(&'static str, &'static str),
+    /// A float NaN, which is neither less than, equal to nor greater than
+    /// anything.
+    NaN,
+    /// The comparison of Python objects raised.
+    #[cfg(feature = "python")]
+    Python(PyErr),
+}
+
+#[cfg(feature = "python")]
+impl From<PyErr> for CmpError {
+    fn from(err: PyErr) -> Self {
+        CmpError::Python(err)
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<CmpError> for PyErr {
+    fn from(err: CmpError) -> PyErr {
+        let msg = match err {
+            CmpError::Types("datetime", "aware datetime")
+            | CmpError::Types("aware datetime", "datetime") => {
+                "can't compare offset-naive and offset-aware datetimes".to_string()
+            }
+            CmpError::Types(a, b) => {
+                let (a, b) = (
+                    a.trim_start_matches("aware "),
+                    b.trim_start_matches("aware "),
+                );
+                format!("'<' not supported between instances of '{a}' and '{b}'")
+            }
+            CmpError::NaN => "a float NaN can't be ordered against other keys".to_string(),
+            CmpError::Python(err) => return err,
+        };
+        PyErr::new::<pyo3::exceptions::PyTypeError, _>(msg)
+    }
+}
+
+impl Elem {
+    /// `Ord::cmp`, or why the elements can't be ordered, so that callers can
+    /// raise the `TypeError` Python would instead of panicking.
+    pub fn try_cmp(&self, other: &Self) -> Result<Ordering, CmpError> {
         match (self, other) {
             // Numbers: Same type
-            (Elem::Float(a), Elem::Float(b)) => a.partial_cmp(b).unwrap(),
-            (Elem::Int(a), Elem::Int(b)) => a.partial_cmp(b).unwrap(),
+            (Elem::Float(a), Elem::Float(b)) => a.partial_cmp(b).ok_or(CmpError::NaN),
+            (Elem::Int(a), Elem::Int(b)) => Ok(a.cmp(b)),
             // Strings
-            (Elem::String(a), Elem::String(b)) => a.cmp(b),
-            // Numbers: Mixed types
-            (Elem::Float(a), Elem::Int(b)) => a.partial_cmp(&(*b as f64)).unwrap(),
-            (Elem::Int(a), Elem::Float(b)) => (*a as f64).partial_cmp(b).unwrap(),
-            // TwoTuple
-            (Elem::TwoTuple(a1, a2), Elem::TwoTuple(b1, b2)) => match a1.partial_cmp(b1) {
-                Some(std::cmp::Ordering::Equal) => a2.partial_cmp(b2).unwrap(),
-                x => x.unwrap(),
-            },
-            // Tuple
-            (Elem::Tuple(a), Elem::Tuple(b)) => a.cmp(b),
+            (Elem::String(a), Elem::String(b)) => Ok(a.cmp(b)),
+            // Tuples of any length order alike
+            (Elem::TwoTuple(a), Elem::TwoTuple(b)) => try_cmp_slices(&a[..], &b[..]),
+            (Elem::Tuple(a), Elem::Tuple(b)) => try_cmp_slices(a, b),
+            (Elem::TwoTuple(a), Elem::Tuple(b)) => try_cmp_slices(&a[..], b),
+            (Elem::Tuple(a), Elem::TwoTuple(b)) => try_cmp_slices(a, &b[..]),
             // Vec
-            (Elem::Vec(a), Elem::Vec(b)) => a.cmp(b),
+            (Elem::Vec(a), Elem::Vec(b)) => try_cmp_slices(a, b),
+            // Bytes
+            (Elem::Bytes(a), Elem::Bytes(b)) => Ok(a.cmp(b)),
+            // Dates and times
+            (Elem::Date(a), Elem::Date(b)) => Ok(a.cmp(b)),
+            (Elem::DateTime(a), Elem::DateTime(b)) => Ok(a.cmp(b)),
+            (Elem::AwareDateTime(a), Elem::AwareDateTime(b)) => Ok(a.utc.cmp(&b.utc)),
             // PyObjects
-            (Elem::PyObj(a), Elem::PyObj(b)) => {
-                Python::with_gil(|py| pyobject_cmp(py, a, b)).unwrap()
-            }
-            (a, b) => panic!("Comparison not supported: {a:?} == {b:?}"),
+            #[cfg(feature = "python")]
+            (Elem::PyObj(a), Elem::PyObj(b)) => Ok(with_py(|py| pyobject_cmp(py, a, b))?),
+            #[cfg(feature = "python")]
+            (Elem::PyObj(_), _) | (_, Elem::PyObj(_)) => Ok(with_py(|py| {
+                pyobject_cmp(py, &self.to_pyobject(py), &other.to_pyobject(py))
+            })?),
+            // Numbers: Mixed types
+            (a, b) => match (a.number(), b.number()) {
+                (Some(x), Some(y)) => number::cmp(x, y).ok_or(CmpError::NaN),
+                _ => Err(CmpError::Types(a.type_name(), b.type_name())),
+            },
+        }
+    }
+
+    /// The name of the Python type of a native element.
+    fn type_name(&self) -> &'static str {
+        match self {
+            Elem::Float(_) => "float",
+            Elem::Int(_) => "int",
+            Elem::String(_) => "str",
+            Elem::TwoTuple(_) | Elem::Tuple(_) => "tuple",
+            Elem::Vec(_) => "list",
+            Elem::Bool(_) => "bool",
+            Elem::Bytes(_) => "bytes",
+            Elem::Date(_) => "date",
+            Elem::DateTime(_) => "datetime",
+            Elem::AwareDateTime(_) => "aware datetime",
+            Elem::Decimal(_) => "Decimal",
+            Elem::Fraction(_) => "Fraction",
+            #[cfg(feature = "python")]
+            Elem::PyObj(_) => "object",
+            Elem::PyNone => "NoneType",
         }
     }
 }
 
-fn pyobject_eq(py: Python, a: &PyObject, b: &PyObject) -> PyResult<bool> {
-    let a = a.downcast::<PyAny>(py)?;
-    let b = b.downcast::<PyAny>(py)?;
-    a.eq(b)
+fn try_cmp_slices(a: &[Elem], b: &[Elem]) -> Result<Ordering, CmpError> {
+    for (x, y) in a.iter().zip(b) {
+        match x.try_cmp(y)? {
+            Ordering::Equal => continue,
+            ordering => return Ok(ordering),
+        }
+    }
+    Ok(a.len().cmp(&b.len()))
 }
 
-fn pyobject_partial_cmp(
-    py: Python,
-    a: &PyObject,
-    b: &PyObject,
-) -> PyResult<Option<std::cmp::Ordering>> {
-    let a = a.downcast::<PyAny>(py)?;
-    let b = b.downcast::<PyAny>(py)?;
+#[cfg(not(feature = "python"))]
+fn cmp_failed(err: CmpError) -> ! {
+    panic!("Comparison not supported: {err:?}")
+}
 
-    if a.lt(b)? {
-        Ok(Some(std::cmp::Ordering::Less))
-    } else if a.gt(b)? {
-        Ok(Some(std::cmp::Ordering::Greater))
-    } else if a.eq(b)? {
-        Ok(Some(std::cmp::Ordering::Equal))
+#[cfg(feature = "python")]
+fn cmp_failed(err: CmpError) -> ! {
+    if CATCHING.with(Cell::get) == 0 {
+        panic!("Comparison not supported: {err:?}")
+    }
+    let err = PyErr::from(err);
+    CMP_ERROR.with(|slot| slot.replace(Some(err)));
+    // unlike `panic!`, doesn't report the unwinding to the panic hook
+    std::panic::resume_unwind(Box::new(CmpUnwind))
+}
+
+/// Payload of the unwinding from a failed comparison to `catch_cmp`.
+#[cfg(feature = "python")]
+struct CmpUnwind;
+
+/// Runs `f`, raising the error of the first comparison in it that fails
+/// instead of panicking. `Ord` has no way to report an error, so a failed
+/// comparison unwinds out of the tree operation. The standard collections
+/// and `PersistentMap` only compare while searching, before they change
+/// anything, so the tree is left as it was. Operations that change a tree in
+/// several steps must check their keys up front.
+#[cfg(feature = "python")]
+pub fn catch_cmp<R>(f: impl FnOnce() -> PyResult<R>) -> PyResult<R> {
+    struct Catching;
+
+    impl Drop for Catching {
+        fn drop(&mut self) {
+            CATCHING.with(|n| n.set(n.get() - 1));
+        }
+    }
+
+    CATCHING.with(|n| n.set(n.get() + 1));
+    let catching = Catching;
+    let output = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    drop(catching);
+
+    match output {
+        Ok(output) => output,
+        Err(payload) if payload.is::<CmpUnwind>() => {
+            Err(CMP_ERROR.with(|slot| slot.take()).unwrap())
+        }
+        Err(payload) => std::panic::resume_unwind(payload),
+    }
+}
+
+#[cfg(feature = "python")]
+thread_local! {
+    /// How many `Attached` guards the current thread holds.
+    static ATTACHED: Cell<usize> = const { Cell::new(0) };
+    /// How many `catch_cmp` calls are running on the current thread.
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+    /// The error of the comparison unwinding to the innermost `catch_cmp`.
+    static CMP_ERROR: RefCell<Option<PyErr>> = const { RefCell::new(None) };
+}
+
+/// Lets comparisons of `PyObj` elements on the current thread use the GIL it
+/// already holds instead of acquiring it on every comparison, which dominates
+/// a tree descent over keys such as `datetime` or `Decimal`. Held by the tree
+/// lock guards for the duration of an operation.
+#[cfg(feature = "python")]
+pub struct Attached {
+    // not `Send`, the count belongs to the thread that created it
+    _thread: std::marker::PhantomData<std::sync::MutexGuard<'static, ()>>,
+}
+
+#[cfg(feature = "python")]
+pub fn attach(_py: Python<'_>) -> Attached {
+    ATTACHED.with(|n| n.set(n.get() + 1));
+    Attached {
+        _thread: std::marker::PhantomData,
+    }
+}
+
+#[cfg(feature = "python")]
+impl Drop for Attached {
+    fn drop(&mut self) {
+        ATTACHED.with(|n| n.set(n.get() - 1));
+    }
+}
+
+/// `Python::allow_threads` for code that may hold an `Attached` guard, so that
+/// a comparison of `PyObj` elements inside `f` acquires the GIL again.
+#[cfg(feature = "python")]
+pub fn allow_threads<T: Ungil, F: Ungil + FnOnce() -> T>(py: Python<'_>, f: F) -> T {
+    struct Restore(usize);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            ATTACHED.with(|n| n.set(self.0));
+        }
+    }
+
+    let _restore = Restore(ATTACHED.with(|n| n.replace(0)));
+    py.allow_threads(f)
+}
+
+#[cfg(feature = "python")]
+fn with_py<R>(f: impl FnOnce(Python<'_>) -> R) -> R {
+    if ATTACHED.with(Cell::get) > 0 {
+        // SAFETY: an `Attached` guard was created from a `Python` token on this
+        // thread and `allow_threads` clears the count while the GIL is released.
+        f(unsafe { Python::assume_gil_acquired() })
     } else {
-        Ok(None)
+        Python::with_gil(f)
     }
 }
 
-fn pyobject_cmp(py: Python, a: &PyObject, b: &PyObject) -> PyResult<std::cmp::Ordering> {
-    let a = a.downcast::<PyAny>(py)?;
-    let b = b.downcast::<PyAny>(py)?;
+#[cfg(feature = "python")]
+fn pyobject_eq(py: Python, a: &PyObject, b: &PyObject) -> PyResult<bool> {
+    let (a, b) = (a.bind(py), b.bind(py));
+    a.eq(b)
+}
+
+#[cfg(feature = "python")]
+fn pyobject_cmp(py: Python, a: &PyObject, b: &PyObject) -> PyResult<Ordering> {
+    let (a, b) = (a.bind(py), b.bind(py));
 
     if a.lt(b)? {
-        Ok(std::cmp::Ordering::Less)
+        Ok(Ordering::Less)
     } else if a.gt(b)? {
-        Ok(std::cmp::Ordering::Greater)
+        Ok(Ordering::Greater)
     } else {
-        Ok(std::cmp::Ordering::Equal)
+        Ok(Ordering::Equal)
     }
 }
 
@@ -211,12 +820,46 @@ fn pyobject_cmp(py: Python, a: &PyObject, b: &PyObject) -> PyResult<std::cmp::Or
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_ordering() {
+        assert_eq!(Elem::Int(1).cmp(&Elem::Float(1.5)), Ordering::Less);
+        assert_eq!(Elem::Float(2.0), Elem::Int(2));
+        assert_eq!(
+            Elem::String("a".into()).cmp(&Elem::String("b".into())),
+            Ordering::Less
+        );
+
+        let pair = |a, b| Elem::pair(Elem::Int(a), Elem::Int(b));
+        assert_eq!(pair(1, 5).cmp(&pair(2, 0)), Ordering::Less);
+        assert_eq!(pair(1, 5).cmp(&pair(1, 4)), Ordering::Greater);
+
+        let elems = vec![Elem::Int(3), Elem::PyNone];
+        assert_eq!(Elem::list(elems.clone()), Elem::list(elems));
+        assert_ne!(Elem::PyNone, Elem::Int(0));
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(std::mem::size_of::<Elem>(), 16);
+    }
+
+    #[test]
+    #[should_panic(expected = "Comparison not supported")]
+    fn test_mixed_types() {
+        let _ = Elem::Int(1).cmp(&Elem::String("1".into()));
+    }
+}
+
+#[cfg(all(test, feature = "python"))]
+mod python_tests {
+    use super::*;
+
     fn make_elem_from_python(code: &str) -> Elem {
         Python::with_gil(|py| {
-            py.eval(code, None, None)
+            let code = std::ffi::CString::new(code).unwrap();
+            py.eval(&code, None, None)
                 .unwrap()
-                .into_py(py)
-                .extract::<Elem>(py)
+                .extract::<Elem>()
                 .unwrap()
         })
     }
@@ -238,27 +881,28 @@ mod tests {
 
         // String
         let elem = make_elem_from_python("'hello'");
-        assert_eq!(elem, Elem::String("hello".to_string()));
+        assert_eq!(elem, Elem::String("hello".into()));
 
         // TwoTuple
         let elem = make_elem_from_python("(1, 2)");
-        assert_eq!(
-            elem,
-            Elem::TwoTuple(Box::new(Elem::Int(1)), Box::new(Elem::Int(2)))
-        );
+        assert_eq!(elem, Elem::pair(Elem::Int(1), Elem::Int(2)));
 
         // Tuple
         let elem = make_elem_from_python("(1, 2, 3)");
         assert_eq!(
             elem,
-            Elem::Tuple(vec![Elem::Int(1), Elem::Int(2), Elem::Int(3),])
+            Elem::tuple(vec![Elem::Int(1), Elem::Int(2), Elem::Int(3),])
         );
+        Python::with_gil(|py| {
+            let ob = elem.to_pyobject(py).into_bound(py);
+            assert!(ob.is_exact_instance_of::<PyTuple>());
+        });
 
         // Vec
         let elem = make_elem_from_python("[1, 2, 3]");
         assert_eq!(
             elem,
-            Elem::Vec(vec![Elem::Int(1), Elem::Int(2), Elem::Int(3),])
+            Elem::list(vec![Elem::Int(1), Elem::Int(2), Elem::Int(3),])
         );
 
         // PyObj
@@ -272,4 +916,195 @@ mod tests {
         let elem = make_elem_from_python("None");
         assert_eq!(elem, Elem::PyNone);
     }
+
+    #[test]
+    fn test_conversion_native_types() {
+        let cases = [
+            ("True", Elem::Bool(true)),
+            ("b'ab'", Elem::bytes(b"ab".to_vec())),
+            ("__import__('datetime').date(2024, 2, 29)", Elem::Date(738_945)),
+            (
+                "__import__('datetime').datetime(1, 1, 2, 0, 0, 1, 5)",
+                Elem::DateTime(86_401_000_005),
+            ),
+            (
+                "__import__('datetime').datetime(1, 1, 2, 1, tzinfo=__import__('datetime').timezone.utc)",
+                Elem::AwareDateTime(Box::new(AwareDateTime {
+                    utc: 90_000_000_000,
+                    offset: 0,
+                })),
+            ),
+            (
+                "__import__('decimal').Decimal('-1.50')",
+                Elem::Decimal(Box::new(Decimal::parse("-1.50").unwrap())),
+            ),
+            (
+                "__import__('fractions').Fraction(2, -4)",
+                Elem::Fraction(Box::new(Fraction::new(-1, 2).unwrap())),
+            ),
+        ];
+        Python::with_gil(|py| {
+            for (code, expected) in cases {
+                let ob = py
+                    .eval(&std::ffi::CString::new(code).unwrap(), None, None)
+                    .unwrap();
+                let elem = ob.extract::<Elem>().unwrap();
+                assert_eq!(format!("{elem:?}"), format!("{expected:?}"), "{code}");
+                // back to an equal object of the same type
+                let output = elem.to_pyobject(py).into_bound(py);
+                assert!(output.get_type().is(ob.get_type()), "{code}");
+                assert!(output.eq(&ob).unwrap(), "{code}");
+            }
+        });
+
+        // values without an exact native form stay Python objects
+        for code in [
+            "__import__('decimal').Decimal('nan')",
+            "__import__('fractions').Fraction(2**70, 3)",
+            "__import__('datetime').datetime(2024, 1, 1, fold=1)",
+            "__import__('datetime').datetime(2024, 1, 1, tzinfo=__import__('datetime').timezone(__import__('datetime').timedelta(hours=1), 'CET'))",
+        ] {
+            match make_elem_from_python(code) {
+                Elem::PyObj(_) => (),
+                elem => panic!("Expected PyObj for {code}, got {elem:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_native_comparisons() {
+        let elem = make_elem_from_python;
+        assert_eq!(elem("True"), elem("1"));
+        assert_eq!(elem("False"), elem("0.0"));
+        assert_eq!(elem("True").cmp(&elem("2")), Ordering::Less);
+        assert_eq!(elem("__import__('decimal').Decimal('0.5')"), elem("0.5"));
+        assert_eq!(
+            elem("__import__('fractions').Fraction(1, 3)").cmp(&elem("0.3333333333333333")),
+            Ordering::Greater
+        );
+        assert_eq!(
+            elem("__import__('decimal').Decimal('0.1')").cmp(&elem("0.1")),
+            Ordering::Less
+        );
+
+        // aware datetimes are equal at the same instant
+        let utc = elem("__import__('datetime').datetime(2024, 1, 1, 12, tzinfo=__import__('datetime').timezone.utc)");
+        let plus_one = elem("__import__('datetime').datetime(2024, 1, 1, 13, tzinfo=__import__('datetime').timezone(__import__('datetime').timedelta(hours=1)))");
+        assert_eq!(utc, plus_one);
+
+        // and never equal to naive ones, which can't be ordered against them
+        let naive = elem("__import__('datetime').datetime(2024, 1, 1, 12)");
+        assert_ne!(utc, naive);
+        assert!(utc.try_cmp(&naive).is_err());
+        Python::with_gil(|py| {
+            let err = PyErr::from(naive.try_cmp(&utc).unwrap_err());
+            assert!(err.is_instance_of::<pyo3::exceptions::PyTypeError>(py));
+            assert!(err.to_string().contains("offset-naive and offset-aware"));
+
+            let pair = |x: &Elem| Elem::pair(Elem::Int(1), x.clone());
+            let err = PyErr::from(pair(&elem("'a'")).try_cmp(&pair(&elem("2"))).unwrap_err());
+            assert!(err.to_string().contains("'str' and 'int'"));
+            assert!(elem("True").try_cmp(&elem("2.5")).is_ok());
+
+            let err = PyErr::from(elem("float('nan')").try_cmp(&elem("1")).unwrap_err());
+            assert!(err.to_string().contains("NaN"));
+
+            // a failed comparison inside a tree operation comes back as the error
+            let mut set = std::collections::BTreeSet::from([pair(&elem("'a'"))]);
+            let err = catch_cmp(|| Ok(set.insert(pair(&elem("2"))))).unwrap_err();
+            assert!(err.is_instance_of::<pyo3::exceptions::PyTypeError>(py));
+            assert_eq!(set.len(), 1);
+        });
+
+        // tuples of different lengths compare like slices
+        let (short, long) = (elem("(1, 2)"), elem("(1, 2, 3)"));
+        assert_eq!(short.cmp(&long), Ordering::Less);
+        assert_eq!(long.cmp(&short), Ordering::Greater);
+        assert_ne!(short, long);
+        assert_eq!(elem("(1, 3)").cmp(&long), Ordering::Greater);
+
+        // equal elements hash alike
+        let hash = |x: &Elem| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            Python::with_gil(|py| x.hash_into(py, &mut hasher)).unwrap();
+            hasher.finish()
+        };
+        assert_eq!(hash(&elem("True")), hash(&elem("1.0")));
+        assert_eq!(
+            hash(&elem("__import__('decimal').Decimal('2.50')")),
+            hash(&elem("__import__('fractions').Fraction(5, 2)"))
+        );
+        assert_eq!(hash(&utc), hash(&plus_one));
+
+        // as do Python objects equal to native elements
+        let pyobjs = [
+            ("__import__('enum').IntEnum('E', 'A').A", "1"),
+            ("type('str', (str,), {})('a')", "'a'"),
+            ("type('tuple', (tuple,), {})((1, 2, 3))", "(1, 2, 3)"),
+            (
+                "__import__('datetime').datetime(2024, 1, 1, 13, tzinfo=__import__('datetime').timezone(__import__('datetime').timedelta(hours=1), 'CET'))",
+                "__import__('datetime').datetime(2024, 1, 1, 12, tzinfo=__import__('datetime').timezone.utc)",
+            ),
+        ];
+        for (a, b) in pyobjs {
+            let (a, b) = (elem(a), elem(b));
+            assert!(matches!(a, Elem::PyObj(_)));
+            assert_eq!(a, b);
+            assert_eq!(hash(&a), hash(&b));
+        }
+    }
+
+    #[test]
+    fn test_conversion_subclasses() {
+        for code in [
+            "__import__('enum').IntEnum('E', 'A').A",
+            "type('str', (str,), {})('a')",
+            "type('tuple', (tuple,), {})((1, 2))",
+        ] {
+            match make_elem_from_python(code) {
+                Elem::PyObj(_) => (),
+                elem => panic!("Expected PyObj for {code}, got {elem:?}"),
+            }
+        }
+
+        // tuples are converted in a single pass, nested ones included
+        let elem = make_elem_from_python("(1, (2, 'a', None), [3.0])");
+        assert_eq!(
+            elem,
+            Elem::tuple(vec![
+                Elem::Int(1),
+                Elem::tuple(vec![Elem::Int(2), Elem::String("a".into()), Elem::PyNone]),
+                Elem::list(vec![Elem::Float(3.0)]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_is_native() {
+        assert!(make_elem_from_python("(1, 'a', [2.0, None])").is_native());
+        assert!(!make_elem_from_python("{'a': 1}").is_native());
+        assert!(!make_elem_from_python("(1, {'a': 1})").is_native());
+        assert!(!make_elem_from_python("[1, 2, {'a': 1}]").is_native());
+    }
+
+    #[test]
+    fn test_attached_comparisons() {
+        let a = make_elem_from_python("frozenset({1})");
+        let b = make_elem_from_python("frozenset({1, 2})");
+
+        Python::with_gil(|py| {
+            let _attached = attach(py);
+            assert_eq!(ATTACHED.with(Cell::get), 1);
+            assert_eq!(a.cmp(&b), Ordering::Less);
+
+            // comparing without the GIL reacquires it
+            allow_threads(py, || {
+                assert_eq!(ATTACHED.with(Cell::get), 0);
+                assert_eq!(b.partial_cmp(&a), Some(Ordering::Greater));
+            });
+            assert_eq!(ATTACHED.with(Cell::get), 1);
+        });
+        assert_eq!(ATTACHED.with(Cell::get), 0);
+        assert_ne!(a, b);
+    }
 }
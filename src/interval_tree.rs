@@ -0,0 +1,425 @@
+use crate::elem::Elem;
+use std::cmp::Ordering;
+use std::panic::{self, AssertUnwindSafe};
+
+// AVL tree of half-open `[start, end)` intervals ordered by `(start, end)`.
+// Nodes live in an arena and refer to each other by index, every node caches
+// the index of the node with the largest `end` in its subtree so queries can
+// skip subtrees that end before the queried range.
+
+#[derive(Clone)]
+pub struct Node {
+    pub start: Elem,
+    pub end: Elem,
+    pub value: Elem,
+    left: Option<usize>,
+    right: Option<usize>,
+    height: usize,
+    max_end: usize,
+}
+
+#[derive(Clone, Default)]
+pub struct IntervalTree {
+    nodes: Vec<Option<Node>>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    len: usize,
+}
+
+impl IntervalTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Adds an interval, intervals equal to existing ones are placed after them.
+    pub fn insert(&mut self, start: Elem, end: Elem, value: Elem) {
+        let node = Node {
+            start,
+            end,
+            value,
+            left: None,
+            right: None,
+            height: 1,
+            max_end: 0,
+        };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = Some(node);
+                index
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        self.node_mut(index).max_end = index;
+
+        // links only change once every comparison on the path succeeded, so a
+        // failed comparison just has to give the slot back
+        let root = panic::catch_unwind(AssertUnwindSafe(|| self.insert_at(self.root, index)));
+        match root {
+            Ok(root) => self.root = Some(root),
+            Err(payload) => {
+                self.nodes[index] = None;
+                self.free.push(index);
+                panic::resume_unwind(payload);
+            }
+        }
+        self.len += 1;
+    }
+
+    /// Removes the first interval equal to `[start, end)` and returns it.
+    pub fn remove(&mut self, start: &Elem, end: &Elem) -> Option<Node> {
+        let (root, removed) = self.remove_at(self.root, start, end);
+        self.root = root;
+
+        let removed = removed?;
+        self.free.push(removed);
+        self.len -= 1;
+        self.nodes[removed].take()
+    }
+
+    /// Returns the intervals overlapping `[start, end)` in order.
+    pub fn overlap(&self, start: &Elem, end: &Elem) -> Vec<&Node> {
+        let mut output = Vec::new();
+        self.search(self.root, start, &|x| x < end, &mut output);
+        output
+    }
+
+    /// Returns the intervals containing `point` in order.
+    pub fn at(&self, point: &Elem) -> Vec<&Node> {
+        let mut output = Vec::new();
+        self.search(self.root, point, &|x| x <= point, &mut output);
+        output
+    }
+
+    pub fn iter(&self) -> Vec<&Node> {
+        let mut output = Vec::with_capacity(self.len);
+        let mut stack = Vec::new();
+        let mut current = self.root;
+
+        while current.is_some() || !stack.is_empty() {
+            while let Some(index) = current {
+                stack.push(index);
+                current = self.node(index).left;
+            }
+            let index = stack.pop().unwrap();
+            output.push(self.node(index));
+            current = self.node(index).right;
+        }
+
+        output
+    }
+
+    fn node(&self, index: usize) -> &Node {
+        self.nodes[index].as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, index: usize) -> &mut Node {
+        self.nodes[index].as_mut().unwrap()
+    }
+
+    fn cmp_nodes(&self, a: usize, b: usize) -> Ordering {
+        let (a, b) = (self.node(a), self.node(b));
+        a.start.cmp(&b.start).then_with(|| a.end.cmp(&b.end))
+    }
+
+    /// Collects the nodes whose end is greater than `after` and whose start
+    /// satisfies `starts_before`, which must be monotonic over the starts.
+    fn search<'a>(
+        &'a self,
+        index: Option<usize>,
+        after: &Elem,
+        starts_before: &dyn Fn(&Elem) -> bool,
+        output: &mut Vec<&'a Node>,
+    ) {
+        let index = match index {
+            Some(index) => index,
+            None => return,
+        };
+        let node = self.node(index);
+        if &self.node(node.max_end).end <= after {
+            return;
+        }
+
+        self.search(node.left, after, starts_before, output);
+        if !starts_before(&node.start) {
+            return;
+        }
+        if &node.end > after {
+            output.push(node);
+        }
+        self.search(node.right, after, starts_before, output);
+    }
+
+    fn insert_at(&mut self, root: Option<usize>, index: usize) -> usize {
+        let root = match root {
+            Some(root) => root,
+            None => return index,
+        };
+
+        if self.cmp_nodes(index, root) == Ordering::Less {
+            let left = self.insert_at(self.node(root).left, index);
+            self.node_mut(root).left = Some(left);
+        } else {
+            let right = self.insert_at(self.node(root).right, index);
+            self.node_mut(root).right = Some(right);
+        }
+
+        self.rebalance(root)
+    }
+
+    fn remove_at(
+        &mut self,
+        root: Option<usize>,
+        start: &Elem,
+        end: &Elem,
+    ) -> (Option<usize>, Option<usize>) {
+        let root = match root {
+            Some(root) => root,
+            None => return (None, None),
+        };
+        let node = self.node(root);
+        let ordering = start.cmp(&node.start).then_with(|| end.cmp(&node.end));
+
+        let removed = match ordering {
+            Ordering::Less => {
+                let (left, removed) = self.remove_at(node.left, start, end);
+                self.node_mut(root).left = left;
+                removed
+            }
+            Ordering::Greater => {
+                let (right, removed) = self.remove_at(node.right, start, end);
+                self.node_mut(root).right = right;
+                removed
+            }
+            Ordering::Equal => {
+                // rotations can move equal intervals to the left, remove the
+                // first one in order
+                let (left, removed) = self.remove_at(node.left, start, end);
+                self.node_mut(root).left = left;
+                if removed.is_some() {
+                    removed
+                } else {
+                    return (self.unlink(root), Some(root));
+                }
+            }
+        };
+
+        (Some(self.rebalance(root)), removed)
+    }
+
+    /// Detaches `index` from its children and returns the root of the subtree
+    /// that replaces it.
+    fn unlink(&mut self, index: usize) -> Option<usize> {
+        let node = self.node(index);
+        let (left, right) = (node.left, node.right);
+
+        match (left, right) {
+            (None, child) | (child, None) => child,
+            (Some(left), Some(right)) => {
+                let (right, min) = self.remove_min(right);
+                let successor = self.node_mut(min);
+                successor.left = Some(left);
+                successor.right = right;
+                Some(self.rebalance(min))
+            }
+        }
+    }
+
+    fn remove_min(&mut self, root: usize) -> (Option<usize>, usize) {
+        match self.node(root).left {
+            None => (self.node(root).right, root),
+            Some(left) => {
+                let (left, min) = self.remove_min(left);
+                self.node_mut(root).left = left;
+                (Some(self.rebalance(root)), min)
+            }
+        }
+    }
+
+    fn height(&self, index: Option<usize>) -> usize {
+        index.map_or(0, |x| self.node(x).height)
+    }
+
+    fn update(&mut self, index: usize) {
+        let node = self.node(index);
+        let (left, right) = (node.left, node.right);
+        let height = 1 + self.height(left).max(self.height(right));
+
+        let mut max_end = index;
+        for child in [left, right].into_iter().flatten() {
+            let child_max = self.node(child).max_end;
+            if self.node(child_max).end > self.node(max_end).end {
+                max_end = child_max;
+            }
+        }
+
+        let node = self.node_mut(index);
+        node.height = height;
+        node.max_end = max_end;
+    }
+
+    fn rotate_left(&mut self, index: usize) -> usize {
+        let right = self.node(index).right.unwrap();
+        self.node_mut(index).right = self.node(right).left;
+        self.node_mut(right).left = Some(index);
+        self.update(index);
+        self.update(right);
+        right
+    }
+
+    fn rotate_right(&mut self, index: usize) -> usize {
+        let left = self.node(index).left.unwrap();
+        self.node_mut(index).left = self.node(left).right;
+        self.node_mut(left).right = Some(index);
+        self.update(index);
+        self.update(left);
+        left
+    }
+
+    fn balance_factor(&self, index: usize) -> isize {
+        let node = self.node(index);
+        self.height(node.left) as isize - self.height(node.right) as isize
+    }
+
+    fn rebalance(&mut self, index: usize) -> usize {
+        self.update(index);
+
+        match self.balance_factor(index) {
+            2.. => {
+                let left = self.node(index).left.unwrap();
+                if self.balance_factor(left) < 0 {
+                    let left = self.rotate_left(left);
+                    self.node_mut(index).left = Some(left);
+                }
+                self.rotate_right(index)
+            }
+            ..=-2 => {
+                let right = self.node(index).right.unwrap();
+                if self.balance_factor(right) > 0 {
+                    let right = self.rotate_right(right);
+                    self.node_mut(index).right = Some(right);
+                }
+                self.rotate_left(index)
+            }
+            _ => index,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_invariants(tree: &IntervalTree, index: Option<usize>) -> usize {
+        let index = match index {
+            Some(index) => index,
+            None => return 0,
+        };
+        let node = tree.node(index);
+        let left = check_invariants(tree, node.left);
+        let right = check_invariants(tree, node.right);
+
+        assert!(left.abs_diff(right) <= 1);
+        assert_eq!(node.height, 1 + left.max(right));
+
+        let max_end = tree
+            .iter_subtree(index)
+            .into_iter()
+            .map(|x| &x.end)
+            .max()
+            .unwrap();
+        assert_eq!(&tree.node(node.max_end).end, max_end);
+
+        node.height
+    }
+
+    impl IntervalTree {
+        fn iter_subtree(&self, index: usize) -> Vec<&Node> {
+            let node = self.node(index);
+            let mut output = Vec::new();
+            if let Some(left) = node.left {
+                output.extend(self.iter_subtree(left));
+            }
+            output.push(node);
+            if let Some(right) = node.right {
+                output.extend(self.iter_subtree(right));
+            }
+            output
+        }
+    }
+
+    fn intervals(nodes: Vec<&Node>) -> Vec<(i64, i64)> {
+        nodes
+            .into_iter()
+            .map(|x| match (&x.start, &x.end) {
+                (Elem::Int(a), Elem::Int(b)) => (*a, *b),
+                _ => panic!("Expected Int"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_interval_tree() {
+        let mut tree = IntervalTree::new();
+        let mut expected = Vec::new();
+
+        // deterministic pseudo-random intervals
+        let mut seed: i64 = 7;
+        for _ in 0..200 {
+            seed = (seed * 1103515245 + 12345) % 2147483648;
+            let start = seed % 100;
+            let end = start + 1 + seed % 13;
+            tree.insert(Elem::Int(start), Elem::Int(end), Elem::PyNone);
+            expected.push((start, end));
+            check_invariants(&tree, tree.root);
+        }
+        expected.sort();
+        assert_eq!(intervals(tree.iter()), expected);
+
+        let overlap = expected
+            .iter()
+            .copied()
+            .filter(|(start, end)| *start < 40 && *end > 30)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            intervals(tree.overlap(&Elem::Int(30), &Elem::Int(40))),
+            overlap
+        );
+
+        let at = expected
+            .iter()
+            .copied()
+            .filter(|(start, end)| *start <= 50 && *end > 50)
+            .collect::<Vec<_>>();
+        assert_eq!(intervals(tree.at(&Elem::Int(50))), at);
+
+        for (start, end) in expected.iter().step_by(2) {
+            assert!(tree.remove(&Elem::Int(*start), &Elem::Int(*end)).is_some());
+            check_invariants(&tree, tree.root);
+        }
+        assert!(tree.remove(&Elem::Int(1000), &Elem::Int(1001)).is_none());
+
+        let remaining = expected
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(intervals(tree.iter()), remaining);
+        assert_eq!(tree.len(), remaining.len());
+    }
+}
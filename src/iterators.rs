@@ -1,27 +1,166 @@
-use std::collections::{btree_map, btree_set};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::ops::Bound;
 
-use crate::elem::Elem;
+use crate::elem::{self, Elem};
+use crate::persistent_map::{self, PersistentMap};
+use pyo3::exceptions;
 use pyo3::prelude::*;
 
+/// Number of entries copied out of a tree each time a cursor's buffer runs
+/// dry.
+pub const BATCH_SIZE: usize = 256;
+
+pub type KeyRange<'a> = (Bound<&'a Elem>, Bound<&'a Elem>);
+
+/// Copies out the entries of `owner` in `range`, at most `BATCH_SIZE` of
+/// them, taking whatever lock the owner needs for the duration of the call.
+pub type Fetch<T> = fn(Python, &PyObject, KeyRange) -> PyResult<Vec<(Elem, T)>>;
+
+/// Iterates over a tree in key order without borrowing it between calls:
+/// entries are fetched in batches that start after the last key seen, so
+/// updates made while iterating are picked up by the following batches.
+pub struct Cursor<T> {
+    pub owner: PyObject,
+    start: Bound<Elem>,
+    stop: Option<Elem>,
+    buffer: VecDeque<(Elem, T)>,
+    fetch: Fetch<T>,
+}
+
+impl<T> Cursor<T> {
+    pub fn new(owner: PyObject, fetch: Fetch<T>) -> Self {
+        Cursor::range(owner, None, None, fetch)
+    }
+
+    /// Cursor over the keys in `[start, stop)`.
+    pub fn range(
+        owner: PyObject,
+        start: Option<Elem>,
+        stop: Option<Elem>,
+        fetch: Fetch<T>,
+    ) -> Self {
+        Cursor {
+            owner,
+            start: start.map_or(Bound::Unbounded, Bound::Included),
+            stop,
+            buffer: VecDeque::new(),
+            fetch,
+        }
+    }
+
+    pub fn next(&mut self, py: Python) -> PyResult<Option<(Elem, T)>> {
+        if self.buffer.is_empty() {
+            elem::catch_cmp(|| self.refill(py))?;
+        }
+
+        Ok(self.buffer.pop_front())
+    }
+
+    fn refill(&mut self, py: Python) -> PyResult<()> {
+        if let (Bound::Included(start) | Bound::Excluded(start), Some(stop)) =
+            (&self.start, &self.stop)
+        {
+            if start >= stop {
+                return Ok(());
+            }
+        }
+        let stop = self.stop.as_ref().map_or(Bound::Unbounded, Bound::Excluded);
+        let batch = (self.fetch)(py, &self.owner, (self.start.as_ref(), stop))?;
+
+        if let Some((key, _)) = batch.last() {
+            self.start = Bound::Excluded(key.clone_ref(py));
+        }
+        self.buffer.extend(batch);
+
+        Ok(())
+    }
+}
+
+impl<T: Clone> Cursor<T> {
+    /// An independent cursor at the same position.
+    pub fn clone_ref(&self, py: Python) -> Self {
+        Cursor {
+            owner: self.owner.clone_ref(py),
+            start: match &self.start {
+                Bound::Included(x) => Bound::Included(x.clone_ref(py)),
+                Bound::Excluded(x) => Bound::Excluded(x.clone_ref(py)),
+                Bound::Unbounded => Bound::Unbounded,
+            },
+            stop: self.stop.as_ref().map(|x| x.clone_ref(py)),
+            buffer: self
+                .buffer
+                .iter()
+                .map(|(key, value)| (key.clone_ref(py), value.clone()))
+                .collect(),
+            fetch: self.fetch,
+        }
+    }
+}
+
+/// The next batch of `btree_map` for a `Fetch`, with values mapped by `f`.
+pub fn map_batch<V, T>(
+    py: Python,
+    btree_map: &BTreeMap<Elem, V>,
+    range: KeyRange,
+    f: impl Fn(&V) -> T,
+) -> Vec<(Elem, T)> {
+    let _attached = elem::attach(py);
+    btree_map
+        .range(range)
+        .take(BATCH_SIZE)
+        .map(|(key, value)| (key.clone_ref(py), f(value)))
+        .collect()
+}
+
+/// The next batch of `map` for a `Fetch`, with values mapped by `f`.
+pub fn persistent_batch<T>(
+    py: Python,
+    map: &PersistentMap,
+    range: KeyRange,
+    f: impl Fn(&Elem) -> T,
+) -> Vec<(Elem, T)> {
+    let _attached = elem::attach(py);
+    map.range(range)
+        .take(BATCH_SIZE)
+        .map(|(key, value)| (key.clone_ref(py), f(value)))
+        .collect()
+}
+
+/// The next batch of `btree_set` for a `Fetch`.
+pub fn set_batch(py: Python, btree_set: &BTreeSet<Elem>, range: KeyRange) -> Vec<(Elem, ())> {
+    let _attached = elem::attach(py);
+    btree_set
+        .range(range)
+        .take(BATCH_SIZE)
+        .map(|key| (key.clone_ref(py), ()))
+        .collect()
+}
+
 // -------------------
 // PyBTreeMapKeys
 // -------------------
 #[pyclass]
 pub struct PyBTreeMapKeys {
-    #[pyo3(get)]
-    pub py_obj: PyObject,
-    // pub py_ref: Arc<RwLock<PyRef<'static, PyBTreeMap>>>,
-    pub iter: btree_map::Keys<'static, Elem, Elem>,
+    pub cursor: Cursor<()>,
 }
 
 #[pymethods]
 impl PyBTreeMapKeys {
+    #[getter]
+    fn py_obj(&self, py: Python) -> PyObject {
+        self.cursor.owner.clone_ref(py)
+    }
+
     fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
         slf
     }
 
-    fn __next__(mut slf: PyRefMut<Self>) -> Option<PyObject> {
-        slf.iter.next().map(|x| x.to_pyobject(slf.py()))
+    fn chunks(&self, py: Python, n: usize) -> PyResult<PyChunksIter> {
+        PyChunksIter::new(ChunksSource::Keys(self.cursor.clone_ref(py)), n)
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        Ok(self.cursor.next(py)?.map(|(key, _)| key.to_pyobject(py)))
     }
 }
 
@@ -30,19 +169,29 @@ impl PyBTreeMapKeys {
 // -------------------
 #[pyclass]
 pub struct PyBTreeMapValues {
-    #[pyo3(get)]
-    pub owner: PyObject,
-    pub iter: btree_map::Values<'static, Elem, Elem>,
+    pub cursor: Cursor<Elem>,
 }
 
 #[pymethods]
 impl PyBTreeMapValues {
+    #[getter]
+    fn owner(&self, py: Python) -> PyObject {
+        self.cursor.owner.clone_ref(py)
+    }
+
     fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
         slf
     }
 
-    fn __next__(mut slf: PyRefMut<Self>) -> Option<PyObject> {
-        slf.iter.next().map(|x| x.to_pyobject(slf.py()))
+    fn chunks(&self, py: Python, n: usize) -> PyResult<PyChunksIter> {
+        PyChunksIter::new(ChunksSource::Values(self.cursor.clone_ref(py)), n)
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        Ok(self
+            .cursor
+            .next(py)?
+            .map(|(_, value)| value.to_pyobject(py)))
     }
 }
 
@@ -51,22 +200,29 @@ impl PyBTreeMapValues {
 // -------------------
 #[pyclass]
 pub struct PyBTreeMapIter {
-    #[pyo3(get)]
-    pub owner: PyObject,
-    pub iter: btree_map::Iter<'static, Elem, Elem>,
+    pub cursor: Cursor<Elem>,
 }
 
 #[pymethods]
 impl PyBTreeMapIter {
+    #[getter]
+    fn owner(&self, py: Python) -> PyObject {
+        self.cursor.owner.clone_ref(py)
+    }
+
     fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
         slf
     }
 
-    fn __next__(mut slf: PyRefMut<Self>) -> Option<(PyObject, PyObject)> {
-        let py = slf.py();
-        slf.iter
-            .next()
-            .map(|(k, v)| (k.to_pyobject(py), v.to_pyobject(py)))
+    fn chunks(&self, py: Python, n: usize) -> PyResult<PyChunksIter> {
+        PyChunksIter::new(ChunksSource::Items(self.cursor.clone_ref(py)), n)
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<(PyObject, PyObject)>> {
+        Ok(self
+            .cursor
+            .next(py)?
+            .map(|(key, value)| (key.to_pyobject(py), value.to_pyobject(py))))
     }
 }
 
@@ -75,20 +231,26 @@ impl PyBTreeMapIter {
 // -------------------
 #[pyclass]
 pub struct PyBTreeSetIter {
-    #[pyo3(get)]
-    pub py_obj: PyObject,
-    // pub py_ref: Arc<RwLock<PyRef<'static, PyBTreeMap>>>,
-    pub iter: btree_set::Iter<'static, Elem>,
+    pub cursor: Cursor<()>,
 }
 
 #[pymethods]
 impl PyBTreeSetIter {
+    #[getter]
+    fn py_obj(&self, py: Python) -> PyObject {
+        self.cursor.owner.clone_ref(py)
+    }
+
     fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
         slf
     }
 
-    fn __next__(mut slf: PyRefMut<Self>) -> Option<PyObject> {
-        slf.iter.next().map(|x| x.to_pyobject(slf.py()))
+    fn chunks(&self, py: Python, n: usize) -> PyResult<PyChunksIter> {
+        PyChunksIter::new(ChunksSource::Set(self.cursor.clone_ref(py)), n)
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        Ok(self.cursor.next(py)?.map(|(key, _)| key.to_pyobject(py)))
     }
 }
 
@@ -97,50 +259,299 @@ impl PyBTreeSetIter {
 // -------------------
 #[pyclass]
 pub struct PyBTreeSeqIter {
-    #[pyo3(get)]
-    pub py_obj: PyObject,
-    // pub py_ref: Arc<RwLock<PyRef<'static, PyBTreeMap>>>,
-    pub iter: InternalPyBTreeSeqIter<'static>,
+    pub iter: InternalPyBTreeSeqIter,
 }
 
 #[pymethods]
 impl PyBTreeSeqIter {
+    #[getter]
+    fn py_obj(&self, py: Python) -> PyObject {
+        self.iter.cursor.owner.clone_ref(py)
+    }
+
     fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
         slf
     }
 
-    fn __next__(mut slf: PyRefMut<Self>) -> Option<PyObject> {
-        slf.iter.next().map(|x| x.to_pyobject(slf.py()))
+    fn chunks(&self, py: Python, n: usize) -> PyResult<PyChunksIter> {
+        PyChunksIter::new(ChunksSource::Seq(self.iter.clone_ref(py)), n)
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        Ok(self.iter.next(py)?.map(|x| x.to_pyobject(py)))
     }
 }
 
-pub struct InternalPyBTreeSeqIter<'a> {
-    pub iter: btree_map::Iter<'a, Elem, usize>,
-    pub elem: Option<&'a Elem>,
-    pub elem_count: usize,
+/// Repeats each element of a seq cursor as many times as it was inserted.
+pub struct InternalPyBTreeSeqIter {
+    pub cursor: Cursor<usize>,
+    pub elem: Option<(Elem, usize)>,
 }
 
-impl<'a> Iterator for InternalPyBTreeSeqIter<'a> {
-    type Item = &'a Elem;
+impl InternalPyBTreeSeqIter {
+    pub fn new(cursor: Cursor<usize>) -> Self {
+        InternalPyBTreeSeqIter { cursor, elem: None }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    fn next(&mut self, py: Python) -> PyResult<Option<Elem>> {
         if self.elem.is_none() {
-            let (elem, count) = self.iter.next()?;
-            self.elem = Some(elem);
-            self.elem_count = *count;
+            self.elem = self.cursor.next(py)?;
         }
-
-        let elem = self.elem.unwrap();
-        if self.elem_count == 0 {
+        let (elem, count) = match &mut self.elem {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        if *count == 0 {
             panic!("invalid state elem_count == 0");
         }
 
-        self.elem_count -= 1;
+        *count -= 1;
+        if *count == 0 {
+            return Ok(self.elem.take().map(|(elem, _)| elem));
+        }
+
+        Ok(Some(elem.clone_ref(py)))
+    }
+
+    fn clone_ref(&self, py: Python) -> Self {
+        InternalPyBTreeSeqIter {
+            cursor: self.cursor.clone_ref(py),
+            elem: self
+                .elem
+                .as_ref()
+                .map(|(elem, count)| (elem.clone_ref(py), *count)),
+        }
+    }
+}
+
+// -------------------
+// PyChunksIter
+// -------------------
+pub enum ChunksSource {
+    Keys(Cursor<()>),
+    Values(Cursor<Elem>),
+    Items(Cursor<Elem>),
+    Set(Cursor<()>),
+    Seq(InternalPyBTreeSeqIter),
+}
+
+/// Yields lists of up to `n` items per call to amortize the cost of crossing
+/// the Python boundary over many elements.
+#[pyclass]
+pub struct PyChunksIter {
+    pub source: ChunksSource,
+    pub n: usize,
+}
+
+impl PyChunksIter {
+    fn new(source: ChunksSource, n: usize) -> PyResult<Self> {
+        if n == 0 {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(
+                "chunk size must be greater than zero",
+            ));
+        }
+        Ok(PyChunksIter { source, n })
+    }
+
+    fn next_item(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        let output = match &mut self.source {
+            ChunksSource::Keys(cursor) | ChunksSource::Set(cursor) => {
+                cursor.next(py)?.map(|(key, _)| key.to_pyobject(py))
+            }
+            ChunksSource::Values(cursor) => {
+                cursor.next(py)?.map(|(_, value)| value.to_pyobject(py))
+            }
+            ChunksSource::Items(cursor) => match cursor.next(py)? {
+                Some(entry) => Some(entry.into_pyobject(py)?.into_any().unbind()),
+                None => None,
+            },
+            ChunksSource::Seq(iter) => iter.next(py)?.map(|x| x.to_pyobject(py)),
+        };
+
+        Ok(output)
+    }
+}
+
+#[pymethods]
+impl PyChunksIter {
+    #[getter]
+    fn owner(&self, py: Python) -> PyObject {
+        match &self.source {
+            ChunksSource::Keys(cursor) | ChunksSource::Set(cursor) => cursor.owner.clone_ref(py),
+            ChunksSource::Values(cursor) | ChunksSource::Items(cursor) => {
+                cursor.owner.clone_ref(py)
+            }
+            ChunksSource::Seq(iter) => iter.cursor.owner.clone_ref(py),
+        }
+    }
+
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<Vec<PyObject>>> {
+        let mut chunk = Vec::with_capacity(self.n.min(BATCH_SIZE));
+        while chunk.len() < self.n {
+            match self.next_item(py)? {
+                Some(item) => chunk.push(item),
+                None => break,
+            }
+        }
+
+        if chunk.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(chunk))
+        }
+    }
+}
+
+// -------------------
+// PyBTreeMultiMapKeys
+// -------------------
+#[pyclass]
+pub struct PyBTreeMultiMapKeys {
+    pub cursor: Cursor<()>,
+}
+
+#[pymethods]
+impl PyBTreeMultiMapKeys {
+    #[getter]
+    fn owner(&self, py: Python) -> PyObject {
+        self.cursor.owner.clone_ref(py)
+    }
+
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        Ok(self.cursor.next(py)?.map(|(key, _)| key.to_pyobject(py)))
+    }
+}
+
+// -------------------
+// PyBTreeMultiMapIter
+// -------------------
+#[pyclass]
+pub struct PyBTreeMultiMapIter {
+    pub iter: InternalPyBTreeMultiMapIter,
+}
+
+#[pymethods]
+impl PyBTreeMultiMapIter {
+    #[getter]
+    fn owner(&self, py: Python) -> PyObject {
+        self.iter.cursor.owner.clone_ref(py)
+    }
+
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<(PyObject, PyObject)>> {
+        Ok(self
+            .iter
+            .next(py)?
+            .map(|(k, v)| (k.to_pyobject(py), v.to_pyobject(py))))
+    }
+}
+
+/// Flattens the values of a multimap cursor into `(key, value)` pairs.
+pub struct InternalPyBTreeMultiMapIter {
+    pub cursor: Cursor<Vec<Elem>>,
+    pub key: Option<Elem>,
+    pub values: std::vec::IntoIter<Elem>,
+}
 
-        if self.elem_count == 0 {
-            self.elem = None;
+impl InternalPyBTreeMultiMapIter {
+    pub fn new(cursor: Cursor<Vec<Elem>>) -> Self {
+        InternalPyBTreeMultiMapIter {
+            cursor,
+            key: None,
+            values: Vec::new().into_iter(),
         }
+    }
+
+    fn next(&mut self, py: Python) -> PyResult<Option<(Elem, Elem)>> {
+        loop {
+            if let (Some(key), Some(value)) = (&self.key, self.values.next()) {
+                return Ok(Some((key.clone_ref(py), value)));
+            }
+            match self.cursor.next(py)? {
+                Some((key, values)) => {
+                    self.key = Some(key);
+                    self.values = values.into_iter();
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+// -------------------
+// PyRangeMapIter
+// -------------------
+#[pyclass]
+pub struct PyRangeMapIter {
+    pub cursor: Cursor<(Elem, Elem)>,
+}
+
+#[pymethods]
+impl PyRangeMapIter {
+    #[getter]
+    fn owner(&self, py: Python) -> PyObject {
+        self.cursor.owner.clone_ref(py)
+    }
+
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<(PyObject, PyObject, PyObject)>> {
+        Ok(self.cursor.next(py)?.map(|(start, (end, value))| {
+            (
+                start.to_pyobject(py),
+                end.to_pyobject(py),
+                value.to_pyobject(py),
+            )
+        }))
+    }
+}
+
+// -------------------
+// PyBTreeMapSnapshotIter
+// -------------------
+#[derive(Clone, Copy)]
+pub enum SnapshotIterKind {
+    Keys,
+    Values,
+    Items,
+}
+
+#[pyclass]
+pub struct PyBTreeMapSnapshotIter {
+    pub iter: persistent_map::IntoIter,
+    pub kind: SnapshotIterKind,
+}
+
+#[pymethods]
+impl PyBTreeMapSnapshotIter {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        let entry = match self.iter.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+        let output = match self.kind {
+            SnapshotIterKind::Keys => entry.0.to_pyobject(py),
+            SnapshotIterKind::Values => entry.1.to_pyobject(py),
+            SnapshotIterKind::Items => (&entry.0, &entry.1).into_pyobject(py)?.into_any().unbind(),
+        };
 
-        Some(elem)
+        Ok(Some(output))
     }
 }
@@ -0,0 +1,568 @@
+use crate::binary::FormatError;
+use crate::calendar::{self, AwareDateTime};
+use crate::elem::Elem;
+use crate::number::{Decimal, Fraction};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, Write};
+
+// JSON encoding of trees, as one array in tree order:
+//   map: [[key, value], ...]    set: [elem, ...]    seq: [elem, ...] with repeats
+// elems map to plain JSON where that is lossless, and to tagged objects otherwise:
+//   None -> null, str -> string, list -> array, int -> number
+//   int beyond +/-2^53 -> {"$int": "<digits>"}, so JavaScript readers keep every digit
+//   float -> number with a fraction or exponent, nan/inf/-inf -> {"$float": "nan"}
+//   tuple -> {"$tuple": [...]}, bool -> true/false
+//   bytes -> {"$bytes": "<hex>"}, Decimal -> {"$decimal": "<str()>"}
+//   Fraction -> {"$fraction": "<numerator>/<denominator>"}
+//   date -> {"$date": "<isoformat()>"}, datetime -> {"$datetime": "<isoformat()>"},
+//   with the offset of an aware one
+
+pub(crate) const MAX_SAFE_INT: u64 = (1 << 53) - 1;
+const MAX_DEPTH: usize = 256;
+
+type Result<T> = std::result::Result<T, FormatError>;
+
+pub fn write_map<'a, W: Write>(
+    mut w: W,
+    entries: impl IntoIterator<Item = (&'a Elem, &'a Elem)>,
+) -> Result<W> {
+    w.write_all(b"[")?;
+    for (i, (key, value)) in entries.into_iter().enumerate() {
+        w.write_all(if i == 0 { b"[" } else { b",[" })?;
+        write_elem(&mut w, key)?;
+        w.write_all(b",")?;
+        write_elem(&mut w, value)?;
+        w.write_all(b"]")?;
+    }
+    w.write_all(b"]")?;
+    Ok(w)
+}
+
+pub fn write_set<W: Write>(w: W, btree_set: &BTreeSet<Elem>) -> Result<W> {
+    write_array(w, btree_set.iter())
+}
+
+pub fn write_seq<W: Write>(w: W, btree_map: &BTreeMap<Elem, usize>) -> Result<W> {
+    let elems = btree_map
+        .iter()
+        .flat_map(|(elem, count)| std::iter::repeat_n(elem, *count));
+    write_array(w, elems)
+}
+
+pub fn read_map<R: BufRead>(inner: R) -> Result<BTreeMap<Elem, Elem>> {
+    let mut items = Vec::new();
+    read_array(inner, |p| {
+        p.expect(b'[')?;
+        let key = p.parse_elem(1)?;
+        p.expect(b',')?;
+        let value = p.parse_elem(1)?;
+        p.expect(b']')?;
+        items.push((key, value));
+        Ok(())
+    })?;
+    Ok(items.into_iter().collect())
+}
+
+pub fn read_set<R: BufRead>(inner: R) -> Result<BTreeSet<Elem>> {
+    let mut items = Vec::new();
+    read_array(inner, |p| {
+        items.push(p.parse_elem(0)?);
+        Ok(())
+    })?;
+    Ok(items.into_iter().collect())
+}
+
+/// Returns the multiplicity of every element along with their total.
+pub fn read_seq<R: BufRead>(inner: R) -> Result<(BTreeMap<Elem, usize>, usize)> {
+    let mut btree_map = BTreeMap::new();
+    let mut length = 0;
+    read_array(inner, |p| {
+        *btree_map.entry(p.parse_elem(0)?).or_insert(0) += 1;
+        length += 1;
+        Ok(())
+    })?;
+    Ok((btree_map, length))
+}
+
+fn write_array<'a, W: Write>(mut w: W, elems: impl Iterator<Item = &'a Elem>) -> Result<W> {
+    w.write_all(b"[")?;
+    for (i, elem) in elems.enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        write_elem(&mut w, elem)?;
+    }
+    w.write_all(b"]")?;
+    Ok(w)
+}
+
+fn write_elems<W: Write>(w: &mut W, elems: &[Elem]) -> Result<()> {
+    w.write_all(b"[")?;
+    for (i, elem) in elems.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        write_elem(w, elem)?;
+    }
+    w.write_all(b"]")?;
+    Ok(())
+}
+
+/// The tag and string of the elems encoded as a tagged string.
+pub(crate) fn to_tagged_str(elem: &Elem) -> Option<(&'static str, String)> {
+    match elem {
+        Elem::Bytes(b) => Some(("$bytes", b.iter().map(|x| format!("{x:02x}")).collect())),
+        Elem::Date(x) => Some(("$date", calendar::format_date(*x))),
+        Elem::DateTime(x) => Some(("$datetime", calendar::format_datetime(*x, None))),
+        Elem::AwareDateTime(x) => Some((
+            "$datetime",
+            calendar::format_datetime(x.local(), Some(x.offset)),
+        )),
+        Elem::Decimal(x) => Some(("$decimal", x.to_string())),
+        Elem::Fraction(x) => Some(("$fraction", x.to_string())),
+        _ => None,
+    }
+}
+
+/// The inverse of `to_tagged_str`, `None` if the tag is unknown or the string
+/// malformed.
+pub(crate) fn from_tagged_str(tag: &str, s: &str) -> Option<Elem> {
+    match tag {
+        "$bytes" => {
+            if !s.len().is_multiple_of(2) || !s.is_ascii() {
+                return None;
+            }
+            let bytes = (0..s.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+                .collect::<Option<Vec<_>>>()?;
+            Some(Elem::bytes(bytes))
+        }
+        "$date" => calendar::parse_date(s).map(Elem::Date),
+        "$datetime" => match calendar::parse_datetime(s)? {
+            (local, None) => Some(Elem::DateTime(local)),
+            (local, Some(offset)) => {
+                let utc = local - offset as i64 * 1_000_000;
+                Some(Elem::AwareDateTime(Box::new(AwareDateTime { utc, offset })))
+            }
+        },
+        "$decimal" => Decimal::parse(s).map(|x| Elem::Decimal(Box::new(x))),
+        "$fraction" => Fraction::parse(s).map(|x| Elem::Fraction(Box::new(x))),
+        _ => None,
+    }
+}
+
+fn write_elem<W: Write>(w: &mut W, elem: &Elem) -> Result<()> {
+    if let Some((tag, s)) = to_tagged_str(elem) {
+        write!(w, "{{\"{tag}\":")?;
+        write_string(w, &s)?;
+        w.write_all(b"}")?;
+        return Ok(());
+    }
+    match elem {
+        Elem::PyNone => w.write_all(b"null")?,
+        Elem::Int(x) if x.unsigned_abs() <= MAX_SAFE_INT => write!(w, "{x}")?,
+        Elem::Int(x) => write!(w, "{{\"$int\":\"{x}\"}}")?,
+        Elem::Float(x) if x.is_nan() => w.write_all(b"{\"$float\":\"nan\"}")?,
+        Elem::Float(x) if x.is_infinite() => {
+            let name = if *x > 0.0 { "inf" } else { "-inf" };
+            write!(w, "{{\"$float\":\"{name}\"}}")?
+        }
+        // debug formatting is the shortest round trip and always has a `.` or `e`
+        Elem::Float(x) => write!(w, "{x:?}")?,
+        Elem::String(s) => write_string(w, s)?,
+        Elem::TwoTuple(pair) => {
+            w.write_all(b"{\"$tuple\":[")?;
+            write_elem(w, &pair[0])?;
+            w.write_all(b",")?;
+            write_elem(w, &pair[1])?;
+            w.write_all(b"]}")?;
+        }
+        Elem::Tuple(v) => {
+            w.write_all(b"{\"$tuple\":")?;
+            write_elems(w, v)?;
+            w.write_all(b"}")?;
+        }
+        Elem::Vec(v) => write_elems(w, v)?,
+        Elem::Bool(x) => write!(w, "{x}")?,
+        #[cfg(feature = "python")]
+        Elem::PyObj(obj) => return Err(crate::binary::unserializable(obj)),
+        Elem::Bytes(_)
+        | Elem::Date(_)
+        | Elem::DateTime(_)
+        | Elem::AwareDateTime(_)
+        | Elem::Decimal(_)
+        | Elem::Fraction(_) => unreachable!("written as tagged strings above"),
+    }
+    Ok(())
+}
+
+fn write_string<W: Write>(w: &mut W, s: &str) -> Result<()> {
+    w.write_all(b"\"")?;
+    let mut start = 0;
+    for (i, byte) in s.bytes().enumerate() {
+        let escaped: &[u8] = match byte {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0..=0x1f => b"",
+            _ => continue,
+        };
+        w.write_all(&s.as_bytes()[start..i])?;
+        if escaped.is_empty() {
+            write!(w, "\\u{byte:04x}")?;
+        } else {
+            w.write_all(escaped)?;
+        }
+        start = i + 1;
+    }
+    w.write_all(&s.as_bytes()[start..])?;
+    w.write_all(b"\"")?;
+    Ok(())
+}
+
+/// Parses a top-level array, handing each item to `item` as it is reached.
+fn read_array<R: BufRead>(
+    inner: R,
+    mut item: impl FnMut(&mut Parser<R>) -> Result<()>,
+) -> Result<()> {
+    let mut p = Parser {
+        bytes: inner.bytes(),
+        peeked: None,
+    };
+
+    p.expect(b'[')?;
+    if p.peek()? == Some(b']') {
+        p.next()?;
+    } else {
+        loop {
+            item(&mut p)?;
+            match p.next()? {
+                Some(b',') => continue,
+                Some(b']') => break,
+                _ => return Err(invalid("expected ',' or ']'")),
+            }
+        }
+    }
+
+    if p.peek()?.is_some() {
+        return Err(invalid("trailing data after array"));
+    }
+    Ok(())
+}
+
+fn invalid(msg: &str) -> FormatError {
+    FormatError::Invalid(format!("invalid JSON: {msg}"))
+}
+
+struct Parser<R: BufRead> {
+    bytes: std::io::Bytes<R>,
+    peeked: Option<u8>,
+}
+
+impl<R: BufRead> Parser<R> {
+    fn next_raw(&mut self) -> Result<Option<u8>> {
+        if let Some(byte) = self.peeked.take() {
+            return Ok(Some(byte));
+        }
+        Ok(self.bytes.next().transpose()?)
+    }
+
+    /// Returns the next byte that isn't whitespace.
+    fn next(&mut self) -> Result<Option<u8>> {
+        loop {
+            match self.next_raw()? {
+                Some(b' ' | b'\n' | b'\r' | b'\t') => continue,
+                byte => return Ok(byte),
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<u8>> {
+        let byte = self.next()?;
+        self.peeked = byte;
+        Ok(byte)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<()> {
+        match self.next()? {
+            Some(byte) if byte == expected => Ok(()),
+            Some(byte) => Err(invalid(&format!(
+                "expected '{}', found '{}'",
+                expected as char, byte as char
+            ))),
+            None => Err(invalid("unexpected end of input")),
+        }
+    }
+
+    fn expect_literal(&mut self, rest: &[u8]) -> Result<()> {
+        for expected in rest {
+            if self.next_raw()? != Some(*expected) {
+                return Err(invalid("unknown literal"));
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_elem(&mut self, depth: usize) -> Result<Elem> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("elements are nested too deeply"));
+        }
+
+        match self.peek()? {
+            Some(b'n') => {
+                self.next()?;
+                self.expect_literal(b"ull")?;
+                Ok(Elem::PyNone)
+            }
+            Some(b't') => {
+                self.next()?;
+                self.expect_literal(b"rue")?;
+                Ok(Elem::Bool(true))
+            }
+            Some(b'f') => {
+                self.next()?;
+                self.expect_literal(b"alse")?;
+                Ok(Elem::Bool(false))
+            }
+            Some(b'"') => Ok(Elem::String(self.parse_string()?.into())),
+            Some(b'[') => Ok(Elem::list(self.parse_elems(depth)?)),
+            Some(b'{') => self.parse_tagged(depth),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(byte) => Err(invalid(&format!("unexpected '{}'", byte as char))),
+            None => Err(invalid("unexpected end of input")),
+        }
+    }
+
+    fn parse_elems(&mut self, depth: usize) -> Result<Vec<Elem>> {
+        self.expect(b'[')?;
+        let mut elems = Vec::new();
+        if self.peek()? == Some(b']') {
+            self.next()?;
+            return Ok(elems);
+        }
+        loop {
+            elems.push(self.parse_elem(depth + 1)?);
+            match self.next()? {
+                Some(b',') => continue,
+                Some(b']') => return Ok(elems),
+                _ => return Err(invalid("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_tagged(&mut self, depth: usize) -> Result<Elem> {
+        self.expect(b'{')?;
+        let tag = self.parse_string()?;
+        self.expect(b':')?;
+
+        let elem = match tag.as_str() {
+            "$tuple" => match self.parse_elems(depth)? {
+                v if v.len() == 2 => {
+                    let mut v = v.into_iter();
+                    let a = v.next().unwrap();
+                    let b = v.next().unwrap();
+                    Elem::pair(a, b)
+                }
+                v => Elem::tuple(v),
+            },
+            "$int" => {
+                let digits = self.parse_string()?;
+                Elem::Int(
+                    digits
+                        .parse()
+                        .map_err(|_| invalid(&format!("bad $int '{digits}'")))?,
+                )
+            }
+            "$float" => match self.parse_string()?.as_str() {
+                "nan" => Elem::Float(f64::NAN),
+                "inf" => Elem::Float(f64::INFINITY),
+                "-inf" => Elem::Float(f64::NEG_INFINITY),
+                name => return Err(invalid(&format!("bad $float '{name}'"))),
+            },
+            "$bytes" | "$date" | "$datetime" | "$decimal" | "$fraction" => {
+                let s = self.parse_string()?;
+                from_tagged_str(&tag, &s).ok_or_else(|| invalid(&format!("bad {tag} '{s}'")))?
+            }
+            tag => return Err(invalid(&format!("unknown tag '{tag}'"))),
+        };
+
+        self.expect(b'}')?;
+        Ok(elem)
+    }
+
+    fn parse_number(&mut self) -> Result<Elem> {
+        let mut token = String::new();
+        while let Some(byte) = self.next_raw()? {
+            if matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+                token.push(byte as char);
+            } else {
+                self.peeked = Some(byte);
+                break;
+            }
+        }
+
+        if token.contains(['.', 'e', 'E']) {
+            token
+                .parse()
+                .map(Elem::Float)
+                .map_err(|_| invalid(&format!("bad number '{token}'")))
+        } else {
+            token
+                .parse()
+                .map(Elem::Int)
+                .map_err(|_| invalid(&format!("integer '{token}' is out of range")))
+        }
+    }
+
+    fn parse_hex(&mut self) -> Result<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .next_raw()?
+                .and_then(|x| (x as char).to_digit(16))
+                .ok_or_else(|| invalid("bad unicode escape"))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            match self.next_raw()? {
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    let c = match self.next_raw()? {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            let mut code = self.parse_hex()?;
+                            if (0xd800..0xdc00).contains(&code) {
+                                self.expect_literal(b"\\u")?;
+                                let low = self.parse_hex()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(invalid("bad surrogate pair"));
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            char::from_u32(code).ok_or_else(|| invalid("bad unicode escape"))?
+                        }
+                        _ => return Err(invalid("bad escape")),
+                    };
+                    bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                Some(byte) => bytes.push(byte),
+                None => return Err(invalid("unterminated string")),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| invalid("string is not valid utf-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_string(btree_map: &BTreeMap<Elem, Elem>) -> String {
+        String::from_utf8(write_map(Vec::new(), btree_map).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_encoding() {
+        let btree_map = BTreeMap::from([
+            (Elem::Int(1), Elem::Float(1.0)),
+            (Elem::Int(2), Elem::Float(f64::NEG_INFINITY)),
+            (Elem::Int(i64::MAX), Elem::String("a\"\u{1}é".into())),
+            (Elem::Int(4), Elem::pair(Elem::PyNone, Elem::list(vec![]))),
+        ]);
+        let json = to_string(&btree_map);
+        assert_eq!(
+            json,
+            "[[1,1.0],[2,{\"$float\":\"-inf\"}],[4,{\"$tuple\":[null,[]]}],\
+             [{\"$int\":\"9223372036854775807\"},\"a\\\"\\u0001é\"]]"
+        );
+        assert_eq!(read_map(json.as_bytes()).unwrap(), btree_map);
+    }
+
+    #[test]
+    fn test_native_types() {
+        let date = calendar::ordinal(2024, 2, 29).unwrap();
+        let time = calendar::join(date, 13, 5, 9, 0).unwrap();
+        let btree_map = BTreeMap::from([
+            (Elem::Int(1), Elem::Bool(false)),
+            (
+                Elem::Int(2),
+                Elem::Fraction(Box::new(Fraction::new(1, 3).unwrap())),
+            ),
+            (
+                Elem::Int(3),
+                Elem::Decimal(Box::new(Decimal::parse("2.50").unwrap())),
+            ),
+            (Elem::Int(4), Elem::bytes(vec![0xca, 0xfe])),
+            (Elem::Int(5), Elem::Date(date)),
+            (Elem::Int(6), Elem::DateTime(time)),
+            (
+                Elem::Int(7),
+                Elem::AwareDateTime(Box::new(AwareDateTime {
+                    utc: time + 3_600_000_000,
+                    offset: -3600,
+                })),
+            ),
+        ]);
+        let json = to_string(&btree_map);
+        assert_eq!(
+            json,
+            "[[1,false],[2,{\"$fraction\":\"1/3\"}],[3,{\"$decimal\":\"2.50\"}],\
+             [4,{\"$bytes\":\"cafe\"}],[5,{\"$date\":\"2024-02-29\"}],\
+             [6,{\"$datetime\":\"2024-02-29T13:05:09\"}],\
+             [7,{\"$datetime\":\"2024-02-29T13:05:09-01:00\"}]]"
+        );
+        assert_eq!(read_map(json.as_bytes()).unwrap(), btree_map);
+
+        for json in [
+            "[tru]",
+            "[{\"$bytes\": \"abc\"}]",
+            "[{\"$date\": \"2023-02-29\"}]",
+            "[{\"$decimal\": \"NaN\"}]",
+            "[{\"$fraction\": \"1/0\"}]",
+        ] {
+            assert!(read_set(json.as_bytes()).is_err(), "{json}");
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let json =
+            " [ [1, \"\\ud83d\\ude00\" ] ,[2, 1e3], [3,-0.5], [4, {\"$tuple\": [1, 2, 3]}] ] ";
+        let btree_map = read_map(json.as_bytes()).unwrap();
+        assert_eq!(
+            btree_map.values().cloned().collect::<Vec<_>>(),
+            vec![
+                Elem::String("\u{1f600}".into()),
+                Elem::Float(1000.0),
+                Elem::Float(-0.5),
+                Elem::tuple(vec![Elem::Int(1), Elem::Int(2), Elem::Int(3)]),
+            ]
+        );
+
+        for json in [
+            "[1,]",
+            "[1] 2",
+            "[{\"$set\": []}]",
+            "[99999999999999999999]",
+            "[",
+        ] {
+            assert!(read_set(json.as_bytes()).is_err(), "{json}");
+        }
+    }
+}
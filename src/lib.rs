@@ -1,19 +1,96 @@
-mod elem;
+//! Ordered collections keyed by `Elem`. The trees, their file formats and the
+//! ordering of `Elem` are plain Rust; the `python` feature (on by default)
+//! adds the `PyObj` variant and the `tree_collections` extension module, and
+//! the `serde` feature implements `Serialize` and `Deserialize` for them.
+//! Depend on the crate with `default-features = false` to use it without an
+//! interpreter.
+
+pub mod aggregate;
+pub mod binary;
+#[cfg(feature = "python")]
+mod bulk;
+pub mod calendar;
+pub mod disk_btree;
+pub mod elem;
+pub mod interval_tree;
+#[cfg(feature = "python")]
 mod iterators;
+pub mod json;
+#[cfg(feature = "python")]
+mod merge;
+pub mod number;
+pub mod persistent_map;
+#[cfg(feature = "python")]
 mod pybtree_map;
+#[cfg(feature = "python")]
+mod pybtree_map_snapshot;
+#[cfg(feature = "python")]
+mod pybtree_multimap;
+#[cfg(feature = "python")]
 mod pybtree_seq;
+#[cfg(feature = "python")]
 mod pybtree_set;
+#[cfg(feature = "python")]
+mod pydisk_btree_map;
+#[cfg(feature = "python")]
+mod pyfrozen_btree_map;
+#[cfg(feature = "python")]
+mod pyfrozen_btree_set;
+#[cfg(feature = "python")]
+mod pyinterval_tree;
+#[cfg(feature = "python")]
+mod pyrange_map;
+#[cfg(feature = "python")]
+mod pytyped_btree_map;
+#[cfg(feature = "serde")]
+pub mod serde_impls;
+pub mod small_str;
+#[cfg(feature = "python")]
+mod tree_lock;
+pub mod write_ahead_log;
 
+#[cfg(feature = "python")]
 use pybtree_map::PyBTreeMap;
+#[cfg(feature = "python")]
+use pybtree_map_snapshot::PyBTreeMapSnapshot;
+#[cfg(feature = "python")]
+use pybtree_multimap::PyBTreeMultiMap;
+#[cfg(feature = "python")]
 use pybtree_seq::PyBTreeSeq;
+#[cfg(feature = "python")]
 use pybtree_set::PyBTreeSet;
+#[cfg(feature = "python")]
+use pydisk_btree_map::{PyDiskBTreeMap, PyDiskBTreeMapRange};
+#[cfg(feature = "python")]
+use pyfrozen_btree_map::PyFrozenBTreeMap;
+#[cfg(feature = "python")]
+use pyfrozen_btree_set::PyFrozenBTreeSet;
+#[cfg(feature = "python")]
+use pyinterval_tree::PyIntervalTree;
+#[cfg(feature = "python")]
 use pyo3::prelude::*;
+#[cfg(feature = "python")]
+use pyrange_map::PyRangeMap;
+#[cfg(feature = "python")]
+use pytyped_btree_map::{PyFloatBTreeMap, PyIntBTreeMap, PyStrBTreeMap};
 
 /// A Python module implemented in Rust.
-#[pymodule]
-fn tree_collections(_py: Python, m: &PyModule) -> PyResult<()> {
+#[cfg(feature = "python")]
+#[pymodule(gil_used = false)]
+fn tree_collections(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyBTreeMap>()?;
+    m.add_class::<PyIntBTreeMap>()?;
+    m.add_class::<PyFloatBTreeMap>()?;
+    m.add_class::<PyStrBTreeMap>()?;
+    m.add_class::<PyBTreeMapSnapshot>()?;
     m.add_class::<PyBTreeSet>()?;
     m.add_class::<PyBTreeSeq>()?;
+    m.add_class::<PyDiskBTreeMap>()?;
+    m.add_class::<PyDiskBTreeMapRange>()?;
+    m.add_class::<PyFrozenBTreeMap>()?;
+    m.add_class::<PyFrozenBTreeSet>()?;
+    m.add_class::<PyBTreeMultiMap>()?;
+    m.add_class::<PyIntervalTree>()?;
+    m.add_class::<PyRangeMap>()?;
     Ok(())
 }
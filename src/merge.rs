@@ -0,0 +1,63 @@
+use crate::elem::Elem;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::PyString;
+
+/// Strategy used by `merge` when a key is present in both trees.
+pub enum OnConflict {
+    /// Take the key/value coming from the other tree.
+    Replace,
+    /// Keep the key/value already present in this tree.
+    Keep,
+    /// Call a Python function to produce the resulting value.
+    Call(PyObject),
+}
+
+impl FromPyObject<'_> for OnConflict {
+    fn extract_bound(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(name) = ob.downcast::<PyString>() {
+            match name.to_cow()?.as_ref() {
+                "replace" => Ok(OnConflict::Replace),
+                "keep" => Ok(OnConflict::Keep),
+                other => Err(PyErr::new::<exceptions::PyValueError, _>(format!(
+                    "on_conflict must be 'replace', 'keep' or a callable, got '{other}'"
+                ))),
+            }
+        } else if ob.is_callable() {
+            Ok(OnConflict::Call(ob.clone().unbind()))
+        } else {
+            Err(PyErr::new::<exceptions::PyTypeError, _>(
+                "on_conflict must be 'replace', 'keep' or a callable",
+            ))
+        }
+    }
+}
+
+/// Resolves a conflict between two equal elements of a set-like tree through
+/// the user callable, checking that the result still compares equal to them.
+pub fn call_resolve_elem(
+    py: Python<'_>,
+    f: &PyObject,
+    current: &Elem,
+    incoming: &Elem,
+) -> PyResult<Elem> {
+    let output = f
+        .call1(py, (current.to_pyobject(py), incoming.to_pyobject(py)))?
+        .extract::<Elem>(py)?;
+
+    if &output != current {
+        return Err(PyErr::new::<exceptions::PyValueError, _>(
+            "on_conflict must return an element equal to the conflicting ones",
+        ));
+    }
+    Ok(output)
+}
+
+/// Returns true when the `[first, last]` key ranges of two trees don't overlap,
+/// in which case they can be concatenated without resolving any conflict.
+pub fn is_disjoint(a: Option<(&Elem, &Elem)>, b: Option<(&Elem, &Elem)>) -> bool {
+    match (a, b) {
+        (Some((a_first, a_last)), Some((b_first, b_last))) => a_last < b_first || b_last < a_first,
+        _ => true,
+    }
+}
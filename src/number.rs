@@ -0,0 +1,799 @@
+//! Exact values of the numeric `Elem` variants. `int`, `float`, `bool`,
+//! `Decimal` and `Fraction` compare and hash by the number they represent, as
+//! they do in Python, so `Fraction(1, 2) == 0.5` while `Decimal("0.1") != 0.1`.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// A `decimal.Decimal` other than NaN, which compares unequal to itself and
+/// stays a Python object. The coefficient and exponent are kept as given, so
+/// `Decimal("1.50")` comes back with its trailing zero.
+#[derive(Clone, Debug)]
+pub enum Decimal {
+    Finite { neg: bool, coeff: u128, exp: i64 },
+    Infinite { neg: bool },
+}
+
+/// A `fractions.Fraction` in lowest terms with a positive denominator.
+#[derive(Clone, Debug)]
+pub struct Fraction {
+    num: i64,
+    den: i64,
+}
+
+/// A borrowed numeric `Elem`, `bool` taking part as an `Int`.
+#[derive(Clone, Copy)]
+pub enum Number<'a> {
+    Int(i64),
+    Float(f64),
+    Decimal(&'a Decimal),
+    Fraction(&'a Fraction),
+}
+
+impl Decimal {
+    /// Parses the output of `str()` on a `Decimal`, `None` for a NaN or a
+    /// coefficient or exponent out of range.
+    pub fn parse(s: &str) -> Option<Decimal> {
+        let (neg, s) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        if s.eq_ignore_ascii_case("infinity") || s.eq_ignore_ascii_case("inf") {
+            return Some(Decimal::Infinite { neg });
+        }
+
+        let (mantissa, exp) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i64>().ok()?),
+            None => (s, 0),
+        };
+        let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if int.is_empty() && frac.is_empty() {
+            return None;
+        }
+
+        let mut coeff = 0u128;
+        for byte in int.bytes().chain(frac.bytes()) {
+            let digit = (byte as char).to_digit(10)?;
+            coeff = coeff.checked_mul(10)?.checked_add(digit as u128)?;
+        }
+        let exp = exp.checked_sub(frac.len() as i64)?;
+        Some(Decimal::Finite { neg, coeff, exp })
+    }
+}
+
+// the same as `str()` on a `Decimal`
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (neg, coeff, exp) = match *self {
+            Decimal::Infinite { neg } => {
+                return f.write_str(if neg { "-Infinity" } else { "Infinity" })
+            }
+            Decimal::Finite { neg, coeff, exp } => (neg, coeff, exp),
+        };
+        if neg {
+            f.write_str("-")?;
+        }
+
+        let digits = coeff.to_string();
+        let adjusted = exp as i128 + digits.len() as i128 - 1;
+        if exp <= 0 && adjusted >= -6 {
+            // plain notation, with the point inside or before the digits
+            let point = digits.len() as i64 + exp;
+            if exp == 0 {
+                f.write_str(&digits)
+            } else if point > 0 {
+                let (int, frac) = digits.split_at(point as usize);
+                write!(f, "{int}.{frac}")
+            } else {
+                write!(f, "0.{}{digits}", "0".repeat(-point as usize))
+            }
+        } else {
+            let (first, rest) = digits.split_at(1);
+            let point = if rest.is_empty() { "" } else { "." };
+            write!(f, "{first}{point}{rest}E{adjusted:+}")
+        }
+    }
+}
+
+impl Fraction {
+    /// `num / den` in lowest terms, `None` for a zero denominator or a value
+    /// that doesn't fit.
+    pub fn new(num: i64, den: i64) -> Option<Fraction> {
+        if den == 0 {
+            return None;
+        }
+        let g = gcd(num.unsigned_abs() as u128, den.unsigned_abs() as u128) as i64;
+        let (num, den) = (num / g, den / g);
+        if den < 0 {
+            Some(Fraction {
+                num: num.checked_neg()?,
+                den: den.checked_neg()?,
+            })
+        } else {
+            Some(Fraction { num, den })
+        }
+    }
+
+    pub fn num(&self) -> i64 {
+        self.num
+    }
+
+    pub fn den(&self) -> i64 {
+        self.den
+    }
+
+    /// Parses the output of `str()` on a `Fraction`, `"3"` or `"-1/2"`.
+    pub fn parse(s: &str) -> Option<Fraction> {
+        match s.split_once('/') {
+            Some((num, den)) => Fraction::new(num.parse().ok()?, den.parse().ok()?),
+            None => Fraction::new(s.parse().ok()?, 1),
+        }
+    }
+}
+
+// the same as `str()` on a `Fraction`
+impl fmt::Display for Fraction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+/// Orders two numbers exactly, `None` if either is NaN.
+pub fn cmp(a: Number, b: Number) -> Option<Ordering> {
+    match (a, b) {
+        (Number::Int(a), Number::Int(b)) => Some(a.cmp(&b)),
+        (Number::Float(a), Number::Float(b)) => a.partial_cmp(&b),
+        (Number::Int(a), Number::Float(b)) => cmp_int_float(a, b),
+        (Number::Float(a), Number::Int(b)) => cmp_int_float(b, a).map(Ordering::reverse),
+        (a, b) => Value::from(a).partial_cmp(&Value::from(b)),
+    }
+}
+
+// 2^63, exactly representable unlike `i64::MAX`
+const TWO_63: f64 = 9_223_372_036_854_775_808.0;
+
+fn cmp_int_float(a: i64, b: f64) -> Option<Ordering> {
+    if b.is_nan() {
+        None
+    } else if b >= TWO_63 {
+        Some(Ordering::Less)
+    } else if b < -TWO_63 {
+        Some(Ordering::Greater)
+    } else {
+        let int = b.trunc();
+        match a.cmp(&(int as i64)) {
+            Ordering::Equal => 0.0.partial_cmp(&(b - int)),
+            ord => Some(ord),
+        }
+    }
+}
+
+/// Feeds `n` into `state` so that equal numbers hash equally whatever their
+/// type: integers as an `i64`, other floats by their bits and what remains
+/// by its exact value in lowest terms.
+pub fn hash<H: Hasher>(n: Number, state: &mut H) {
+    match n {
+        Number::Int(x) => {
+            0u8.hash(state);
+            x.hash(state);
+        }
+        Number::Float(x) if x.fract() == 0.0 && (-TWO_63..TWO_63).contains(&x) => {
+            0u8.hash(state);
+            (x as i64).hash(state);
+        }
+        Number::Float(x) => {
+            1u8.hash(state);
+            x.to_bits().hash(state);
+        }
+        n => match Value::from(n) {
+            Value::Finite(x) => {
+                if let Some(int) = x.to_i64() {
+                    hash(Number::Int(int), state);
+                } else if let Some(float) = x.to_f64() {
+                    hash(Number::Float(float), state);
+                } else {
+                    x.hash_exact(state);
+                }
+            }
+            Value::Infinite { neg } => hash(
+                Number::Float(if neg {
+                    f64::NEG_INFINITY
+                } else {
+                    f64::INFINITY
+                }),
+                state,
+            ),
+            Value::NaN => hash(Number::Float(f64::NAN), state),
+        },
+    }
+}
+
+/// An exact sum of ints and floats that doesn't depend on the order they are
+/// added in. Ints add up as integers, floats as the non-overlapping partials
+/// of `math.fsum`, and the total is rounded once when it's read.
+#[derive(Clone, Debug, Default)]
+pub struct Sum {
+    int: i128,
+    // the finite floats, their exact sum is the sum of these in increasing
+    // magnitude
+    partials: Vec<f64>,
+    // the infinite and NaN floats
+    special: f64,
+    floats: bool,
+}
+
+impl Sum {
+    pub fn add_int(&mut self, x: i64) {
+        // can't overflow before 2^64 additions
+        self.int += x as i128;
+    }
+
+    pub fn add_float(&mut self, x: f64) {
+        self.floats = true;
+        if x.is_finite() {
+            add_partial(&mut self.partials, x);
+        } else {
+            self.special += x;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Sum) {
+        self.int += other.int;
+        self.special += other.special;
+        self.floats |= other.floats;
+        for x in &other.partials {
+            add_partial(&mut self.partials, *x);
+        }
+    }
+
+    /// The total while only ints were added, it may not fit an `i64`.
+    pub fn to_int(&self) -> Option<i128> {
+        (!self.floats).then_some(self.int)
+    }
+
+    /// The total rounded to the nearest float.
+    pub fn to_f64(&self) -> f64 {
+        if self.special != 0.0 || self.special.is_nan() {
+            return self.special;
+        }
+
+        // splits the int into parts of at most 53 bits, each exact as a float
+        let mut partials = self.partials.clone();
+        let low = (1i128 << 53) - 1;
+        add_partial(&mut partials, (self.int & low) as f64);
+        add_partial(
+            &mut partials,
+            ((self.int >> 53) & low) as f64 * 2f64.powi(53),
+        );
+        add_partial(&mut partials, (self.int >> 106) as f64 * 2f64.powi(106));
+
+        // rounds the exact sum once, as `math.fsum` does
+        let mut n = partials.len();
+        let mut hi = 0.0;
+        let mut lo = 0.0;
+        if n > 0 {
+            n -= 1;
+            hi = partials[n];
+            while n > 0 {
+                let x = hi;
+                n -= 1;
+                let y = partials[n];
+                hi = x + y;
+                lo = y - (hi - x);
+                if lo != 0.0 {
+                    break;
+                }
+            }
+            // halfway cases round to even unless the partials below push it
+            if n > 0 && ((lo < 0.0 && partials[n - 1] < 0.0) || (lo > 0.0 && partials[n - 1] > 0.0))
+            {
+                let y = lo * 2.0;
+                let x = hi + y;
+                if y == x - hi {
+                    hi = x;
+                }
+            }
+        }
+        hi
+    }
+}
+
+/// Adds `x` to the partials of a `Sum` without rounding. A partial sum
+/// beyond the float range becomes infinite, as it would in Python's `sum`.
+fn add_partial(partials: &mut Vec<f64>, mut x: f64) {
+    let mut i = 0;
+    for j in 0..partials.len() {
+        let mut y = partials[j];
+        if x.abs() < y.abs() {
+            (x, y) = (y, x);
+        }
+        let hi = x + y;
+        let lo = y - (hi - x);
+        if lo != 0.0 {
+            partials[i] = lo;
+            i += 1;
+        }
+        x = hi;
+    }
+    partials.truncate(i);
+    if x != 0.0 {
+        partials.push(x);
+    }
+}
+
+enum Value {
+    NaN,
+    Infinite { neg: bool },
+    Finite(Exact),
+}
+
+/// `(-1)^neg * coeff / den * 10^exp10 * 2^exp2`
+#[derive(Clone, Copy)]
+struct Exact {
+    neg: bool,
+    coeff: u128,
+    den: u128,
+    exp10: i64,
+    exp2: i64,
+}
+
+impl Exact {
+    fn int(neg: bool, coeff: u128) -> Exact {
+        Exact {
+            neg,
+            coeff,
+            den: 1,
+            exp10: 0,
+            exp2: 0,
+        }
+    }
+
+    fn signum(&self) -> i8 {
+        match (self.coeff, self.neg) {
+            (0, _) => 0,
+            (_, true) => -1,
+            (_, false) => 1,
+        }
+    }
+
+    fn cmp_magnitude(&self, other: &Exact) -> Ordering {
+        // log2 of the ratio, the exponents subtracted first so that huge ones
+        // that cancel out don't lose precision
+        let estimate = (self.coeff as f64).log2() - (other.coeff as f64).log2()
+            + (other.den as f64).log2()
+            - (self.den as f64).log2()
+            + (self.exp10 as i128 - other.exp10 as i128) as f64 * std::f64::consts::LOG2_10
+            + (self.exp2 as i128 - other.exp2 as i128) as f64;
+        if estimate > 2.0 {
+            return Ordering::Greater;
+        } else if estimate < -2.0 {
+            return Ordering::Less;
+        }
+
+        // within a factor of 4 the coefficients bound the exponent differences,
+        // so the exact products stay small
+        let mut left = Big::from(self.coeff).mul(&Big::from(other.den));
+        let mut right = Big::from(other.coeff).mul(&Big::from(self.den));
+        let exp10 = self.exp10.min(other.exp10);
+        left.mul_pow10((self.exp10 - exp10) as u32);
+        right.mul_pow10((other.exp10 - exp10) as u32);
+        let exp2 = self.exp2.min(other.exp2);
+        left.shl((self.exp2 - exp2) as u32);
+        right.shl((other.exp2 - exp2) as u32);
+        left.cmp(&right)
+    }
+
+    fn to_i64(self) -> Option<i64> {
+        if self.coeff == 0 {
+            return Some(0);
+        } else if self.den != 1 {
+            // fractions are in lowest terms
+            return None;
+        }
+        let mut x = self.coeff;
+        if self.exp10 >= 0 {
+            x = x.checked_mul(10u128.checked_pow(self.exp10.try_into().ok()?)?)?;
+        } else {
+            let p = 10u128.checked_pow((-self.exp10).try_into().ok()?);
+            x = match p {
+                Some(p) if x.is_multiple_of(p) => x / p,
+                None if x == 0 => 0,
+                _ => return None,
+            };
+        }
+        if self.exp2 >= 0 {
+            let shift: u32 = self
+                .exp2
+                .try_into()
+                .ok()
+                .filter(|&n| n <= x.leading_zeros())?;
+            x <<= shift;
+        } else if (x.trailing_zeros() as i64) >= -self.exp2 {
+            x >>= -self.exp2;
+        } else {
+            return None;
+        }
+
+        let x = i128::try_from(x).ok()?;
+        i64::try_from(if self.neg { -x } else { x }).ok()
+    }
+
+    /// The float equal to `self`, if there is one.
+    fn to_f64(self) -> Option<f64> {
+        let candidate = if self.den == 1 && self.exp2 == 0 {
+            // correctly rounded, so exact whenever `self` is a float
+            format!("{}e{}", self.coeff, self.exp10)
+                .parse::<f64>()
+                .ok()?
+        } else if self.exp10 == 0 && self.exp2 == 0 {
+            // a float needs a power of two denominator and at most 53
+            // significant bits, both converted exactly
+            self.coeff as f64 / self.den as f64
+        } else {
+            return None;
+        };
+        let candidate = if self.neg { -candidate } else { candidate };
+
+        let float = Value::from(Number::Float(candidate));
+        match float {
+            Value::Finite(x) if x.cmp_magnitude(&self) == Ordering::Equal => Some(candidate),
+            _ => None,
+        }
+    }
+
+    /// Hashes a `Decimal` or `Fraction` that is neither an `i64` nor a float
+    /// by its lowest terms, or by its normalized decimal when the denominator
+    /// is too large for any `Fraction` to be equal to it.
+    fn hash_exact<H: Hasher>(&self, state: &mut H) {
+        if self.den != 1 {
+            // a fraction, already in lowest terms
+            (8u8, self.neg, self.coeff, self.den).hash(state);
+            return;
+        }
+
+        // not zero, that is an int
+        let (mut coeff, mut exp) = (self.coeff, self.exp10);
+        while coeff % 10 == 0 {
+            coeff /= 10;
+            exp += 1;
+        }
+        if exp < 0 {
+            // without trailing zeros `coeff` shares at most one of the factors
+            // 2 and 5 with 10^k
+            let k = exp.unsigned_abs();
+            let (mut num, mut twos, mut fives) = (coeff, k, k);
+            while twos > 0 && num % 2 == 0 {
+                num /= 2;
+                twos -= 1;
+            }
+            while fives > 0 && num % 5 == 0 {
+                num /= 5;
+                fives -= 1;
+            }
+            let pow = |base: u128, n: u64| base.checked_pow(n.try_into().ok()?);
+            if let Some(den) = pow(2, twos)
+                .zip(pow(5, fives))
+                .and_then(|(a, b)| a.checked_mul(b))
+            {
+                (8u8, self.neg, num, den).hash(state);
+                return;
+            }
+        }
+        (9u8, self.neg, coeff, exp).hash(state);
+    }
+}
+
+impl From<Number<'_>> for Value {
+    fn from(n: Number) -> Value {
+        match n {
+            Number::Int(x) => Value::Finite(Exact::int(x < 0, x.unsigned_abs() as u128)),
+            Number::Float(x) if x.is_nan() => Value::NaN,
+            Number::Float(x) if x.is_infinite() => Value::Infinite { neg: x < 0.0 },
+            Number::Float(x) => {
+                let bits = x.to_bits();
+                let exp = ((bits >> 52) & 0x7ff) as i64;
+                let frac = bits & ((1 << 52) - 1);
+                let (coeff, exp2) = if exp == 0 {
+                    (frac, -1074)
+                } else {
+                    (frac | (1 << 52), exp - 1075)
+                };
+                Value::Finite(Exact {
+                    exp2,
+                    ..Exact::int(x.is_sign_negative(), coeff as u128)
+                })
+            }
+            Number::Decimal(Decimal::Infinite { neg }) => Value::Infinite { neg: *neg },
+            Number::Decimal(&Decimal::Finite { neg, coeff, exp }) => Value::Finite(Exact {
+                exp10: exp,
+                ..Exact::int(neg, coeff)
+            }),
+            Number::Fraction(x) => Value::Finite(Exact {
+                den: x.den as u128,
+                ..Exact::int(x.num < 0, x.num.unsigned_abs() as u128)
+            }),
+        }
+    }
+}
+
+impl Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::NaN, _) | (_, Value::NaN) => None,
+            (Value::Infinite { neg: a }, Value::Infinite { neg: b }) => Some(b.cmp(a)),
+            (Value::Infinite { neg }, Value::Finite(_)) => Some(if *neg {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }),
+            (Value::Finite(_), Value::Infinite { neg }) => Some(if *neg {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            }),
+            (Value::Finite(a), Value::Finite(b)) => Some(match a.signum().cmp(&b.signum()) {
+                Ordering::Equal if a.signum() == 0 => Ordering::Equal,
+                Ordering::Equal if a.neg => a.cmp_magnitude(b).reverse(),
+                Ordering::Equal => a.cmp_magnitude(b),
+                ord => ord,
+            }),
+        }
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// An unsigned integer of any size, just enough for `cmp_magnitude`.
+struct Big(Vec<u32>);
+
+impl From<u128> for Big {
+    fn from(x: u128) -> Big {
+        Big((0..4).map(|i| (x >> (32 * i)) as u32).collect())
+    }
+}
+
+impl Big {
+    fn mul(&self, other: &Big) -> Big {
+        let mut out = vec![0u32; self.0.len() + other.0.len()];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in other.0.iter().enumerate() {
+                let x = out[i + j] as u64 + a as u64 * b as u64 + carry;
+                out[i + j] = x as u32;
+                carry = x >> 32;
+            }
+            out[i + other.0.len()] = carry as u32;
+        }
+        Big(out)
+    }
+
+    fn mul_small(&mut self, m: u32) {
+        let mut carry = 0u64;
+        for limb in &mut self.0 {
+            let x = *limb as u64 * m as u64 + carry;
+            *limb = x as u32;
+            carry = x >> 32;
+        }
+        if carry > 0 {
+            self.0.push(carry as u32);
+        }
+    }
+
+    fn mul_pow10(&mut self, mut n: u32) {
+        while n >= 9 {
+            self.mul_small(1_000_000_000);
+            n -= 9;
+        }
+        self.mul_small(10u32.pow(n));
+    }
+
+    fn shl(&mut self, bits: u32) {
+        let (words, bits) = ((bits / 32) as usize, bits % 32);
+        if bits > 0 {
+            let mut carry = 0u32;
+            for limb in &mut self.0 {
+                let x = *limb;
+                *limb = (x << bits) | carry;
+                carry = x >> (32 - bits);
+            }
+            self.0.push(carry);
+        }
+        self.0.splice(0..0, std::iter::repeat_n(0, words));
+    }
+
+    fn cmp(&self, other: &Big) -> Ordering {
+        let len = |x: &Big| x.0.iter().rposition(|&limb| limb != 0).map_or(0, |i| i + 1);
+        let (a, b) = (&self.0[..len(self)], &other.0[..len(other)]);
+        a.len()
+            .cmp(&b.len())
+            .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    fn decimal(s: &str) -> Decimal {
+        Decimal::parse(s).unwrap()
+    }
+
+    fn fraction(num: i64, den: i64) -> Fraction {
+        Fraction::new(num, den).unwrap()
+    }
+
+    fn hash_of(n: Number) -> u64 {
+        let mut state = DefaultHasher::new();
+        hash(n, &mut state);
+        state.finish()
+    }
+
+    #[test]
+    fn test_decimal_str() {
+        for s in [
+            "0",
+            "-0",
+            "1.50",
+            "123",
+            "0.000001",
+            "1E-7",
+            "1E+2",
+            "-1.23E+5",
+            "0E-8",
+            "Infinity",
+            "-Infinity",
+            "12.345",
+            "0.0012",
+        ] {
+            assert_eq!(decimal(s).to_string(), s);
+        }
+        assert_eq!(decimal("1e2").to_string(), "1E+2");
+        assert!(Decimal::parse("NaN").is_none());
+        assert!(Decimal::parse("1".repeat(40).as_str()).is_none());
+    }
+
+    #[test]
+    fn test_fraction_str() {
+        assert_eq!(fraction(2, -4).to_string(), "-1/2");
+        assert_eq!(fraction(6, 3).to_string(), "2");
+        assert_eq!(Fraction::parse("-1/2").unwrap().to_string(), "-1/2");
+        assert!(Fraction::parse("1/0").is_none());
+    }
+
+    #[test]
+    fn test_cmp() {
+        let half = fraction(1, 2);
+        let third = fraction(1, 3);
+        let point_five = decimal("0.50");
+        let point_one = decimal("0.1");
+        let big = decimal("1E+400");
+
+        assert_eq!(
+            cmp(Number::Fraction(&half), Number::Float(0.5)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            cmp(Number::Decimal(&point_five), Number::Fraction(&half)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            cmp(Number::Decimal(&point_one), Number::Float(0.1)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            cmp(
+                Number::Fraction(&third),
+                Number::Decimal(&decimal("0.3333"))
+            ),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            cmp(Number::Decimal(&big), Number::Float(f64::MAX)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            cmp(Number::Decimal(&big), Number::Float(f64::INFINITY)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            cmp(Number::Decimal(&decimal("-0")), Number::Int(0)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            cmp(Number::Decimal(&decimal("-2")), Number::Fraction(&half)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(cmp(Number::Fraction(&half), Number::Float(f64::NAN)), None);
+
+        // ints and floats compare exactly too
+        assert_eq!(
+            cmp(Number::Int((1 << 53) + 1), Number::Float(2f64.powi(53))),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            cmp(Number::Int(i64::MAX), Number::Float(TWO_63)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            cmp(Number::Int(-3), Number::Float(-2.5)),
+            Some(Ordering::Less)
+        );
+    }
+
+    #[test]
+    fn test_sum() {
+        let sum_of = |ints: &[i64], floats: &[f64]| {
+            let mut sum = Sum::default();
+            ints.iter().for_each(|x| sum.add_int(*x));
+            floats.iter().for_each(|x| sum.add_float(*x));
+            sum
+        };
+
+        // ints stay exact past the `i64` range
+        let sum = sum_of(&[i64::MAX, i64::MAX, -1], &[]);
+        assert_eq!(sum.to_int(), Some(2 * i64::MAX as i128 - 1));
+
+        // floats round once whatever the order or grouping
+        for floats in [[1e20, 1.0, -1e20], [1.0, 1e20, -1e20], [-1e20, 1e20, 1.0]] {
+            assert_eq!(sum_of(&[], &floats).to_f64(), 1.0);
+            let mut sum = sum_of(&[], &floats[..1]);
+            sum.merge(&sum_of(&[], &floats[1..]));
+            assert_eq!(sum.to_f64(), 1.0);
+            assert_eq!(sum.to_int(), None);
+        }
+        assert_eq!(sum_of(&[1 << 60, 1], &[0.5]).to_f64(), 2f64.powi(60));
+        assert_eq!(
+            sum_of(&[i64::MAX, i64::MAX], &[-1e19]).to_f64(),
+            8446744073709551614.0
+        );
+        assert_eq!(sum_of(&[], &[0.1; 10]).to_f64(), 1.0);
+
+        assert!(sum_of(&[1], &[f64::INFINITY, f64::NEG_INFINITY])
+            .to_f64()
+            .is_nan());
+        assert_eq!(sum_of(&[1], &[f64::INFINITY, 2.0]).to_f64(), f64::INFINITY);
+        assert_eq!(sum_of(&[], &[]).to_int(), Some(0));
+    }
+
+    #[test]
+    fn test_hash() {
+        let two = decimal("2.00");
+        assert_eq!(hash_of(Number::Decimal(&two)), hash_of(Number::Int(2)));
+        assert_eq!(
+            hash_of(Number::Fraction(&fraction(4, 2))),
+            hash_of(Number::Float(2.0))
+        );
+        assert_eq!(
+            hash_of(Number::Fraction(&fraction(1, 4))),
+            hash_of(Number::Decimal(&decimal("0.25")))
+        );
+        assert_eq!(
+            hash_of(Number::Fraction(&fraction(1, 10))),
+            hash_of(Number::Decimal(&decimal("0.100")))
+        );
+        assert_eq!(
+            hash_of(Number::Decimal(&decimal("1E+30"))),
+            hash_of(Number::Decimal(&decimal("1000E+27")))
+        );
+        assert_eq!(
+            hash_of(Number::Float(-TWO_63)),
+            hash_of(Number::Int(i64::MIN))
+        );
+        assert_ne!(
+            hash_of(Number::Fraction(&fraction(1, 3))),
+            hash_of(Number::Fraction(&fraction(1, 6)))
+        );
+    }
+}
@@ -0,0 +1,753 @@
+use crate::aggregate::{Aggregate, AggregateKind, Fold, NotANumber, Summary};
+use crate::elem::Elem;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::Arc;
+
+// AVL tree with reference counted nodes. Updates copy the nodes on the path
+// to the changed entry when they are shared and update them in place
+// otherwise, so cloning a map is O(1) and clones never observe each other's
+// updates. Every node also stores the size of its subtree, which makes
+// `nth`, `split_off` and `append` O(log n), and the summary of its values
+// for the aggregate the map keeps, if any.
+
+#[derive(Clone)]
+struct Node {
+    entry: Arc<(Elem, Elem)>,
+    left: Link,
+    right: Link,
+    height: usize,
+    len: usize,
+    summary: Option<Summary>,
+}
+
+type Link = Option<Arc<Node>>;
+
+#[derive(Clone, Default)]
+pub struct PersistentMap {
+    root: Link,
+    aggregate: Option<AggregateKind>,
+}
+
+impl PersistentMap {
+    /// Builds a balanced tree in O(n) over `entries`, whose keys must be
+    /// strictly increasing.
+    pub fn from_sorted(entries: Vec<(Elem, Elem)>) -> Self {
+        let entries = entries.into_iter().map(Arc::new).collect::<Vec<_>>();
+        PersistentMap {
+            root: build_balanced(None, &entries),
+            aggregate: None,
+        }
+    }
+
+    /// The map keeping the `aggregate` of its values, rebuilt in O(n) unless
+    /// it already kept that one.
+    pub fn with_aggregate(self, aggregate: Option<AggregateKind>) -> Result<Self, NotANumber> {
+        if aggregate == self.aggregate {
+            return Ok(self);
+        }
+        if let Some(kind) = aggregate {
+            self.values().try_for_each(|value| kind.check(value))?;
+        }
+
+        let entries = self.into_iter().collect::<Vec<_>>();
+        Ok(PersistentMap {
+            root: build_balanced(aggregate, &entries),
+            aggregate,
+        })
+    }
+
+    pub fn aggregate(&self) -> Option<AggregateKind> {
+        self.aggregate
+    }
+
+    /// Aggregate of the values whose keys are in `[start, stop)`, a missing
+    /// bound leaves that side of the range open. `None` if the map keeps no
+    /// aggregate.
+    pub fn range_aggregate(&self, start: Option<&Elem>, stop: Option<&Elem>) -> Option<Aggregate> {
+        let mut fold = Fold::new(self.aggregate?);
+        fold_range(&mut fold, &self.root, start, stop);
+        Some(fold.finish())
+    }
+
+    pub fn len(&self) -> usize {
+        len(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.root = None;
+    }
+
+    /// Moves the entries out, leaving the map empty with the same aggregate.
+    pub fn take(&mut self) -> Self {
+        PersistentMap {
+            root: self.root.take(),
+            aggregate: self.aggregate,
+        }
+    }
+
+    pub fn get(&self, key: &Elem) -> Option<&Elem> {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+
+    pub fn get_key_value(&self, key: &Elem) -> Option<(&Elem, &Elem)> {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            match key.cmp(&node.entry.0) {
+                Ordering::Less => link = &node.left,
+                Ordering::Greater => link = &node.right,
+                Ordering::Equal => return Some((&node.entry.0, &node.entry.1)),
+            }
+        }
+        None
+    }
+
+    pub fn contains_key(&self, key: &Elem) -> bool {
+        self.get_key_value(key).is_some()
+    }
+
+    pub fn first_key_value(&self) -> Option<(&Elem, &Elem)> {
+        let mut node = self.root.as_ref()?;
+        while let Some(left) = &node.left {
+            node = left;
+        }
+        Some((&node.entry.0, &node.entry.1))
+    }
+
+    pub fn last_key_value(&self) -> Option<(&Elem, &Elem)> {
+        let mut node = self.root.as_ref()?;
+        while let Some(right) = &node.right {
+            node = right;
+        }
+        Some((&node.entry.0, &node.entry.1))
+    }
+
+    /// The entry at position `n` in key order, in O(log n).
+    pub fn nth(&self, mut n: usize) -> Option<(&Elem, &Elem)> {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            let left = len(&node.left);
+            match n.cmp(&left) {
+                Ordering::Less => link = &node.left,
+                Ordering::Equal => return Some((&node.entry.0, &node.entry.1)),
+                Ordering::Greater => {
+                    n -= left + 1;
+                    link = &node.right;
+                }
+            }
+        }
+        None
+    }
+
+    /// Like `BTreeMap::insert`, an existing key is kept and its value replaced.
+    /// The value must suit the aggregate the map keeps.
+    pub fn insert(&mut self, key: Elem, value: Elem) -> Option<Elem> {
+        insert(self.aggregate, &mut self.root, key, value).map(into_value)
+    }
+
+    pub fn remove(&mut self, key: &Elem) -> Option<Elem> {
+        // checked first so that a missing key doesn't copy any shared node
+        if !self.contains_key(key) {
+            return None;
+        }
+        Some(into_value(remove(self.aggregate, &mut self.root, key)))
+    }
+
+    /// Moves the entries whose keys are greater than or equal to `key` into
+    /// the returned map.
+    pub fn split_off(&mut self, key: &Elem) -> Self {
+        let kind = self.aggregate;
+        let (left, entry, right) = split(kind, self.root.take(), key);
+        self.root = left;
+        let root = match entry {
+            Some(entry) => Some(join(kind, None, entry, right)),
+            None => right,
+        };
+        PersistentMap {
+            root,
+            aggregate: kind,
+        }
+    }
+
+    /// Moves the entries of `other` to the end of `self`. Every key of `other`
+    /// must be greater than the keys of `self`, and both maps must keep the
+    /// same aggregate.
+    pub fn append(&mut self, other: &mut Self) {
+        debug_assert_eq!(self.aggregate, other.aggregate);
+        let kind = self.aggregate;
+        let mut right = other.root.take();
+        if right.is_some() {
+            let entry = remove_min(kind, &mut right);
+            self.root = Some(join(kind, self.root.take(), entry, right));
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        let mut iter = Iter {
+            stack: Vec::new(),
+            len: self.len(),
+        };
+        iter.push_left(&self.root);
+        iter
+    }
+
+    pub fn keys(&self) -> impl ExactSizeIterator<Item = &Elem> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl ExactSizeIterator<Item = &Elem> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// The entries whose keys are within `(start, end)`, in key order.
+    pub fn range<'a>(&'a self, (start, end): (Bound<&'a Elem>, Bound<&'a Elem>)) -> Range<'a> {
+        let mut stack = Vec::new();
+        let mut link = &self.root;
+        while let Some(node) = link {
+            let after_start = match start {
+                Bound::Included(start) => &node.entry.0 >= start,
+                Bound::Excluded(start) => &node.entry.0 > start,
+                Bound::Unbounded => true,
+            };
+            if after_start {
+                stack.push(&**node);
+                link = &node.left;
+            } else {
+                link = &node.right;
+            }
+        }
+
+        Range {
+            iter: Iter { stack, len: 0 },
+            end,
+        }
+    }
+}
+
+impl From<BTreeMap<Elem, Elem>> for PersistentMap {
+    fn from(btree_map: BTreeMap<Elem, Elem>) -> Self {
+        PersistentMap::from_sorted(btree_map.into_iter().collect())
+    }
+}
+
+impl<'a> IntoIterator for &'a PersistentMap {
+    type Item = (&'a Elem, &'a Elem);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl IntoIterator for PersistentMap {
+    type Item = Arc<(Elem, Elem)>;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        let mut iter = IntoIter { stack: Vec::new() };
+        iter.push_left(self.root);
+        iter
+    }
+}
+
+pub struct Iter<'a> {
+    stack: Vec<&'a Node>,
+    len: usize,
+}
+
+impl<'a> Iter<'a> {
+    fn push_left(&mut self, mut link: &'a Link) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a Elem, &'a Elem);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(&node.right);
+        self.len = self.len.saturating_sub(1);
+        Some((&node.entry.0, &node.entry.1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+pub struct Range<'a> {
+    iter: Iter<'a>,
+    end: Bound<&'a Elem>,
+}
+
+impl<'a> Iterator for Range<'a> {
+    type Item = (&'a Elem, &'a Elem);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key, value) = self.iter.next()?;
+        let before_end = match self.end {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+        if !before_end {
+            self.iter.stack.clear();
+            return None;
+        }
+        Some((key, value))
+    }
+}
+
+/// In-order iterator that owns the nodes it still has to visit, so it stays
+/// valid however the map it came from is updated.
+#[derive(Clone)]
+pub struct IntoIter {
+    stack: Vec<Arc<Node>>,
+}
+
+impl IntoIter {
+    fn push_left(&mut self, mut link: Link) {
+        while let Some(node) = link {
+            link = node.left.clone();
+            self.stack.push(node);
+        }
+    }
+}
+
+impl Iterator for IntoIter {
+    type Item = Arc<(Elem, Elem)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left(node.right.clone());
+        Some(node.entry.clone())
+    }
+}
+
+/// The value of a removed entry, cloned if a clone of the map still holds it.
+fn into_value(entry: Arc<(Elem, Elem)>) -> Elem {
+    Arc::try_unwrap(entry).map_or_else(|entry| entry.1.clone(), |(_, value)| value)
+}
+
+fn height(link: &Link) -> usize {
+    link.as_ref().map_or(0, |x| x.height)
+}
+
+fn len(link: &Link) -> usize {
+    link.as_ref().map_or(0, |x| x.len)
+}
+
+fn summary(link: &Link) -> Option<&Summary> {
+    link.as_ref().and_then(|x| x.summary.as_ref())
+}
+
+impl Node {
+    /// Recomputes what the node keeps about its subtree from its children.
+    fn update(&mut self, kind: Option<AggregateKind>) {
+        self.height = 1 + height(&self.left).max(height(&self.right));
+        self.len = 1 + len(&self.left) + len(&self.right);
+        self.summary = kind.and_then(|kind| {
+            let mut fold = Fold::new(kind);
+            fold.subtree(summary(&self.left), 0);
+            fold.entry(&self.entry);
+            fold.subtree(summary(&self.right), 0);
+            fold.into_summary()
+        });
+    }
+}
+
+fn make(
+    kind: Option<AggregateKind>,
+    entry: Arc<(Elem, Elem)>,
+    left: Link,
+    right: Link,
+) -> Arc<Node> {
+    let mut node = Node {
+        entry,
+        left,
+        right,
+        height: 0,
+        len: 0,
+        summary: None,
+    };
+    node.update(kind);
+    Arc::new(node)
+}
+
+/// Joins two subtrees whose heights differ by at most two under `entry`.
+fn balance(
+    kind: Option<AggregateKind>,
+    entry: Arc<(Elem, Elem)>,
+    left: Link,
+    right: Link,
+) -> Arc<Node> {
+    let (left_height, right_height) = (height(&left), height(&right));
+
+    if left_height > right_height + 1 {
+        let left = left.unwrap();
+        if height(&left.left) >= height(&left.right) {
+            let right = make(kind, entry, left.right.clone(), right);
+            make(kind, left.entry.clone(), left.left.clone(), Some(right))
+        } else {
+            let middle = left.right.as_ref().unwrap();
+            let new_left = make(
+                kind,
+                left.entry.clone(),
+                left.left.clone(),
+                middle.left.clone(),
+            );
+            let new_right = make(kind, entry, middle.right.clone(), right);
+            make(kind, middle.entry.clone(), Some(new_left), Some(new_right))
+        }
+    } else if right_height > left_height + 1 {
+        let right = right.unwrap();
+        if height(&right.right) >= height(&right.left) {
+            let left = make(kind, entry, left, right.left.clone());
+            make(kind, right.entry.clone(), Some(left), right.right.clone())
+        } else {
+            let middle = right.left.as_ref().unwrap();
+            let new_left = make(kind, entry, left, middle.left.clone());
+            let new_right = make(
+                kind,
+                right.entry.clone(),
+                middle.right.clone(),
+                right.right.clone(),
+            );
+            make(kind, middle.entry.clone(), Some(new_left), Some(new_right))
+        }
+    } else {
+        make(kind, entry, left, right)
+    }
+}
+
+/// Restores the node at `link` after one of its subtrees changed. The node is
+/// updated in place unless it needs a rotation.
+fn rebalance(kind: Option<AggregateKind>, link: &mut Link) {
+    let node = link.as_mut().unwrap();
+    if height(&node.left).abs_diff(height(&node.right)) <= 1 {
+        Arc::make_mut(node).update(kind);
+        return;
+    }
+
+    let Node {
+        entry, left, right, ..
+    } = Arc::unwrap_or_clone(link.take().unwrap());
+    *link = Some(balance(kind, entry, left, right));
+}
+
+/// Joins two subtrees of any heights under `entry`, whose key must be
+/// between the keys of `left` and `right`, in O(height difference).
+fn join(
+    kind: Option<AggregateKind>,
+    left: Link,
+    entry: Arc<(Elem, Elem)>,
+    right: Link,
+) -> Arc<Node> {
+    let (left_height, right_height) = (height(&left), height(&right));
+
+    if left_height > right_height + 1 {
+        let left = Arc::unwrap_or_clone(left.unwrap());
+        let right = join(kind, left.right, entry, right);
+        balance(kind, left.entry, left.left, Some(right))
+    } else if right_height > left_height + 1 {
+        let right = Arc::unwrap_or_clone(right.unwrap());
+        let left = join(kind, left, entry, right.left);
+        balance(kind, right.entry, Some(left), right.right)
+    } else {
+        make(kind, entry, left, right)
+    }
+}
+
+/// Splits a tree into the keys less than `key`, the entry of `key` if
+/// present, and the keys greater than `key`.
+fn split(
+    kind: Option<AggregateKind>,
+    link: Link,
+    key: &Elem,
+) -> (Link, Option<Arc<(Elem, Elem)>>, Link) {
+    let node = match link {
+        Some(node) => Arc::unwrap_or_clone(node),
+        None => return (None, None, None),
+    };
+
+    match key.cmp(&node.entry.0) {
+        Ordering::Less => {
+            let (left, entry, right) = split(kind, node.left, key);
+            (left, entry, Some(join(kind, right, node.entry, node.right)))
+        }
+        Ordering::Greater => {
+            let (left, entry, right) = split(kind, node.right, key);
+            (Some(join(kind, node.left, node.entry, left)), entry, right)
+        }
+        Ordering::Equal => (node.left, Some(node.entry), node.right),
+    }
+}
+
+fn build_balanced(kind: Option<AggregateKind>, entries: &[Arc<(Elem, Elem)>]) -> Link {
+    if entries.is_empty() {
+        return None;
+    }
+    let mid = entries.len() / 2;
+    let left = build_balanced(kind, &entries[..mid]);
+    let right = build_balanced(kind, &entries[mid + 1..]);
+    Some(make(kind, entries[mid].clone(), left, right))
+}
+
+/// Adds the subtrees and entries of `link` within `[start, stop)` to `fold`.
+fn fold_range(fold: &mut Fold, link: &Link, start: Option<&Elem>, stop: Option<&Elem>) {
+    let node = match link {
+        Some(node) => node,
+        None => return,
+    };
+
+    if start.is_none() && stop.is_none() {
+        return fold.subtree(node.summary.as_ref(), node.len);
+    }
+    if start.is_some_and(|start| &node.entry.0 < start) {
+        return fold_range(fold, &node.right, start, stop);
+    }
+    if stop.is_some_and(|stop| &node.entry.0 >= stop) {
+        return fold_range(fold, &node.left, start, stop);
+    }
+
+    // the node is inside the range, so its left subtree is only bounded by
+    // `start` and its right subtree only by `stop`
+    fold_range(fold, &node.left, start, None);
+    fold.entry(&node.entry);
+    fold_range(fold, &node.right, None, stop);
+}
+
+/// Returns the replaced entry if the key was already present.
+fn insert(
+    kind: Option<AggregateKind>,
+    link: &mut Link,
+    key: Elem,
+    value: Elem,
+) -> Option<Arc<(Elem, Elem)>> {
+    let node = match link {
+        Some(node) => Arc::make_mut(node),
+        None => {
+            *link = Some(make(kind, Arc::new((key, value)), None, None));
+            return None;
+        }
+    };
+
+    let replaced = match key.cmp(&node.entry.0) {
+        Ordering::Less => insert(kind, &mut node.left, key, value),
+        Ordering::Greater => insert(kind, &mut node.right, key, value),
+        Ordering::Equal => {
+            let entry = Arc::new((node.entry.0.clone(), value));
+            Some(std::mem::replace(&mut node.entry, entry))
+        }
+    };
+
+    rebalance(kind, link);
+    replaced
+}
+
+/// Returns the removed entry, `key` must be present.
+fn remove(kind: Option<AggregateKind>, link: &mut Link, key: &Elem) -> Arc<(Elem, Elem)> {
+    let node = Arc::make_mut(link.as_mut().unwrap());
+
+    let removed = match key.cmp(&node.entry.0) {
+        Ordering::Less => remove(kind, &mut node.left, key),
+        Ordering::Greater => remove(kind, &mut node.right, key),
+        Ordering::Equal if node.right.is_none() => {
+            let removed = node.entry.clone();
+            *link = node.left.take();
+            return removed;
+        }
+        Ordering::Equal => {
+            let entry = remove_min(kind, &mut node.right);
+            std::mem::replace(&mut node.entry, entry)
+        }
+    };
+
+    rebalance(kind, link);
+    removed
+}
+
+fn remove_min(kind: Option<AggregateKind>, link: &mut Link) -> Arc<(Elem, Elem)> {
+    let node = Arc::make_mut(link.as_mut().unwrap());
+    if node.left.is_none() {
+        let entry = node.entry.clone();
+        *link = node.right.take();
+        return entry;
+    }
+
+    let entry = remove_min(kind, &mut node.left);
+    rebalance(kind, link);
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_invariants(link: &Link) -> usize {
+        let node = match link {
+            Some(node) => node,
+            None => return 0,
+        };
+        let left = check_invariants(&node.left);
+        let right = check_invariants(&node.right);
+
+        assert!(left.abs_diff(right) <= 1);
+        assert_eq!(node.height, 1 + left.max(right));
+        assert_eq!(node.len, 1 + len(&node.left) + len(&node.right));
+
+        node.height
+    }
+
+    fn keys(map: &PersistentMap) -> Vec<i64> {
+        map.keys()
+            .map(|x| match x {
+                Elem::Int(key) => *key,
+                _ => panic!("Expected Int"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_persistent_map() {
+        let mut map = PersistentMap::default();
+        let mut expected = BTreeMap::new();
+        let mut snapshots = Vec::new();
+
+        // deterministic pseudo-random updates
+        let mut seed: i64 = 3;
+        for i in 0..500 {
+            seed = (seed * 1103515245 + 12345) % 2147483648;
+            let key = seed % 100;
+            if seed % 3 == 0 {
+                let output = map.remove(&Elem::Int(key));
+                assert_eq!(output, expected.remove(&key).map(Elem::Int));
+            } else {
+                let output = map.insert(Elem::Int(key), Elem::Int(i));
+                assert_eq!(output, expected.insert(key, i).map(Elem::Int));
+            }
+            check_invariants(&map.root);
+            assert_eq!(map.len(), expected.len());
+
+            if i % 50 == 0 {
+                snapshots.push((map.clone(), expected.clone()));
+            }
+        }
+
+        for (snapshot, expected) in snapshots {
+            assert_eq!(
+                keys(&snapshot),
+                expected.keys().copied().collect::<Vec<_>>()
+            );
+            for (key, value) in expected {
+                assert_eq!(snapshot.get(&Elem::Int(key)), Some(&Elem::Int(value)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_split_off_and_append() {
+        let entries = (0..100).map(|x| (Elem::Int(x), Elem::Int(-x))).collect();
+        let map = PersistentMap::from_sorted(entries);
+
+        for key in [-1, 0, 37, 99, 100] {
+            let mut left = map.clone();
+            let mut right = left.split_off(&Elem::Int(key));
+            check_invariants(&left.root);
+            check_invariants(&right.root);
+            let key = key.clamp(0, 100);
+            assert_eq!(keys(&left), (0..key).collect::<Vec<_>>());
+            assert_eq!(keys(&right), (key..100).collect::<Vec<_>>());
+
+            left.append(&mut right);
+            check_invariants(&left.root);
+            assert!(right.is_empty());
+            assert_eq!(keys(&left), (0..100).collect::<Vec<_>>());
+        }
+        // the splits never touched the shared tree
+        assert_eq!(keys(&map), (0..100).collect::<Vec<_>>());
+        assert_eq!(map.nth(42), Some((&Elem::Int(42), &Elem::Int(-42))));
+        assert_eq!(map.nth(100), None);
+
+        let range = map
+            .range((
+                Bound::Excluded(&Elem::Int(10)),
+                Bound::Included(&Elem::Int(13)),
+            ))
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(range, [11, 12, 13].map(Elem::Int));
+    }
+
+    fn to_int(aggregate: Aggregate) -> Option<i128> {
+        match aggregate {
+            Aggregate::Sum(sum) => sum.to_int(),
+            Aggregate::Extreme(Some(Elem::Int(x))) => Some(x.into()),
+            Aggregate::Extreme(_) => None,
+            Aggregate::Count(x) => Some(x as i128),
+        }
+    }
+
+    #[test]
+    fn test_range_aggregate() {
+        let entries = (0..40)
+            .map(|x| (Elem::Int(x), Elem::Int((x * 7) % 13 - 6)))
+            .collect();
+        let map = PersistentMap::from_sorted(entries);
+        assert!(map.range_aggregate(None, None).is_none());
+
+        for kind in [
+            AggregateKind::Sum,
+            AggregateKind::Min,
+            AggregateKind::Max,
+            AggregateKind::Count,
+        ] {
+            let mut map = map.clone().with_aggregate(Some(kind)).unwrap();
+            map.remove(&Elem::Int(17));
+            map.insert(Elem::Int(50), Elem::Int(i64::MAX));
+            map.insert(Elem::Int(51), Elem::Int(i64::MAX));
+            let mut right = map.split_off(&Elem::Int(20));
+            map.append(&mut right);
+            check_invariants(&map.root);
+
+            for start in (-1..55).step_by(3) {
+                for stop in (start..55).step_by(4) {
+                    let (start, stop) = (Elem::Int(start), Elem::Int(stop));
+                    let values = map
+                        .range((Bound::Included(&start), Bound::Excluded(&stop)))
+                        .map(|(_, value)| match value {
+                            Elem::Int(x) => i128::from(*x),
+                            _ => panic!("Expected Int"),
+                        });
+                    let expected = match kind {
+                        AggregateKind::Sum => Some(values.sum()),
+                        AggregateKind::Min => values.min(),
+                        AggregateKind::Max => values.max(),
+                        AggregateKind::Count => Some(values.count() as i128),
+                    };
+                    let output = map.range_aggregate(Some(&start), Some(&stop)).unwrap();
+                    assert_eq!(to_int(output), expected);
+                }
+            }
+        }
+
+        let mut map = map;
+        map.insert(Elem::Int(0), Elem::String("a".into()));
+        assert!(map
+            .clone()
+            .with_aggregate(Some(AggregateKind::Count))
+            .is_ok());
+        assert!(map.with_aggregate(Some(AggregateKind::Sum)).is_err());
+    }
+}
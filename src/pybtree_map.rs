@@ -1,182 +1,785 @@
-use crate::elem::Elem;
-use crate::iterators::{PyBTreeMapIter, PyBTreeMapKeys, PyBTreeMapValues};
+use crate::aggregate::{AggregateKind, Fold};
+use crate::binary;
+use crate::bulk;
+use crate::elem::{self, Elem};
+use crate::iterators::{self, Cursor, KeyRange, PyBTreeMapIter, PyBTreeMapKeys, PyBTreeMapValues};
+use crate::json;
+use crate::merge::{self, OnConflict};
+use crate::persistent_map::PersistentMap;
+use crate::pybtree_map_snapshot::PyBTreeMapSnapshot;
+use crate::pydisk_btree_map::PyDiskBTreeMap;
+use crate::pyfrozen_btree_map::PyFrozenBTreeMap;
+use crate::tree_lock::TreeLock;
+use crate::write_ahead_log::{self, Record, SyncPolicy, WriteAheadLog};
 use pyo3::exceptions;
 use pyo3::prelude::*;
-use pyo3::types::{PyIterator, PyMapping, PySequence, PyTuple};
-use std::collections::{btree_map, BTreeMap};
+use pyo3::types::{PyBytes, PyDict, PyList, PyTuple, PyType};
+use std::collections::BTreeMap;
+use std::io::Cursor as IoCursor;
+use std::path::PathBuf;
 
-#[pyclass]
+#[pyclass(module = "tree_collections.tree_collections", frozen)]
 pub struct PyBTreeMap {
-    pub btree_map: BTreeMap<Elem, Elem>,
+    pub state: TreeLock<BTreeMapState>,
 }
 
-unsafe impl Send for PyBTreeMap {}
+pub struct BTreeMapState {
+    // copy-on-write, shared with the snapshots taken from it, keeps the
+    // aggregate the map was created with
+    pub map: PersistentMap,
+    // every update is appended here before it's applied, set by `recover()`
+    pub log: Option<WriteAheadLog>,
+}
 
 #[pymethods]
 impl PyBTreeMap {
     #[new]
-    #[pyo3(signature = (input=None))]
-    pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self> {
-        let mut btree = BTreeMap::<Elem, Elem>::new();
-
-        if let Some(input) = input {
-            let iter: &PyIterator = if let Ok(input) = input.downcast::<PyMapping>(py) {
-                input.items()?.iter()?
-            } else if let Ok(input) = input.downcast::<PySequence>(py) {
-                input.iter()?
-            } else if let Ok(input) = input.downcast::<PyIterator>(py) {
-                input
-            } else {
-                return Err(PyErr::new::<exceptions::PyTypeError, _>(
-                    "Expected a mapping or iterable of tuples",
-                ));
-            };
+    #[pyo3(signature = (input=None, aggregate=None))]
+    pub fn new(
+        input: Option<PyObject>,
+        aggregate: Option<AggregateKind>,
+        py: Python,
+    ) -> PyResult<Self> {
+        let items = match input {
+            Some(input) => bulk::extract_pairs(input, py)?,
+            None => Vec::new(),
+        };
+        let map = elem::catch_cmp(|| {
+            Ok(bulk::allow_threads_if_native(
+                py,
+                items,
+                |(key, _)| key,
+                |items| PersistentMap::from_sorted(bulk::build_entries(items)),
+            ))
+        })?;
+
+        PyBTreeMap::with_aggregate(map, aggregate)
+    }
 
-            for x in iter {
-                let x = x?.downcast::<PyTuple>()?;
-                let (key, value) = match (x.get_item(0), x.get_item(1)) {
-                    (Ok(key), Ok(value)) => (key, value),
-                    _ => {
-                        return Err(PyErr::new::<exceptions::PyTypeError, _>(
-                            "iterable of tuples must contain two elements",
-                        ))
+    #[classmethod]
+    #[pyo3(signature = (input, validate=true, aggregate=None))]
+    pub fn from_sorted(
+        _cls: &Bound<'_, PyType>,
+        input: PyObject,
+        validate: bool,
+        aggregate: Option<AggregateKind>,
+        py: Python,
+    ) -> PyResult<Self> {
+        let items = bulk::extract_pairs(input, py)?;
+        let map = elem::catch_cmp(|| {
+            bulk::allow_threads_if_native(
+                py,
+                items,
+                |(key, _)| key,
+                |items| {
+                    if validate {
+                        bulk::check_sorted(&items, |(key, _)| key)?;
                     }
-                };
-                let elem_key = key.extract::<Elem>()?;
-                let elem_value = value.extract::<Elem>()?;
-                btree.insert(elem_key, elem_value);
-            }
-        }
+                    PyResult::Ok(PersistentMap::from_sorted(bulk::build_entries(items)))
+                },
+            )
+        })?;
 
-        Ok(PyBTreeMap { btree_map: btree })
+        PyBTreeMap::with_aggregate(map, aggregate)
     }
 
-    pub fn insert(
-        mut slf: PyRefMut<'_, Self>,
-        key: PyObject,
-        value: PyObject,
-    ) -> PyResult<Option<PyObject>> {
+    pub fn insert(&self, py: Python, key: PyObject, value: PyObject) -> PyResult<Option<Elem>> {
         // cast to orderable type
-        let py = slf.py();
         let elem_key = key.extract::<Elem>(py)?;
         let elem_value = value.extract::<Elem>(py)?;
-        let output = slf.btree_map.insert(elem_key, elem_value);
 
-        Ok(output.map(|x| x.into_py(py)))
+        let mut state = self.state.write(py)?;
+        elem::catch_cmp(|| state.insert_elem(elem_key, elem_value))
     }
 
-    pub fn get(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<Option<PyObject>> {
-        let py = slf.py();
+    pub fn get(&self, py: Python, key: PyObject) -> PyResult<Option<PyObject>> {
         let key = key.extract::<Elem>(py)?;
-        let output = slf.btree_map.get(&key);
+        let state = self.state.read(py)?;
+        let output = elem::catch_cmp(|| Ok(state.map.get(&key)))?;
 
         Ok(output.map(|x| x.to_pyobject(py)))
     }
 
-    pub fn remove(mut slf: PyRefMut<'_, Self>, key: PyObject) -> PyResult<Option<PyObject>> {
-        let py = slf.py();
+    pub fn remove(&self, py: Python, key: PyObject) -> PyResult<Option<Elem>> {
         let key = key.extract::<Elem>(py)?;
-        let output = slf.btree_map.remove(&key).map(|x| x.into_py(py));
-
-        Ok(output)
+        let mut state = self.state.write(py)?;
+        elem::catch_cmp(|| state.remove_elem(&key))
     }
 
-    pub fn contains_key(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<bool> {
-        let py = slf.py();
+    pub fn contains_key(&self, py: Python, key: PyObject) -> PyResult<bool> {
         let elem_key = key.extract::<Elem>(py)?;
-        Ok(slf.btree_map.contains_key(&elem_key))
+        let state = self.state.read(py)?;
+        elem::catch_cmp(|| Ok(state.map.contains_key(&elem_key)))
     }
 
-    pub fn nth(mut slf: PyRefMut<'_, Self>, mut n: i64) -> PyResult<Option<(PyObject, PyObject)>> {
-        let py = slf.py();
+    pub fn insert_many(&self, py: Python, input: PyObject) -> PyResult<Vec<Option<Elem>>> {
+        let items = bulk::extract_pairs(input, py)?;
+        let mut state = self.state.write(py)?;
+        state.check_entries(items.iter().map(|(key, value)| (key, value)))?;
+        let mut output = Vec::new();
+        output.resize_with(items.len(), || None);
 
-        if n >= slf.btree_map.len() as i64 {
+        elem::catch_cmp(|| {
+            for (i, (key, value)) in bulk::sort_batch(items, |(key, _)| key) {
+                output[i] = state.insert_elem(key, value)?;
+            }
+            Ok(output)
+        })
+    }
+
+    pub fn update_many(&self, py: Python, input: PyObject) -> PyResult<()> {
+        let items = bulk::extract_pairs(input, py)?;
+        let mut state = self.state.write(py)?;
+        state.check_entries(items.iter().map(|(key, value)| (key, value)))?;
+
+        elem::catch_cmp(|| {
+            for (_, (key, value)) in bulk::sort_batch(items, |(key, _)| key) {
+                state.insert_elem(key, value)?;
+            }
+            Ok(())
+        })
+    }
+
+    #[pyo3(signature = (keys, default=None))]
+    pub fn get_many(
+        &self,
+        py: Python,
+        keys: PyObject,
+        default: Option<PyObject>,
+    ) -> PyResult<Vec<Option<PyObject>>> {
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = Vec::with_capacity(keys.len());
+        output.resize_with(keys.len(), || default.as_ref().map(|x| x.clone_ref(py)));
+        let state = self.state.read(py)?;
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                if let Some(value) = state.map.get(&key) {
+                    output[i] = Some(value.to_pyobject(py));
+                }
+            }
+            Ok(output)
+        })
+    }
+
+    pub fn contains_many(&self, py: Python, keys: PyObject) -> PyResult<Vec<bool>> {
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = vec![false; keys.len()];
+        let state = self.state.read(py)?;
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                output[i] = state.map.contains_key(&key);
+            }
+            Ok(output)
+        })
+    }
+
+    pub fn remove_many(&self, py: Python, keys: PyObject) -> PyResult<Vec<Option<Elem>>> {
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = Vec::new();
+        output.resize_with(keys.len(), || None);
+        let mut state = self.state.write(py)?;
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                output[i] = state.remove_elem(&key)?;
+            }
+            Ok(output)
+        })
+    }
+
+    pub fn nth(&self, py: Python, mut n: i64) -> PyResult<Option<(PyObject, PyObject)>> {
+        let state = self.state.read(py)?;
+        let map = &state.map;
+
+        if n >= map.len() as i64 {
             return Ok(None);
         }
         if n < 0 {
-            n = slf.btree_map.len() as i64 + n;
+            n += map.len() as i64;
         }
         if n < 0 {
             return Ok(None);
         }
-        let n = n as usize;
 
-        let key_value_fn = |(key, value): (&Elem, &Elem)| -> (PyObject, PyObject) {
-            (Elem::to_pyobject(key, py), Elem::to_pyobject(value, py))
-        };
-        let entry_fn = |entry: btree_map::OccupiedEntry<Elem, Elem>| -> (PyObject, PyObject) {
-            key_value_fn((entry.key(), entry.get()))
-        };
+        let output = map
+            .nth(n as usize)
+            .map(|(key, value)| (key.to_pyobject(py), value.to_pyobject(py)));
+
+        Ok(output)
+    }
+
+    pub fn len(&self, py: Python) -> PyResult<usize> {
+        Ok(self.state.read(py)?.map.len())
+    }
 
-        let output = if n == 0 {
-            slf.btree_map.first_entry().map(entry_fn)
-        } else if n == slf.btree_map.len() - 1 {
-            slf.btree_map.last_entry().map(entry_fn)
-        } else {
-            slf.btree_map.iter().nth(n).map(key_value_fn)
+    pub fn is_empty(&self, py: Python) -> PyResult<bool> {
+        Ok(self.state.read(py)?.map.is_empty())
+    }
+
+    pub fn clear(&self, py: Python) -> PyResult<()> {
+        let mut state = self.state.write(py)?;
+        if let Some(log) = &mut state.log {
+            log.append(Record::Clear)?;
+        }
+        state.map.clear();
+
+        Ok(())
+    }
+
+    pub fn copy(&self, py: Python) -> PyResult<Self> {
+        let state = self.state.read(py)?;
+        let output = BTreeMapState {
+            map: state.map.clone(),
+            log: None,
         };
 
-        Ok(output)
+        Ok(output.into())
+    }
+
+    pub fn __copy__(&self, py: Python) -> PyResult<Self> {
+        self.copy(py)
     }
 
-    pub fn len(&self) -> usize {
-        return self.btree_map.len();
+    pub fn __deepcopy__(&self, py: Python, memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let state = self.state.read(py)?;
+        let btree_map = elem::catch_cmp(|| {
+            state
+                .map
+                .iter()
+                .map(|(key, value)| Ok((key.deepcopy(py, memo)?, value.deepcopy(py, memo)?)))
+                .collect::<PyResult<BTreeMap<_, _>>>()
+        })?;
+
+        PyBTreeMap::with_aggregate(btree_map.into(), state.map.aggregate())
     }
 
-    pub fn is_empty(&self) -> bool {
-        return self.btree_map.is_empty();
+    /// Pickles as `(keys, values, aggregate)` in key order.
+    pub fn __getstate__(&self, py: Python) -> PyResult<PyObject> {
+        let state = self.state.read(py)?;
+        let keys = PyList::new(py, state.map.keys())?;
+        let values = PyList::new(py, state.map.values())?;
+        let aggregate = state.map.aggregate().map(|x| x.name());
+
+        Ok((keys, values, aggregate)
+            .into_pyobject(py)?
+            .into_any()
+            .unbind())
     }
 
-    pub fn clear(&mut self) {
-        self.btree_map.clear();
+    pub fn __setstate__(&self, py: Python, state: &Bound<'_, PyAny>) -> PyResult<()> {
+        let (keys, values, aggregate) =
+            state.extract::<(Vec<Elem>, Vec<Elem>, Option<AggregateKind>)>()?;
+        let items = keys.into_iter().zip(values).collect();
+        let map = elem::catch_cmp(|| Ok(PersistentMap::from_sorted(bulk::build_entries(items))))?;
+        let output = BTreeMapState::with_aggregate(map, aggregate)?;
+        *self.state.write(py)? = output;
+
+        Ok(())
     }
 
-    pub fn keys(slf: PyRef<'_, Self>) -> PyBTreeMapKeys {
-        let slf = &slf;
-        let owner = slf.into_py(slf.py());
-        let iter = slf.btree_map.keys();
+    pub fn __reduce__<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<(Bound<'py, PyType>, Bound<'py, PyTuple>, PyObject)> {
+        let cls = py.get_type::<Self>();
+        Ok((cls, PyTuple::empty(py), self.__getstate__(py)?))
+    }
 
-        return PyBTreeMapKeys {
-            py_obj: owner.clone(),
-            // py_ref: slf.clone(),
-            iter: unsafe {
-                std::mem::transmute::<
-                    btree_map::Keys<'_, Elem, Elem>,
-                    btree_map::Keys<'static, Elem, Elem>,
-                >(iter)
-            },
-        };
+    /// Encodes the map in the binary format of the `binary` module.
+    pub fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let state = self.state.read(py)?;
+        let cursor = elem::allow_threads(py, || {
+            binary::write_map(IoCursor::new(Vec::new()), &state.map)
+        })?;
+        Ok(PyBytes::new(py, &cursor.into_inner()))
     }
 
-    pub fn values(slf: PyRef<'_, Self>) -> PyBTreeMapValues {
-        let slf = &slf;
-        let owner = slf.into_py(slf.py());
-        let iter = slf.btree_map.values();
+    #[classmethod]
+    #[pyo3(signature = (data, aggregate=None))]
+    pub fn from_bytes(
+        _cls: &Bound<'_, PyType>,
+        data: &[u8],
+        aggregate: Option<AggregateKind>,
+        py: Python,
+    ) -> PyResult<Self> {
+        let btree_map =
+            elem::catch_cmp(|| Ok(elem::allow_threads(py, || binary::read_map(data))?))?;
+        PyBTreeMap::with_aggregate(btree_map.into(), aggregate)
+    }
 
-        return PyBTreeMapValues {
-            owner: owner.clone(),
-            iter: unsafe {
-                std::mem::transmute::<
-                    btree_map::Values<'_, Elem, Elem>,
-                    btree_map::Values<'static, Elem, Elem>,
-                >(iter)
-            },
-        };
+    pub fn save(&self, py: Python, path: PathBuf) -> PyResult<()> {
+        let state = self.state.read(py)?;
+        elem::allow_threads(py, || {
+            binary::save(path, |writer| binary::write_map(writer, &state.map))
+        })?;
+        Ok(())
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (path, aggregate=None))]
+    pub fn load(
+        _cls: &Bound<'_, PyType>,
+        path: PathBuf,
+        aggregate: Option<AggregateKind>,
+        py: Python,
+    ) -> PyResult<Self> {
+        let btree_map = elem::catch_cmp(|| {
+            Ok(elem::allow_threads(py, || {
+                binary::read_map(binary::open(path)?)
+            })?)
+        })?;
+        PyBTreeMap::with_aggregate(btree_map.into(), aggregate)
     }
 
-    pub fn items(slf: PyRef<'_, Self>) -> PyBTreeMapIter {
-        let slf = &slf;
-        let owner = slf.into_py(slf.py());
-        let iter = slf.btree_map.iter();
+    /// Opens a map stored in the page file at `path`, creating it if missing.
+    /// At most `cache_size` nodes are held in memory.
+    #[classmethod]
+    #[pyo3(signature = (path, cache_size=1024))]
+    pub fn open(
+        _cls: &Bound<'_, PyType>,
+        path: PathBuf,
+        cache_size: usize,
+    ) -> PyResult<PyDiskBTreeMap> {
+        PyDiskBTreeMap::open(path, cache_size)
+    }
 
-        return PyBTreeMapIter {
-            owner: owner.clone(),
-            iter: unsafe {
-                std::mem::transmute::<
-                    btree_map::Iter<'_, Elem, Elem>,
-                    btree_map::Iter<'static, Elem, Elem>,
-                >(iter)
-            },
-        };
+    /// Rebuilds a map from the log at `log_path` and its snapshot, creating
+    /// them if missing, and logs every later update there.
+    #[classmethod]
+    #[pyo3(signature = (log_path, sync=SyncPolicy::Always, compact_every=None, aggregate=None))]
+    pub fn recover(
+        _cls: &Bound<'_, PyType>,
+        log_path: PathBuf,
+        sync: SyncPolicy,
+        compact_every: Option<usize>,
+        aggregate: Option<AggregateKind>,
+    ) -> PyResult<Self> {
+        let (log, btree_map) =
+            elem::catch_cmp(|| Ok(WriteAheadLog::recover(log_path, sync, compact_every)?))?;
+        let mut output = BTreeMapState::with_aggregate(btree_map.into(), aggregate)?;
+        output.log = Some(log);
+
+        Ok(output.into())
+    }
+
+    /// Saves the map as the log's snapshot and empties the log.
+    pub fn compact_log(&self, py: Python) -> PyResult<()> {
+        let mut state = self.state.write(py)?;
+        let state = &mut *state;
+        let log = state.log.as_mut().ok_or_else(missing_log)?;
+        log.compact(&state.map)?;
+        Ok(())
+    }
+
+    /// Fsyncs the log regardless of its sync policy.
+    pub fn sync_log(&self, py: Python) -> PyResult<()> {
+        self.state.write(py)?.log_mut()?.sync()?;
+        Ok(())
+    }
+
+    /// Syncs and detaches the log, later updates are no longer logged.
+    pub fn close_log(&self, py: Python) -> PyResult<()> {
+        let mut state = self.state.write(py)?;
+        state.log_mut()?.sync()?;
+        state.log = None;
+        Ok(())
+    }
+
+    /// Encodes the map as JSON in the format of the `json` module.
+    pub fn to_json(&self, py: Python) -> PyResult<String> {
+        let state = self.state.read(py)?;
+        let bytes = elem::allow_threads(py, || json::write_map(Vec::new(), &state.map))?;
+        Ok(String::from_utf8(bytes).unwrap())
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (data, aggregate=None))]
+    pub fn from_json(
+        _cls: &Bound<'_, PyType>,
+        data: &str,
+        aggregate: Option<AggregateKind>,
+        py: Python,
+    ) -> PyResult<Self> {
+        let btree_map =
+            elem::catch_cmp(|| Ok(elem::allow_threads(py, || json::read_map(data.as_bytes()))?))?;
+        PyBTreeMap::with_aggregate(btree_map.into(), aggregate)
+    }
+
+    pub fn save_json(&self, py: Python, path: PathBuf) -> PyResult<()> {
+        let state = self.state.read(py)?;
+        elem::allow_threads(py, || {
+            binary::save(path, |writer| json::write_map(writer, &state.map))
+        })?;
+        Ok(())
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (path, aggregate=None))]
+    pub fn load_json(
+        _cls: &Bound<'_, PyType>,
+        path: PathBuf,
+        aggregate: Option<AggregateKind>,
+        py: Python,
+    ) -> PyResult<Self> {
+        let btree_map = elem::catch_cmp(|| {
+            Ok(elem::allow_threads(py, || {
+                json::read_map(binary::open(path)?)
+            })?)
+        })?;
+        PyBTreeMap::with_aggregate(btree_map.into(), aggregate)
+    }
+
+    pub fn freeze(&self, py: Python) -> PyResult<PyFrozenBTreeMap> {
+        let btree_map = self
+            .state
+            .read(py)?
+            .map
+            .iter()
+            .map(|(key, value)| (key.clone_ref(py), value.clone_ref(py)))
+            .collect();
+
+        Ok(PyFrozenBTreeMap::from_map(btree_map))
+    }
+
+    /// Returns a read-only view of the current contents in O(1). The snapshot
+    /// shares the nodes of the map, and while it's alive an update copies the
+    /// O(log n) nodes on the path to the entry it changes.
+    pub fn snapshot(&self, py: Python) -> PyResult<PyBTreeMapSnapshot> {
+        let map = self.state.read(py)?.map.clone();
+        Ok(PyBTreeMapSnapshot { map })
+    }
+
+    /// Aggregate of the values whose keys are in `[start, stop)`, a missing
+    /// bound leaves that side of the range open.
+    #[pyo3(signature = (start=None, stop=None))]
+    pub fn range_aggregate(
+        &self,
+        py: Python,
+        start: Option<PyObject>,
+        stop: Option<PyObject>,
+    ) -> PyResult<PyObject> {
+        let start = start.map(|x| x.extract::<Elem>(py)).transpose()?;
+        let stop = stop.map(|x| x.extract::<Elem>(py)).transpose()?;
+        let state = self.state.read(py)?;
+        let kind = state.map.aggregate().ok_or_else(missing_aggregate)?;
+
+        let output = elem::catch_cmp(|| match (&start, &stop) {
+            (Some(start), Some(stop)) if start > stop => Ok(Fold::new(kind).finish()),
+            _ => state
+                .map
+                .range_aggregate(start.as_ref(), stop.as_ref())
+                .ok_or_else(missing_aggregate),
+        })?;
+
+        output.to_object(py)
+    }
+
+    /// Aggregate of the values whose keys are less than `key`.
+    pub fn prefix_aggregate(&self, py: Python, key: PyObject) -> PyResult<PyObject> {
+        let key = key.extract::<Elem>(py)?;
+        let state = self.state.read(py)?;
+        let output = elem::catch_cmp(|| Ok(state.map.range_aggregate(None, Some(&key))))?;
+
+        output.ok_or_else(missing_aggregate)?.to_object(py)
+    }
+
+    #[getter]
+    pub fn aggregate(&self, py: Python) -> PyResult<Option<&'static str>> {
+        Ok(self.state.read(py)?.map.aggregate().map(|x| x.name()))
+    }
+
+    pub fn keys_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.state.read(py)?.map.keys())
+    }
+
+    pub fn values_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.state.read(py)?.map.values())
+    }
+
+    pub fn items_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.state.read(py)?.map.iter())
+    }
+
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for (key, value) in self.state.read(py)?.map.iter() {
+            dict.set_item(key, value)?;
+        }
+        Ok(dict)
+    }
+
+    pub fn split_off(&self, py: Python, key: PyObject) -> PyResult<Self> {
+        let key = key.extract::<Elem>(py)?;
+        let mut state = self.state.write(py)?;
+        // looked up first so that a key that can't be ordered fails before
+        // the tree is taken apart
+        elem::catch_cmp(|| Ok(state.map.contains_key(&key)))?;
+        let map = state.map.split_off(&key);
+        state.compact_log()?;
+
+        Ok(BTreeMapState { map, log: None }.into())
+    }
+
+    pub fn append(&self, py: Python, other: &Bound<'_, Self>) -> PyResult<()> {
+        self.merge(py, other, OnConflict::Replace)
+    }
+
+    #[pyo3(signature = (other, on_conflict=OnConflict::Replace))]
+    pub fn merge(
+        &self,
+        py: Python,
+        other: &Bound<'_, Self>,
+        on_conflict: OnConflict,
+    ) -> PyResult<()> {
+        let (mut dst, mut src) = self.state.write_pair(&other.get().state, py)?;
+        dst.check_entries(src.map.iter())?;
+        let output = elem::catch_cmp(|| dst.merge_elems(py, &mut src.map, &on_conflict));
+
+        // the logs are compacted even on error since `other` may have been
+        // partially merged
+        dst.compact_log()?;
+        src.compact_log()?;
+        output
+    }
+
+    pub fn keys(slf: &Bound<'_, Self>) -> PyBTreeMapKeys {
+        let owner = slf.clone().into_any().unbind();
+        PyBTreeMapKeys {
+            cursor: Cursor::new(owner, fetch_keys),
+        }
+    }
+
+    pub fn values(slf: &Bound<'_, Self>) -> PyBTreeMapValues {
+        let owner = slf.clone().into_any().unbind();
+        PyBTreeMapValues {
+            cursor: Cursor::new(owner, fetch_items),
+        }
+    }
+
+    pub fn items(slf: &Bound<'_, Self>) -> PyBTreeMapIter {
+        let owner = slf.clone().into_any().unbind();
+        PyBTreeMapIter {
+            cursor: Cursor::new(owner, fetch_items),
+        }
+    }
+}
+
+impl PyBTreeMap {
+    pub fn with_aggregate(map: PersistentMap, aggregate: Option<AggregateKind>) -> PyResult<Self> {
+        Ok(BTreeMapState::with_aggregate(map, aggregate)?.into())
+    }
+}
+
+impl From<BTreeMapState> for PyBTreeMap {
+    fn from(state: BTreeMapState) -> Self {
+        PyBTreeMap {
+            state: TreeLock::new(state),
+        }
+    }
+}
+
+impl BTreeMapState {
+    pub fn with_aggregate(map: PersistentMap, aggregate: Option<AggregateKind>) -> PyResult<Self> {
+        Ok(BTreeMapState {
+            map: map.with_aggregate(aggregate)?,
+            log: None,
+        })
+    }
+
+    fn log_mut(&mut self) -> PyResult<&mut WriteAheadLog> {
+        self.log.as_mut().ok_or_else(missing_log)
+    }
+
+    /// Fails if any of `entries` can't be aggregated or logged, so batches are
+    /// rejected before any of their entries is applied.
+    fn check_entries<'a>(
+        &self,
+        entries: impl Iterator<Item = (&'a Elem, &'a Elem)>,
+    ) -> PyResult<()> {
+        let kind = self.map.aggregate();
+        if kind.is_none() && self.log.is_none() {
+            return Ok(());
+        }
+        for (key, value) in entries {
+            if let Some(kind) = kind {
+                kind.check(value)?;
+            }
+            if self.log.is_some() {
+                write_ahead_log::check_serializable(key)?;
+                write_ahead_log::check_serializable(value)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_elem(&mut self, key: Elem, value: Elem) -> PyResult<Option<Elem>> {
+        if let Some(kind) = self.map.aggregate() {
+            kind.check(&value)?;
+        }
+        // looked up first so that a key that can't be ordered fails before
+        // it's logged
+        self.map.contains_key(&key);
+        self.append_log(Record::Insert(&key, &value))?;
+
+        Ok(self.map.insert(key, value))
+    }
+
+    fn remove_elem(&mut self, key: &Elem) -> PyResult<Option<Elem>> {
+        if !self.map.contains_key(key) {
+            return Ok(None);
+        }
+        self.append_log(Record::Remove(key))?;
+
+        Ok(self.map.remove(key))
+    }
+
+    /// Appends `record` to the log, compacting it first when it's due so a
+    /// failed compaction leaves the update unapplied.
+    fn append_log(&mut self, record: Record) -> PyResult<()> {
+        if let Some(log) = &mut self.log {
+            if log.needs_compaction() {
+                log.compact(&self.map)?;
+            }
+            log.append(record)?;
+        }
+        Ok(())
+    }
+
+    /// Brings the log up to date after a bulk update of `map`.
+    fn compact_log(&mut self) -> PyResult<()> {
+        if let Some(log) = &mut self.log {
+            log.compact(&self.map)?;
+        }
+
+        Ok(())
+    }
+
+    fn merge_elems(
+        &mut self,
+        py: Python,
+        src: &mut PersistentMap,
+        on_conflict: &OnConflict,
+    ) -> PyResult<()> {
+        let kind = self.map.aggregate();
+        let logged = self.log.is_some();
+        let dst = &mut self.map;
+
+        if merge::is_disjoint(key_range(dst), key_range(src)) {
+            // `append` needs the keys of the appended map to come after the others
+            let before = match (key_range(dst), key_range(src)) {
+                (Some((dst_first, _)), Some((_, src_last))) => src_last < dst_first,
+                _ => false,
+            };
+            // the values were checked against the aggregate of `dst` already
+            let mut moved = src.clone().with_aggregate(kind)?;
+            src.clear();
+            if before {
+                moved.append(dst);
+                *dst = moved;
+            } else {
+                dst.append(&mut moved);
+            }
+            return Ok(());
+        }
+
+        let entries = src.take();
+        for (key, value) in entries.iter() {
+            let current = elem::catch_cmp(|| Ok(dst.get(key)));
+            let output = current.and_then(|current| match (current, on_conflict) {
+                (None, _) | (Some(_), OnConflict::Replace) => Ok(Some(value.clone_ref(py))),
+                (Some(_), OnConflict::Keep) => Ok(None),
+                (Some(current), OnConflict::Call(f)) => {
+                    let args = (key, current, value);
+                    let output = f.call1(py, args)?.extract::<Elem>(py)?;
+                    if let Some(kind) = kind {
+                        kind.check(&output)?;
+                    }
+                    if logged {
+                        write_ahead_log::check_serializable(&output)?;
+                    }
+                    Ok(Some(output))
+                }
+            });
+            match output {
+                Ok(Some(value)) => {
+                    dst.insert(key.clone_ref(py), value);
+                }
+                Ok(None) => (),
+                Err(err) => {
+                    // leave the unmerged entries in `other`
+                    *src = entries.clone().split_off(key);
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn fetch_keys(py: Python, owner: &PyObject, range: KeyRange) -> PyResult<Vec<(Elem, ())>> {
+    let state = owner
+        .downcast_bound::<PyBTreeMap>(py)?
+        .get()
+        .state
+        .read(py)?;
+    Ok(iterators::persistent_batch(py, &state.map, range, |_| ()))
+}
+
+fn fetch_items(py: Python, owner: &PyObject, range: KeyRange) -> PyResult<Vec<(Elem, Elem)>> {
+    let state = owner
+        .downcast_bound::<PyBTreeMap>(py)?
+        .get()
+        .state
+        .read(py)?;
+    Ok(iterators::persistent_batch(
+        py,
+        &state.map,
+        range,
+        |value| value.clone_ref(py),
+    ))
+}
+
+fn missing_aggregate() -> PyErr {
+    PyErr::new::<exceptions::PyValueError, _>("map was created without an aggregate")
+}
+
+fn missing_log() -> PyErr {
+    PyErr::new::<exceptions::PyValueError, _>("map was created without a log")
+}
+
+fn key_range(map: &PersistentMap) -> Option<(&Elem, &Elem)> {
+    let (first, _) = map.first_key_value()?;
+    let (last, _) = map.last_key_value()?;
+    Some((first, last))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_isolation() {
+        Python::with_gil(|py| {
+            let tree = PyBTreeMap::new(None, None, py).unwrap();
+            let key = |key: i64| key.into_pyobject(py).unwrap().into_any().unbind();
+            let insert = |x: i64| tree.insert(py, key(x), key(-x));
+
+            insert(1).unwrap();
+            insert(2).unwrap();
+            let first = tree.snapshot(py).unwrap();
+            insert(3).unwrap();
+            tree.remove(py, key(1)).unwrap();
+            let second = tree.snapshot(py).unwrap();
+            insert(2).unwrap();
+
+            let keys = |map: &PersistentMap| map.keys().cloned().collect::<Vec<_>>();
+            assert_eq!(keys(&first.map), [1, 2].map(Elem::Int));
+            assert_eq!(keys(&second.map), [2, 3].map(Elem::Int));
+            assert_eq!(first.map.get(&Elem::Int(2)), Some(&Elem::Int(-2)));
+            assert_eq!(tree.len(py).unwrap(), 2);
+        });
     }
 }
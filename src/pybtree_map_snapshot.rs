@@ -0,0 +1,126 @@
+use crate::elem::{self, Elem};
+use crate::iterators::{PyBTreeMapSnapshotIter, SnapshotIterKind};
+use crate::persistent_map::PersistentMap;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use std::collections::BTreeMap;
+
+/// Read-only view of a `PyBTreeMap` at the time `snapshot()` was called.
+#[pyclass(module = "tree_collections.tree_collections", frozen)]
+pub struct PyBTreeMapSnapshot {
+    pub map: PersistentMap,
+}
+
+#[pymethods]
+impl PyBTreeMapSnapshot {
+    pub fn get(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<Option<PyObject>> {
+        let py = slf.py();
+        let key = key.extract::<Elem>(py)?;
+        let output = elem::catch_cmp(|| Ok(slf.map.get(&key)))?;
+
+        Ok(output.map(|x| x.to_pyobject(py)))
+    }
+
+    pub fn __getitem__(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<PyObject> {
+        let py = slf.py();
+        let elem = key.extract::<Elem>(py)?;
+
+        match elem::catch_cmp(|| Ok(slf.map.get(&elem)))? {
+            Some(value) => Ok(value.to_pyobject(py)),
+            None => Err(PyErr::new::<exceptions::PyKeyError, _>(key)),
+        }
+    }
+
+    pub fn contains_key(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<bool> {
+        let py = slf.py();
+        let elem_key = key.extract::<Elem>(py)?;
+        elem::catch_cmp(|| Ok(slf.map.contains_key(&elem_key)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn keys_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let keys = self
+            .map
+            .iter()
+            .map(|(key, _)| key.to_pyobject(py))
+            .collect::<Vec<_>>();
+        PyList::new(py, keys)
+    }
+
+    pub fn values_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let values = self
+            .map
+            .iter()
+            .map(|(_, value)| value.to_pyobject(py))
+            .collect::<Vec<_>>();
+        PyList::new(py, values)
+    }
+
+    pub fn items_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let items = self
+            .map
+            .iter()
+            .map(|(key, value)| (key.to_pyobject(py), value.to_pyobject(py)))
+            .collect::<Vec<_>>();
+        PyList::new(py, items)
+    }
+
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for (key, value) in self.map.iter() {
+            dict.set_item(key, value)?;
+        }
+        Ok(dict)
+    }
+
+    /// Snapshots are immutable, so copies share the same object.
+    pub fn copy(slf: PyRef<'_, Self>) -> Py<Self> {
+        slf.into()
+    }
+
+    pub fn __copy__(slf: PyRef<'_, Self>) -> Py<Self> {
+        slf.into()
+    }
+
+    pub fn __deepcopy__(&self, py: Python, memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let btree_map = elem::catch_cmp(|| {
+            self.map
+                .iter()
+                .map(|(key, value)| Ok((key.deepcopy(py, memo)?, value.deepcopy(py, memo)?)))
+                .collect::<PyResult<BTreeMap<_, _>>>()
+        })?;
+
+        Ok(PyBTreeMapSnapshot {
+            map: btree_map.into(),
+        })
+    }
+
+    pub fn keys(&self) -> PyBTreeMapSnapshotIter {
+        self.iter(SnapshotIterKind::Keys)
+    }
+
+    pub fn values(&self) -> PyBTreeMapSnapshotIter {
+        self.iter(SnapshotIterKind::Values)
+    }
+
+    pub fn items(&self) -> PyBTreeMapSnapshotIter {
+        self.iter(SnapshotIterKind::Items)
+    }
+}
+
+impl PyBTreeMapSnapshot {
+    fn iter(&self, kind: SnapshotIterKind) -> PyBTreeMapSnapshotIter {
+        PyBTreeMapSnapshotIter {
+            iter: self.map.clone().into_iter(),
+            kind,
+        }
+    }
+}
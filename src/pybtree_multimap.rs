@@ -0,0 +1,232 @@
+use crate::bulk;
+use crate::elem::{self, Elem};
+use crate::iterators::{
+    self, Cursor, InternalPyBTreeMultiMapIter, KeyRange, PyBTreeMultiMapIter, PyBTreeMultiMapKeys,
+};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyTuple, PyType};
+use std::collections::{btree_map, BTreeMap};
+
+#[pyclass(module = "tree_collections.tree_collections")]
+pub struct PyBTreeMultiMap {
+    pub btree_map: BTreeMap<Elem, Vec<Elem>>,
+    pub length: usize,
+}
+
+#[pymethods]
+impl PyBTreeMultiMap {
+    #[new]
+    #[pyo3(signature = (input=None))]
+    pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self> {
+        let items = match input {
+            Some(input) => bulk::extract_pairs(input, py)?,
+            None => Vec::new(),
+        };
+        let (btree_map, length) = elem::catch_cmp(|| {
+            Ok(bulk::allow_threads_if_native(
+                py,
+                items,
+                |(key, _)| key,
+                bulk::build_multimap,
+            ))
+        })?;
+
+        Ok(PyBTreeMultiMap { btree_map, length })
+    }
+
+    pub fn add(mut slf: PyRefMut<'_, Self>, key: PyObject, value: PyObject) -> PyResult<()> {
+        // cast to orderable type
+        let py = slf.py();
+        let elem_key = key.extract::<Elem>(py)?;
+        let elem_value = value.extract::<Elem>(py)?;
+        let entry = elem::catch_cmp(|| Ok(slf.btree_map.entry(elem_key)))?;
+        entry.or_default().push(elem_value);
+        slf.length += 1;
+
+        Ok(())
+    }
+
+    pub fn get_all<'py>(slf: PyRef<'py, Self>, key: PyObject) -> PyResult<Bound<'py, PyList>> {
+        let py = slf.py();
+        let key = key.extract::<Elem>(py)?;
+        let values = elem::catch_cmp(|| Ok(slf.btree_map.get(&key)))?;
+        let values = values.map(|x| x.as_slice()).unwrap_or(&[]);
+
+        PyList::new(py, values)
+    }
+
+    pub fn count(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<usize> {
+        let py = slf.py();
+        let key = key.extract::<Elem>(py)?;
+
+        let values = elem::catch_cmp(|| Ok(slf.btree_map.get(&key)))?;
+
+        Ok(values.map_or(0, |x| x.len()))
+    }
+
+    pub fn remove_one(
+        mut slf: PyRefMut<'_, Self>,
+        key: PyObject,
+        value: PyObject,
+    ) -> PyResult<bool> {
+        let py = slf.py();
+        let key = key.extract::<Elem>(py)?;
+        let value = value.extract::<Elem>(py)?;
+
+        let mut entry = match elem::catch_cmp(|| Ok(slf.btree_map.entry(key)))? {
+            btree_map::Entry::Vacant(_) => return Ok(false),
+            btree_map::Entry::Occupied(entry) => entry,
+        };
+        let values = entry.get_mut();
+        let index = elem::catch_cmp(|| Ok(values.iter().position(|x| x == &value)))?;
+        let index = match index {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+        values.remove(index);
+        if values.is_empty() {
+            entry.remove();
+        }
+        slf.length -= 1;
+
+        Ok(true)
+    }
+
+    pub fn remove<'py>(
+        mut slf: PyRefMut<'py, Self>,
+        key: PyObject,
+    ) -> PyResult<Bound<'py, PyList>> {
+        let py = slf.py();
+        let key = key.extract::<Elem>(py)?;
+        let values = elem::catch_cmp(|| Ok(slf.btree_map.remove(&key)))?;
+        let values = values.unwrap_or_default();
+        slf.length -= values.len();
+
+        PyList::new(py, values)
+    }
+
+    pub fn contains_key(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<bool> {
+        let py = slf.py();
+        let elem_key = key.extract::<Elem>(py)?;
+        elem::catch_cmp(|| Ok(slf.btree_map.contains_key(&elem_key)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.btree_map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.btree_map.clear();
+        self.length = 0;
+    }
+
+    pub fn copy(&self) -> Self {
+        PyBTreeMultiMap {
+            btree_map: self.btree_map.clone(),
+            length: self.length,
+        }
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.copy()
+    }
+
+    pub fn __deepcopy__(&self, py: Python, memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let btree_map = elem::catch_cmp(|| {
+            self.btree_map
+                .iter()
+                .map(|(key, values)| {
+                    let values = values
+                        .iter()
+                        .map(|x| x.deepcopy(py, memo))
+                        .collect::<PyResult<_>>()?;
+                    Ok((key.deepcopy(py, memo)?, values))
+                })
+                .collect::<PyResult<_>>()
+        })?;
+
+        Ok(PyBTreeMultiMap {
+            btree_map,
+            length: self.length,
+        })
+    }
+
+    /// Pickles as `(keys, values)` where `values` holds one list per key.
+    pub fn __getstate__(&self, py: Python) -> PyResult<PyObject> {
+        let keys = PyList::new(py, self.btree_map.keys())?;
+        let values = PyList::new(py, self.btree_map.values())?;
+
+        Ok((keys, values).into_pyobject(py)?.into_any().unbind())
+    }
+
+    pub fn __setstate__(&mut self, state: &Bound<'_, PyAny>) -> PyResult<()> {
+        let (keys, values) = state.extract::<(Vec<Elem>, Vec<Vec<Elem>>)>()?;
+        if keys.len() != values.len() {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(
+                "invalid state: keys and values differ in length",
+            ));
+        }
+        let length = values.iter().map(|x| x.len()).sum();
+        self.btree_map = elem::catch_cmp(|| Ok(keys.into_iter().zip(values).collect()))?;
+        self.length = length;
+
+        Ok(())
+    }
+
+    pub fn __reduce__<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<(Bound<'py, PyType>, Bound<'py, PyTuple>, PyObject)> {
+        let cls = py.get_type::<Self>();
+        Ok((cls, PyTuple::empty(py), self.__getstate__(py)?))
+    }
+
+    pub fn keys(slf: &Bound<'_, Self>) -> PyBTreeMultiMapKeys {
+        let owner = slf.clone().into_any().unbind();
+        PyBTreeMultiMapKeys {
+            cursor: Cursor::new(owner, fetch_keys),
+        }
+    }
+
+    pub fn items(slf: &Bound<'_, Self>) -> PyBTreeMultiMapIter {
+        let owner = slf.clone().into_any().unbind();
+        PyBTreeMultiMapIter {
+            iter: InternalPyBTreeMultiMapIter::new(Cursor::new(owner, fetch_items)),
+        }
+    }
+
+    /// Iterates over the `(key, value)` pairs with `start <= key < stop`, a
+    /// missing bound leaves that side of the range open.
+    #[pyo3(signature = (start=None, stop=None))]
+    pub fn range(
+        slf: &Bound<'_, Self>,
+        start: Option<PyObject>,
+        stop: Option<PyObject>,
+    ) -> PyResult<PyBTreeMultiMapIter> {
+        let py = slf.py();
+        let start = start.map(|x| x.extract::<Elem>(py)).transpose()?;
+        let stop = stop.map(|x| x.extract::<Elem>(py)).transpose()?;
+        let owner = slf.clone().into_any().unbind();
+
+        Ok(PyBTreeMultiMapIter {
+            iter: InternalPyBTreeMultiMapIter::new(Cursor::range(owner, start, stop, fetch_items)),
+        })
+    }
+}
+
+fn fetch_keys(py: Python, owner: &PyObject, range: KeyRange) -> PyResult<Vec<(Elem, ())>> {
+    let slf = owner.downcast_bound::<PyBTreeMultiMap>(py)?.try_borrow()?;
+    Ok(iterators::map_batch(py, &slf.btree_map, range, |_| ()))
+}
+
+fn fetch_items(py: Python, owner: &PyObject, range: KeyRange) -> PyResult<Vec<(Elem, Vec<Elem>)>> {
+    let slf = owner.downcast_bound::<PyBTreeMultiMap>(py)?.try_borrow()?;
+    Ok(iterators::map_batch(py, &slf.btree_map, range, |values| {
+        values.iter().map(|x| x.clone_ref(py)).collect()
+    }))
+}
@@ -1,104 +1,183 @@
-use crate::elem::Elem;
-use crate::iterators::{InternalPyBTreeSeqIter, PyBTreeSeqIter};
+use crate::binary;
+use crate::bulk;
+use crate::elem::{self, Elem};
+use crate::iterators::{self, Cursor, InternalPyBTreeSeqIter, KeyRange, PyBTreeSeqIter};
+use crate::json;
+use crate::merge::{self, OnConflict};
+use crate::tree_lock::TreeLock;
 use pyo3::exceptions;
 use pyo3::prelude::*;
-use pyo3::types::{PyIterator, PySequence};
+use pyo3::types::{PyBytes, PyList, PyTuple, PyType};
 use std::collections::{btree_map, BTreeMap};
+use std::io::Cursor as IoCursor;
+use std::path::PathBuf;
 
-#[pyclass]
+#[pyclass(module = "tree_collections.tree_collections", frozen)]
 pub struct PyBTreeSeq {
+    pub state: TreeLock<BTreeSeqState>,
+}
+
+pub struct BTreeSeqState {
     pub btree_map: BTreeMap<Elem, usize>,
     pub length: usize,
 }
 
-unsafe impl Send for PyBTreeSeq {}
-
 #[pymethods]
 impl PyBTreeSeq {
     #[new]
     #[pyo3(signature = (input=None))]
     pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self> {
-        let mut btree_map = BTreeMap::<Elem, usize>::new();
-        let mut length: usize = 0;
-
-        if let Some(input) = input {
-            let iter: &PyIterator = if let Ok(input) = input.downcast::<PySequence>(py) {
-                input.iter()?
-            } else if let Ok(input) = input.downcast::<PyIterator>(py) {
-                input
-            } else {
-                return Err(PyErr::new::<exceptions::PyTypeError, _>(
-                    "Expected a sequence or iterable",
-                ));
-            };
+        let items = match input {
+            Some(input) => bulk::extract_elems(input, py)?,
+            None => Vec::new(),
+        };
+        let (btree_map, length) = elem::catch_cmp(|| {
+            Ok(bulk::allow_threads_if_native(
+                py,
+                items,
+                |elem| elem,
+                bulk::build_seq,
+            ))
+        })?;
 
-            for x in iter {
-                let elem = x?.downcast::<PyAny>()?;
-                let elem = elem.extract::<Elem>()?;
-                btree_map.entry(elem).and_modify(|x| *x += 1).or_insert(1);
-                length += 1;
-            }
-        }
+        Ok(BTreeSeqState { btree_map, length }.into())
+    }
 
-        Ok(PyBTreeSeq {
-            btree_map: btree_map,
-            length: length,
-        })
+    #[classmethod]
+    #[pyo3(signature = (input, validate=true))]
+    pub fn from_sorted(
+        _cls: &Bound<'_, PyType>,
+        input: PyObject,
+        validate: bool,
+        py: Python,
+    ) -> PyResult<Self> {
+        let items = bulk::extract_elems(input, py)?;
+        let (btree_map, length) = elem::catch_cmp(|| {
+            bulk::allow_threads_if_native(
+                py,
+                items,
+                |elem| elem,
+                |items| {
+                    if validate {
+                        bulk::check_sorted(&items, |elem| elem)?;
+                    }
+                    PyResult::Ok(bulk::build_seq(items))
+                },
+            )
+        })?;
+
+        Ok(BTreeSeqState { btree_map, length }.into())
     }
 
-    pub fn insert(mut slf: PyRefMut<'_, Self>, key: PyObject) -> PyResult<bool> {
+    pub fn insert(&self, py: Python, key: PyObject) -> PyResult<bool> {
         // cast to orderable type
-        let py = slf.py();
         let elem = key.extract::<Elem>(py)?;
-        let output = slf
-            .btree_map
-            .entry(elem)
-            .and_modify(|x| *x += 1)
-            .or_insert(1);
-
-        Ok(output == &1)
+        let mut state = self.state.write(py)?;
+        elem::catch_cmp(|| Ok(state.insert_elem(elem)))
     }
 
-    pub fn get(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<Option<PyObject>> {
-        let py = slf.py();
+    pub fn get(&self, py: Python, key: PyObject) -> PyResult<Option<PyObject>> {
         let key = key.extract::<Elem>(py)?;
-        let output = slf.btree_map.get_key_value(&key);
+        let state = self.state.read(py)?;
+        let output = elem::catch_cmp(|| Ok(state.btree_map.get_key_value(&key)))?;
 
         Ok(output.map(|(x, _)| x.to_pyobject(py)))
     }
 
-    pub fn remove(mut slf: PyRefMut<'_, Self>, key: PyObject) -> PyResult<bool> {
-        let py = slf.py();
+    pub fn remove(&self, py: Python, key: PyObject) -> PyResult<bool> {
         let key = key.extract::<Elem>(py)?;
-        let entry = slf.btree_map.entry(key).and_modify(|x| *x -= 1);
 
-        match entry {
-            btree_map::Entry::Vacant(_) => Ok(false),
-            btree_map::Entry::Occupied(entry) => {
-                if *entry.get() == 0 {
-                    entry.remove();
-                    Ok(false)
-                } else {
-                    Ok(true)
+        let mut state = self.state.write(py)?;
+        elem::catch_cmp(|| Ok(state.remove_elem(key)))
+    }
+
+    pub fn contains(&self, py: Python, key: PyObject) -> PyResult<bool> {
+        let elem_key = key.extract::<Elem>(py)?;
+        let state = self.state.read(py)?;
+        elem::catch_cmp(|| Ok(state.btree_map.contains_key(&elem_key)))
+    }
+
+    pub fn insert_many(&self, py: Python, input: PyObject) -> PyResult<Vec<bool>> {
+        let elems = bulk::extract_elems(input, py)?;
+        let mut output = vec![false; elems.len()];
+        let mut state = self.state.write(py)?;
+
+        elem::catch_cmp(|| {
+            for (i, elem) in bulk::sort_batch(elems, |elem| elem) {
+                output[i] = state.insert_elem(elem);
+            }
+            Ok(output)
+        })
+    }
+
+    pub fn update_many(&self, py: Python, input: PyObject) -> PyResult<()> {
+        let elems = bulk::extract_elems(input, py)?;
+        let mut state = self.state.write(py)?;
+
+        elem::catch_cmp(|| {
+            for (_, elem) in bulk::sort_batch(elems, |elem| elem) {
+                state.insert_elem(elem);
+            }
+            Ok(())
+        })
+    }
+
+    #[pyo3(signature = (keys, default=None))]
+    pub fn get_many(
+        &self,
+        py: Python,
+        keys: PyObject,
+        default: Option<PyObject>,
+    ) -> PyResult<Vec<Option<PyObject>>> {
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = Vec::with_capacity(keys.len());
+        output.resize_with(keys.len(), || default.as_ref().map(|x| x.clone_ref(py)));
+        let state = self.state.read(py)?;
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                if let Some((elem, _)) = state.btree_map.get_key_value(&key) {
+                    output[i] = Some(elem.to_pyobject(py));
                 }
             }
-        }
+            Ok(output)
+        })
     }
 
-    pub fn contains(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<bool> {
-        let py = slf.py();
-        let elem_key = key.extract::<Elem>(py)?;
-        Ok(slf.btree_map.contains_key(&elem_key))
+    pub fn contains_many(&self, py: Python, keys: PyObject) -> PyResult<Vec<bool>> {
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = vec![false; keys.len()];
+        let state = self.state.read(py)?;
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                output[i] = state.btree_map.contains_key(&key);
+            }
+            Ok(output)
+        })
+    }
+
+    pub fn remove_many(&self, py: Python, keys: PyObject) -> PyResult<Vec<bool>> {
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = vec![false; keys.len()];
+        let mut state = self.state.write(py)?;
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                output[i] = state.remove_elem(key);
+            }
+            Ok(output)
+        })
     }
 
-    pub fn nth(mut slf: PyRefMut<'_, Self>, mut n: i64) -> PyResult<Option<PyObject>> {
-        let py = slf.py();
+    pub fn nth(&self, py: Python, mut n: i64) -> PyResult<Option<PyObject>> {
+        let state = self.state.read(py)?;
 
-        if n >= slf.length as i64 {
+        if n >= state.length as i64 {
             return Ok(None);
         }
         if n < 0 {
-            n = slf.length as i64 + n;
+            n += state.length as i64;
         }
         if n < 0 {
             return Ok(None);
@@ -106,56 +185,319 @@ impl PyBTreeSeq {
         let n = n as usize;
 
         let output = if n == 0 {
-            let entry = slf.btree_map.first_entry().unwrap();
-            entry.key().to_pyobject(py)
-        } else if n == slf.length - 1 {
-            let entry = slf.btree_map.last_entry().unwrap();
-            entry.key().to_pyobject(py)
+            state.btree_map.first_key_value().unwrap().0
+        } else if n == state.length - 1 {
+            state.btree_map.last_key_value().unwrap().0
         } else {
-            let key = PyBTreeSeq::interal_iter(&slf).nth(n).unwrap();
-            key.to_pyobject(py)
+            state
+                .btree_map
+                .iter()
+                .flat_map(|(elem, count)| std::iter::repeat_n(elem, *count))
+                .nth(n)
+                .unwrap()
         };
 
-        Ok(Some(output))
+        Ok(Some(output.to_pyobject(py)))
     }
 
-    pub fn len(&self) -> usize {
-        return self.btree_map.len();
+    pub fn len(&self, py: Python) -> PyResult<usize> {
+        Ok(self.state.read(py)?.btree_map.len())
     }
 
-    pub fn is_empty(&self) -> bool {
-        return self.btree_map.is_empty();
+    pub fn is_empty(&self, py: Python) -> PyResult<bool> {
+        Ok(self.state.read(py)?.btree_map.is_empty())
     }
 
-    pub fn clear(&mut self) {
-        self.btree_map.clear();
+    pub fn clear(&self, py: Python) -> PyResult<()> {
+        let mut state = self.state.write(py)?;
+        state.btree_map.clear();
+        state.length = 0;
+        Ok(())
     }
 
-    pub fn iter(slf: PyRefMut<'_, Self>) -> PyBTreeSeqIter {
-        let slf = &slf;
-        let owner = slf.into_py(slf.py());
-        let iter = PyBTreeSeq::interal_iter(slf);
+    pub fn to_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        let state = self.state.read(py)?;
+        let elems = state
+            .btree_map
+            .iter()
+            .flat_map(|(elem, count)| std::iter::repeat_n(elem, *count))
+            .collect::<Vec<_>>();
+        PyList::new(py, elems)
+    }
 
-        return PyBTreeSeqIter {
-            py_obj: owner.clone(),
-            iter: unsafe {
-                std::mem::transmute::<InternalPyBTreeSeqIter<'_>, InternalPyBTreeSeqIter<'static>>(
-                    iter,
-                )
-            },
+    pub fn copy(&self, py: Python) -> PyResult<Self> {
+        let state = self.state.read(py)?;
+        let output = BTreeSeqState {
+            btree_map: state.btree_map.clone(),
+            length: state.length,
         };
+
+        Ok(output.into())
+    }
+
+    pub fn __copy__(&self, py: Python) -> PyResult<Self> {
+        self.copy(py)
+    }
+
+    pub fn __deepcopy__(&self, py: Python, memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let state = self.state.read(py)?;
+        let btree_map = elem::catch_cmp(|| {
+            state
+                .btree_map
+                .iter()
+                .map(|(key, count)| Ok((key.deepcopy(py, memo)?, *count)))
+                .collect::<PyResult<_>>()
+        })?;
+        let output = BTreeSeqState {
+            btree_map,
+            length: state.length,
+        };
+
+        Ok(output.into())
+    }
+
+    /// Pickles as `(elements, counts)` with each distinct element once.
+    pub fn __getstate__(&self, py: Python) -> PyResult<PyObject> {
+        let state = self.state.read(py)?;
+        let keys = PyList::new(py, state.btree_map.keys())?;
+        let counts = state.btree_map.values().copied().collect::<Vec<_>>();
+
+        Ok((keys, counts).into_pyobject(py)?.into_any().unbind())
+    }
+
+    pub fn __setstate__(&self, py: Python, state: &Bound<'_, PyAny>) -> PyResult<()> {
+        let (keys, counts) = state.extract::<(Vec<Elem>, Vec<usize>)>()?;
+        if keys.len() != counts.len() {
+            return Err(PyErr::new::<exceptions::PyValueError, _>(
+                "invalid state: elements and counts differ in length",
+            ));
+        }
+        let items = keys.into_iter().zip(counts).collect();
+        let (btree_map, length) = elem::catch_cmp(|| {
+            bulk::allow_threads_if_native(py, items, |(elem, _)| elem, bulk::build_counts)
+        })?;
+        *self.state.write(py)? = BTreeSeqState { btree_map, length };
+
+        Ok(())
+    }
+
+    pub fn __reduce__<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<(Bound<'py, PyType>, Bound<'py, PyTuple>, PyObject)> {
+        let cls = py.get_type::<Self>();
+        Ok((cls, PyTuple::empty(py), self.__getstate__(py)?))
+    }
+
+    /// Encodes the seq in the binary format of the `binary` module.
+    pub fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let state = self.state.read(py)?;
+        let cursor = elem::allow_threads(py, || {
+            binary::write_seq(IoCursor::new(Vec::new()), &state.btree_map)
+        })?;
+        Ok(PyBytes::new(py, &cursor.into_inner()))
+    }
+
+    #[classmethod]
+    pub fn from_bytes(_cls: &Bound<'_, PyType>, data: &[u8], py: Python) -> PyResult<Self> {
+        let (btree_map, length) =
+            elem::catch_cmp(|| Ok(elem::allow_threads(py, || binary::read_seq(data))?))?;
+        Ok(BTreeSeqState { btree_map, length }.into())
+    }
+
+    pub fn save(&self, py: Python, path: PathBuf) -> PyResult<()> {
+        let state = self.state.read(py)?;
+        elem::allow_threads(py, || {
+            binary::save(path, |writer| binary::write_seq(writer, &state.btree_map))
+        })?;
+        Ok(())
+    }
+
+    #[classmethod]
+    pub fn load(_cls: &Bound<'_, PyType>, path: PathBuf, py: Python) -> PyResult<Self> {
+        let (btree_map, length) = elem::catch_cmp(|| {
+            Ok(elem::allow_threads(py, || {
+                binary::read_seq(binary::open(path)?)
+            })?)
+        })?;
+        Ok(BTreeSeqState { btree_map, length }.into())
+    }
+
+    /// Encodes the seq as JSON in the format of the `json` module.
+    pub fn to_json(&self, py: Python) -> PyResult<String> {
+        let state = self.state.read(py)?;
+        let bytes = elem::allow_threads(py, || json::write_seq(Vec::new(), &state.btree_map))?;
+        Ok(String::from_utf8(bytes).unwrap())
+    }
+
+    #[classmethod]
+    pub fn from_json(_cls: &Bound<'_, PyType>, data: &str, py: Python) -> PyResult<Self> {
+        let (btree_map, length) =
+            elem::catch_cmp(|| Ok(elem::allow_threads(py, || json::read_seq(data.as_bytes()))?))?;
+        Ok(BTreeSeqState { btree_map, length }.into())
+    }
+
+    pub fn save_json(&self, py: Python, path: PathBuf) -> PyResult<()> {
+        let state = self.state.read(py)?;
+        elem::allow_threads(py, || {
+            binary::save(path, |writer| json::write_seq(writer, &state.btree_map))
+        })?;
+        Ok(())
+    }
+
+    #[classmethod]
+    pub fn load_json(_cls: &Bound<'_, PyType>, path: PathBuf, py: Python) -> PyResult<Self> {
+        let (btree_map, length) = elem::catch_cmp(|| {
+            Ok(elem::allow_threads(py, || {
+                json::read_seq(binary::open(path)?)
+            })?)
+        })?;
+        Ok(BTreeSeqState { btree_map, length }.into())
+    }
+
+    pub fn split_off(&self, py: Python, key: PyObject) -> PyResult<Self> {
+        let key = key.extract::<Elem>(py)?;
+        let mut state = self.state.write(py)?;
+        // looked up first so that a key that can't be ordered fails before
+        // the tree is taken apart
+        elem::catch_cmp(|| Ok(state.btree_map.contains_key(&key)))?;
+        let btree_map = state.btree_map.split_off(&key);
+        let length = btree_map.values().sum();
+        state.length -= length;
+
+        Ok(BTreeSeqState { btree_map, length }.into())
+    }
+
+    pub fn append(&self, py: Python, other: &Bound<'_, Self>) -> PyResult<()> {
+        self.merge(py, other, OnConflict::Replace)
+    }
+
+    #[pyo3(signature = (other, on_conflict=OnConflict::Replace))]
+    pub fn merge(
+        &self,
+        py: Python,
+        other: &Bound<'_, Self>,
+        on_conflict: OnConflict,
+    ) -> PyResult<()> {
+        let (mut slf, mut other) = self.state.write_pair(&other.get().state, py)?;
+        let slf = &mut *slf;
+        let other = &mut *other;
+        let dst = &mut slf.btree_map;
+        let src = &mut other.btree_map;
+
+        if elem::catch_cmp(|| Ok(merge::is_disjoint(key_range(dst), key_range(src))))? {
+            elem::catch_cmp(|| {
+                dst.append(src);
+                Ok(())
+            })?;
+            slf.length += other.length;
+            other.length = 0;
+            return Ok(());
+        }
+
+        // counts of equal elements are always added up, `on_conflict` only
+        // decides which of the two elements is kept in the tree
+        while let Some((elem, count)) = src.pop_first() {
+            let current = match elem::catch_cmp(|| Ok(dst.remove_entry(&elem))) {
+                Ok(current) => current,
+                Err(err) => {
+                    // leave the unmerged elements in `other`
+                    src.insert(elem, count);
+                    return Err(err);
+                }
+            };
+            match current {
+                None => {
+                    dst.insert(elem, count);
+                }
+                Some((current, current_count)) => {
+                    let output = match &on_conflict {
+                        OnConflict::Replace => elem,
+                        OnConflict::Keep => current,
+                        OnConflict::Call(f) => {
+                            match merge::call_resolve_elem(py, f, &current, &elem) {
+                                Ok(output) => output,
+                                Err(err) => {
+                                    // leave the unmerged elements in `other`
+                                    dst.insert(current, current_count);
+                                    src.insert(elem, count);
+                                    return Err(err);
+                                }
+                            }
+                        }
+                    };
+                    dst.insert(output, current_count + count);
+                }
+            }
+            slf.length += count;
+            other.length -= count;
+        }
+
+        Ok(())
+    }
+
+    pub fn iter(slf: &Bound<'_, Self>) -> PyBTreeSeqIter {
+        let owner = slf.clone().into_any().unbind();
+        PyBTreeSeqIter {
+            iter: InternalPyBTreeSeqIter::new(Cursor::new(owner, fetch)),
+        }
     }
 }
 
-impl PyBTreeSeq {
-    fn interal_iter<'a>(slf: &'a PyRefMut<'_, Self>) -> InternalPyBTreeSeqIter<'a> {
-        let slf = &slf;
-        let iter = slf.btree_map.iter();
-
-        return InternalPyBTreeSeqIter {
-            iter: iter,
-            elem: None,
-            elem_count: 0,
+impl From<BTreeSeqState> for PyBTreeSeq {
+    fn from(state: BTreeSeqState) -> Self {
+        PyBTreeSeq {
+            state: TreeLock::new(state),
+        }
+    }
+}
+
+impl BTreeSeqState {
+    fn insert_elem(&mut self, elem: Elem) -> bool {
+        let output = self
+            .btree_map
+            .entry(elem)
+            .and_modify(|x| *x += 1)
+            .or_insert(1);
+        let inserted = output == &1;
+        self.length += 1;
+
+        inserted
+    }
+
+    fn remove_elem(&mut self, key: Elem) -> bool {
+        let entry = self.btree_map.entry(key).and_modify(|x| *x -= 1);
+
+        let output = match entry {
+            btree_map::Entry::Vacant(_) => return false,
+            btree_map::Entry::Occupied(entry) => {
+                if *entry.get() == 0 {
+                    entry.remove();
+                    false
+                } else {
+                    true
+                }
+            }
         };
+        self.length -= 1;
+
+        output
     }
 }
+
+fn fetch(py: Python, owner: &PyObject, range: KeyRange) -> PyResult<Vec<(Elem, usize)>> {
+    let state = owner
+        .downcast_bound::<PyBTreeSeq>(py)?
+        .get()
+        .state
+        .read(py)?;
+    Ok(iterators::map_batch(py, &state.btree_map, range, |count| {
+        *count
+    }))
+}
+
+fn key_range(btree_map: &BTreeMap<Elem, usize>) -> Option<(&Elem, &Elem)> {
+    let (first, _) = btree_map.first_key_value()?;
+    let (last, _) = btree_map.last_key_value()?;
+    Some((first, last))
+}
@@ -1,84 +1,177 @@
-use crate::elem::Elem;
-use crate::iterators::PyBTreeSetIter;
-use pyo3::exceptions;
+use crate::binary;
+use crate::bulk;
+use crate::elem::{self, Elem};
+use crate::iterators::{self, Cursor, KeyRange, PyBTreeSetIter};
+use crate::json;
+use crate::merge::{self, OnConflict};
+use crate::pyfrozen_btree_set::PyFrozenBTreeSet;
+use crate::tree_lock::TreeLock;
 use pyo3::prelude::*;
-use pyo3::types::{PyIterator, PySequence};
-use std::collections::{btree_set, BTreeSet};
+use pyo3::types::{PyBytes, PyList, PyTuple, PyType};
+use std::collections::BTreeSet;
+use std::io::Cursor as IoCursor;
+use std::path::PathBuf;
 
-#[pyclass]
+#[pyclass(module = "tree_collections.tree_collections", frozen)]
 pub struct PyBTreeSet {
-    pub btree_set: BTreeSet<Elem>,
+    pub btree_set: TreeLock<BTreeSet<Elem>>,
 }
 
-unsafe impl Send for PyBTreeSet {}
-
 #[pymethods]
 impl PyBTreeSet {
     #[new]
     #[pyo3(signature = (input=None))]
     pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self> {
-        let mut btree_set = BTreeSet::<Elem>::new();
-
-        if let Some(input) = input {
-            let iter: &PyIterator = if let Ok(input) = input.downcast::<PySequence>(py) {
-                input.iter()?
-            } else if let Ok(input) = input.downcast::<PyIterator>(py) {
-                input
-            } else {
-                return Err(PyErr::new::<exceptions::PyTypeError, _>(
-                    "Expected a sequence or iterable",
-                ));
-            };
+        let items = match input {
+            Some(input) => bulk::extract_elems(input, py)?,
+            None => Vec::new(),
+        };
+        let btree_set = elem::catch_cmp(|| {
+            Ok(bulk::allow_threads_if_native(
+                py,
+                items,
+                |elem| elem,
+                bulk::build_set,
+            ))
+        })?;
 
-            for x in iter {
-                let elem = x?.downcast::<PyAny>()?;
-                let elem = elem.extract::<Elem>()?;
-                btree_set.insert(elem);
-            }
-        }
+        Ok(btree_set.into())
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (input, validate=true))]
+    pub fn from_sorted(
+        _cls: &Bound<'_, PyType>,
+        input: PyObject,
+        validate: bool,
+        py: Python,
+    ) -> PyResult<Self> {
+        let items = bulk::extract_elems(input, py)?;
+        let btree_set = elem::catch_cmp(|| {
+            bulk::allow_threads_if_native(
+                py,
+                items,
+                |elem| elem,
+                |items| {
+                    if validate {
+                        bulk::check_sorted(&items, |elem| elem)?;
+                    }
+                    PyResult::Ok(bulk::build_set(items))
+                },
+            )
+        })?;
 
-        Ok(PyBTreeSet { btree_set })
+        Ok(btree_set.into())
     }
 
-    pub fn insert(mut slf: PyRefMut<'_, Self>, key: PyObject) -> PyResult<bool> {
+    pub fn insert(&self, py: Python, key: PyObject) -> PyResult<bool> {
         // cast to orderable type
-        let py = slf.py();
         let elem = key.extract::<Elem>(py)?;
-        let output = slf.btree_set.insert(elem);
-
-        Ok(output)
+        let mut btree_set = self.btree_set.write(py)?;
+        elem::catch_cmp(|| Ok(btree_set.insert(elem)))
     }
 
-    pub fn get(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<Option<PyObject>> {
-        let py = slf.py();
+    pub fn get(&self, py: Python, key: PyObject) -> PyResult<Option<PyObject>> {
         let key = key.extract::<Elem>(py)?;
-        let output = slf.btree_set.get(&key);
+        let btree_set = self.btree_set.read(py)?;
+        let output = elem::catch_cmp(|| Ok(btree_set.get(&key)))?;
 
         Ok(output.map(|x| x.to_pyobject(py)))
     }
 
-    pub fn remove(mut slf: PyRefMut<'_, Self>, key: PyObject) -> PyResult<bool> {
-        let py = slf.py();
+    pub fn remove(&self, py: Python, key: PyObject) -> PyResult<bool> {
         let key = key.extract::<Elem>(py)?;
-        let output = slf.btree_set.remove(&key);
-
-        Ok(output)
+        let mut btree_set = self.btree_set.write(py)?;
+        elem::catch_cmp(|| Ok(btree_set.remove(&key)))
     }
 
-    pub fn contains(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<bool> {
-        let py = slf.py();
+    pub fn contains(&self, py: Python, key: PyObject) -> PyResult<bool> {
         let elem_key = key.extract::<Elem>(py)?;
-        Ok(slf.btree_set.contains(&elem_key))
+        let btree_set = self.btree_set.read(py)?;
+        elem::catch_cmp(|| Ok(btree_set.contains(&elem_key)))
+    }
+
+    pub fn insert_many(&self, py: Python, input: PyObject) -> PyResult<Vec<bool>> {
+        let elems = bulk::extract_elems(input, py)?;
+        let mut output = vec![false; elems.len()];
+        let mut btree_set = self.btree_set.write(py)?;
+
+        elem::catch_cmp(|| {
+            for (i, elem) in bulk::sort_batch(elems, |elem| elem) {
+                output[i] = btree_set.insert(elem);
+            }
+            Ok(output)
+        })
+    }
+
+    pub fn update_many(&self, py: Python, input: PyObject) -> PyResult<()> {
+        let elems = bulk::extract_elems(input, py)?;
+        let mut btree_set = self.btree_set.write(py)?;
+
+        elem::catch_cmp(|| {
+            for (_, elem) in bulk::sort_batch(elems, |elem| elem) {
+                btree_set.insert(elem);
+            }
+            Ok(())
+        })
+    }
+
+    #[pyo3(signature = (keys, default=None))]
+    pub fn get_many(
+        &self,
+        py: Python,
+        keys: PyObject,
+        default: Option<PyObject>,
+    ) -> PyResult<Vec<Option<PyObject>>> {
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = Vec::with_capacity(keys.len());
+        output.resize_with(keys.len(), || default.as_ref().map(|x| x.clone_ref(py)));
+        let btree_set = self.btree_set.read(py)?;
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                if let Some(elem) = btree_set.get(&key) {
+                    output[i] = Some(elem.to_pyobject(py));
+                }
+            }
+            Ok(output)
+        })
     }
 
-    pub fn nth(slf: PyRef<'_, Self>, mut n: i64) -> PyResult<Option<PyObject>> {
-        let py = slf.py();
+    pub fn contains_many(&self, py: Python, keys: PyObject) -> PyResult<Vec<bool>> {
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = vec![false; keys.len()];
+        let btree_set = self.btree_set.read(py)?;
 
-        if n >= slf.btree_set.len() as i64 {
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                output[i] = btree_set.contains(&key);
+            }
+            Ok(output)
+        })
+    }
+
+    pub fn remove_many(&self, py: Python, keys: PyObject) -> PyResult<Vec<bool>> {
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = vec![false; keys.len()];
+        let mut btree_set = self.btree_set.write(py)?;
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                output[i] = btree_set.remove(&key);
+            }
+            Ok(output)
+        })
+    }
+
+    pub fn nth(&self, py: Python, mut n: i64) -> PyResult<Option<PyObject>> {
+        let btree_set = self.btree_set.read(py)?;
+
+        if n >= btree_set.len() as i64 {
             return Ok(None);
         }
         if n < 0 {
-            n = slf.btree_set.len() as i64 + n;
+            n += btree_set.len() as i64;
         }
         if n < 0 {
             return Ok(None);
@@ -86,41 +179,242 @@ impl PyBTreeSet {
         let n = n as usize;
 
         let output = if n == 0 {
-            slf.btree_set.first()
-        } else if n == slf.btree_set.len() - 1 {
-            slf.btree_set.last()
+            btree_set.first()
+        } else if n == btree_set.len() - 1 {
+            btree_set.last()
         } else {
-            slf.btree_set.iter().nth(n)
+            btree_set.iter().nth(n)
         };
 
         Ok(output.map(|x| x.to_pyobject(py)))
     }
 
-    pub fn len(&self) -> usize {
-        return self.btree_set.len();
+    pub fn len(&self, py: Python) -> PyResult<usize> {
+        Ok(self.btree_set.read(py)?.len())
     }
 
-    pub fn is_empty(&self) -> bool {
-        return self.btree_set.is_empty();
+    pub fn is_empty(&self, py: Python) -> PyResult<bool> {
+        Ok(self.btree_set.read(py)?.is_empty())
     }
 
-    pub fn clear(&mut self) {
-        self.btree_set.clear();
+    pub fn clear(&self, py: Python) -> PyResult<()> {
+        self.btree_set.write(py)?.clear();
+        Ok(())
     }
 
-    pub fn iter(slf: PyRef<'_, Self>) -> PyBTreeSetIter {
-        let slf = &slf;
-        let owner = slf.into_py(slf.py());
-        let iter = slf.btree_set.iter();
+    pub fn to_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.btree_set.read(py)?.iter())
+    }
 
-        return PyBTreeSetIter {
-            py_obj: owner.clone(),
-            // py_ref: slf.clone(),
-            iter: unsafe {
-                std::mem::transmute::<btree_set::Iter<'_, Elem>, btree_set::Iter<'static, Elem>>(
-                    iter,
-                )
-            },
-        };
+    pub fn copy(&self, py: Python) -> PyResult<Self> {
+        Ok(self.btree_set.read(py)?.clone().into())
+    }
+
+    pub fn __copy__(&self, py: Python) -> PyResult<Self> {
+        self.copy(py)
+    }
+
+    pub fn __deepcopy__(&self, py: Python, memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let btree_set = self.btree_set.read(py)?;
+        let btree_set = elem::catch_cmp(|| {
+            btree_set
+                .iter()
+                .map(|x| x.deepcopy(py, memo))
+                .collect::<PyResult<BTreeSet<_>>>()
+        })?;
+
+        Ok(btree_set.into())
+    }
+
+    /// Pickles as the list of elements in order.
+    pub fn __getstate__(&self, py: Python) -> PyResult<PyObject> {
+        Ok(self.to_list(py)?.into_any().unbind())
+    }
+
+    pub fn __setstate__(&self, py: Python, state: &Bound<'_, PyAny>) -> PyResult<()> {
+        let items = state.extract::<Vec<Elem>>()?;
+        let btree_set = elem::catch_cmp(|| Ok(bulk::build_set(items)))?;
+        *self.btree_set.write(py)? = btree_set;
+
+        Ok(())
+    }
+
+    pub fn __reduce__<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<(Bound<'py, PyType>, Bound<'py, PyTuple>, PyObject)> {
+        let cls = py.get_type::<Self>();
+        Ok((cls, PyTuple::empty(py), self.__getstate__(py)?))
     }
+
+    /// Encodes the set in the binary format of the `binary` module.
+    pub fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let btree_set = self.btree_set.read(py)?;
+        let cursor = elem::allow_threads(py, || {
+            binary::write_set(IoCursor::new(Vec::new()), &btree_set)
+        })?;
+        Ok(PyBytes::new(py, &cursor.into_inner()))
+    }
+
+    #[classmethod]
+    pub fn from_bytes(_cls: &Bound<'_, PyType>, data: &[u8], py: Python) -> PyResult<Self> {
+        let btree_set =
+            elem::catch_cmp(|| Ok(elem::allow_threads(py, || binary::read_set(data))?))?;
+        Ok(btree_set.into())
+    }
+
+    pub fn save(&self, py: Python, path: PathBuf) -> PyResult<()> {
+        let btree_set = self.btree_set.read(py)?;
+        elem::allow_threads(py, || {
+            binary::save(path, |writer| binary::write_set(writer, &btree_set))
+        })?;
+        Ok(())
+    }
+
+    #[classmethod]
+    pub fn load(_cls: &Bound<'_, PyType>, path: PathBuf, py: Python) -> PyResult<Self> {
+        let btree_set = elem::catch_cmp(|| {
+            Ok(elem::allow_threads(py, || {
+                binary::read_set(binary::open(path)?)
+            })?)
+        })?;
+        Ok(btree_set.into())
+    }
+
+    /// Encodes the set as JSON in the format of the `json` module.
+    pub fn to_json(&self, py: Python) -> PyResult<String> {
+        let btree_set = self.btree_set.read(py)?;
+        let bytes = elem::allow_threads(py, || json::write_set(Vec::new(), &btree_set))?;
+        Ok(String::from_utf8(bytes).unwrap())
+    }
+
+    #[classmethod]
+    pub fn from_json(_cls: &Bound<'_, PyType>, data: &str, py: Python) -> PyResult<Self> {
+        let btree_set =
+            elem::catch_cmp(|| Ok(elem::allow_threads(py, || json::read_set(data.as_bytes()))?))?;
+        Ok(btree_set.into())
+    }
+
+    pub fn save_json(&self, py: Python, path: PathBuf) -> PyResult<()> {
+        let btree_set = self.btree_set.read(py)?;
+        elem::allow_threads(py, || {
+            binary::save(path, |writer| json::write_set(writer, &btree_set))
+        })?;
+        Ok(())
+    }
+
+    #[classmethod]
+    pub fn load_json(_cls: &Bound<'_, PyType>, path: PathBuf, py: Python) -> PyResult<Self> {
+        let btree_set = elem::catch_cmp(|| {
+            Ok(elem::allow_threads(py, || {
+                json::read_set(binary::open(path)?)
+            })?)
+        })?;
+        Ok(btree_set.into())
+    }
+
+    pub fn freeze(&self, py: Python) -> PyResult<PyFrozenBTreeSet> {
+        let btree_set = self
+            .btree_set
+            .read(py)?
+            .iter()
+            .map(|x| x.clone_ref(py))
+            .collect();
+        Ok(PyFrozenBTreeSet::from_set(btree_set))
+    }
+
+    pub fn split_off(&self, py: Python, key: PyObject) -> PyResult<Self> {
+        let key = key.extract::<Elem>(py)?;
+        let mut btree_set = self.btree_set.write(py)?;
+        // looked up first so that a key that can't be ordered fails before
+        // the tree is taken apart
+        elem::catch_cmp(|| Ok(btree_set.contains(&key)))?;
+        let btree_set = btree_set.split_off(&key);
+
+        Ok(btree_set.into())
+    }
+
+    pub fn append(&self, py: Python, other: &Bound<'_, Self>) -> PyResult<()> {
+        self.merge(py, other, OnConflict::Replace)
+    }
+
+    #[pyo3(signature = (other, on_conflict=OnConflict::Replace))]
+    pub fn merge(
+        &self,
+        py: Python,
+        other: &Bound<'_, Self>,
+        on_conflict: OnConflict,
+    ) -> PyResult<()> {
+        let (mut dst, mut src) = self.btree_set.write_pair(&other.get().btree_set, py)?;
+        let dst = &mut *dst;
+        let src = &mut *src;
+
+        if elem::catch_cmp(|| Ok(merge::is_disjoint(key_range(dst), key_range(src))))? {
+            return elem::catch_cmp(|| {
+                dst.append(src);
+                Ok(())
+            });
+        }
+
+        while let Some(elem) = src.pop_first() {
+            let current = match elem::catch_cmp(|| Ok(dst.get(&elem))) {
+                Ok(Some(current)) => current,
+                Ok(None) => {
+                    dst.insert(elem);
+                    continue;
+                }
+                Err(err) => {
+                    // leave the unmerged elements in `other`
+                    src.insert(elem);
+                    return Err(err);
+                }
+            };
+            match &on_conflict {
+                OnConflict::Replace => {
+                    dst.replace(elem);
+                }
+                OnConflict::Keep => (),
+                OnConflict::Call(f) => match merge::call_resolve_elem(py, f, current, &elem) {
+                    Ok(output) => {
+                        dst.replace(output);
+                    }
+                    Err(err) => {
+                        // leave the unmerged elements in `other`
+                        src.insert(elem);
+                        return Err(err);
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn iter(slf: &Bound<'_, Self>) -> PyBTreeSetIter {
+        let owner = slf.clone().into_any().unbind();
+        PyBTreeSetIter {
+            cursor: Cursor::new(owner, fetch),
+        }
+    }
+}
+
+impl From<BTreeSet<Elem>> for PyBTreeSet {
+    fn from(btree_set: BTreeSet<Elem>) -> Self {
+        PyBTreeSet {
+            btree_set: TreeLock::new(btree_set),
+        }
+    }
+}
+
+fn fetch(py: Python, owner: &PyObject, range: KeyRange) -> PyResult<Vec<(Elem, ())>> {
+    let btree_set = owner
+        .downcast_bound::<PyBTreeSet>(py)?
+        .get()
+        .btree_set
+        .read(py)?;
+    Ok(iterators::set_batch(py, &btree_set, range))
+}
+
+fn key_range(btree_set: &BTreeSet<Elem>) -> Option<(&Elem, &Elem)> {
+    Some((btree_set.first()?, btree_set.last()?))
 }
@@ -0,0 +1,165 @@
+use crate::disk_btree::DiskBTree;
+use crate::elem::{self, Elem};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use std::collections::VecDeque;
+use std::ops::Bound;
+use std::path::PathBuf;
+
+// entries fetched from the page file per refill of a range iterator
+const BATCH_SIZE: usize = 256;
+
+/// Map backed by a page file, created with `PyBTreeMap.open(path)`.
+#[pyclass(module = "tree_collections.tree_collections")]
+pub struct PyDiskBTreeMap {
+    tree: Option<DiskBTree>,
+}
+
+#[pymethods]
+impl PyDiskBTreeMap {
+    pub fn get(&mut self, key: PyObject, py: Python) -> PyResult<Option<PyObject>> {
+        let key = key.extract::<Elem>(py)?;
+        let tree = self.tree()?;
+        let output = elem::catch_cmp(|| Ok(tree.get(&key)?))?;
+
+        Ok(output.map(|x| x.to_pyobject(py)))
+    }
+
+    pub fn __getitem__(&mut self, key: PyObject, py: Python) -> PyResult<PyObject> {
+        let elem = key.extract::<Elem>(py)?;
+
+        let tree = self.tree()?;
+        match elem::catch_cmp(|| Ok(tree.get(&elem)?))? {
+            Some(value) => Ok(value.to_pyobject(py)),
+            None => Err(PyErr::new::<exceptions::PyKeyError, _>(key)),
+        }
+    }
+
+    pub fn contains_key(&mut self, key: PyObject, py: Python) -> PyResult<bool> {
+        let key = key.extract::<Elem>(py)?;
+        let tree = self.tree()?;
+        elem::catch_cmp(|| Ok(tree.get(&key)?.is_some()))
+    }
+
+    pub fn insert(
+        &mut self,
+        key: PyObject,
+        value: PyObject,
+        py: Python,
+    ) -> PyResult<Option<PyObject>> {
+        let key = key.extract::<Elem>(py)?;
+        let value = value.extract::<Elem>(py)?;
+        let tree = self.tree()?;
+        let old = elem::catch_cmp(|| Ok(tree.insert(key, value)?))?;
+
+        Ok(old.map(|x| x.to_pyobject(py)))
+    }
+
+    pub fn remove(&mut self, key: PyObject, py: Python) -> PyResult<Option<PyObject>> {
+        let key = key.extract::<Elem>(py)?;
+        let tree = self.tree()?;
+        let old = elem::catch_cmp(|| Ok(tree.remove(&key)?))?;
+
+        Ok(old.map(|x| x.to_pyobject(py)))
+    }
+
+    pub fn len(&mut self) -> PyResult<usize> {
+        Ok(self.tree()?.len())
+    }
+
+    pub fn is_empty(&mut self) -> PyResult<bool> {
+        Ok(self.tree()?.is_empty())
+    }
+
+    /// Iterates over the entries with `start <= key < stop` in key order. It
+    /// reads the file in batches that each resume after the last key read, so
+    /// updates made while iterating show up once they're past the current batch.
+    #[pyo3(signature = (start=None, stop=None))]
+    pub fn range(
+        slf: PyRef<'_, Self>,
+        start: Option<PyObject>,
+        stop: Option<PyObject>,
+    ) -> PyResult<PyDiskBTreeMapRange> {
+        let py = slf.py();
+        let start = start.map(|x| x.extract::<Elem>(py)).transpose()?;
+        let stop = stop.map(|x| x.extract::<Elem>(py)).transpose()?;
+
+        Ok(PyDiskBTreeMapRange {
+            owner: slf.into(),
+            start: start.map_or(Bound::Unbounded, Bound::Included),
+            stop,
+            buffer: VecDeque::new(),
+        })
+    }
+
+    /// Makes every update so far durable.
+    pub fn flush(&mut self) -> PyResult<()> {
+        self.tree()?.flush()?;
+        Ok(())
+    }
+
+    /// Flushes and closes the file, later calls raise `ValueError`.
+    pub fn close(&mut self) -> PyResult<()> {
+        if let Some(mut tree) = self.tree.take() {
+            tree.flush()?;
+        }
+        Ok(())
+    }
+
+    #[getter]
+    pub fn closed(&self) -> bool {
+        self.tree.is_none()
+    }
+}
+
+impl PyDiskBTreeMap {
+    pub fn open(path: PathBuf, cache_size: usize) -> PyResult<Self> {
+        let tree = DiskBTree::open(path, cache_size)?;
+        Ok(PyDiskBTreeMap { tree: Some(tree) })
+    }
+
+    fn tree(&mut self) -> PyResult<&mut DiskBTree> {
+        self.tree
+            .as_mut()
+            .ok_or_else(|| PyErr::new::<exceptions::PyValueError, _>("page file is closed"))
+    }
+}
+
+#[pyclass]
+pub struct PyDiskBTreeMapRange {
+    owner: Py<PyDiskBTreeMap>,
+    /// Lower bound of the next batch.
+    start: Bound<Elem>,
+    stop: Option<Elem>,
+    buffer: VecDeque<(Elem, Elem)>,
+}
+
+#[pymethods]
+impl PyDiskBTreeMapRange {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<(PyObject, PyObject)>> {
+        let py = slf.py();
+
+        if slf.buffer.is_empty() {
+            let mut owner = slf.owner.borrow_mut(py);
+            let tree = owner.tree()?;
+            let batch = elem::catch_cmp(|| {
+                Ok(tree.range(slf.start.as_ref(), slf.stop.as_ref(), BATCH_SIZE)?)
+            })?;
+            drop(owner);
+
+            if let Some((key, _)) = batch.last() {
+                slf.start = Bound::Excluded(key.clone());
+            }
+            slf.buffer.extend(batch);
+        }
+
+        Ok(slf
+            .buffer
+            .pop_front()
+            .map(|(key, value)| (key.to_pyobject(py), value.to_pyobject(py))))
+    }
+}
@@ -0,0 +1,301 @@
+use crate::bulk;
+use crate::elem::{self, Elem};
+use crate::iterators::{self, Cursor, KeyRange, PyBTreeMapIter, PyBTreeMapKeys, PyBTreeMapValues};
+use crate::persistent_map::PersistentMap;
+use crate::pybtree_map::PyBTreeMap;
+use pyo3::basic::CompareOp;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyList, PyType};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::Hasher;
+use std::sync::OnceLock;
+
+/// Immutable `PyBTreeMap` that can be used as a dict key or set member.
+#[pyclass(frozen, module = "tree_collections.tree_collections")]
+pub struct PyFrozenBTreeMap {
+    pub btree_map: BTreeMap<Elem, Elem>,
+    // computed on the first `__hash__` so unhashable values only fail there
+    hash: OnceLock<isize>,
+}
+
+#[pymethods]
+impl PyFrozenBTreeMap {
+    #[new]
+    #[pyo3(signature = (input=None))]
+    pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self> {
+        let items = match input {
+            Some(input) => bulk::extract_pairs(input, py)?,
+            None => Vec::new(),
+        };
+        let btree_map = elem::catch_cmp(|| {
+            Ok(bulk::allow_threads_if_native(
+                py,
+                items,
+                |(key, _)| key,
+                bulk::build_map,
+            ))
+        })?;
+
+        Ok(PyFrozenBTreeMap::from_map(btree_map))
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (input, validate=true))]
+    pub fn from_sorted(
+        _cls: &Bound<'_, PyType>,
+        input: PyObject,
+        validate: bool,
+        py: Python,
+    ) -> PyResult<Self> {
+        let items = bulk::extract_pairs(input, py)?;
+        let btree_map = elem::catch_cmp(|| {
+            bulk::allow_threads_if_native(
+                py,
+                items,
+                |(key, _)| key,
+                |items| {
+                    if validate {
+                        bulk::check_sorted(&items, |(key, _)| key)?;
+                    }
+                    PyResult::Ok(bulk::build_map(items))
+                },
+            )
+        })?;
+
+        Ok(PyFrozenBTreeMap::from_map(btree_map))
+    }
+
+    pub fn get(&self, key: PyObject, py: Python) -> PyResult<Option<PyObject>> {
+        let _attached = elem::attach(py);
+        let key = key.extract::<Elem>(py)?;
+        let output = elem::catch_cmp(|| Ok(self.btree_map.get(&key)))?;
+
+        Ok(output.map(|x| x.to_pyobject(py)))
+    }
+
+    pub fn __getitem__(&self, key: PyObject, py: Python) -> PyResult<PyObject> {
+        let _attached = elem::attach(py);
+        let elem = key.extract::<Elem>(py)?;
+
+        match elem::catch_cmp(|| Ok(self.btree_map.get(&elem)))? {
+            Some(value) => Ok(value.to_pyobject(py)),
+            None => Err(PyErr::new::<exceptions::PyKeyError, _>(key)),
+        }
+    }
+
+    pub fn contains_key(&self, key: PyObject, py: Python) -> PyResult<bool> {
+        let _attached = elem::attach(py);
+        let key = key.extract::<Elem>(py)?;
+        elem::catch_cmp(|| Ok(self.btree_map.contains_key(&key)))
+    }
+
+    #[pyo3(signature = (keys, default=None))]
+    pub fn get_many(
+        &self,
+        keys: PyObject,
+        default: Option<PyObject>,
+        py: Python,
+    ) -> PyResult<Vec<Option<PyObject>>> {
+        let _attached = elem::attach(py);
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = Vec::with_capacity(keys.len());
+        output.resize_with(keys.len(), || default.as_ref().map(|x| x.clone_ref(py)));
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                if let Some(value) = self.btree_map.get(&key) {
+                    output[i] = Some(value.to_pyobject(py));
+                }
+            }
+
+            Ok(output)
+        })
+    }
+
+    pub fn contains_many(&self, keys: PyObject, py: Python) -> PyResult<Vec<bool>> {
+        let _attached = elem::attach(py);
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = vec![false; keys.len()];
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                output[i] = self.btree_map.contains_key(&key);
+            }
+
+            Ok(output)
+        })
+    }
+
+    pub fn nth(&self, mut n: i64, py: Python) -> Option<(PyObject, PyObject)> {
+        let len = self.btree_map.len() as i64;
+        if n < 0 {
+            n += len;
+        }
+        if n < 0 || n >= len {
+            return None;
+        }
+
+        let output = self.btree_map.iter().nth(n as usize);
+        output.map(|(key, value)| (key.to_pyobject(py), value.to_pyobject(py)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.btree_map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.btree_map.is_empty()
+    }
+
+    pub fn keys_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.btree_map.keys())
+    }
+
+    pub fn values_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.btree_map.values())
+    }
+
+    pub fn items_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.btree_map.iter())
+    }
+
+    pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let dict = PyDict::new(py);
+        for (key, value) in self.btree_map.iter() {
+            dict.set_item(key, value)?;
+        }
+        Ok(dict)
+    }
+
+    /// Frozen maps are immutable, so copies share the same object.
+    pub fn copy(slf: PyRef<'_, Self>) -> Py<Self> {
+        slf.into()
+    }
+
+    pub fn __copy__(slf: PyRef<'_, Self>) -> Py<Self> {
+        slf.into()
+    }
+
+    pub fn __deepcopy__(&self, py: Python, memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let btree_map = elem::catch_cmp(|| {
+            self.btree_map
+                .iter()
+                .map(|(key, value)| Ok((key.deepcopy(py, memo)?, value.deepcopy(py, memo)?)))
+                .collect::<PyResult<_>>()
+        })?;
+
+        Ok(PyFrozenBTreeMap::from_map(btree_map))
+    }
+
+    pub fn __getstate__(&self, py: Python) -> PyResult<PyObject> {
+        Ok(self.items_list(py)?.into_any().unbind())
+    }
+
+    /// Frozen maps can't be updated in place, so they unpickle through the
+    /// constructor rather than `__setstate__`.
+    pub fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyType>, (PyObject,))> {
+        let cls = py.get_type::<Self>();
+        Ok((cls, (self.__getstate__(py)?,)))
+    }
+
+    pub fn thaw(&self, py: Python) -> PyResult<PyBTreeMap> {
+        let entries = self
+            .btree_map
+            .iter()
+            .map(|(key, value)| (key.clone_ref(py), value.clone_ref(py)))
+            .collect();
+
+        PyBTreeMap::with_aggregate(PersistentMap::from_sorted(entries), None)
+    }
+
+    pub fn __hash__(&self, py: Python) -> PyResult<isize> {
+        if let Some(hash) = self.hash.get() {
+            return Ok(*hash);
+        }
+
+        let hashes = self
+            .btree_map
+            .iter()
+            .map(|(key, value)| {
+                let mut state = DefaultHasher::new();
+                key.hash_into(py, &mut state)?;
+                value.hash_into(py, &mut state)?;
+                Ok(state.finish())
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        Ok(*self
+            .hash
+            .get_or_init(|| elem::hash_unordered(hashes.into_iter())))
+    }
+
+    pub fn __richcmp__(
+        &self,
+        other: &Bound<'_, PyAny>,
+        op: CompareOp,
+        py: Python,
+    ) -> PyResult<PyObject> {
+        let _attached = elem::attach(py);
+        let other = match other.downcast::<Self>() {
+            Ok(other) => other.get(),
+            Err(_) => return Ok(py.NotImplemented()),
+        };
+
+        let output = match op {
+            CompareOp::Eq => elem::catch_cmp(|| Ok(self.btree_map == other.btree_map))?,
+            CompareOp::Ne => elem::catch_cmp(|| Ok(self.btree_map != other.btree_map))?,
+            _ => return Ok(py.NotImplemented()),
+        };
+        Ok(PyBool::new(py, output).to_owned().into_any().unbind())
+    }
+
+    pub fn keys(slf: &Bound<'_, Self>) -> PyBTreeMapKeys {
+        let owner = slf.clone().into_any().unbind();
+        PyBTreeMapKeys {
+            cursor: Cursor::new(owner, fetch_keys),
+        }
+    }
+
+    pub fn values(slf: &Bound<'_, Self>) -> PyBTreeMapValues {
+        let owner = slf.clone().into_any().unbind();
+        PyBTreeMapValues {
+            cursor: Cursor::new(owner, fetch_items),
+        }
+    }
+
+    pub fn items(slf: &Bound<'_, Self>) -> PyBTreeMapIter {
+        let owner = slf.clone().into_any().unbind();
+        PyBTreeMapIter {
+            cursor: Cursor::new(owner, fetch_items),
+        }
+    }
+}
+
+impl PyFrozenBTreeMap {
+    pub fn from_map(btree_map: BTreeMap<Elem, Elem>) -> Self {
+        PyFrozenBTreeMap {
+            btree_map,
+            hash: OnceLock::new(),
+        }
+    }
+}
+
+fn fetch_keys(py: Python, owner: &PyObject, range: KeyRange) -> PyResult<Vec<(Elem, ())>> {
+    let btree_map = &owner
+        .downcast_bound::<PyFrozenBTreeMap>(py)?
+        .get()
+        .btree_map;
+    Ok(iterators::map_batch(py, btree_map, range, |_| ()))
+}
+
+fn fetch_items(py: Python, owner: &PyObject, range: KeyRange) -> PyResult<Vec<(Elem, Elem)>> {
+    let btree_map = &owner
+        .downcast_bound::<PyFrozenBTreeMap>(py)?
+        .get()
+        .btree_map;
+    Ok(iterators::map_batch(py, btree_map, range, |value| {
+        value.clone_ref(py)
+    }))
+}
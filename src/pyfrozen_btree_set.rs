@@ -0,0 +1,243 @@
+use crate::bulk;
+use crate::elem::{self, Elem};
+use crate::iterators::{self, Cursor, KeyRange, PyBTreeSetIter};
+use crate::pybtree_set::PyBTreeSet;
+use pyo3::basic::CompareOp;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyList, PyType};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::Hasher;
+use std::sync::OnceLock;
+
+/// Immutable `PyBTreeSet` that can be used as a dict key or set member.
+#[pyclass(frozen, module = "tree_collections.tree_collections")]
+pub struct PyFrozenBTreeSet {
+    pub btree_set: BTreeSet<Elem>,
+    // computed on the first `__hash__` so unhashable elements only fail there
+    hash: OnceLock<isize>,
+}
+
+#[pymethods]
+impl PyFrozenBTreeSet {
+    #[new]
+    #[pyo3(signature = (input=None))]
+    pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self> {
+        let items = match input {
+            Some(input) => bulk::extract_elems(input, py)?,
+            None => Vec::new(),
+        };
+        let btree_set = elem::catch_cmp(|| {
+            Ok(bulk::allow_threads_if_native(
+                py,
+                items,
+                |x| x,
+                bulk::build_set,
+            ))
+        })?;
+
+        Ok(PyFrozenBTreeSet::from_set(btree_set))
+    }
+
+    #[classmethod]
+    #[pyo3(signature = (input, validate=true))]
+    pub fn from_sorted(
+        _cls: &Bound<'_, PyType>,
+        input: PyObject,
+        validate: bool,
+        py: Python,
+    ) -> PyResult<Self> {
+        let items = bulk::extract_elems(input, py)?;
+        let btree_set = elem::catch_cmp(|| {
+            bulk::allow_threads_if_native(
+                py,
+                items,
+                |x| x,
+                |items| {
+                    if validate {
+                        bulk::check_sorted(&items, |x| x)?;
+                    }
+                    PyResult::Ok(bulk::build_set(items))
+                },
+            )
+        })?;
+
+        Ok(PyFrozenBTreeSet::from_set(btree_set))
+    }
+
+    pub fn get(&self, key: PyObject, py: Python) -> PyResult<Option<PyObject>> {
+        let _attached = elem::attach(py);
+        let key = key.extract::<Elem>(py)?;
+        let output = elem::catch_cmp(|| Ok(self.btree_set.get(&key)))?;
+
+        Ok(output.map(|x| x.to_pyobject(py)))
+    }
+
+    pub fn contains(&self, key: PyObject, py: Python) -> PyResult<bool> {
+        let _attached = elem::attach(py);
+        let key = key.extract::<Elem>(py)?;
+        elem::catch_cmp(|| Ok(self.btree_set.contains(&key)))
+    }
+
+    #[pyo3(signature = (keys, default=None))]
+    pub fn get_many(
+        &self,
+        keys: PyObject,
+        default: Option<PyObject>,
+        py: Python,
+    ) -> PyResult<Vec<Option<PyObject>>> {
+        let _attached = elem::attach(py);
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = Vec::with_capacity(keys.len());
+        output.resize_with(keys.len(), || default.as_ref().map(|x| x.clone_ref(py)));
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                if let Some(elem) = self.btree_set.get(&key) {
+                    output[i] = Some(elem.to_pyobject(py));
+                }
+            }
+
+            Ok(output)
+        })
+    }
+
+    pub fn contains_many(&self, keys: PyObject, py: Python) -> PyResult<Vec<bool>> {
+        let _attached = elem::attach(py);
+        let keys = bulk::extract_elems(keys, py)?;
+        let mut output = vec![false; keys.len()];
+
+        elem::catch_cmp(|| {
+            for (i, key) in bulk::sort_batch(keys, |key| key) {
+                output[i] = self.btree_set.contains(&key);
+            }
+
+            Ok(output)
+        })
+    }
+
+    pub fn nth(&self, mut n: i64, py: Python) -> Option<PyObject> {
+        let len = self.btree_set.len() as i64;
+        if n < 0 {
+            n += len;
+        }
+        if n < 0 || n >= len {
+            return None;
+        }
+
+        let output = self.btree_set.iter().nth(n as usize);
+        output.map(|x| x.to_pyobject(py))
+    }
+
+    pub fn len(&self) -> usize {
+        self.btree_set.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.btree_set.is_empty()
+    }
+
+    pub fn to_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+        PyList::new(py, self.btree_set.iter())
+    }
+
+    /// Frozen sets are immutable, so copies share the same object.
+    pub fn copy(slf: PyRef<'_, Self>) -> Py<Self> {
+        slf.into()
+    }
+
+    pub fn __copy__(slf: PyRef<'_, Self>) -> Py<Self> {
+        slf.into()
+    }
+
+    pub fn __deepcopy__(&self, py: Python, memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let btree_set = elem::catch_cmp(|| {
+            self.btree_set
+                .iter()
+                .map(|x| x.deepcopy(py, memo))
+                .collect::<PyResult<_>>()
+        })?;
+
+        Ok(PyFrozenBTreeSet::from_set(btree_set))
+    }
+
+    pub fn __getstate__(&self, py: Python) -> PyResult<PyObject> {
+        Ok(self.to_list(py)?.into_any().unbind())
+    }
+
+    /// Frozen sets can't be updated in place, so they unpickle through the
+    /// constructor rather than `__setstate__`.
+    pub fn __reduce__<'py>(&self, py: Python<'py>) -> PyResult<(Bound<'py, PyType>, (PyObject,))> {
+        let cls = py.get_type::<Self>();
+        Ok((cls, (self.__getstate__(py)?,)))
+    }
+
+    pub fn thaw(&self, py: Python) -> PyBTreeSet {
+        let btree_set = self.btree_set.iter().map(|x| x.clone_ref(py));
+        PyBTreeSet::from(btree_set.collect::<BTreeSet<_>>())
+    }
+
+    pub fn __hash__(&self, py: Python) -> PyResult<isize> {
+        if let Some(hash) = self.hash.get() {
+            return Ok(*hash);
+        }
+
+        let hashes = self
+            .btree_set
+            .iter()
+            .map(|x| {
+                let mut state = DefaultHasher::new();
+                x.hash_into(py, &mut state)?;
+                Ok(state.finish())
+            })
+            .collect::<PyResult<Vec<_>>>()?;
+
+        Ok(*self
+            .hash
+            .get_or_init(|| elem::hash_unordered(hashes.into_iter())))
+    }
+
+    pub fn __richcmp__(
+        &self,
+        other: &Bound<'_, PyAny>,
+        op: CompareOp,
+        py: Python,
+    ) -> PyResult<PyObject> {
+        let _attached = elem::attach(py);
+        let other = match other.downcast::<Self>() {
+            Ok(other) => other.get(),
+            Err(_) => return Ok(py.NotImplemented()),
+        };
+
+        let output = match op {
+            CompareOp::Eq => elem::catch_cmp(|| Ok(self.btree_set == other.btree_set))?,
+            CompareOp::Ne => elem::catch_cmp(|| Ok(self.btree_set != other.btree_set))?,
+            _ => return Ok(py.NotImplemented()),
+        };
+        Ok(PyBool::new(py, output).to_owned().into_any().unbind())
+    }
+
+    pub fn iter(slf: &Bound<'_, Self>) -> PyBTreeSetIter {
+        let owner = slf.clone().into_any().unbind();
+        PyBTreeSetIter {
+            cursor: Cursor::new(owner, fetch),
+        }
+    }
+}
+
+impl PyFrozenBTreeSet {
+    pub fn from_set(btree_set: BTreeSet<Elem>) -> Self {
+        PyFrozenBTreeSet {
+            btree_set,
+            hash: OnceLock::new(),
+        }
+    }
+}
+
+fn fetch(py: Python, owner: &PyObject, range: KeyRange) -> PyResult<Vec<(Elem, ())>> {
+    let btree_set = &owner
+        .downcast_bound::<PyFrozenBTreeSet>(py)?
+        .get()
+        .btree_set;
+    Ok(iterators::set_batch(py, btree_set, range))
+}
@@ -0,0 +1,177 @@
+use crate::elem::{self, Elem};
+use crate::interval_tree::{IntervalTree, Node};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyIterator, PyList, PySequence, PyTuple, PyType};
+
+#[pyclass(module = "tree_collections.tree_collections")]
+pub struct PyIntervalTree {
+    pub tree: IntervalTree,
+}
+
+#[pymethods]
+impl PyIntervalTree {
+    #[new]
+    #[pyo3(signature = (input=None))]
+    pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self> {
+        let mut tree = IntervalTree::new();
+
+        if let Some(input) = input {
+            let input = input.bind(py);
+            let iter = if let Ok(input) = input.downcast::<PySequence>() {
+                input.try_iter()?
+            } else if let Ok(input) = input.downcast::<PyIterator>() {
+                input.clone()
+            } else {
+                return Err(PyErr::new::<exceptions::PyTypeError, _>(
+                    "Expected an iterable of (start, end, value) tuples",
+                ));
+            };
+
+            elem::catch_cmp(|| {
+                for x in iter {
+                    let (start, end, value) = x?.extract::<(Elem, Elem, Elem)>()?;
+                    check_interval(&start, &end)?;
+                    tree.insert(start, end, value);
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(PyIntervalTree { tree })
+    }
+
+    pub fn add(
+        mut slf: PyRefMut<'_, Self>,
+        start: PyObject,
+        end: PyObject,
+        value: PyObject,
+    ) -> PyResult<()> {
+        // cast to orderable type
+        let py = slf.py();
+        let start = start.extract::<Elem>(py)?;
+        let end = end.extract::<Elem>(py)?;
+        let value = value.extract::<Elem>(py)?;
+        elem::catch_cmp(|| {
+            check_interval(&start, &end)?;
+            slf.tree.insert(start, end, value);
+            Ok(())
+        })
+    }
+
+    pub fn remove(
+        mut slf: PyRefMut<'_, Self>,
+        start: PyObject,
+        end: PyObject,
+    ) -> PyResult<Option<PyObject>> {
+        let py = slf.py();
+        let start = start.extract::<Elem>(py)?;
+        let end = end.extract::<Elem>(py)?;
+        let output = elem::catch_cmp(|| Ok(slf.tree.remove(&start, &end)))?;
+
+        Ok(output.map(|x| x.value.to_pyobject(py)))
+    }
+
+    pub fn overlap<'py>(
+        slf: PyRef<'py, Self>,
+        start: PyObject,
+        end: PyObject,
+    ) -> PyResult<Bound<'py, PyList>> {
+        let py = slf.py();
+        let start = start.extract::<Elem>(py)?;
+        let end = end.extract::<Elem>(py)?;
+
+        let nodes = elem::catch_cmp(|| Ok(slf.tree.overlap(&start, &end)))?;
+        nodes_to_list(py, nodes)
+    }
+
+    pub fn at<'py>(slf: PyRef<'py, Self>, point: PyObject) -> PyResult<Bound<'py, PyList>> {
+        let py = slf.py();
+        let point = point.extract::<Elem>(py)?;
+
+        let nodes = elem::catch_cmp(|| Ok(slf.tree.at(&point)))?;
+        nodes_to_list(py, nodes)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+
+    pub fn copy(&self) -> Self {
+        PyIntervalTree {
+            tree: self.tree.clone(),
+        }
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.copy()
+    }
+
+    pub fn __deepcopy__(&self, py: Python, memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let mut tree = IntervalTree::new();
+        elem::catch_cmp(|| {
+            for node in self.tree.iter() {
+                tree.insert(
+                    node.start.deepcopy(py, memo)?,
+                    node.end.deepcopy(py, memo)?,
+                    node.value.deepcopy(py, memo)?,
+                );
+            }
+            Ok(())
+        })?;
+
+        Ok(PyIntervalTree { tree })
+    }
+
+    /// Pickles as the list of `(start, end, value)` intervals in order.
+    pub fn __getstate__(&self, py: Python) -> PyResult<PyObject> {
+        Ok(nodes_to_list(py, self.tree.iter())?.into_any().unbind())
+    }
+
+    pub fn __setstate__(&mut self, state: &Bound<'_, PyAny>) -> PyResult<()> {
+        let intervals = state.extract::<Vec<(Elem, Elem, Elem)>>()?;
+        let mut tree = IntervalTree::new();
+        elem::catch_cmp(|| {
+            for (start, end, value) in intervals {
+                tree.insert(start, end, value);
+            }
+            Ok(())
+        })?;
+        self.tree = tree;
+
+        Ok(())
+    }
+
+    pub fn __reduce__<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<(Bound<'py, PyType>, Bound<'py, PyTuple>, PyObject)> {
+        let cls = py.get_type::<Self>();
+        Ok((cls, PyTuple::empty(py), self.__getstate__(py)?))
+    }
+
+    pub fn iter<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyIterator>> {
+        nodes_to_list(py, self.tree.iter())?.try_iter()
+    }
+}
+
+fn check_interval(start: &Elem, end: &Elem) -> PyResult<()> {
+    if start >= end {
+        return Err(PyErr::new::<exceptions::PyValueError, _>(
+            "interval start must be less than its end",
+        ));
+    }
+    Ok(())
+}
+
+fn nodes_to_list<'py>(py: Python<'py>, nodes: Vec<&Node>) -> PyResult<Bound<'py, PyList>> {
+    PyList::new(py, nodes.into_iter().map(|x| (&x.start, &x.end, &x.value)))
+}
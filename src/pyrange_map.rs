@@ -0,0 +1,295 @@
+use crate::elem::{self, Elem};
+use crate::iterators::{self, Cursor, KeyRange, PyRangeMapIter};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyIterator, PyList, PySequence, PyTuple, PyType};
+use std::collections::BTreeMap;
+
+/// Maps the half-open `[start, end)` ranges to values. Ranges never overlap
+/// and adjacent ranges with equal values are merged into one.
+#[pyclass(module = "tree_collections.tree_collections")]
+pub struct PyRangeMap {
+    // start -> (end, value)
+    pub btree_map: BTreeMap<Elem, (Elem, Elem)>,
+}
+
+#[pymethods]
+impl PyRangeMap {
+    #[new]
+    #[pyo3(signature = (input=None))]
+    pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self> {
+        let mut range_map = PyRangeMap {
+            btree_map: BTreeMap::new(),
+        };
+
+        if let Some(input) = input {
+            let input = input.bind(py);
+            let iter = if let Ok(input) = input.downcast::<PySequence>() {
+                input.try_iter()?
+            } else if let Ok(input) = input.downcast::<PyIterator>() {
+                input.clone()
+            } else {
+                return Err(PyErr::new::<exceptions::PyTypeError, _>(
+                    "Expected an iterable of (start, end, value) tuples",
+                ));
+            };
+
+            elem::catch_cmp(|| {
+                for x in iter {
+                    let (start, end, value) = x?.extract::<(Elem, Elem, Elem)>()?;
+                    check_range(&start, &end)?;
+                    range_map.set_range_elems(py, start, end, value);
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(range_map)
+    }
+
+    pub fn set_range(
+        mut slf: PyRefMut<'_, Self>,
+        start: PyObject,
+        end: PyObject,
+        value: PyObject,
+    ) -> PyResult<()> {
+        // cast to orderable type
+        let py = slf.py();
+        let start = start.extract::<Elem>(py)?;
+        let end = end.extract::<Elem>(py)?;
+        let value = value.extract::<Elem>(py)?;
+        elem::catch_cmp(|| {
+            check_range(&start, &end)?;
+            slf.set_range_elems(py, start, end, value);
+            Ok(())
+        })
+    }
+
+    pub fn remove_range(
+        mut slf: PyRefMut<'_, Self>,
+        start: PyObject,
+        end: PyObject,
+    ) -> PyResult<()> {
+        let py = slf.py();
+        let start = start.extract::<Elem>(py)?;
+        let end = end.extract::<Elem>(py)?;
+        elem::catch_cmp(|| {
+            check_range(&start, &end)?;
+            slf.remove_range_elems(py, &start, &end);
+            Ok(())
+        })
+    }
+
+    pub fn get(slf: PyRef<'_, Self>, point: PyObject) -> PyResult<Option<PyObject>> {
+        let py = slf.py();
+        let point = point.extract::<Elem>(py)?;
+        let output = elem::catch_cmp(|| Ok(slf.range_at(&point)))?;
+
+        Ok(output.map(|(_, (_, value))| value.to_pyobject(py)))
+    }
+
+    pub fn __getitem__(slf: PyRef<'_, Self>, point: PyObject) -> PyResult<PyObject> {
+        let py = slf.py();
+        let elem = point.extract::<Elem>(py)?;
+
+        match elem::catch_cmp(|| Ok(slf.range_at(&elem)))? {
+            Some((_, (_, value))) => Ok(value.to_pyobject(py)),
+            None => Err(PyErr::new::<exceptions::PyKeyError, _>(point)),
+        }
+    }
+
+    /// Returns the sub-ranges of `[start, end)` not covered by any range.
+    pub fn gaps<'py>(
+        slf: PyRef<'py, Self>,
+        start: PyObject,
+        end: PyObject,
+    ) -> PyResult<Bound<'py, PyList>> {
+        let py = slf.py();
+        let start = start.extract::<Elem>(py)?;
+        let end = end.extract::<Elem>(py)?;
+        let gaps = elem::catch_cmp(|| {
+            check_range(&start, &end)?;
+
+            let mut gaps = Vec::new();
+            let mut cursor = &start;
+            if let Some((_, (range_end, _))) = slf.btree_map.range(..&start).next_back() {
+                if range_end > cursor {
+                    cursor = range_end;
+                }
+            }
+            for (range_start, (range_end, _)) in slf.btree_map.range(&start..&end) {
+                if range_start > cursor {
+                    gaps.push((cursor.to_pyobject(py), range_start.to_pyobject(py)));
+                }
+                cursor = range_end;
+            }
+            if cursor < &end {
+                gaps.push((cursor.to_pyobject(py), end.to_pyobject(py)));
+            }
+            Ok(gaps)
+        })?;
+
+        PyList::new(py, gaps)
+    }
+
+    pub fn len(&self) -> usize {
+        self.btree_map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.btree_map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.btree_map.clear();
+    }
+
+    pub fn copy(&self) -> Self {
+        PyRangeMap {
+            btree_map: self.btree_map.clone(),
+        }
+    }
+
+    pub fn __copy__(&self) -> Self {
+        self.copy()
+    }
+
+    pub fn __deepcopy__(&self, py: Python, memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let btree_map = elem::catch_cmp(|| {
+            self.btree_map
+                .iter()
+                .map(|(start, (end, value))| {
+                    let range = (end.deepcopy(py, memo)?, value.deepcopy(py, memo)?);
+                    Ok((start.deepcopy(py, memo)?, range))
+                })
+                .collect::<PyResult<_>>()
+        })?;
+
+        Ok(PyRangeMap { btree_map })
+    }
+
+    /// Pickles as the list of `(start, end, value)` ranges in order.
+    pub fn __getstate__(&self, py: Python) -> PyResult<PyObject> {
+        let ranges = self
+            .btree_map
+            .iter()
+            .map(|(start, (end, value))| (start, end, value));
+
+        Ok(PyList::new(py, ranges)?.into_any().unbind())
+    }
+
+    pub fn __setstate__(&mut self, state: &Bound<'_, PyAny>) -> PyResult<()> {
+        let ranges = state.extract::<Vec<(Elem, Elem, Elem)>>()?;
+        self.btree_map = elem::catch_cmp(|| {
+            Ok(ranges
+                .into_iter()
+                .map(|(start, end, value)| (start, (end, value)))
+                .collect())
+        })?;
+
+        Ok(())
+    }
+
+    pub fn __reduce__<'py>(
+        &self,
+        py: Python<'py>,
+    ) -> PyResult<(Bound<'py, PyType>, Bound<'py, PyTuple>, PyObject)> {
+        let cls = py.get_type::<Self>();
+        Ok((cls, PyTuple::empty(py), self.__getstate__(py)?))
+    }
+
+    pub fn iter(slf: &Bound<'_, Self>) -> PyRangeMapIter {
+        let owner = slf.clone().into_any().unbind();
+        PyRangeMapIter {
+            cursor: Cursor::new(owner, fetch),
+        }
+    }
+}
+
+impl PyRangeMap {
+    fn range_at(&self, point: &Elem) -> Option<(&Elem, &(Elem, Elem))> {
+        self.btree_map
+            .range(..=point)
+            .next_back()
+            .filter(|(_, (end, _))| end > point)
+    }
+
+    fn set_range_elems(&mut self, py: Python, mut start: Elem, mut end: Elem, value: Elem) {
+        // find the neighbouring ranges that hold an equal value before the
+        // map changes, so a comparison that fails leaves it as it was. The
+        // left one touches `start` once cut and the one covering `end` starts
+        // there once cut.
+        let left = self.btree_map.range(..&start).next_back();
+        let merge_left = matches!(left, Some((_, (left_end, left_value)))
+            if left_end >= &start && left_value == &value);
+        let merge_right = matches!(self.range_at(&end), Some((_, (_, right_value)))
+            if right_value == &value);
+
+        self.remove_range_elems(py, &start, &end);
+
+        if merge_left {
+            let (left_start, _) = self.btree_map.range(..&start).next_back().unwrap();
+            let left_start = left_start.clone_ref(py);
+            self.btree_map.remove(&left_start);
+            start = left_start;
+        }
+        if merge_right {
+            let (right_end, _) = self.btree_map.remove(&end).unwrap();
+            end = right_end;
+        }
+
+        self.btree_map.insert(start, (end, value));
+    }
+
+    fn remove_range_elems(&mut self, py: Python, start: &Elem, end: &Elem) {
+        // look `end` up first, the cut below changes the map before the
+        // split-off part is inserted at `end`
+        self.btree_map.contains_key(end);
+
+        // cut the range that starts before `start` and overlaps it
+        if let Some((_, (left_end, left_value))) = self.btree_map.range_mut(..start).next_back() {
+            if &*left_end > start {
+                let old_end = std::mem::replace(left_end, start.clone_ref(py));
+                if &old_end > end {
+                    let value = left_value.clone_ref(py);
+                    self.btree_map.insert(end.clone_ref(py), (old_end, value));
+                    return;
+                }
+            }
+        }
+
+        // drop the ranges starting inside `[start, end)`, keeping the part of
+        // the last one that extends past `end`
+        while let Some(range_start) = self
+            .btree_map
+            .range(start..end)
+            .next()
+            .map(|(x, _)| x.clone_ref(py))
+        {
+            let (range_end, value) = self.btree_map.remove(&range_start).unwrap();
+            if &range_end > end {
+                self.btree_map.insert(end.clone_ref(py), (range_end, value));
+                break;
+            }
+        }
+    }
+}
+
+fn fetch(py: Python, owner: &PyObject, range: KeyRange) -> PyResult<Vec<(Elem, (Elem, Elem))>> {
+    let slf = owner.downcast_bound::<PyRangeMap>(py)?.try_borrow()?;
+    Ok(iterators::map_batch(
+        py,
+        &slf.btree_map,
+        range,
+        |(end, value)| (end.clone_ref(py), value.clone_ref(py)),
+    ))
+}
+
+fn check_range(start: &Elem, end: &Elem) -> PyResult<()> {
+    if start >= end {
+        return Err(PyErr::new::<exceptions::PyValueError, _>(
+            "range start must be less than its end",
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,495 @@
+use crate::bulk;
+use crate::elem::{self, Elem};
+use crate::iterators::{
+    Cursor, KeyRange, PyBTreeMapIter, PyBTreeMapKeys, PyBTreeMapValues, BATCH_SIZE,
+};
+use crate::tree_lock::TreeLock;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyFloat, PyInt, PyList, PyString, PyTuple, PyType};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+// The typed maps store their keys as plain Rust values instead of `Elem`s, so
+// comparisons skip the dispatch on the `Elem` variants and never call back
+// into Python. Values are still `Elem`s.
+
+/// A key type of the typed maps.
+pub trait TypedKey: Ord + Sized {
+    /// The Python type of the keys, named in the TypeError for other keys.
+    const TYPE_NAME: &'static str;
+
+    fn extract_key(ob: &Bound<'_, PyAny>) -> PyResult<Self>;
+
+    fn to_elem(&self) -> Elem;
+
+    /// Inverse of `to_elem`, for the positions kept by the iterators.
+    fn from_elem(elem: &Elem) -> Self;
+}
+
+fn mismatched_key<K: TypedKey>(ob: &Bound<'_, PyAny>) -> PyErr {
+    let name = ob
+        .get_type()
+        .name()
+        .map(|x| x.to_string())
+        .unwrap_or_default();
+    PyErr::new::<exceptions::PyTypeError, _>(format!(
+        "expected a key of type '{}', got '{name}'",
+        K::TYPE_NAME
+    ))
+}
+
+impl TypedKey for i64 {
+    const TYPE_NAME: &'static str = "int";
+
+    fn extract_key(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if !ob.is_instance_of::<PyInt>() {
+            return Err(mismatched_key::<Self>(ob));
+        }
+        ob.extract()
+    }
+
+    fn to_elem(&self) -> Elem {
+        Elem::Int(*self)
+    }
+
+    fn from_elem(elem: &Elem) -> Self {
+        match elem {
+            Elem::Int(x) => *x,
+            _ => unreachable!("int map position is not an int: {elem:?}"),
+        }
+    }
+}
+
+/// An `f64` with a total order: `-0.0` equals `0.0` like in Python, and NaNs
+/// are equal to each other and greater than every number.
+#[derive(Clone, Copy, Debug)]
+pub struct OrderedFloat(pub f64);
+
+impl PartialEq for OrderedFloat {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrderedFloat {}
+
+impl PartialOrd for OrderedFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderedFloat {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .partial_cmp(&other.0)
+            .unwrap_or_else(|| self.0.is_nan().cmp(&other.0.is_nan()))
+    }
+}
+
+impl<'py> IntoPyObject<'py> for &OrderedFloat {
+    type Target = PyFloat;
+    type Output = Bound<'py, PyFloat>;
+    type Error = Infallible;
+
+    fn into_pyobject(self, py: Python<'py>) -> Result<Self::Output, Self::Error> {
+        Ok(PyFloat::new(py, self.0))
+    }
+}
+
+impl TypedKey for OrderedFloat {
+    const TYPE_NAME: &'static str = "float";
+
+    /// Ints are accepted and stored as floats.
+    fn extract_key(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(x) = ob.downcast::<PyFloat>() {
+            Ok(OrderedFloat(x.value()))
+        } else if ob.is_instance_of::<PyInt>() {
+            Ok(OrderedFloat(ob.extract()?))
+        } else {
+            Err(mismatched_key::<Self>(ob))
+        }
+    }
+
+    fn to_elem(&self) -> Elem {
+        Elem::Float(self.0)
+    }
+
+    fn from_elem(elem: &Elem) -> Self {
+        match elem {
+            Elem::Float(x) => OrderedFloat(*x),
+            _ => unreachable!("float map position is not a float: {elem:?}"),
+        }
+    }
+}
+
+impl TypedKey for String {
+    const TYPE_NAME: &'static str = "str";
+
+    fn extract_key(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let ob = ob
+            .downcast::<PyString>()
+            .map_err(|_| mismatched_key::<Self>(ob))?;
+        Ok(ob.to_str()?.to_owned())
+    }
+
+    fn to_elem(&self) -> Elem {
+        Elem::String(self.as_str().into())
+    }
+
+    fn from_elem(elem: &Elem) -> Self {
+        match elem {
+            Elem::String(s) => s.to_string(),
+            _ => unreachable!("str map position is not a str: {elem:?}"),
+        }
+    }
+}
+
+/// The next batch of `btree_map` for a `Fetch`, with values mapped by `f`.
+fn batch<K: TypedKey, T>(
+    btree_map: &BTreeMap<K, Elem>,
+    (start, stop): KeyRange,
+    f: impl Fn(&Elem) -> T,
+) -> Vec<(Elem, T)> {
+    let range = (start.map(K::from_elem), stop.map(K::from_elem));
+    btree_map
+        .range(range)
+        .take(BATCH_SIZE)
+        .map(|(key, value)| (key.to_elem(), f(value)))
+        .collect()
+}
+
+macro_rules! typed_btree_map {
+    ($(#[$meta:meta])* $name:ident, $key:ty) => {
+        $(#[$meta])*
+        #[pyclass(module = "tree_collections.tree_collections", frozen)]
+        pub struct $name {
+            pub btree_map: TreeLock<BTreeMap<$key, Elem>>,
+        }
+
+        #[pymethods]
+        impl $name {
+            #[new]
+            #[pyo3(signature = (input=None))]
+            pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self> {
+                let items = match input {
+                    Some(input) => bulk::extract_pairs_with(input, py, <$key>::extract_key)?,
+                    None => Vec::new(),
+                };
+                let btree_map = elem::allow_threads(py, || bulk::build_map(items));
+
+                Ok(btree_map.into())
+            }
+
+            #[classmethod]
+            #[pyo3(signature = (input, validate=true))]
+            pub fn from_sorted(
+                _cls: &Bound<'_, PyType>,
+                input: PyObject,
+                validate: bool,
+                py: Python,
+            ) -> PyResult<Self> {
+                let items = bulk::extract_pairs_with(input, py, <$key>::extract_key)?;
+                let btree_map = elem::allow_threads(py, || {
+                    if validate {
+                        bulk::check_sorted(&items, |(key, _)| key)?;
+                    }
+                    PyResult::Ok(bulk::build_map(items))
+                })?;
+
+                Ok(btree_map.into())
+            }
+
+            pub fn insert(
+                &self,
+                py: Python,
+                key: PyObject,
+                value: PyObject,
+            ) -> PyResult<Option<Elem>> {
+                let key = <$key>::extract_key(key.bind(py))?;
+                let value = value.extract::<Elem>(py)?;
+
+                Ok(self.btree_map.write(py)?.insert(key, value))
+            }
+
+            pub fn get(&self, py: Python, key: PyObject) -> PyResult<Option<PyObject>> {
+                let key = <$key>::extract_key(key.bind(py))?;
+                let btree_map = self.btree_map.read(py)?;
+
+                Ok(btree_map.get(&key).map(|x| x.to_pyobject(py)))
+            }
+
+            pub fn remove(&self, py: Python, key: PyObject) -> PyResult<Option<Elem>> {
+                let key = <$key>::extract_key(key.bind(py))?;
+                Ok(self.btree_map.write(py)?.remove(&key))
+            }
+
+            pub fn contains_key(&self, py: Python, key: PyObject) -> PyResult<bool> {
+                let key = <$key>::extract_key(key.bind(py))?;
+                Ok(self.btree_map.read(py)?.contains_key(&key))
+            }
+
+            pub fn insert_many(&self, py: Python, input: PyObject) -> PyResult<Vec<Option<Elem>>> {
+                let items = bulk::extract_pairs_with(input, py, <$key>::extract_key)?;
+                let mut output = Vec::new();
+                output.resize_with(items.len(), || None);
+                let mut btree_map = self.btree_map.write(py)?;
+
+                for (i, (key, value)) in bulk::sort_batch(items, |(key, _)| key) {
+                    output[i] = btree_map.insert(key, value);
+                }
+
+                Ok(output)
+            }
+
+            pub fn update_many(&self, py: Python, input: PyObject) -> PyResult<()> {
+                let items = bulk::extract_pairs_with(input, py, <$key>::extract_key)?;
+                let mut btree_map = self.btree_map.write(py)?;
+
+                for (_, (key, value)) in bulk::sort_batch(items, |(key, _)| key) {
+                    btree_map.insert(key, value);
+                }
+
+                Ok(())
+            }
+
+            #[pyo3(signature = (keys, default=None))]
+            pub fn get_many(
+                &self,
+                py: Python,
+                keys: PyObject,
+                default: Option<PyObject>,
+            ) -> PyResult<Vec<Option<PyObject>>> {
+                let keys = bulk::extract_elems_with(keys, py, <$key>::extract_key)?;
+                let mut output = Vec::with_capacity(keys.len());
+                output.resize_with(keys.len(), || default.as_ref().map(|x| x.clone_ref(py)));
+                let btree_map = self.btree_map.read(py)?;
+
+                for (i, key) in bulk::sort_batch(keys, |key| key) {
+                    if let Some(value) = btree_map.get(&key) {
+                        output[i] = Some(value.to_pyobject(py));
+                    }
+                }
+
+                Ok(output)
+            }
+
+            pub fn contains_many(&self, py: Python, keys: PyObject) -> PyResult<Vec<bool>> {
+                let keys = bulk::extract_elems_with(keys, py, <$key>::extract_key)?;
+                let mut output = vec![false; keys.len()];
+                let btree_map = self.btree_map.read(py)?;
+
+                for (i, key) in bulk::sort_batch(keys, |key| key) {
+                    output[i] = btree_map.contains_key(&key);
+                }
+
+                Ok(output)
+            }
+
+            pub fn remove_many(&self, py: Python, keys: PyObject) -> PyResult<Vec<Option<Elem>>> {
+                let keys = bulk::extract_elems_with(keys, py, <$key>::extract_key)?;
+                let mut output = Vec::new();
+                output.resize_with(keys.len(), || None);
+                let mut btree_map = self.btree_map.write(py)?;
+
+                for (i, key) in bulk::sort_batch(keys, |key| key) {
+                    output[i] = btree_map.remove(&key);
+                }
+
+                Ok(output)
+            }
+
+            pub fn nth(&self, py: Python, mut n: i64) -> PyResult<Option<(PyObject, PyObject)>> {
+                let btree_map = self.btree_map.read(py)?;
+
+                if n >= btree_map.len() as i64 {
+                    return Ok(None);
+                }
+                if n < 0 {
+                    n += btree_map.len() as i64;
+                }
+                if n < 0 {
+                    return Ok(None);
+                }
+                let n = n as usize;
+
+                let output = if n == 0 {
+                    btree_map.first_key_value()
+                } else if n == btree_map.len() - 1 {
+                    btree_map.last_key_value()
+                } else {
+                    btree_map.iter().nth(n)
+                };
+
+                Ok(output.map(|(key, value)| (key.to_elem().to_pyobject(py), value.to_pyobject(py))))
+            }
+
+            pub fn len(&self, py: Python) -> PyResult<usize> {
+                Ok(self.btree_map.read(py)?.len())
+            }
+
+            pub fn is_empty(&self, py: Python) -> PyResult<bool> {
+                Ok(self.btree_map.read(py)?.is_empty())
+            }
+
+            pub fn clear(&self, py: Python) -> PyResult<()> {
+                self.btree_map.write(py)?.clear();
+                Ok(())
+            }
+
+            pub fn keys_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+                PyList::new(py, self.btree_map.read(py)?.keys())
+            }
+
+            pub fn values_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+                PyList::new(py, self.btree_map.read(py)?.values())
+            }
+
+            pub fn items_list<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyList>> {
+                PyList::new(py, self.btree_map.read(py)?.iter())
+            }
+
+            pub fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+                let dict = PyDict::new(py);
+                for (key, value) in self.btree_map.read(py)?.iter() {
+                    dict.set_item(key, value)?;
+                }
+                Ok(dict)
+            }
+
+            pub fn copy(&self, py: Python) -> PyResult<Self> {
+                Ok(self.btree_map.read(py)?.clone().into())
+            }
+
+            pub fn __copy__(&self, py: Python) -> PyResult<Self> {
+                self.copy(py)
+            }
+
+            pub fn __deepcopy__(&self, py: Python, memo: &Bound<'_, PyAny>) -> PyResult<Self> {
+                let btree_map = self
+                    .btree_map
+                    .read(py)?
+                    .iter()
+                    .map(|(key, value)| Ok((key.clone(), value.deepcopy(py, memo)?)))
+                    .collect::<PyResult<BTreeMap<_, _>>>()?;
+
+                Ok(btree_map.into())
+            }
+
+            /// Pickles as `(keys, values)` in key order.
+            pub fn __getstate__(&self, py: Python) -> PyResult<PyObject> {
+                let btree_map = self.btree_map.read(py)?;
+                let keys = PyList::new(py, btree_map.keys())?;
+                let values = PyList::new(py, btree_map.values())?;
+
+                Ok((keys, values).into_pyobject(py)?.into_any().unbind())
+            }
+
+            pub fn __setstate__(&self, py: Python, state: &Bound<'_, PyAny>) -> PyResult<()> {
+                let (keys, values) = state.extract::<(Vec<Bound<'_, PyAny>>, Vec<Elem>)>()?;
+                let keys = keys
+                    .iter()
+                    .map(|key| <$key>::extract_key(key))
+                    .collect::<PyResult<Vec<_>>>()?;
+                *self.btree_map.write(py)? = bulk::build_map(keys.into_iter().zip(values).collect());
+
+                Ok(())
+            }
+
+            pub fn __reduce__<'py>(
+                &self,
+                py: Python<'py>,
+            ) -> PyResult<(Bound<'py, PyType>, Bound<'py, PyTuple>, PyObject)> {
+                let cls = py.get_type::<Self>();
+                Ok((cls, PyTuple::empty(py), self.__getstate__(py)?))
+            }
+
+            pub fn keys(slf: &Bound<'_, Self>) -> PyBTreeMapKeys {
+                let owner = slf.clone().into_any().unbind();
+                PyBTreeMapKeys {
+                    cursor: Cursor::new(owner, Self::fetch_keys),
+                }
+            }
+
+            pub fn values(slf: &Bound<'_, Self>) -> PyBTreeMapValues {
+                let owner = slf.clone().into_any().unbind();
+                PyBTreeMapValues {
+                    cursor: Cursor::new(owner, Self::fetch_items),
+                }
+            }
+
+            pub fn items(slf: &Bound<'_, Self>) -> PyBTreeMapIter {
+                let owner = slf.clone().into_any().unbind();
+                PyBTreeMapIter {
+                    cursor: Cursor::new(owner, Self::fetch_items),
+                }
+            }
+        }
+
+        impl $name {
+            fn fetch_keys(py: Python, owner: &PyObject, range: KeyRange) -> PyResult<Vec<(Elem, ())>> {
+                let btree_map = owner.downcast_bound::<Self>(py)?.get().btree_map.read(py)?;
+                Ok(batch(&btree_map, range, |_| ()))
+            }
+
+            fn fetch_items(
+                py: Python,
+                owner: &PyObject,
+                range: KeyRange,
+            ) -> PyResult<Vec<(Elem, Elem)>> {
+                let btree_map = owner.downcast_bound::<Self>(py)?.get().btree_map.read(py)?;
+                Ok(batch(&btree_map, range, |value| value.clone_ref(py)))
+            }
+        }
+
+        impl From<BTreeMap<$key, Elem>> for $name {
+            fn from(btree_map: BTreeMap<$key, Elem>) -> Self {
+                $name {
+                    btree_map: TreeLock::new(btree_map),
+                }
+            }
+        }
+    };
+}
+
+typed_btree_map!(
+    /// A map restricted to `int` keys that fit in an `i64`.
+    PyIntBTreeMap,
+    i64
+);
+
+typed_btree_map!(
+    /// A map restricted to `float` keys, `int` keys are converted.
+    PyFloatBTreeMap,
+    OrderedFloat
+);
+
+typed_btree_map!(
+    /// A map restricted to `str` keys.
+    PyStrBTreeMap,
+    String
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordered_float() {
+        let mut floats = [2.0, f64::NAN, -1.0, f64::INFINITY, -0.0, 0.0]
+            .into_iter()
+            .map(OrderedFloat)
+            .collect::<Vec<_>>();
+        floats.sort();
+
+        assert_eq!(floats[0].0, -1.0);
+        assert_eq!(floats[1], OrderedFloat(0.0));
+        assert_eq!(floats[2], OrderedFloat(0.0));
+        assert_eq!(floats[3].0, 2.0);
+        assert_eq!(floats[4].0, f64::INFINITY);
+        assert!(floats[5].0.is_nan());
+        assert_eq!(OrderedFloat(f64::NAN), OrderedFloat(-f64::NAN));
+    }
+}
@@ -0,0 +1,549 @@
+use crate::calendar::{self, AwareDateTime};
+use crate::elem::Elem;
+use crate::interval_tree::IntervalTree;
+use crate::json::{self, MAX_SAFE_INT};
+use crate::number::{Decimal, Fraction};
+use crate::persistent_map::PersistentMap;
+use serde::de::{self, Deserialize, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Serde representation of `Elem`, chosen by the format.
+//
+// Human readable formats use the encoding of the `json` module, so the output
+// of `to_json()` on the Python side deserializes as-is:
+//   None -> unit, str -> string, list -> seq, int -> i64 or {"$int": "digits"}
+//   when out of the ±(2^53 - 1) range, float -> f64 or {"$float": "nan" |
+//   "inf" | "-inf"}, tuple -> {"$tuple": [...]}, bool -> bool, and bytes,
+//   dates, `Decimal` and `Fraction` -> {"$bytes" | "$date" | ...: "text"}
+// Compact formats (bincode, MessagePack) use an externally tagged enum named
+// `Elem` with the variants below, in this order. Dates are ordinals, times
+// microseconds, aware ones `(utc, offset)`, `Decimal` its `str()` and
+// `Fraction` a `(numerator, denominator)` pair.
+//
+// Tuples of two elements always deserialize as `TwoTuple`, as they do when
+// converted from Python, so ordering matches the Python side. Trees are
+// sequences of their entries in order, maps as `[key, value]` pairs.
+
+const VARIANTS: &[&str] = &[
+    "none",
+    "int",
+    "float",
+    "str",
+    "tuple",
+    "list",
+    "bool",
+    "bytes",
+    "date",
+    "datetime",
+    "aware_datetime",
+    "decimal",
+    "fraction",
+];
+
+impl Serialize for Elem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            if let Some((tag, s)) = json::to_tagged_str(self) {
+                return tagged(serializer, tag, &s);
+            }
+            return match self {
+                Elem::PyNone => serializer.serialize_unit(),
+                Elem::Int(x) if x.unsigned_abs() <= MAX_SAFE_INT => serializer.serialize_i64(*x),
+                Elem::Int(x) => tagged(serializer, "$int", &x.to_string()),
+                Elem::Float(x) if x.is_nan() => tagged(serializer, "$float", "nan"),
+                Elem::Float(x) if x.is_infinite() => {
+                    tagged(serializer, "$float", if *x > 0.0 { "inf" } else { "-inf" })
+                }
+                Elem::Float(x) => serializer.serialize_f64(*x),
+                Elem::String(s) => serializer.serialize_str(s),
+                // a slice, not an array, so that it is a sequence with a length
+                Elem::TwoTuple(pair) => tagged(serializer, "$tuple", &pair[..]),
+                Elem::Tuple(v) => tagged(serializer, "$tuple", &**v),
+                Elem::Vec(v) => v.serialize(serializer),
+                Elem::Bool(x) => serializer.serialize_bool(*x),
+                #[cfg(feature = "python")]
+                Elem::PyObj(obj) => Err(serde::ser::Error::custom(crate::binary::unserializable(
+                    obj,
+                ))),
+                Elem::Bytes(_)
+                | Elem::Date(_)
+                | Elem::DateTime(_)
+                | Elem::AwareDateTime(_)
+                | Elem::Decimal(_)
+                | Elem::Fraction(_) => unreachable!("serialized as tagged strings above"),
+            };
+        }
+
+        match self {
+            Elem::PyNone => serializer.serialize_unit_variant("Elem", 0, VARIANTS[0]),
+            Elem::Int(x) => serializer.serialize_newtype_variant("Elem", 1, VARIANTS[1], x),
+            Elem::Float(x) => serializer.serialize_newtype_variant("Elem", 2, VARIANTS[2], x),
+            Elem::String(s) => {
+                serializer.serialize_newtype_variant("Elem", 3, VARIANTS[3], s.as_str())
+            }
+            Elem::TwoTuple(pair) => {
+                serializer.serialize_newtype_variant("Elem", 4, VARIANTS[4], &pair[..])
+            }
+            Elem::Tuple(v) => serializer.serialize_newtype_variant("Elem", 4, VARIANTS[4], v),
+            Elem::Vec(v) => serializer.serialize_newtype_variant("Elem", 5, VARIANTS[5], v),
+            Elem::Bool(x) => serializer.serialize_newtype_variant("Elem", 6, VARIANTS[6], x),
+            Elem::Bytes(b) => {
+                serializer.serialize_newtype_variant("Elem", 7, VARIANTS[7], &Bytes(b))
+            }
+            Elem::Date(x) => serializer.serialize_newtype_variant("Elem", 8, VARIANTS[8], x),
+            Elem::DateTime(x) => serializer.serialize_newtype_variant("Elem", 9, VARIANTS[9], x),
+            Elem::AwareDateTime(x) => {
+                let value = (x.utc, x.offset);
+                serializer.serialize_newtype_variant("Elem", 10, VARIANTS[10], &value)
+            }
+            Elem::Decimal(x) => {
+                serializer.serialize_newtype_variant("Elem", 11, VARIANTS[11], &x.to_string())
+            }
+            Elem::Fraction(x) => {
+                let value = (x.num(), x.den());
+                serializer.serialize_newtype_variant("Elem", 12, VARIANTS[12], &value)
+            }
+            #[cfg(feature = "python")]
+            Elem::PyObj(obj) => Err(serde::ser::Error::custom(crate::binary::unserializable(
+                obj,
+            ))),
+        }
+    }
+}
+
+fn tagged<S: Serializer, T: Serialize + ?Sized>(
+    serializer: S,
+    tag: &str,
+    value: &T,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(tag, value)?;
+    map.end()
+}
+
+/// Bytes as a byte string rather than a sequence of integers.
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_byte_buf(ByteBufVisitor)
+    }
+}
+
+struct ByteBufVisitor;
+
+impl<'de> de::Visitor<'de> for ByteBufVisitor {
+    type Value = ByteBuf;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, b: &[u8]) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(b.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, b: Vec<u8>) -> Result<ByteBuf, E> {
+        Ok(ByteBuf(b))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(ByteBuf(bytes))
+    }
+}
+
+fn tuple(mut elems: Vec<Elem>) -> Elem {
+    if elems.len() == 2 {
+        let b = elems.pop().unwrap();
+        let a = elems.pop().unwrap();
+        Elem::pair(a, b)
+    } else {
+        Elem::tuple(elems)
+    }
+}
+
+impl<'de> Deserialize<'de> for Elem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(ElemVisitor)
+        } else {
+            deserializer.deserialize_enum("Elem", VARIANTS, ElemVisitor)
+        }
+    }
+}
+
+struct ElemVisitor;
+
+impl<'de> de::Visitor<'de> for ElemVisitor {
+    type Value = Elem;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an int, float, str, tuple, list, bool or None")
+    }
+
+    fn visit_bool<E: de::Error>(self, x: bool) -> Result<Elem, E> {
+        Ok(Elem::Bool(x))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Elem, E> {
+        Ok(Elem::PyNone)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Elem, E> {
+        Ok(Elem::PyNone)
+    }
+
+    fn visit_i64<E: de::Error>(self, x: i64) -> Result<Elem, E> {
+        Ok(Elem::Int(x))
+    }
+
+    fn visit_u64<E: de::Error>(self, x: u64) -> Result<Elem, E> {
+        i64::try_from(x)
+            .map(Elem::Int)
+            .map_err(|_| E::custom(format!("int {x} is out of range")))
+    }
+
+    fn visit_f64<E: de::Error>(self, x: f64) -> Result<Elem, E> {
+        Ok(Elem::Float(x))
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Elem, E> {
+        Ok(Elem::String(s.into()))
+    }
+
+    fn visit_string<E: de::Error>(self, s: String) -> Result<Elem, E> {
+        Ok(Elem::String(s.into()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Elem, A::Error> {
+        let mut elems = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(elem) = seq.next_element()? {
+            elems.push(elem);
+        }
+        Ok(Elem::list(elems))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Elem, A::Error> {
+        let tag = map
+            .next_key::<String>()?
+            .ok_or_else(|| de::Error::custom("expected a tagged object, found {}"))?;
+        let elem = match tag.as_str() {
+            "$tuple" => tuple(map.next_value()?),
+            "$int" => {
+                let digits = map.next_value::<String>()?;
+                let x = digits
+                    .parse()
+                    .map_err(|_| de::Error::custom(format!("invalid $int '{digits}'")))?;
+                Elem::Int(x)
+            }
+            "$float" => match map.next_value::<String>()?.as_str() {
+                "nan" => Elem::Float(f64::NAN),
+                "inf" => Elem::Float(f64::INFINITY),
+                "-inf" => Elem::Float(f64::NEG_INFINITY),
+                other => return Err(de::Error::custom(format!("invalid $float '{other}'"))),
+            },
+            "$bytes" | "$date" | "$datetime" | "$decimal" | "$fraction" => {
+                let s = map.next_value::<String>()?;
+                json::from_tagged_str(&tag, &s)
+                    .ok_or_else(|| de::Error::custom(format!("invalid {tag} '{s}'")))?
+            }
+            other => return Err(de::Error::custom(format!("unknown tag '{other}'"))),
+        };
+        if map.next_key::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::custom(format!(
+                "expected only '{tag}' in tagged object"
+            )));
+        }
+        Ok(elem)
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Elem, A::Error> {
+        let (tag, variant) = data.variant::<Tag>()?;
+        match tag {
+            Tag::None => variant.unit_variant().map(|_| Elem::PyNone),
+            Tag::Int => variant.newtype_variant().map(Elem::Int),
+            Tag::Float => variant.newtype_variant().map(Elem::Float),
+            Tag::Str => variant
+                .newtype_variant::<String>()
+                .map(|s| Elem::String(s.into())),
+            Tag::Tuple => variant.newtype_variant().map(tuple),
+            Tag::List => variant.newtype_variant().map(Elem::Vec),
+            Tag::Bool => variant.newtype_variant().map(Elem::Bool),
+            Tag::Bytes => variant
+                .newtype_variant::<ByteBuf>()
+                .map(|b| Elem::bytes(b.0)),
+            Tag::Date => {
+                let x = variant.newtype_variant()?;
+                if !calendar::is_valid_date(x) {
+                    return Err(de::Error::custom(format!("invalid date {x}")));
+                }
+                Ok(Elem::Date(x))
+            }
+            Tag::DateTime => {
+                let x = variant.newtype_variant()?;
+                if !calendar::is_valid_datetime(x) {
+                    return Err(de::Error::custom(format!("invalid datetime {x}")));
+                }
+                Ok(Elem::DateTime(x))
+            }
+            Tag::AwareDateTime => {
+                let (utc, offset) = variant.newtype_variant::<(i64, i32)>()?;
+                let x = AwareDateTime { utc, offset };
+                if !calendar::is_valid_offset(offset)
+                    || !calendar::is_valid_datetime(utc.saturating_add(offset as i64 * 1_000_000))
+                {
+                    return Err(de::Error::custom(format!("invalid datetime {x:?}")));
+                }
+                Ok(Elem::AwareDateTime(Box::new(x)))
+            }
+            Tag::Decimal => {
+                let s = variant.newtype_variant::<String>()?;
+                match Decimal::parse(&s) {
+                    Some(x) => Ok(Elem::Decimal(Box::new(x))),
+                    None => Err(de::Error::custom(format!("invalid decimal '{s}'"))),
+                }
+            }
+            Tag::Fraction => {
+                let (num, den) = variant.newtype_variant::<(i64, i64)>()?;
+                match Fraction::new(num, den) {
+                    Some(x) => Ok(Elem::Fraction(Box::new(x))),
+                    None => Err(de::Error::custom(format!("invalid fraction {num}/{den}"))),
+                }
+            }
+        }
+    }
+}
+
+enum Tag {
+    None,
+    Int,
+    Float,
+    Str,
+    Tuple,
+    List,
+    Bool,
+    Bytes,
+    Date,
+    DateTime,
+    AwareDateTime,
+    Decimal,
+    Fraction,
+}
+
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_identifier(TagVisitor)
+    }
+}
+
+struct TagVisitor;
+
+impl<'de> de::Visitor<'de> for TagVisitor {
+    type Value = Tag;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an Elem variant")
+    }
+
+    fn visit_u64<E: de::Error>(self, index: u64) -> Result<Tag, E> {
+        match VARIANTS.get(index as usize) {
+            Some(name) => self.visit_str(name),
+            None => Err(E::invalid_value(de::Unexpected::Unsigned(index), &self)),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<Tag, E> {
+        match name {
+            "none" => Ok(Tag::None),
+            "int" => Ok(Tag::Int),
+            "float" => Ok(Tag::Float),
+            "str" => Ok(Tag::Str),
+            "tuple" => Ok(Tag::Tuple),
+            "list" => Ok(Tag::List),
+            "bool" => Ok(Tag::Bool),
+            "bytes" => Ok(Tag::Bytes),
+            "date" => Ok(Tag::Date),
+            "datetime" => Ok(Tag::DateTime),
+            "aware_datetime" => Ok(Tag::AwareDateTime),
+            "decimal" => Ok(Tag::Decimal),
+            "fraction" => Ok(Tag::Fraction),
+            _ => Err(E::unknown_variant(name, VARIANTS)),
+        }
+    }
+}
+
+/// Serializes a `BTreeMap<Elem, Elem>` as `[key, value]` pairs, for use with
+/// `#[serde(with = "tree_collections::serde_impls::map")]`. Formats like JSON
+/// can't have `Elem` keys in a map.
+pub mod map {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        btree_map: &BTreeMap<Elem, Elem>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(btree_map.iter())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<Elem, Elem>, D::Error> {
+        let entries = Vec::<(Elem, Elem)>::deserialize(deserializer)?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
+impl Serialize for PersistentMap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.len()))?;
+        for entry in self.iter() {
+            seq.serialize_element(&entry)?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for PersistentMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(map::deserialize(deserializer)?.into())
+    }
+}
+
+/// Intervals are `[start, end, value]` triples ordered by `(start, end)`.
+impl Serialize for IntervalTree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            self.iter()
+                .into_iter()
+                .map(|x| (&x.start, &x.end, &x.value)),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for IntervalTree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut tree = IntervalTree::new();
+        for (start, end, value) in Vec::<(Elem, Elem, Elem)>::deserialize(deserializer)? {
+            tree.insert(start, end, value);
+        }
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn sample() -> BTreeMap<Elem, Elem> {
+        BTreeMap::from([
+            (Elem::Int(-(1 << 60)), Elem::PyNone),
+            (Elem::Int(1), Elem::Float(f64::INFINITY)),
+            (Elem::Int(2), Elem::String("zwei".into())),
+            (Elem::Int(3), Elem::pair(Elem::Int(1), Elem::Float(0.5))),
+            (
+                Elem::Int(4),
+                Elem::tuple(vec![Elem::Int(1), Elem::Int(2), Elem::Int(3)]),
+            ),
+            (
+                Elem::Int(5),
+                Elem::list(vec![Elem::list(vec![]), Elem::PyNone]),
+            ),
+            (Elem::Int(6), Elem::Bool(true)),
+            (Elem::Int(7), Elem::bytes(vec![0, 1, 255])),
+            (Elem::Int(8), Elem::Date(739_000)),
+            (
+                Elem::Int(9),
+                Elem::AwareDateTime(Box::new(AwareDateTime {
+                    utc: 63_850_000_000_000_000,
+                    offset: 19_800,
+                })),
+            ),
+            (
+                Elem::Int(10),
+                Elem::Decimal(Box::new(Decimal::parse("-0.001").unwrap())),
+            ),
+            (
+                Elem::Int(11),
+                Elem::Fraction(Box::new(Fraction::new(22, 7).unwrap())),
+            ),
+        ])
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Snapshot {
+        #[serde(with = "map")]
+        entries: BTreeMap<Elem, Elem>,
+    }
+
+    #[test]
+    fn test_json_matches_json_module() {
+        let btree_map = sample();
+        let expected = crate::json::write_map(Vec::new(), &btree_map).unwrap();
+        let expected = String::from_utf8(expected).unwrap();
+
+        let output = serde_json::to_string(&PersistentMap::from(btree_map.clone())).unwrap();
+        assert_eq!(output, expected);
+
+        let output = serde_json::from_str::<PersistentMap>(&expected).unwrap();
+        assert_eq!(output.iter().count(), btree_map.len());
+        assert!(output.iter().eq(btree_map.iter()));
+
+        let elems =
+            serde_json::from_str::<BTreeSet<Elem>>(r#"[{"$tuple":[2,"b"]},{"$tuple":[1,"a"]}]"#)
+                .unwrap();
+        assert_eq!(
+            elems.into_iter().next(),
+            Some(Elem::pair(Elem::Int(1), Elem::String("a".into())))
+        );
+    }
+
+    #[test]
+    fn test_compact_formats() {
+        let snapshot = Snapshot { entries: sample() };
+
+        let bytes = bincode::serialize(&snapshot).unwrap();
+        assert_eq!(bincode::deserialize::<Snapshot>(&bytes).unwrap(), snapshot);
+
+        let bytes = rmp_serde::to_vec(&snapshot).unwrap();
+        assert_eq!(rmp_serde::from_slice::<Snapshot>(&bytes).unwrap(), snapshot);
+
+        let mut tree = IntervalTree::new();
+        tree.insert(Elem::Int(5), Elem::Int(9), Elem::String("b".into()));
+        tree.insert(Elem::Int(1), Elem::Int(3), Elem::PyNone);
+        let bytes = bincode::serialize(&tree).unwrap();
+        let output = bincode::deserialize::<IntervalTree>(&bytes).unwrap();
+        let starts = output
+            .iter()
+            .iter()
+            .map(|x| x.start.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![Elem::Int(1), Elem::Int(5)]);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(serde_json::from_str::<Elem>(r#"{"$set":[]}"#).is_err());
+        assert!(serde_json::from_str::<Elem>(r#"{"$float":"big"}"#).is_err());
+        assert!(serde_json::from_str::<Elem>(r#"{"$tuple":[],"x":1}"#).is_err());
+        assert!(serde_json::from_str::<Elem>("18446744073709551615").is_err());
+        assert_eq!(
+            serde_json::from_str::<Elem>(r#"{"$int":"-9223372036854775808"}"#).unwrap(),
+            Elem::Int(i64::MIN)
+        );
+    }
+}
@@ -0,0 +1,133 @@
+//! An immutable string that stores up to `INLINE_CAP` bytes inline and longer
+//! strings behind a single thin pointer, so that it takes 16 bytes instead of
+//! the 24 of a `String` and most keys need no allocation at all.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+const INLINE_CAP: usize = 14;
+
+#[derive(Clone)]
+pub struct SmallStr(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Inline { len: u8, bytes: [u8; INLINE_CAP] },
+    // boxed twice so that the pointer is thin
+    Heap(Box<Box<str>>),
+}
+
+impl SmallStr {
+    pub fn as_str(&self) -> &str {
+        match &self.0 {
+            // only ever filled from a `&str` cut at its length
+            Repr::Inline { len, bytes } => std::str::from_utf8(&bytes[..*len as usize]).unwrap(),
+            Repr::Heap(s) => s,
+        }
+    }
+}
+
+impl From<&str> for SmallStr {
+    fn from(s: &str) -> Self {
+        if s.len() <= INLINE_CAP {
+            let mut bytes = [0; INLINE_CAP];
+            bytes[..s.len()].copy_from_slice(s.as_bytes());
+            SmallStr(Repr::Inline {
+                len: s.len() as u8,
+                bytes,
+            })
+        } else {
+            SmallStr(Repr::Heap(Box::new(s.into())))
+        }
+    }
+}
+
+impl From<String> for SmallStr {
+    fn from(s: String) -> Self {
+        if s.len() <= INLINE_CAP {
+            SmallStr::from(s.as_str())
+        } else {
+            SmallStr(Repr::Heap(Box::new(s.into_boxed_str())))
+        }
+    }
+}
+
+impl Deref for SmallStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for SmallStr {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for SmallStr {}
+
+impl PartialOrd for SmallStr {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SmallStr {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // byte order is the order of `str`
+        self.as_bytes().cmp(other.as_bytes())
+    }
+}
+
+impl Hash for SmallStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Debug for SmallStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_and_heap() {
+        for s in [
+            "",
+            "a",
+            "héllo",
+            "fourteen bytes",
+            "fifteen bytes!!",
+            "\u{1f600}".repeat(10).as_str(),
+        ] {
+            let small = SmallStr::from(s);
+            assert_eq!(small.as_str(), s);
+            assert_eq!(SmallStr::from(s.to_string()).as_str(), s);
+            assert_eq!(
+                matches!(small.0, Repr::Inline { .. }),
+                s.len() <= INLINE_CAP
+            );
+        }
+        assert_eq!(std::mem::size_of::<SmallStr>(), 16);
+    }
+
+    #[test]
+    fn test_order() {
+        let mut strs = ["b", "a long string on the heap", "ab", "", "a"]
+            .map(SmallStr::from)
+            .to_vec();
+        strs.sort();
+
+        let strs = strs.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+        assert_eq!(strs, ["", "a", "a long string on the heap", "ab", "b"]);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::elem::{self, Attached};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+thread_local! {
+    /// Addresses of the locks held by the current thread, used to turn a
+    /// reentrant acquisition (e.g. a merge callback touching the tree being
+    /// merged) into an error instead of a deadlock.
+    static HELD: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Guards the contents of a tree shared between Python threads. Any number
+/// of readers may hold it at once while a writer has it to itself. A thread
+/// blocked on the lock releases the GIL (or detaches from the interpreter on
+/// a free-threaded build) so that the holder can make progress. The guards
+/// keep comparisons of `PyObj` keys on the token the caller already has.
+pub struct TreeLock<T> {
+    lock: RwLock<T>,
+}
+
+pub struct TreeReadGuard<'a, T> {
+    guard: RwLockReadGuard<'a, T>,
+    _held: Held,
+    _attached: Attached,
+}
+
+pub struct TreeWriteGuard<'a, T> {
+    guard: RwLockWriteGuard<'a, T>,
+    _held: Held,
+    _attached: Attached,
+}
+
+struct Held(usize);
+
+impl Held {
+    fn acquire(id: usize) -> PyResult<Self> {
+        HELD.with(|held| {
+            let mut held = held.borrow_mut();
+            if held.contains(&id) {
+                return Err(PyErr::new::<exceptions::PyRuntimeError, _>(
+                    "tree is already in use by the current thread",
+                ));
+            }
+            held.push(id);
+            Ok(Held(id))
+        })
+    }
+}
+
+impl Drop for Held {
+    fn drop(&mut self) {
+        HELD.with(|held| {
+            let mut held = held.borrow_mut();
+            if let Some(i) = held.iter().rposition(|&id| id == self.0) {
+                held.swap_remove(i);
+            }
+        });
+    }
+}
+
+impl<T: Send + Sync> TreeLock<T> {
+    pub fn new(value: T) -> Self {
+        TreeLock {
+            lock: RwLock::new(value),
+        }
+    }
+
+    fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// A panic while the lock was held leaves the tree as consistent as it
+    /// was without the lock, so poisoning is ignored throughout.
+    pub fn read(&self, py: Python) -> PyResult<TreeReadGuard<'_, T>> {
+        let held = Held::acquire(self.id())?;
+        loop {
+            match self.lock.try_read() {
+                Ok(guard) => {
+                    return Ok(TreeReadGuard {
+                        guard,
+                        _held: held,
+                        _attached: elem::attach(py),
+                    })
+                }
+                Err(TryLockError::Poisoned(err)) => {
+                    return Ok(TreeReadGuard {
+                        guard: err.into_inner(),
+                        _held: held,
+                        _attached: elem::attach(py),
+                    })
+                }
+                Err(TryLockError::WouldBlock) => elem::allow_threads(py, || drop(self.lock.read())),
+            }
+        }
+    }
+
+    pub fn write(&self, py: Python) -> PyResult<TreeWriteGuard<'_, T>> {
+        let held = Held::acquire(self.id())?;
+        loop {
+            match self.lock.try_write() {
+                Ok(guard) => {
+                    return Ok(TreeWriteGuard {
+                        guard,
+                        _held: held,
+                        _attached: elem::attach(py),
+                    })
+                }
+                Err(TryLockError::Poisoned(err)) => {
+                    return Ok(TreeWriteGuard {
+                        guard: err.into_inner(),
+                        _held: held,
+                        _attached: elem::attach(py),
+                    })
+                }
+                Err(TryLockError::WouldBlock) => {
+                    elem::allow_threads(py, || drop(self.lock.write()))
+                }
+            }
+        }
+    }
+
+    /// Write-locks two trees in address order so that concurrent merges in
+    /// opposite directions cannot deadlock.
+    pub fn write_pair<'a>(
+        &'a self,
+        other: &'a Self,
+        py: Python,
+    ) -> PyResult<(TreeWriteGuard<'a, T>, TreeWriteGuard<'a, T>)> {
+        if self.id() < other.id() {
+            let first = self.write(py)?;
+            Ok((first, other.write(py)?))
+        } else {
+            let second = other.write(py)?;
+            Ok((self.write(py)?, second))
+        }
+    }
+}
+
+impl<T> Deref for TreeReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> Deref for TreeWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for TreeWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
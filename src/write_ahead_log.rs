@@ -0,0 +1,349 @@
+use crate::binary::{self, Crc32, FormatError, Result};
+use crate::elem::Elem;
+#[cfg(feature = "python")]
+use pyo3::{exceptions, prelude::*};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// Append-only log of map updates, replayed on top of the last snapshot.
+//
+// header: magic b"TCWL" | version: u16 | reserved: u16
+// records: length: u32 | crc32 of payload: u32 | payload
+// payload: op: u8, then elems in the binary module's encoding
+//   1 insert (key, value) | 2 remove (key) | 3 clear
+//
+// Compaction saves the map to `<log>.snapshot` in the binary format and then
+// empties the log. Replaying a log over a snapshot that already includes it
+// gives the same map, so a crash between the two steps is harmless.
+
+const MAGIC: &[u8; 4] = b"TCWL";
+const VERSION: u16 = 1;
+const HEADER_LEN: u64 = 8;
+const INSERT: u8 = 1;
+const REMOVE: u8 = 2;
+const CLEAR: u8 = 3;
+
+pub enum Record<'a> {
+    Insert(&'a Elem, &'a Elem),
+    Remove(&'a Elem),
+    Clear,
+}
+
+/// When appended records are fsynced. `Interval` syncs on the first append
+/// after the interval has passed, so an idle log may stay unsynced longer.
+#[derive(Clone, Copy)]
+pub enum SyncPolicy {
+    Always,
+    Interval(Duration),
+    Never,
+}
+
+#[cfg(feature = "python")]
+impl FromPyObject<'_> for SyncPolicy {
+    fn extract_bound(ob: &Bound<'_, PyAny>) -> PyResult<Self> {
+        if let Ok(seconds) = ob.extract::<f64>() {
+            return Duration::try_from_secs_f64(seconds)
+                .map(SyncPolicy::Interval)
+                .map_err(|_| {
+                    PyErr::new::<exceptions::PyValueError, _>(
+                        "sync interval must be a non-negative number of seconds",
+                    )
+                });
+        }
+        match ob.extract::<String>()?.as_str() {
+            "always" => Ok(SyncPolicy::Always),
+            "never" => Ok(SyncPolicy::Never),
+            name => Err(PyErr::new::<exceptions::PyValueError, _>(format!(
+                "unknown sync policy '{name}', expected 'always', 'never' or seconds"
+            ))),
+        }
+    }
+}
+
+pub struct WriteAheadLog {
+    path: PathBuf,
+    file: File,
+    sync: SyncPolicy,
+    last_sync: Instant,
+    /// Records appended since the last compaction.
+    records: usize,
+    compact_every: Option<usize>,
+}
+
+impl WriteAheadLog {
+    /// Rebuilds the map from the snapshot and log at `path`, dropping a torn
+    /// final record, and opens the log for appending.
+    pub fn recover<P: AsRef<Path>>(
+        path: P,
+        sync: SyncPolicy,
+        compact_every: Option<usize>,
+    ) -> Result<(Self, BTreeMap<Elem, Elem>)> {
+        let path = path.as_ref().to_path_buf();
+        let snapshot = snapshot_path(&path);
+
+        let mut btree_map = match File::open(&snapshot) {
+            Ok(file) => binary::read_map(BufReader::new(file))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let records = replay(&mut file, &mut btree_map)?;
+
+        let log = WriteAheadLog {
+            path,
+            file,
+            sync,
+            last_sync: Instant::now(),
+            records,
+            compact_every,
+        };
+        Ok((log, btree_map))
+    }
+
+    /// Fails without writing anything if the record can't be encoded.
+    pub fn append(&mut self, record: Record) -> Result<()> {
+        let mut payload = Vec::new();
+        match record {
+            Record::Insert(key, value) => {
+                payload.push(INSERT);
+                binary::encode_elem(&mut payload, key)?;
+                binary::encode_elem(&mut payload, value)?;
+            }
+            Record::Remove(key) => {
+                payload.push(REMOVE);
+                binary::encode_elem(&mut payload, key)?;
+            }
+            Record::Clear => payload.push(CLEAR),
+        }
+
+        let mut crc = Crc32::new();
+        crc.update(&payload);
+        let mut buf = Vec::with_capacity(payload.len() + 8);
+        buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&crc.finish().to_le_bytes());
+        buf.extend_from_slice(&payload);
+        // a single write so a crash leaves at most a torn final record
+        self.file.write_all(&buf)?;
+        self.records += 1;
+
+        match self.sync {
+            SyncPolicy::Always => self.sync()?,
+            SyncPolicy::Interval(interval) if self.last_sync.elapsed() >= interval => {
+                self.sync()?
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Whether `compact_every` records were appended since the last compaction.
+    pub fn needs_compaction(&self) -> bool {
+        self.compact_every
+            .is_some_and(|compact_every| self.records >= compact_every)
+    }
+
+    /// Replaces the snapshot with `entries` and empties the log.
+    pub fn compact<'a>(
+        &mut self,
+        entries: impl IntoIterator<Item = (&'a Elem, &'a Elem), IntoIter: ExactSizeIterator>,
+    ) -> Result<()> {
+        let snapshot = snapshot_path(&self.path);
+        let mut temp = OsString::from(snapshot.as_os_str());
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        binary::save(&temp, |writer| binary::write_map(writer, entries))?;
+        fs::rename(&temp, &snapshot)?;
+        // makes the rename durable, not supported on every platform
+        if let Some(dir) = snapshot.parent().and_then(|x| File::open(x).ok()) {
+            let _ = dir.sync_all();
+        }
+
+        self.file.set_len(HEADER_LEN)?;
+        self.sync()?;
+        self.records = 0;
+        Ok(())
+    }
+}
+
+/// Returns the snapshot path for the log at `path`, `<path>.snapshot`.
+pub fn snapshot_path(path: &Path) -> PathBuf {
+    let mut snapshot = OsString::from(path.as_os_str());
+    snapshot.push(".snapshot");
+    PathBuf::from(snapshot)
+}
+
+/// Fails if `elem` can't be written to a log.
+pub fn check_serializable(elem: &Elem) -> Result<()> {
+    binary::encode_elem(&mut Vec::new(), elem)
+}
+
+/// Applies the records in `file` to `btree_map`, truncating a torn final
+/// record, and returns how many were applied.
+fn replay(file: &mut File, btree_map: &mut BTreeMap<Elem, Elem>) -> Result<usize> {
+    let len = file.metadata()?.len();
+    if len < HEADER_LEN {
+        // a new log, or one torn while its header was written
+        file.set_len(0)?;
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&[0, 0]);
+        file.write_all(&header)?;
+        file.sync_all()?;
+        return Ok(0);
+    }
+
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(&mut *file);
+    let mut header = [0u8; HEADER_LEN as usize];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(FormatError::Invalid(
+            "not a tree_collections log".to_string(),
+        ));
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if version != VERSION {
+        return Err(FormatError::Invalid(format!(
+            "unsupported log version {version}, expected {VERSION}"
+        )));
+    }
+
+    let mut offset = HEADER_LEN;
+    let mut records = 0;
+    while offset < len {
+        let mut frame = [0u8; 8];
+        if len - offset < 8 {
+            break;
+        }
+        reader.read_exact(&mut frame)?;
+        let size = u32::from_le_bytes(frame[..4].try_into().unwrap()) as u64;
+        let checksum = u32::from_le_bytes(frame[4..].try_into().unwrap());
+        let end = offset + 8 + size;
+        if end > len {
+            break;
+        }
+
+        let mut payload = vec![0u8; size as usize];
+        reader.read_exact(&mut payload)?;
+        let mut crc = Crc32::new();
+        crc.update(&payload);
+        if crc.finish() != checksum {
+            if end == len {
+                break;
+            }
+            return Err(FormatError::Invalid(format!(
+                "log is corrupted at offset {offset}"
+            )));
+        }
+
+        apply(&payload, btree_map)
+            .ok_or_else(|| FormatError::Invalid(format!("log is corrupted at offset {offset}")))?;
+        offset = end;
+        records += 1;
+    }
+
+    drop(reader);
+    if offset < len {
+        file.set_len(offset)?;
+        file.sync_all()?;
+    }
+    Ok(records)
+}
+
+fn apply(mut payload: &[u8], btree_map: &mut BTreeMap<Elem, Elem>) -> Option<()> {
+    let (op, rest) = payload.split_first()?;
+    payload = rest;
+
+    match *op {
+        INSERT => {
+            let key = binary::decode_elem(&mut payload).ok()?;
+            let value = binary::decode_elem(&mut payload).ok()?;
+            btree_map.insert(key, value);
+        }
+        REMOVE => {
+            let key = binary::decode_elem(&mut payload).ok()?;
+            btree_map.remove(&key);
+        }
+        CLEAR => btree_map.clear(),
+        _ => return None,
+    }
+
+    payload.is_empty().then_some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(snapshot_path(&path));
+        path
+    }
+
+    #[test]
+    fn test_recover() {
+        let path = temp_path("test_recover");
+
+        let (mut log, btree_map) = WriteAheadLog::recover(&path, SyncPolicy::Never, None).unwrap();
+        assert!(btree_map.is_empty());
+        for i in 0..10 {
+            log.append(Record::Insert(&Elem::Int(i), &Elem::Int(i * i)))
+                .unwrap();
+        }
+        log.append(Record::Remove(&Elem::Int(3))).unwrap();
+        let mut expected = (0..10)
+            .filter(|x| *x != 3)
+            .map(|x| (Elem::Int(x), Elem::Int(x * x)))
+            .collect::<BTreeMap<_, _>>();
+        log.compact(&expected).unwrap();
+
+        log.append(Record::Clear).unwrap();
+        log.append(Record::Insert(&Elem::Int(1), &Elem::PyNone))
+            .unwrap();
+        log.append(Record::Insert(&Elem::Int(2), &Elem::PyNone))
+            .unwrap();
+        drop(log);
+        expected = BTreeMap::from([(Elem::Int(1), Elem::PyNone), (Elem::Int(2), Elem::PyNone)]);
+
+        let (_, btree_map) = WriteAheadLog::recover(&path, SyncPolicy::Never, None).unwrap();
+        assert_eq!(btree_map, expected);
+
+        // a torn final record is dropped and truncated away
+        let len = fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(len - 1).unwrap();
+        drop(file);
+        let (mut log, btree_map) = WriteAheadLog::recover(&path, SyncPolicy::Never, None).unwrap();
+        assert_eq!(btree_map.len(), 1);
+        log.append(Record::Insert(&Elem::Int(5), &Elem::PyNone))
+            .unwrap();
+        drop(log);
+        let (_, btree_map) = WriteAheadLog::recover(&path, SyncPolicy::Never, None).unwrap();
+        assert_eq!(
+            btree_map.keys().collect::<Vec<_>>(),
+            vec![&Elem::Int(1), &Elem::Int(5)]
+        );
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(snapshot_path(&path)).unwrap();
+    }
+}
@@ -0,0 +1,154 @@
+import math
+import random
+
+import pytest
+import tree_collections as tc
+
+
+def brute_force(items, start, stop, f):
+  values = [v for k, v in items.items() if start <= k < stop]
+  return f(values)
+
+
+class TestAggregate:
+
+  def test_sum(self):
+    tree = tc.TreeDict({1: 10, 2: 20, 3: 30, 4: 40}, aggregate="sum")
+
+    assert tree.aggregate == "sum"
+    assert tree.range_aggregate(2, 4) == 50
+    assert tree.range_aggregate() == 100
+    assert tree.range_aggregate(start=3) == 70
+    assert tree.range_aggregate(stop=3) == 30
+    assert tree.prefix_aggregate(3) == 30
+    assert tree.range_aggregate(10, 20) == 0
+    assert tree.range_aggregate(4, 2) == 0
+
+  def test_min_max(self):
+    items = {1: 5.0, 2: -1, 3: 7, 4: 2.5}
+    low = tc.TreeDict(items, aggregate="min")
+    high = tc.TreeDict(items, aggregate="max")
+
+    assert low.range_aggregate() == -1
+    assert low.range_aggregate(3, 5) == 2.5
+    assert high.range_aggregate() == 7
+    assert high.prefix_aggregate(3) == 5.0
+    assert low.range_aggregate(10, 20) is None
+    assert high.prefix_aggregate(0) is None
+
+  def test_count(self):
+    tree = tc.TreeDict({"a": "x", "b": None, "c": [1, 2]}, aggregate="count")
+
+    assert tree.range_aggregate("a", "c") == 2
+    assert tree.prefix_aggregate("z") == 3
+
+  def test_updates(self):
+    tree = tc.TreeDict(aggregate="sum")
+    tree[1] = 1
+    tree[2] = 2
+    tree[3] = 3
+    tree[2] = 20
+    assert tree.range_aggregate() == 24
+
+    del tree[1]
+    assert tree.range_aggregate() == 23
+
+    tree.insert_many([(4, 4), (5, 5)])
+    tree.remove_many([3, 10])
+    assert tree.range_aggregate() == 29
+
+    tree.clear()
+    assert tree.range_aggregate() == 0
+
+  def test_split_and_merge(self):
+    tree = tc.TreeDict({i: i for i in range(10)}, aggregate="sum")
+    right = tree.split_off(5)
+
+    assert right.aggregate == "sum"
+    assert tree.range_aggregate() == 10
+    assert right.range_aggregate() == 35
+
+    tree.merge(tc.TreeDict({4: 100, 20: 1}), on_conflict=lambda k, a, b: a + b)
+    assert tree.range_aggregate() == 111
+
+    tree.append(right)
+    assert tree.range_aggregate() == 146
+    assert right.range_aggregate() == 0
+
+    low = tc.TreeDict({-2: 7, -1: -3}, aggregate="max")
+    tree.append(low)
+    assert tree.range_aggregate() == 150
+    assert tree.range_aggregate(stop=0) == 4
+    assert low.aggregate == "max"
+    assert low.range_aggregate() is None
+
+  def test_exact_sums(self):
+    tree = tc.TreeDict({1: 2**62, 2: 2**62, 3: 2**62}, aggregate="sum")
+    assert tree.range_aggregate() == 3 * 2**62
+    del tree[2]
+    assert tree.range_aggregate() == 2**63
+
+    tree = tc.TreeDict({1: 1e20, 2: 1.0, 3: -1e20}, aggregate="sum")
+    assert tree.range_aggregate() == 1.0
+    tree[4] = 2**62
+    assert tree.range_aggregate() == float(2**62 + 1)
+
+  def test_nan(self):
+    nan = float("nan")
+    low = tc.TreeDict({1: 1.0, 2: nan, 3: -1.0}, aggregate="min")
+    high = tc.TreeDict({1: 1.0, 2: nan, 3: -1.0}, aggregate="max")
+
+    assert math.isnan(low.range_aggregate())
+    assert math.isnan(high.prefix_aggregate(3))
+    assert low.range_aggregate(start=3) == -1.0
+    assert high.prefix_aggregate(2) == 1.0
+
+  def test_invalid_values(self):
+    tree = tc.TreeDict({1: 1}, aggregate="sum")
+
+    with pytest.raises(TypeError):
+      tree[2] = "two"
+    with pytest.raises(TypeError):
+      tree.insert_many([(3, 3), (4, "four")])
+    with pytest.raises(TypeError):
+      tree.merge(tc.TreeDict({5: "five"}))
+    with pytest.raises(TypeError):
+      tc.TreeDict({1: "one"}, aggregate="max")
+    with pytest.raises(ValueError):
+      tc.TreeDict(aggregate="mean")
+    with pytest.raises(ValueError):
+      tc.TreeDict({1: 1}).range_aggregate()
+
+    assert tree.to_dict() == {1: 1}
+    assert tree.range_aggregate() == 1
+
+  def test_random(self):
+    items = {}
+    trees = {
+        "sum": (tc.TreeDict(aggregate="sum"), sum),
+        "min": (tc.TreeDict(aggregate="min"), lambda x: min(x, default=None)),
+        "max": (tc.TreeDict(aggregate="max"), lambda x: max(x, default=None)),
+        "count": (tc.TreeDict(aggregate="count"), len),
+    }
+
+    for _ in range(2000):
+      key = random.randint(0, 200)
+      if random.random() < 0.3:
+        items.pop(key, None)
+        for tree, _ in trees.values():
+          tree._tree.remove(key)
+      else:
+        value = random.randint(-1000, 1000)
+        items[key] = value
+        for tree, _ in trees.values():
+          tree[key] = value
+
+    for _ in range(100):
+      start, stop = sorted(random.sample(range(-10, 210), 2))
+      for tree, f in trees.values():
+        assert tree.range_aggregate(start, stop) == brute_force(
+            items, start, stop, f
+        )
+        assert tree.prefix_aggregate(stop) == brute_force(
+            items, float("-inf"), stop, f
+        )
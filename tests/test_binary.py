@@ -0,0 +1,84 @@
+import math
+
+import pytest
+
+import tree_collections as tc
+
+
+class Point:
+
+  def __init__(self, x):
+    self.x = x
+
+  def __lt__(self, other):
+    return self.x < other.x
+
+  def __eq__(self, other):
+    return isinstance(other, Point) and self.x == other.x
+
+
+class TestBinary:
+
+  def test_tree_dict_variants(self):
+    # keys must share a type, so the other variants are covered as values
+    tree = tc.TreeDict({
+        -(2**63): None,
+        -1: 1.5,
+        0: "zéro",
+        1: (1, "a"),
+        2: (1, 2, 3),
+        3: [[], [1.0]],
+        2**63 - 1: float("inf"),
+    })
+    output = tc.TreeDict.from_bytes(tree.to_bytes())
+    assert output.items_list() == tree.items_list()
+
+    output = tc.TreeDict.from_bytes(tc.TreeDict({1: float("nan")}).to_bytes())
+    assert math.isnan(output[1])
+
+  def test_tree_dict_aggregate(self):
+    tree = tc.TreeDict({1: 10, 2: 20}, aggregate="sum")
+    output = tc.TreeDict.from_bytes(tree.to_bytes(), aggregate="sum")
+    assert output.range_aggregate() == 30
+
+  def test_save_load(self, tmp_path):
+    path = tmp_path / "tree.bin"
+
+    tree = tc.TreeDict((i, str(i)) for i in range(1000))
+    tree.save(path)
+    assert tc.TreeDict.load(str(path)).items_list() == tree.items_list()
+
+    tree_set = tc.TreeSet(["b", "a", "c"])
+    tree_set.save(path)
+    assert list(tc.TreeSet.load(path)) == ["a", "b", "c"]
+
+    seq = tc.TreeSeq([3, 1, 3, 2, 3])
+    seq.save(path)
+    output = tc.TreeSeq.load(path)
+    assert list(output) == [1, 2, 3, 3, 3]
+
+  def test_unserializable(self, tmp_path):
+    tree = tc.TreeDict({1: Point(1)})
+    with pytest.raises(TypeError, match="Point"):
+      tree.to_bytes()
+
+    path = tmp_path / "tree.bin"
+    with pytest.raises(TypeError):
+      tc.TreeSet([Point(1)]).save(path)
+    assert not path.exists()
+
+  def test_invalid(self):
+    data = tc.TreeDict({1: "one"}).to_bytes()
+
+    with pytest.raises(ValueError, match="checksum"):
+      tc.TreeDict.from_bytes(data[:-1] + b"x")
+    with pytest.raises(ValueError):
+      tc.TreeDict.from_bytes(data[:-1])
+    with pytest.raises(ValueError, match="expected a set, found a map"):
+      tc.TreeSet.from_bytes(data)
+    with pytest.raises(ValueError, match="not a tree_collections file"):
+      tc.TreeDict.from_bytes(b"\x00" * 32)
+
+  def test_load_missing(self, tmp_path):
+    with pytest.raises(FileNotFoundError):
+      tc.TreeDict.load(tmp_path / "missing.bin")
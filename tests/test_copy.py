@@ -0,0 +1,97 @@
+import copy
+
+import tree_collections as tc
+
+
+class Box:
+
+  def __init__(self, value):
+    self.value = value
+
+  def __lt__(self, other):
+    return self.value < other.value
+
+  def __eq__(self, other):
+    return self.value == other.value
+
+
+class TestCopy:
+
+  def test_tree_dict(self):
+    value = [1, 2]
+    payload = Box(0)
+    tree = tc.TreeDict({1: value, 2: payload})
+
+    shallow = copy.copy(tree)
+    deep = copy.deepcopy(tree)
+    tree[3] = "three"
+
+    assert shallow.keys_list() == [1, 2]
+    assert deep.keys_list() == [1, 2]
+    assert shallow[2] is payload
+    assert deep[2] is not payload
+    assert deep[2].value == 0
+    assert tree.copy().keys_list() == [1, 2, 3]
+
+  def test_tree_dict_options(self):
+    tree = tc.TreeDict({1: 1, 2: 2}, aggregate="sum")
+    tree.snapshot()
+
+    for other in (tree.copy(), copy.deepcopy(tree)):
+      other[3] = 3
+      assert other.aggregate == "sum"
+      assert other.range_aggregate() == 6
+      assert other.snapshot().keys_list() == [1, 2, 3]
+    assert tree.range_aggregate() == 3
+    assert tree.snapshot().keys_list() == [1, 2]
+
+  def test_memo(self):
+    shared = Box(1)
+    tree = tc.TreeDict({1: shared, 2: shared})
+    deep = copy.deepcopy(tree)
+
+    assert deep[1] is deep[2]
+    assert deep[1] is not shared
+
+  def test_tree_set_and_seq(self):
+    tset = tc.TreeSet([Box(1), Box(2)])
+    tseq = tc.TreeSeq([1, 1, 2])
+
+    set_copy = copy.copy(tset)
+    set_deep = copy.deepcopy(tset)
+    seq_copy = copy.copy(tseq)
+    tset.add(Box(3))
+    tseq.insert_many([3])
+
+    assert [x.value for x in set_copy] == [1, 2]
+    assert [x.value for x in set_deep] == [1, 2]
+    assert list(set_deep)[0] is not list(tset)[0]
+    assert list(seq_copy) == [1, 1, 2]
+    assert list(copy.deepcopy(tseq)) == [1, 1, 2, 3]
+
+  def test_other_collections(self):
+    multi = tc.TreeMultiDict([(1, "a"), (1, "b")])
+    intervals = tc.IntervalTree([(0, 5, "a")])
+    ranges = tc.RangeMap([(0, 5, "a")])
+
+    multi_copy = copy.copy(multi)
+    intervals_copy = copy.deepcopy(intervals)
+    ranges_copy = ranges.copy()
+    multi.add(2, "c")
+    intervals.add(1, 2, "b")
+    ranges.set_range(5, 6, "b")
+
+    assert multi_copy[1] == ["a", "b"]
+    assert len(multi_copy) == 2
+    assert list(intervals_copy) == [(0, 5, "a")]
+    assert list(ranges_copy) == [(0, 5, "a")]
+
+  def test_frozen(self):
+    fset = tc.FrozenTreeSet([1, 2])
+    fdict = tc.FrozenTreeDict({1: [1]})
+
+    assert copy.copy(fset) is fset
+    assert copy.copy(fdict) is fdict
+    deep = copy.deepcopy(fdict)
+    assert deep == fdict
+    assert deep[1] is not fdict[1]
@@ -0,0 +1,71 @@
+import pytest
+
+import tree_collections as tc
+
+
+class TestDiskTreeDict:
+
+  def test_get_insert_remove(self, tmp_path):
+    with tc.TreeDict.open(tmp_path / "index.tcbt") as tree:
+      assert len(tree) == 0
+      tree[2] = "two"
+      tree[1] = ("one", 1)
+      tree[3] = None
+      assert tree.get(1) == ("one", 1)
+      assert tree[3] is None
+      assert 2 in tree
+      assert 4 not in tree
+      del tree[2]
+      with pytest.raises(KeyError):
+        del tree[2]
+      with pytest.raises(KeyError):
+        tree[2]
+      assert list(tree) == [1, 3]
+
+  def test_reopen(self, tmp_path):
+    path = tmp_path / "index.tcbt"
+    tree = tc.TreeDict.open(path, cache_size=16)
+    for i in range(5000):
+      tree[i] = "value %d" % i
+    for i in range(0, 5000, 2):
+      del tree[i]
+    tree.close()
+    assert tree.closed
+    with pytest.raises(ValueError, match="closed"):
+      tree[1]
+
+    tree = tc.TreeDict.open(str(path))
+    assert len(tree) == 2500
+    assert tree[4999] == "value 4999"
+    assert list(tree.range(10, 16)) == [(11, "value 11"), (13, "value 13"),
+                                        (15, "value 15")]
+    tree.close()
+
+  def test_range_across_updates(self, tmp_path):
+    with tc.TreeDict.open(tmp_path / "index.tcbt") as tree:
+      for i in range(1000):
+        tree[i] = i
+      output = []
+      for key, _ in tree.range(500):
+        output.append(key)
+        if key == 500:
+          tree[5000] = None
+      assert output == list(range(500, 1000)) + [5000]
+
+  def test_unserializable(self, tmp_path):
+
+    class Point:
+      pass
+
+    with tc.TreeDict.open(tmp_path / "index.tcbt") as tree:
+      with pytest.raises(TypeError, match="Point"):
+        tree[1] = Point()
+      with pytest.raises(ValueError, match="too large"):
+        tree[1] = "x" * 10000
+      assert len(tree) == 0
+
+  def test_not_a_page_file(self, tmp_path):
+    path = tmp_path / "data.bin"
+    path.write_bytes(b"\x00" * 8192)
+    with pytest.raises(ValueError, match="not a tree_collections page file"):
+      tc.TreeDict.open(path)
@@ -0,0 +1,82 @@
+import pytest
+import tree_collections as tc
+
+
+class TestFrozenTreeSet:
+
+  def test_basic(self):
+    fset = tc.FrozenTreeSet([3, 1, 2])
+
+    assert list(fset) == [1, 2, 3]
+    assert 2 in fset
+    assert len(fset) == 3
+    assert fset.nth(-1) == 3
+    assert fset.contains_many([1, 4]) == [True, False]
+
+  def test_hash(self):
+    a = tc.FrozenTreeSet(["x", "y"])
+    b = tc.FrozenTreeSet(["y", "x", "x"])
+    c = tc.FrozenTreeSet(["x"])
+
+    assert a == b
+    assert a != c
+    assert hash(a) == hash(b)
+    assert hash(tc.FrozenTreeSet([1, 2])) == hash(tc.FrozenTreeSet([1.0, 2.0]))
+
+    tags = {a: "both", c: "one"}
+    assert tags[b] == "both"
+    assert len({a, b, c}) == 2
+
+  def test_unhashable(self):
+    fset = tc.FrozenTreeSet([(1, [2, 3])])
+    assert isinstance(hash(fset), int)
+
+    fset = tc.FrozenTreeSet([{"a": 1}])
+    with pytest.raises(TypeError):
+      hash(fset)
+
+  def test_freeze_thaw(self):
+    tset = tc.TreeSet([1, 2])
+    fset = tset.freeze()
+    tset.add(3)
+
+    assert list(fset) == [1, 2]
+    thawed = fset.thaw()
+    thawed.add(0)
+    assert list(thawed) == [0, 1, 2]
+    assert list(fset) == [1, 2]
+
+
+class TestFrozenTreeDict:
+
+  def test_basic(self):
+    fdict = tc.FrozenTreeDict({2: "b", 1: "a"})
+
+    assert list(fdict) == [1, 2]
+    assert fdict[1] == "a"
+    assert fdict.get(3) is None
+    assert dict(fdict.items()) == {1: "a", 2: "b"}
+    assert fdict.nth(0) == (1, "a")
+    with pytest.raises(KeyError):
+      fdict[3]
+
+  def test_hash(self):
+    a = tc.FrozenTreeDict({1: "a", 2: "b"})
+    b = tc.FrozenTreeDict([(2, "b"), (1, "a")])
+    c = tc.FrozenTreeDict({1: "b", 2: "a"})
+
+    assert a == b
+    assert a != c
+    assert hash(a) == hash(b)
+    assert len({a, b, c}) == 2
+
+  def test_freeze_thaw(self):
+    tdict = tc.TreeDict({1: "a"})
+    fdict = tdict.freeze()
+    tdict[2] = "b"
+
+    assert fdict.to_dict() == {1: "a"}
+    thawed = fdict.thaw()
+    thawed[3] = "c"
+    assert thawed.to_dict() == {1: "a", 3: "c"}
+    assert fdict.to_dict() == {1: "a"}
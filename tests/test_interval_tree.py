@@ -0,0 +1,70 @@
+import random
+
+import pytest
+import tree_collections as tc
+
+
+class TestIntervalTree:
+
+  def test_basic(self):
+    tree = tc.IntervalTree()
+    tree.add(5, 10, "a")
+    tree.add(0, 3, "b")
+    tree.add(2, 8, "c")
+
+    assert len(tree) == 3
+    assert list(tree) == [(0, 3, "b"), (2, 8, "c"), (5, 10, "a")]
+
+  def test_overlap(self):
+    tree = tc.IntervalTree([(5, 10, "a"), (0, 3, "b"), (2, 8, "c"), (12, 15, "d")])
+
+    assert tree.overlap(3, 5) == [(2, 8, "c")]
+    assert tree.overlap(0, 1) == [(0, 3, "b")]
+    assert tree.overlap(9, 13) == [(5, 10, "a"), (12, 15, "d")]
+    assert tree.overlap(10, 12) == []
+
+  def test_at(self):
+    tree = tc.IntervalTree([(5, 10, "a"), (0, 3, "b"), (2, 8, "c")])
+
+    assert tree.at(2) == [(0, 3, "b"), (2, 8, "c")]
+    assert tree.at(3) == [(2, 8, "c")]
+    assert tree.at(10) == []
+
+  def test_remove(self):
+    tree = tc.IntervalTree([(0, 3, "a"), (0, 3, "b"), (2, 8, "c")])
+
+    assert tree.remove(0, 3) == "a"
+    assert list(tree) == [(0, 3, "b"), (2, 8, "c")]
+    assert tree.remove(0, 4) is None
+    assert len(tree) == 2
+
+  def test_invalid_interval(self):
+    tree = tc.IntervalTree()
+
+    with pytest.raises(ValueError):
+      tree.add(3, 3, "a")
+
+  def test_random(self):
+    random.seed(0)
+    intervals = []
+    tree = tc.IntervalTree()
+    for i in range(500):
+      start = random.random() * 100
+      end = start + random.random() * 10 + 1e-9
+      intervals.append((start, end, i))
+      tree.add(start, end, i)
+
+    for _ in range(50):
+      a = random.random() * 100
+      b = a + random.random() * 5
+      expected = sorted(x for x in intervals if x[0] < b and x[1] > a)
+      assert tree.overlap(a, b) == expected
+
+  def test_incomparable_bounds(self):
+    tree = tc.IntervalTree([(1, 3, "x")])
+    with pytest.raises(TypeError):
+      tree.add("a", "b", 1)
+    with pytest.raises(TypeError, match="NaN"):
+      tree.add(float("nan"), 2, 1)
+    tree.add(2, 5, "y")
+    assert tree.overlap(0, 10) == [(1, 3, "x"), (2, 5, "y")]
@@ -0,0 +1,92 @@
+import json
+import math
+
+import pytest
+
+import tree_collections as tc
+
+
+class Point:
+
+  def __init__(self, x):
+    self.x = x
+
+  def __lt__(self, other):
+    return self.x < other.x
+
+  def __eq__(self, other):
+    return isinstance(other, Point) and self.x == other.x
+
+
+class TestJson:
+
+  def test_tree_dict_round_trip(self):
+    tree = tc.TreeDict({
+        (2, "b"): None,
+        (1, "a"): [1, (2, 3)],
+        (1, "b"): 1.5,
+        (3, "c"): "line\n\"quoted\" ☃",
+    })
+    data = tree.to_json()
+    assert json.loads(data)[0] == [{"$tuple": [1, "a"]}, [1, {"$tuple": [2, 3]}]]
+
+    output = tc.TreeDict.from_json(data)
+    assert output.items_list() == tree.items_list()
+
+  def test_tagged_numbers(self):
+    tree = tc.TreeDict({
+        1: 2**60,
+        2: float("inf"),
+        3: float("-inf"),
+        4: 1.0,
+        5: -(2**63),
+    })
+    data = tree.to_json()
+    assert json.loads(data) == [
+        [1, {"$int": str(2**60)}],
+        [2, {"$float": "inf"}],
+        [3, {"$float": "-inf"}],
+        [4, 1.0],
+        [5, {"$int": str(-(2**63))}],
+    ]
+    output = tc.TreeDict.from_json(data)
+    assert output.items_list() == tree.items_list()
+    assert isinstance(output[4], float)
+
+    output = tc.TreeDict.from_json(tc.TreeDict({1: float("nan")}).to_json())
+    assert math.isnan(output[1])
+
+  def test_set_and_seq(self):
+    tree_set = tc.TreeSet([3.5, -1.0, 2.25])
+    assert tree_set.to_json() == "[-1.0,2.25,3.5]"
+    assert list(tc.TreeSet.from_json(tree_set.to_json())) == [-1.0, 2.25, 3.5]
+
+    seq = tc.TreeSeq(["b", "a", "b"])
+    assert seq.to_json() == '["a","b","b"]'
+    assert list(tc.TreeSeq.from_json(seq.to_json())) == ["a", "b", "b"]
+
+  def test_reads_plain_json(self):
+    output = tc.TreeDict.from_json(json.dumps([[2, "two"], [1, "one"]]))
+    assert output.items_list() == [(1, "one"), (2, "two")]
+
+  def test_save_load(self, tmp_path):
+    path = tmp_path / "tree.json"
+
+    tree = tc.TreeDict(((i, str(i)) for i in range(1000)), aggregate="count")
+    tree.save_json(path)
+    output = tc.TreeDict.load_json(path, aggregate="count")
+    assert output.items_list() == tree.items_list()
+    assert output.range_aggregate() == 1000
+
+    tc.TreeSeq([1, 1, 2]).save_json(str(path))
+    assert list(tc.TreeSeq.load_json(path)) == [1, 1, 2]
+
+  def test_errors(self):
+    with pytest.raises(TypeError, match="Point"):
+      tc.TreeSet([Point(1)]).to_json()
+    with pytest.raises(ValueError, match="unknown tag"):
+      tc.TreeSet.from_json('[{"$set": []}]')
+    with pytest.raises(ValueError):
+      tc.TreeDict.from_json("[[1, 2]")
+    with pytest.raises(ValueError):
+      tc.TreeDict.from_json("[[1, 2]] trailing")
@@ -0,0 +1,81 @@
+import pytest
+
+import tree_collections as tc
+
+
+class Point:
+
+  def __init__(self, x):
+    self.x = x
+
+
+class TestWriteAheadLog:
+
+  def test_recover(self, tmp_path):
+    path = tmp_path / "index.log"
+
+    tree = tc.TreeDict.recover(path)
+    assert len(tree) == 0
+    for i in range(100):
+      tree[i] = str(i)
+    del tree[5]
+    tree.remove_many([6, 7])
+    tree.update_many([(200, "x"), (201, "y")])
+    tree.close_log()
+
+    output = tc.TreeDict.recover(str(path))
+    assert output.items_list() == tree.items_list()
+
+    output.clear()
+    output[1] = (1, [2.5, None])
+    output.close_log()
+    assert tc.TreeDict.recover(path).items_list() == [(1, (1, [2.5, None]))]
+
+  def test_truncated_final_record(self, tmp_path):
+    path = tmp_path / "index.log"
+    tree = tc.TreeDict.recover(path, sync="never")
+    tree[1] = "one"
+    tree[2] = "two"
+    tree.close_log()
+
+    data = path.read_bytes()
+    path.write_bytes(data[:-3])
+    tree = tc.TreeDict.recover(path)
+    assert tree.items_list() == [(1, "one")]
+    tree[3] = "three"
+    tree.close_log()
+    assert tc.TreeDict.recover(path).items_list() == [(1, "one"), (3, "three")]
+
+  def test_compaction(self, tmp_path):
+    path = tmp_path / "index.log"
+    snapshot = tmp_path / "index.log.snapshot"
+
+    tree = tc.TreeDict.recover(path, sync=0.5, compact_every=10)
+    for i in range(25):
+      tree[i] = i * i
+    assert snapshot.exists()
+    tree.close_log()
+
+    output = tc.TreeDict.recover(path, aggregate="sum")
+    assert output.items_list() == tree.items_list()
+    assert output.range_aggregate() == sum(i * i for i in range(25))
+
+    output.compact_log()
+    assert len(path.read_bytes()) == 8
+    assert tc.TreeDict.load(snapshot).items_list() == tree.items_list()
+
+  def test_unserializable(self, tmp_path):
+    tree = tc.TreeDict.recover(tmp_path / "index.log")
+    tree[1] = "one"
+    with pytest.raises(TypeError, match="Point"):
+      tree[2] = Point(2)
+    with pytest.raises(TypeError, match="Point"):
+      tree.update_many([(3, "three"), (4, Point(4))])
+    assert tree.items_list() == [(1, "one")]
+
+  def test_without_log(self, tmp_path):
+    tree = tc.TreeDict({1: Point(1)})
+    with pytest.raises(ValueError, match="without a log"):
+      tree.sync_log()
+    with pytest.raises(ValueError):
+      tc.TreeDict.recover(tmp_path / "index.log", sync="sometimes")
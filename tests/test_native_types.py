@@ -0,0 +1,147 @@
+import datetime
+import decimal
+import enum
+import fractions
+
+import pytest
+
+import tree_collections as tc
+
+UTC = datetime.timezone.utc
+CET = datetime.timezone(datetime.timedelta(hours=1))
+
+
+class TestNativeTypes:
+
+  def test_round_trip_types(self):
+    keys = [
+        True,
+        b"\x00bytes",
+        datetime.date(2024, 2, 29),
+        datetime.datetime(2024, 2, 29, 13, 5, 9, 250),
+        datetime.datetime(2024, 2, 29, 13, 5, 9, tzinfo=CET),
+        decimal.Decimal("-1.50"),
+        fractions.Fraction(1, 3),
+    ]
+    for key in keys:
+      tree = tc.TreeDict({key: 1})
+      (output,) = tree.keys_list()
+      assert type(output) is type(key)
+      assert output == key
+      assert str(output) == str(key)
+
+  def test_bool_as_int(self):
+    tree = tc.TreeDict({True: "a", 0: "b", 2.5: "c"})
+    assert tree.keys_list() == [0, True, 2.5]
+    assert tree[1] == "a"
+    assert tree[False] == "b"
+
+  def test_exact_numbers(self):
+    tree = tc.TreeSet([
+        decimal.Decimal("0.1"),
+        0.1,
+        fractions.Fraction(1, 10),
+        decimal.Decimal(2**70),
+        2**62,
+    ])
+    # 0.1 as a float is slightly above one tenth
+    assert list(tree) == [decimal.Decimal("0.1"), 0.1, 2**62, decimal.Decimal(2**70)]
+    assert fractions.Fraction(1, 10) in tree
+    assert decimal.Decimal("4611686018427387904.0") in tree
+
+  def test_datetimes(self):
+    noon = datetime.datetime(2024, 1, 1, 12, tzinfo=UTC)
+    tree = tc.TreeDict({noon: "utc"})
+    assert tree[datetime.datetime(2024, 1, 1, 13, tzinfo=CET)] == "utc"
+
+    dates = tc.TreeSet([datetime.date(2024, 1, 2), datetime.date(2023, 12, 31)])
+    assert list(dates) == [datetime.date(2023, 12, 31), datetime.date(2024, 1, 2)]
+
+  def test_other_timezones(self):
+    zoneinfo = pytest.importorskip("zoneinfo")
+    try:
+      paris = zoneinfo.ZoneInfo("Europe/Paris")
+    except zoneinfo.ZoneInfoNotFoundError:
+      pytest.skip("no timezone database")
+
+    key = datetime.datetime(2024, 1, 1, 13, tzinfo=paris)
+    tree = tc.TreeDict({datetime.datetime(2024, 1, 1, 12, tzinfo=UTC): 1})
+    assert tree[key] == 1
+    tree[key] = 2
+    assert len(tree) == 1
+
+  def test_serialization(self):
+    tree = tc.TreeDict({
+        1: b"\xca\xfe",
+        2: datetime.date(2024, 2, 29),
+        3: datetime.datetime(2024, 2, 29, 13, 5, 9, tzinfo=CET),
+        4: decimal.Decimal("2.50"),
+        5: fractions.Fraction(-2, 6),
+        6: False,
+    })
+    assert tc.TreeDict.from_bytes(tree.to_bytes()).items_list() == tree.items_list()
+
+    data = tree.to_json()
+    assert '{"$bytes":"cafe"}' in data
+    assert '{"$datetime":"2024-02-29T13:05:09+01:00"}' in data
+    output = tc.TreeDict.from_json(data)
+    assert output.items_list() == tree.items_list()
+    assert [type(x) for x in output.values()] == [type(x) for x in tree.values()]
+
+  def test_hash_matches_equality(self):
+    E = enum.IntEnum("E", "A")
+    assert tc.FrozenTreeSet([E.A]) == tc.FrozenTreeSet([1])
+    assert hash(tc.FrozenTreeSet([E.A])) == hash(tc.FrozenTreeSet([1]))
+    assert hash(tc.FrozenTreeSet([True])) == hash(tc.FrozenTreeSet([1.0]))
+
+    named = datetime.timezone(datetime.timedelta(hours=1), "CET")
+    a = tc.FrozenTreeDict({datetime.datetime(2024, 1, 1, 13, tzinfo=named): 1})
+    b = tc.FrozenTreeDict({datetime.datetime(2024, 1, 1, 12, tzinfo=UTC): 1})
+    assert a == b
+    assert hash(a) == hash(b)
+
+  def test_naive_and_aware(self):
+    naive = datetime.datetime(2024, 1, 1, 12)
+    aware = datetime.datetime(2024, 1, 1, 12, tzinfo=UTC)
+
+    tree = tc.TreeDict({aware: 1})
+    with pytest.raises(TypeError, match="offset-naive and offset-aware"):
+      tree[naive] = 2
+    assert tree.items_list() == [(aware, 1)]
+
+    with pytest.raises(TypeError, match="offset-naive and offset-aware"):
+      tc.TreeSet([naive, aware])
+    with pytest.raises(TypeError):
+      tc.TreeSet([aware]).add(naive)
+    with pytest.raises(TypeError):
+      tc.TreeDict({datetime.date(2024, 1, 1): 1})[naive] = 2
+
+  def test_tuples_of_different_lengths(self):
+    tree = tc.TreeSet([(1, 2, 3), (1, 2), (0, 5, 5)])
+    assert tree.to_list() == [(0, 5, 5), (1, 2), (1, 2, 3)]
+    assert (1, 2) in tree
+    assert (1, 2, 4) not in tree
+
+  def test_keys_that_cant_be_ordered(self):
+    # only the second tuple element is incomparable, found past the first key
+    with pytest.raises(TypeError, match="'str' and 'int'"):
+      tc.TreeSet([(1, "a"), (2, 3), (2, "b")])
+    with pytest.raises(TypeError):
+      tc.TreeSet([1, object()])
+    with pytest.raises(TypeError, match="NaN"):
+      tc.TreeSet([1.0, float("nan")])
+    with pytest.raises(TypeError):
+      tc.FrozenTreeSet([(1, "a"), (2, 3), (2, "b")])
+    with pytest.raises(TypeError):
+      tc.FrozenTreeDict({1: "a", "b": 2})
+
+    tree = tc.TreeDict({1: "a", 2: "b"})
+    with pytest.raises(TypeError):
+      tree[object()] = 1
+    with pytest.raises(TypeError):
+      tree[float("nan")] = 1
+    with pytest.raises(TypeError):
+      tree.get("c")
+    assert tree.items_list() == [(1, "a"), (2, "b")]
+    tree[3] = "c"
+    assert tree.keys_list() == [1, 2, 3]
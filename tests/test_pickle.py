@@ -0,0 +1,110 @@
+import pickle
+
+import pytest
+
+import tree_collections as tc
+from tree_collections.tree_collections import PyBTreeSeq
+
+
+class Point:
+
+  def __init__(self, x):
+    self.x = x
+
+  def __lt__(self, other):
+    return self.x < other.x
+
+  def __eq__(self, other):
+    return isinstance(other, Point) and self.x == other.x
+
+
+def round_trip(obj):
+  return pickle.loads(pickle.dumps(obj))
+
+
+class TestPickle:
+
+  def test_tree_dict_variants(self):
+    # one key per Elem variant family, values cover every variant
+    tree = tc.TreeDict({
+        1: 1.5,
+        2: 2,
+        3: "three",
+        4: (1, "a"),
+        5: (1, 2, 3),
+        6: [1, [2, 3]],
+        7: Point(7),
+        8: None,
+    })
+
+    assert round_trip(tree).items_list() == tree.items_list()
+
+  def test_key_variants(self):
+    for keys in (
+        [1.5, 0.5],
+        [3, 1, 2],
+        ["b", "a"],
+        [(2, "b"), (1, "a")],
+        [(1, 2, 3), (0, 1, 2)],
+        [[2], [1, 2]],
+        [Point(2), Point(1)],
+    ):
+      tset = tc.TreeSet(keys)
+      assert round_trip(tset).to_list() == tset.to_list()
+
+  def test_tuple_keys(self):
+    tree = tc.TreeDict({(1, 2, 3): "a", (4, (5, 6, 7), 8): "b"})
+    other = round_trip(tree)
+
+    assert other[(1, 2, 3)] == "a"
+    assert other[(4, (5, 6, 7), 8)] == "b"
+    assert all(type(key) is tuple for key in other.keys_list())
+    assert type(other.keys_list()[1][1]) is tuple
+
+  def test_tree_dict_options(self):
+    tree = tc.TreeDict({1: 10, 2: 20}, aggregate="max")
+    other = round_trip(tree)
+
+    assert other.aggregate == "max"
+    assert other.range_aggregate() == 20
+
+  def test_tree_seq_multiplicities(self):
+    tseq = tc.TreeSeq([3, 1, 1, 2, 3, 3])
+    other = round_trip(tseq)
+
+    assert list(other) == [1, 1, 2, 3, 3, 3]
+    assert other.to_list() == tseq.to_list()
+
+  def test_state_is_compact(self):
+    tree = PyBTreeSeq(["a"] * 1000)
+    assert tree.__getstate__() == (["a"], [1000])
+
+  def test_invalid_seq_state(self):
+    for state in ((["a", "a"], [1, 2]), (["a", "b"], [1, 0]), (["a"], [1, 2])):
+      with pytest.raises(ValueError, match="invalid state"):
+        PyBTreeSeq().__setstate__(state)
+    with pytest.raises(TypeError):
+      PyBTreeSeq().__setstate__(([1, "a"], [1, 1]))
+
+    tree = PyBTreeSeq()
+    tree.__setstate__((["b", "a"], [2, 1]))
+    assert tree.to_list() == ["a", "b", "b"]
+    assert tree.nth(2) == "b" and tree.nth(3) is None
+
+  def test_other_collections(self):
+    multi = tc.TreeMultiDict([(2, "b"), (1, "a"), (1, "c")])
+    intervals = tc.IntervalTree([(0, 5, "a"), (0, 5, "b"), (2, 3, "c")])
+    ranges = tc.RangeMap([(0, 5, "a"), (5, 10, "b")])
+
+    assert list(round_trip(multi).items()) == list(multi.items())
+    assert len(round_trip(multi)) == 3
+    assert list(round_trip(intervals)) == list(intervals)
+    assert list(round_trip(ranges)) == list(ranges)
+
+  def test_frozen(self):
+    fset = tc.FrozenTreeSet([(1, 2), (0, 1)])
+    fdict = tc.FrozenTreeDict({"a": 1, "b": [2]})
+
+    assert round_trip(fset) == fset
+    assert hash(round_trip(fset)) == hash(fset)
+    assert round_trip(fdict) == fdict
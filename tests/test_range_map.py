@@ -0,0 +1,96 @@
+import random
+
+import pytest
+import tree_collections as tc
+
+
+class TestRangeMap:
+
+  def test_basic(self):
+    rmap = tc.RangeMap()
+    rmap.set_range(0, 10, "a")
+    rmap.set_range(20, 30, "b")
+
+    assert rmap[0] == "a"
+    assert rmap[9.5] == "a"
+    assert rmap[25] == "b"
+    assert 15 not in rmap
+    assert rmap.get(10) is None
+    assert list(rmap) == [(0, 10, "a"), (20, 30, "b")]
+
+    with pytest.raises(KeyError):
+      rmap[30]
+
+  def test_split(self):
+    rmap = tc.RangeMap([(0, 10, "a")])
+    rmap.set_range(3, 6, "b")
+
+    assert list(rmap) == [(0, 3, "a"), (3, 6, "b"), (6, 10, "a")]
+
+  def test_overwrite(self):
+    rmap = tc.RangeMap([(0, 5, "a"), (5, 10, "b"), (10, 15, "c")])
+    rmap.set_range(3, 12, "d")
+
+    assert list(rmap) == [(0, 3, "a"), (3, 12, "d"), (12, 15, "c")]
+
+  def test_coalesce(self):
+    rmap = tc.RangeMap([(0, 5, "a"), (10, 15, "a")])
+    rmap.set_range(5, 10, "a")
+
+    assert list(rmap) == [(0, 15, "a")]
+
+    rmap.set_range(3, 7, "a")
+    assert list(rmap) == [(0, 15, "a")]
+
+  def test_remove_range(self):
+    rmap = tc.RangeMap([(0, 10, "a"), (10, 20, "b")])
+    rmap.remove_range(5, 15)
+
+    assert list(rmap) == [(0, 5, "a"), (15, 20, "b")]
+
+    rmap.remove_range(1, 2)
+    assert list(rmap) == [(0, 1, "a"), (2, 5, "a"), (15, 20, "b")]
+
+  def test_gaps(self):
+    rmap = tc.RangeMap([(0, 5, "a"), (10, 15, "b")])
+
+    assert rmap.gaps(-5, 20) == [(-5, 0), (5, 10), (15, 20)]
+    assert rmap.gaps(2, 12) == [(5, 10)]
+    assert rmap.gaps(1, 4) == []
+
+  def test_invalid_range(self):
+    rmap = tc.RangeMap()
+
+    with pytest.raises(ValueError):
+      rmap.set_range(5, 5, "a")
+
+  def test_random(self):
+    random.seed(0)
+    N = 50
+    expected = [None] * N
+    rmap = tc.RangeMap()
+    for _ in range(200):
+      a = random.randrange(N)
+      b = random.randrange(a + 1, N + 1)
+      if random.random() < 0.2:
+        rmap.remove_range(a, b)
+        value = None
+      else:
+        value = random.choice("xyz")
+        rmap.set_range(a, b, value)
+      expected[a:b] = [value] * (b - a)
+
+      assert [rmap.get(i) for i in range(N)] == expected
+      ranges = list(rmap)
+      for (_, end, value), (start, _, next_value) in zip(ranges, ranges[1:]):
+        assert end < start or value != next_value
+
+  def test_incomparable_bounds(self):
+    rmap = tc.RangeMap([(0, 10, "a"), (20, 30, "b")])
+    with pytest.raises(TypeError):
+      rmap.set_range(5, "x", "c")
+    with pytest.raises(TypeError):
+      rmap.remove_range(5, object())
+    with pytest.raises(TypeError):
+      rmap.get(object())
+    assert list(rmap) == [(0, 10, "a"), (20, 30, "b")]
@@ -0,0 +1,73 @@
+import random
+
+import tree_collections as tc
+
+
+class TestSnapshot:
+
+  def test_basic(self):
+    tree = tc.TreeDict({1: "one", 2: "two"})
+    snapshot = tree.snapshot()
+
+    tree[3] = "three"
+    tree[1] = "uno"
+    del tree[2]
+
+    assert dict(snapshot) == {1: "one", 2: "two"}
+    assert snapshot[1] == "one"
+    assert 3 not in snapshot
+    assert len(snapshot) == 2
+    assert tree.to_dict() == {1: "uno", 3: "three"}
+    assert tree.snapshot().to_dict() == {1: "uno", 3: "three"}
+
+  def test_iterator_ignores_writes(self):
+    tree = tc.TreeDict({i: i for i in range(100)})
+    items = iter(tree.snapshot().items())
+    next(items)
+
+    tree.clear()
+    tree[1000] = 1000
+
+    assert list(items) == [(i, i) for i in range(1, 100)]
+
+  def test_split_and_merge(self):
+    tree = tc.TreeDict({i: i for i in range(10)})
+    before = tree.snapshot()
+
+    right = tree.split_off(5)
+    after_split = tree.snapshot()
+    tree.merge(right)
+
+    assert before.keys_list() == list(range(10))
+    assert after_split.keys_list() == list(range(5))
+    assert tree.snapshot().keys_list() == list(range(10))
+
+  def test_random(self):
+    tree = tc.TreeDict()
+    expected = {}
+    snapshots = []
+
+    for i in range(2000):
+      key = random.randint(0, 300)
+      if random.random() < 0.3:
+        tree.remove_many([key])
+        expected.pop(key, None)
+      else:
+        tree[key] = i
+        expected[key] = i
+      if i % 100 == 0:
+        snapshots.append((tree.snapshot(), dict(sorted(expected.items()))))
+
+    for snapshot, expected in snapshots:
+      assert snapshot.items_list() == list(expected.items())
+
+  def test_released_snapshots(self):
+    # the first snapshot is released before the map is updated again
+    tree = tc.TreeDict({1: "one"})
+    assert tree.snapshot().keys_list() == [1]
+    tree[2] = "two"
+    snapshot = tree.snapshot()
+    tree[3] = "three"
+
+    assert snapshot.keys_list() == [1, 2]
+    assert tree.snapshot().keys_list() == [1, 2, 3]
@@ -0,0 +1,149 @@
+import threading
+
+import pytest
+import tree_collections as tc
+
+N_THREADS = 4
+N_ITEMS = 2000
+
+
+def run_threads(*targets):
+  errors = []
+
+  def wrap(target):
+    def run():
+      try:
+        target()
+      except BaseException as e:
+        errors.append(e)
+
+    return run
+
+  threads = [threading.Thread(target=wrap(target)) for target in targets]
+  for thread in threads:
+    thread.start()
+  for thread in threads:
+    thread.join()
+
+  if errors:
+    raise errors[0]
+
+
+class TestThreads:
+
+  def test_tree_dict_writers(self):
+    tree = tc.TreeDict()
+
+    def writer(offset):
+      def run():
+        for i in range(offset, N_ITEMS, N_THREADS):
+          tree[i] = str(i)
+
+      return run
+
+    run_threads(*(writer(offset) for offset in range(N_THREADS)))
+
+    assert len(tree) == N_ITEMS
+    assert tree.keys_list() == list(range(N_ITEMS))
+    assert tree.values_list() == [str(i) for i in range(N_ITEMS)]
+
+  def test_tree_dict_readers_and_writers(self):
+    tree = tc.TreeDict((i, i) for i in range(0, N_ITEMS, 2))
+
+    def writer():
+      for i in range(1, N_ITEMS, 2):
+        tree[i] = i
+      for i in range(0, N_ITEMS, 2):
+        del tree[i]
+
+    def reader():
+      for _ in range(20):
+        keys = list(tree)
+        # iteration resumes after the last key seen, so the keys stay sorted
+        # even while the writer is mutating the tree
+        assert keys == sorted(set(keys))
+        for key, value in tree.items():
+          assert key == value
+
+    run_threads(writer, *(reader for _ in range(N_THREADS)))
+
+    assert tree.keys_list() == list(range(1, N_ITEMS, 2))
+
+  def test_tree_set_readers_and_writers(self):
+    tset = tc.TreeSet()
+
+    def writer(offset):
+      def run():
+        for i in range(offset, N_ITEMS, N_THREADS):
+          tset.add(i)
+
+      return run
+
+    def reader():
+      for _ in range(20):
+        elems = list(tset)
+        assert elems == sorted(set(elems))
+
+    run_threads(
+        *(writer(offset) for offset in range(N_THREADS)),
+        *(reader for _ in range(N_THREADS)),
+    )
+
+    assert list(tset) == list(range(N_ITEMS))
+
+  def test_tree_seq_writers(self):
+    seq = tc.TreeSeq()
+
+    def writer():
+      for i in range(N_ITEMS // 10):
+        seq.insert_many([i % 10])
+
+    run_threads(*(writer for _ in range(N_THREADS)))
+
+    assert seq.to_list() == sorted(list(range(10)) * (N_THREADS * N_ITEMS // 100))
+
+  def test_merge_in_both_directions(self):
+    a = tc.TreeDict((i, i) for i in range(0, N_ITEMS, 2))
+    b = tc.TreeDict((i, i) for i in range(1, N_ITEMS, 2))
+
+    def merge(dst, src):
+      def run():
+        for _ in range(20):
+          dst.merge(src, on_conflict="keep")
+
+      return run
+
+    run_threads(merge(a, b), merge(b, a))
+
+    assert sorted(a.keys_list() + b.keys_list()) == list(range(N_ITEMS))
+
+  def test_reentrant_access(self):
+    a = tc.TreeDict({1: 1})
+    b = tc.TreeDict({1: 2})
+
+    def resolve(key, current, new):
+      return a[key] + new
+
+    with pytest.raises(RuntimeError):
+      a.merge(b, on_conflict=resolve)
+
+    assert a[1] == 1
+    assert b[1] == 2
+
+  def test_bulk_construction(self):
+    inputs = [list(range(i, N_ITEMS * N_THREADS, N_THREADS)) for i in range(N_THREADS)]
+    outputs = [None] * N_THREADS
+
+    def build(i):
+      def run():
+        keys = inputs[i][::-1]
+        tree = tc.TreeDict(zip(keys, keys))
+        outputs[i] = tc.TreeDict.from_bytes(tree.to_bytes())
+
+      return run
+
+    run_threads(*(build(i) for i in range(N_THREADS)))
+
+    for keys, tree in zip(inputs, outputs):
+      assert tree.keys_list() == keys
+      assert tree.values_list() == keys
@@ -0,0 +1,56 @@
+import pytest
+import tree_collections as tc
+
+
+class TestTreeMultiDict:
+
+  def test_basic(self):
+    tree = tc.TreeMultiDict()
+    tree.add(2, "b1")
+    tree.add(1, "a1")
+    tree.add(2, "b2")
+    tree.add(2, "b3")
+
+    assert len(tree) == 4
+    assert list(tree) == [1, 2]
+    assert tree[2] == ["b1", "b2", "b3"]
+    assert tree.count(2) == 3
+    assert tree.count(3) == 0
+    assert tree.get_all(3) == []
+    assert list(tree.items()) == [(1, "a1"), (2, "b1"), (2, "b2"), (2, "b3")]
+    assert list(tree.values()) == ["a1", "b1", "b2", "b3"]
+
+    with pytest.raises(KeyError):
+      tree[3]
+
+  def test_constructor(self):
+    tree = tc.TreeMultiDict([(2, "b1"), (1, "a1"), (2, "b2"), (1, "a2")])
+
+    assert len(tree) == 4
+    assert list(tree.items()) == [(1, "a1"), (1, "a2"), (2, "b1"), (2, "b2")]
+
+  def test_remove(self):
+    tree = tc.TreeMultiDict([(1, "a"), (1, "b"), (1, "a"), (2, "c")])
+
+    assert tree.remove_one(1, "a")
+    assert tree[1] == ["b", "a"]
+    assert not tree.remove_one(1, "x")
+    assert not tree.remove_one(3, "a")
+    assert len(tree) == 3
+
+    del tree[1]
+    assert list(tree) == [2]
+    assert len(tree) == 1
+
+    assert tree.remove_one(2, "c")
+    assert 2 not in tree
+    assert len(tree) == 0
+
+  def test_range(self):
+    tree = tc.TreeMultiDict([(i, v) for i in range(5) for v in "ab"])
+
+    assert list(tree.range(1, 3)) == [(1, "a"), (1, "b"), (2, "a"), (2, "b")]
+    assert list(tree.range(start=4)) == [(4, "a"), (4, "b")]
+    assert list(tree.range(stop=1)) == [(0, "a"), (0, "b")]
+    assert list(tree.range(3, 1)) == []
+    assert list(tree.range(2, 2)) == []
@@ -1,4 +1,6 @@
+import pytest
 import tree_collections as tc
+from tree_collections.tree_collections import PyBTreeSeq
 
 
 class TestTreeSet:
@@ -28,3 +30,70 @@ class TestTreeSet:
         assert tset[-7] == 1
         assert tset[-8] == 0
         assert tset[-9] == -1
+
+    def test_length_after_updates(self):
+        tree = PyBTreeSeq([1, 2])
+        tree.insert(3)
+        assert tree.nth(-1) == 3
+        assert tree.remove(3) is False
+        assert tree.nth(-1) == 2
+        tree.clear()
+        assert tree.nth(0) is None
+
+    def test_split_off(self):
+        tseq = tc.TreeSeq([3, 1, 2, 1, 3, 2])
+        upper = tseq.split_off(2)
+
+        assert list(tseq) == [1, 1]
+        assert list(upper) == [2, 2, 3, 3]
+        assert upper[-1] == 3
+        assert tseq[-1] == 1
+
+    def test_merge(self):
+        a = tc.TreeSeq([1, 2, 2])
+        b = tc.TreeSeq([2, 3])
+        a.merge(b)
+
+        assert list(a) == [1, 2, 2, 2, 3]
+        assert a[4] == 3
+        assert list(b) == []
+
+    def test_append(self):
+        a = tc.TreeSeq([1, 1])
+        b = tc.TreeSeq([2, 2])
+        a.append(b)
+
+        assert list(a) == [1, 1, 2, 2]
+        assert a[-1] == 2
+
+    def test_from_sorted(self):
+        tseq = tc.TreeSeq.from_sorted([1, 1, 2, 3, 3, 3])
+        assert list(tseq) == [1, 1, 2, 3, 3, 3]
+        assert tseq[-1] == 3
+        assert tseq[2] == 2
+
+        with pytest.raises(ValueError):
+            tc.TreeSeq.from_sorted([1, 3, 2])
+
+    def test_batch(self):
+        tseq = tc.TreeSeq([1])
+
+        assert tseq.insert_many([2, 1, 2]) == [True, False, False]
+        assert list(tseq) == [1, 1, 2, 2]
+        assert tseq[-1] == 2
+
+        tseq.update_many([0])
+        assert list(tseq) == [0, 1, 1, 2, 2]
+
+        assert tseq.get_many([2, 3]) == [2, None]
+        assert tseq.contains_many([3, 0]) == [False, True]
+
+        tseq.remove_many([1, 2, 2])
+        assert list(tseq) == [0, 1]
+        assert tseq[-1] == 1
+
+    def test_export(self):
+        tseq = tc.TreeSeq([2, 1, 2, 1, 3])
+
+        assert tseq.to_list() == [1, 1, 2, 2, 3]
+        assert list(tseq.chunks(2)) == [[1, 1], [2, 2], [3]]
@@ -1,3 +1,4 @@
+import pytest
 import tree_collections as tc
 
 
@@ -10,3 +11,76 @@ class TestTreeSet:
   def test_basic2(self):
     tset = tc.TreeSet([8, 0, 2])
     assert list(tset) == [0, 2, 8]
+
+  def test_split_off(self):
+    tset = tc.TreeSet(range(10))
+    upper = tset.split_off(4)
+
+    assert list(tset) == [0, 1, 2, 3]
+    assert list(upper) == list(range(4, 10))
+
+  def test_append(self):
+    lower = tc.TreeSet([1, 2, 3])
+    upper = tc.TreeSet([4, 5])
+    lower.append(upper)
+
+    assert list(lower) == [1, 2, 3, 4, 5]
+    assert len(upper) == 0
+
+  def test_merge(self):
+    a = tc.TreeSet([1, 2, 3])
+    b = tc.TreeSet([2.0, 4])
+    a.merge(b)
+    assert list(a) == [1, 2, 3, 4]
+    assert type(list(a)[1]) is float
+
+    a = tc.TreeSet([1, 2, 3])
+    b = tc.TreeSet([2.0, 4])
+    a.merge(b, on_conflict="keep")
+    assert type(list(a)[1]) is int
+
+    a = tc.TreeSet([1, 2, 3])
+    b = tc.TreeSet([2.0, 4])
+    with pytest.raises(ValueError):
+      a.merge(b, on_conflict=lambda x, y: 5)
+
+  def test_from_sorted(self):
+    tset = tc.TreeSet.from_sorted([1, 2, 2, 3])
+    assert list(tset) == [1, 2, 3]
+
+    with pytest.raises(ValueError):
+      tc.TreeSet.from_sorted([1, 3, 2])
+
+  def test_constructor_duplicates(self):
+    tset = tc.TreeSet([2, 1, 1.0])
+    assert list(tset) == [1, 2]
+    assert type(list(tset)[0]) is int
+
+  def test_batch(self):
+    tset = tc.TreeSet([1])
+
+    assert tset.insert_many([3, 1, 2, 3]) == [True, False, True, False]
+    assert list(tset) == [1, 2, 3]
+
+    tset.update_many(iter([5, 0]))
+    assert list(tset) == [0, 1, 2, 3, 5]
+
+    assert tset.get_many([5, 4], default=-1) == [5, -1]
+    assert tset.contains_many([4, 5]) == [False, True]
+    assert tset.remove_many([5, 4, 5]) == [True, False, False]
+    assert list(tset) == [0, 1, 2, 3]
+
+  def test_batch_iterables(self):
+    tset = tc.TreeSet({3, 1, 2})
+    assert list(tset) == [1, 2, 3]
+
+    assert tset.contains_many({1, 5}) in ([True, False], [False, True])
+    assert tset.get_many({2: "a", 7: "b"}.keys(), default=-1) == [2, -1]
+    assert tset.remove_many(x for x in [3, 4]) == [True, False]
+    assert list(tset) == [1, 2]
+
+  def test_export(self):
+    tset = tc.TreeSet([3, 1, 2])
+
+    assert tset.to_list() == [1, 2, 3]
+    assert list(tset.chunks(2)) == [[1, 2], [3]]
@@ -0,0 +1,104 @@
+import copy
+import math
+import pickle
+
+import pytest
+import tree_collections as tc
+from tree_collections.tree_collections import PyFloatBTreeMap, PyIntBTreeMap, PyStrBTreeMap
+
+
+class TestTypedKeys:
+
+  @pytest.mark.parametrize(
+      "key_type, keys",
+      [
+          (int, [5, -3, 2**62, 0]),
+          (float, [2.5, -1.0, math.inf, 0.0]),
+          (str, ["b", "", "ab", "a"]),
+      ],
+  )
+  def test_basic(self, key_type, keys):
+    tree = tc.TreeDict(zip(keys, range(len(keys))), key_type=key_type)
+
+    assert tree.keys_list() == sorted(keys)
+    assert list(tree) == sorted(keys)
+    assert tree.items_list() == sorted(zip(keys, range(len(keys))))
+    assert tree[keys[0]] == 0
+    assert keys[1] in tree
+
+    del tree[keys[1]]
+    assert keys[1] not in tree
+    assert len(tree) == len(keys) - 1
+
+  def test_classes(self):
+    assert isinstance(tc.TreeDict(key_type=int)._tree, PyIntBTreeMap)
+    assert isinstance(tc.TreeDict(key_type=float)._tree, PyFloatBTreeMap)
+    assert isinstance(tc.TreeDict(key_type=str)._tree, PyStrBTreeMap)
+
+    with pytest.raises(ValueError):
+      tc.TreeDict(key_type=bytes)
+    with pytest.raises(ValueError):
+      tc.TreeDict(key_type=int, aggregate="sum")
+
+  def test_mismatched_keys(self):
+    tree = tc.TreeDict({1: "a"}, key_type=int)
+
+    with pytest.raises(TypeError, match="expected a key of type 'int', got 'str'"):
+      tree["1"] = "b"
+    with pytest.raises(TypeError):
+      tree[1.5] = "b"
+    with pytest.raises(TypeError):
+      tc.TreeDict({"a": 1}, key_type=float)
+    with pytest.raises(TypeError):
+      tc.TreeDict({1: 1}, key_type=str)
+    with pytest.raises(OverflowError):
+      tree[2**64] = "b"
+
+    assert tree.to_dict() == {1: "a"}
+
+  def test_float_keys(self):
+    tree = tc.TreeDict(key_type=float)
+    tree[1] = "int"
+    tree[-0.0] = "zero"
+    tree[0.0] = "also zero"
+    tree[math.nan] = "nan"
+    tree[-math.inf] = "-inf"
+
+    assert tree.keys_list()[:3] == [-math.inf, 0.0, 1.0]
+    assert math.isnan(tree.keys_list()[3])
+    assert tree[0.0] == "also zero"
+    assert tree[math.nan] == "nan"
+
+  def test_iteration(self):
+    tree = tc.TreeDict(((i, str(i)) for i in range(1000)), key_type=int)
+
+    assert list(tree.keys()) == list(range(1000))
+    assert list(tree.values()) == [str(i) for i in range(1000)]
+    assert list(tree.items()) == [(i, str(i)) for i in range(1000)]
+    assert tree.nth(-1) == (999, "999")
+
+  def test_from_sorted(self):
+    tree = tc.TreeDict.from_sorted([("a", 1), ("b", 2)], key_type=str)
+    assert tree.items_list() == [("a", 1), ("b", 2)]
+
+    with pytest.raises(ValueError):
+      tc.TreeDict.from_sorted([("b", 1), ("a", 2)], key_type=str)
+
+  def test_bulk(self):
+    tree = tc.TreeDict(key_type=int)
+
+    assert tree.insert_many([(3, "c"), (1, "a"), (3, "d")]) == [None, None, "c"]
+    assert tree.get_many([3, 2, 1], "x") == ["d", "x", "a"]
+    assert tree.contains_many([1, 2]) == [True, False]
+    assert tree.remove_many([1, 2]) == ["a", None]
+
+  def test_copy_and_pickle(self):
+    tree = tc.TreeDict({"a": [1], "b": [2]}, key_type=str)
+
+    deep = copy.deepcopy(tree)
+    deep["a"].append(3)
+    assert tree["a"] == [1]
+
+    output = pickle.loads(pickle.dumps(tree))
+    assert isinstance(output._tree, PyStrBTreeMap)
+    assert output.items_list() == tree.items_list()
@@ -1,3 +1,4 @@
+import enum
 import itertools
 import random
 import time
@@ -128,3 +129,153 @@ class TestTreeCollections:
     for k, v in items:
       time.sleep(0.2)
       print(k, v)
+
+  def test_split_off(self):
+    tree = tc.TreeDict({i: str(i) for i in range(10)})
+    upper = tree.split_off(6)
+
+    assert list(tree.items()) == [(i, str(i)) for i in range(6)]
+    assert list(upper.items()) == [(i, str(i)) for i in range(6, 10)]
+
+  def test_append(self):
+    lower = tc.TreeDict({i: str(i) for i in range(5)})
+    upper = tc.TreeDict({i: str(i) for i in range(5, 10)})
+    lower.append(upper)
+
+    assert list(lower.items()) == [(i, str(i)) for i in range(10)]
+    assert len(upper) == 0
+
+    upper = tc.TreeDict({i: str(i) for i in range(5, 10)})
+    lower = tc.TreeDict({i: str(i) for i in range(5)})
+    upper.append(lower)
+
+    assert list(upper.items()) == [(i, str(i)) for i in range(10)]
+    assert len(lower) == 0
+
+  def test_merge(self):
+    a = tc.TreeDict({1: "a1", 2: "a2", 3: "a3"})
+    b = tc.TreeDict({2: "b2", 3: "b3", 4: "b4"})
+    a.merge(b)
+    assert list(a.items()) == [(1, "a1"), (2, "b2"), (3, "b3"), (4, "b4")]
+    assert len(b) == 0
+
+    a = tc.TreeDict({1: "a1", 2: "a2", 3: "a3"})
+    b = tc.TreeDict({2: "b2", 3: "b3", 4: "b4"})
+    a.merge(b, on_conflict="keep")
+    assert list(a.items()) == [(1, "a1"), (2, "a2"), (3, "a3"), (4, "b4")]
+
+    a = tc.TreeDict({1: 1, 2: 2})
+    b = tc.TreeDict({2: 20, 3: 30})
+    a.merge(b, on_conflict=lambda key, x, y: x + y)
+    assert list(a.items()) == [(1, 1), (2, 22), (3, 30)]
+
+  def test_merge_invalid_on_conflict(self):
+    a = tc.TreeDict({1: 1})
+    b = tc.TreeDict({1: 2})
+
+    with pytest.raises(ValueError):
+      a.merge(b, on_conflict="sum")
+
+    with pytest.raises(TypeError):
+      a.merge(b, on_conflict=1)
+
+  def test_merge_callable_error(self):
+    a = tc.TreeDict({1: 1, 2: 2})
+    b = tc.TreeDict({2: 20, 3: 30})
+
+    def fail(key, x, y):
+      raise RuntimeError
+
+    with pytest.raises(RuntimeError):
+      a.merge(b, on_conflict=fail)
+
+    assert list(a.items()) == [(1, 1), (2, 2)]
+    assert list(b.items()) == [(2, 20), (3, 30)]
+
+  def test_from_sorted(self):
+    tree = tc.TreeDict.from_sorted([(i, str(i)) for i in range(100)])
+    assert list(tree.items()) == [(i, str(i)) for i in range(100)]
+
+    tree = tc.TreeDict.from_sorted({1: "a", 2: "b"})
+    assert list(tree.items()) == [(1, "a"), (2, "b")]
+
+    with pytest.raises(ValueError, match="index 2"):
+      tc.TreeDict.from_sorted([(1, "a"), (2, "b"), (0, "c")])
+
+    tree = tc.TreeDict.from_sorted([(2, "b"), (1, "a")], validate=False)
+    assert list(tree.items()) == [(1, "a"), (2, "b")]
+
+  def test_constructor_duplicates(self):
+    tree = tc.TreeDict([(1, "a"), (2, "b"), (1.0, "c")])
+    assert list(tree.items()) == [(1, "c"), (2, "b")]
+    assert type(tree.nth(0)[0]) is int
+
+  def test_batch(self):
+    tree = tc.TreeDict({1: "a"})
+
+    assert tree.insert_many([(3, "c"), (1, "x"), (2, "b"), (3, "d")]) == [
+        None,
+        "a",
+        None,
+        "c",
+    ]
+    assert list(tree.items()) == [(1, "x"), (2, "b"), (3, "d")]
+
+    tree.update_many({4: "e", 0: "z"})
+    assert list(tree) == [0, 1, 2, 3, 4]
+
+    assert tree.get_many([4, 9, 0]) == ["e", None, "z"]
+    assert tree.get_many([4, 9], default="?") == ["e", "?"]
+    assert tree.contains_many([9, 1, 2]) == [False, True, True]
+
+    assert tree.remove_many([2, 9, 2]) == ["b", None, None]
+    assert list(tree) == [0, 1, 3, 4]
+
+  def test_export(self):
+    tree = tc.TreeDict({3: "c", 1: "a", 2: "b"})
+
+    assert tree.keys_list() == [1, 2, 3]
+    assert tree.values_list() == ["a", "b", "c"]
+    assert tree.items_list() == [(1, "a"), (2, "b"), (3, "c")]
+    assert tree.to_dict() == {1: "a", 2: "b", 3: "c"}
+    assert list(tree.to_dict()) == [1, 2, 3]
+
+    tree = tc.TreeDict({(1, 2, 3): "a", (4, 5, 6): "b"})
+    assert tree.keys_list() == [(1, 2, 3), (4, 5, 6)]
+    assert tree.to_dict() == {(1, 2, 3): "a", (4, 5, 6): "b"}
+    assert tree.snapshot().to_dict() == {(1, 2, 3): "a", (4, 5, 6): "b"}
+
+  def test_chunks(self):
+    tree = tc.TreeDict({i: str(i) for i in range(5)})
+
+    assert list(tree.keys().chunks(2)) == [[0, 1], [2, 3], [4]]
+    assert list(tree.values().chunks(3)) == [["0", "1", "2"], ["3", "4"]]
+    assert list(tree.items().chunks(5)) == [[(i, str(i)) for i in range(5)]]
+
+    items = tree.items()
+    next(items)
+    assert list(items.chunks(10)) == [[(i, str(i)) for i in range(1, 5)]]
+
+    with pytest.raises(ValueError):
+      tree.keys().chunks(0)
+
+  def test_key_subclasses(self):
+    class Color(enum.IntEnum):
+      RED = 1
+      GREEN = 2
+
+    tree = tc.TreeDict({Color.GREEN: "green", Color.RED: "red"})
+    assert tree.keys_list() == [Color.RED, Color.GREEN]
+    assert all(type(key) is Color for key in tree)
+
+    tree = tc.TreeDict({True: 1, False: 0})
+    assert tree.keys_list() == [False, True]
+    assert all(type(key) is bool for key in tree)
+
+  def test_key_named_like_builtin(self):
+    # only the builtin types themselves are converted, not lookalikes
+    Fake = type("int", (), {"__lt__": lambda a, b: id(a) < id(b)})
+    key = Fake()
+    tree = tc.TreeDict({key: 1})
+
+    assert tree.keys_list()[0] is key
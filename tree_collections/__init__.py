@@ -1,3 +1,10 @@
 from .tree_dict import TreeDict as TreeDict
+from .tree_dict import TreeDictSnapshot as TreeDictSnapshot
+from .tree_dict import FrozenTreeDict as FrozenTreeDict
+from .tree_dict import DiskTreeDict as DiskTreeDict
 from .tree_set import TreeSet as TreeSet
+from .tree_set import FrozenTreeSet as FrozenTreeSet
 from .tree_seq import TreeSeq as TreeSeq
+from .tree_multi_dict import TreeMultiDict as TreeMultiDict
+from .interval_tree import IntervalTree as IntervalTree
+from .range_map import RangeMap as RangeMap
@@ -0,0 +1,52 @@
+from tree_collections.tree_collections import PyIntervalTree
+import typing as tp
+
+K = tp.TypeVar("K")
+V = tp.TypeVar("V")
+
+
+class IntervalTree(tp.Generic[K, V]):
+  if tp.TYPE_CHECKING:
+    _tree: PyIntervalTree[K, V]
+
+  def __init__(self, intervals: tp.Optional[tp.Iterable[tp.Tuple[K, K, V]]] = None):
+    if intervals is not None:
+      self._tree = PyIntervalTree(intervals)
+    else:
+      self._tree = PyIntervalTree()
+
+  @classmethod
+  def _from_tree(cls, tree: "PyIntervalTree[K, V]") -> "IntervalTree[K, V]":
+    output = cls.__new__(cls)
+    output._tree = tree
+    return output
+
+  def copy(self) -> "IntervalTree[K, V]":
+    return IntervalTree._from_tree(self._tree.copy())
+
+  def __copy__(self) -> "IntervalTree[K, V]":
+    return self.copy()
+
+  def __deepcopy__(self, memo: tp.Dict[int, tp.Any]) -> "IntervalTree[K, V]":
+    return IntervalTree._from_tree(self._tree.__deepcopy__(memo))
+
+  def __iter__(self) -> tp.Iterator[tp.Tuple[K, K, V]]:
+    return self._tree.iter()
+
+  def __len__(self) -> int:
+    return self._tree.len()
+
+  def add(self, start: K, end: K, value: V) -> None:
+    self._tree.add(start, end, value)
+
+  def remove(self, start: K, end: K) -> tp.Optional[V]:
+    return self._tree.remove(start, end)
+
+  def overlap(self, start: K, end: K) -> tp.List[tp.Tuple[K, K, V]]:
+    return self._tree.overlap(start, end)
+
+  def at(self, point: K) -> tp.List[tp.Tuple[K, K, V]]:
+    return self._tree.at(point)
+
+  def clear(self) -> None:
+    self._tree.clear()
@@ -0,0 +1,66 @@
+from tree_collections.tree_collections import PyRangeMap
+import typing as tp
+
+K = tp.TypeVar("K")
+V = tp.TypeVar("V")
+T = tp.TypeVar("T")
+
+
+class RangeMap(tp.Generic[K, V]):
+  if tp.TYPE_CHECKING:
+    _tree: PyRangeMap[K, V]
+
+  def __init__(self, ranges: tp.Optional[tp.Iterable[tp.Tuple[K, K, V]]] = None):
+    if ranges is not None:
+      self._tree = PyRangeMap(ranges)
+    else:
+      self._tree = PyRangeMap()
+
+  @classmethod
+  def _from_tree(cls, tree: "PyRangeMap[K, V]") -> "RangeMap[K, V]":
+    output = cls.__new__(cls)
+    output._tree = tree
+    return output
+
+  def copy(self) -> "RangeMap[K, V]":
+    return RangeMap._from_tree(self._tree.copy())
+
+  def __copy__(self) -> "RangeMap[K, V]":
+    return self.copy()
+
+  def __deepcopy__(self, memo: tp.Dict[int, tp.Any]) -> "RangeMap[K, V]":
+    return RangeMap._from_tree(self._tree.__deepcopy__(memo))
+
+  def __getitem__(self, point: K) -> V:
+    return self._tree[point]
+
+  def __contains__(self, point: object) -> bool:
+    try:
+      self._tree[point]
+    except KeyError:
+      return False
+    return True
+
+  def __iter__(self) -> tp.Iterator[tp.Tuple[K, K, V]]:
+    return iter(self._tree.iter())
+
+  def __len__(self) -> int:
+    return self._tree.len()
+
+  def get(self, point: K, default: tp.Optional[T] = None) -> tp.Union[V, T, None]:
+    try:
+      return self._tree[point]
+    except KeyError:
+      return default
+
+  def set_range(self, start: K, end: K, value: V) -> None:
+    self._tree.set_range(start, end, value)
+
+  def remove_range(self, start: K, end: K) -> None:
+    self._tree.remove_range(start, end)
+
+  def gaps(self, start: K, end: K) -> tp.List[tp.Tuple[K, K]]:
+    return self._tree.gaps(start, end)
+
+  def clear(self) -> None:
+    self._tree.clear()
@@ -1,35 +1,311 @@
+import os
 import typing as tp
 
 K = tp.TypeVar("K")
 V = tp.TypeVar("V")
+T = tp.TypeVar("T")
+
+Aggregate = tp.Literal["sum", "min", "max", "count"]
+
+class ChunkableIterator(tp.Iterator[T]):
+    def __next__(self) -> T: ...
+    # fn chunks(slf: PyRef<Self>, n: usize) -> PyResult<PyChunksIter>
+    def chunks(self, n: int) -> tp.Iterator[list[T]]: ...
 
 class PyBTreeMap(tp.Generic[K, V]):
     def __init__(
         self,
         other: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]], None] = None,
+        aggregate: tp.Optional[Aggregate] = None,
     ) -> None: ...
+    @classmethod
+    def from_sorted(
+        cls,
+        other: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]]],
+        validate: bool = True,
+        aggregate: tp.Optional[Aggregate] = None,
+    ) -> PyBTreeMap[K, V]: ...
+    # fn aggregate(&self) -> Option<&'static str>
+    @property
+    def aggregate(self) -> tp.Optional[Aggregate]: ...
     def insert(self, key: K, value: V) -> tp.Optional[V]: ...
     def get(self, key: K) -> tp.Optional[V]: ...
     def remove(self, key: K) -> tp.Optional[V]: ...
     def contains_key(self, key: object) -> bool: ...
+    def insert_many(
+        self, items: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]]]
+    ) -> list[tp.Optional[V]]: ...
+    def update_many(
+        self, items: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]]]
+    ) -> None: ...
+    def get_many(
+        self, keys: tp.Iterable[K], default: tp.Optional[T] = None
+    ) -> list[tp.Union[V, T, None]]: ...
+    def contains_many(self, keys: tp.Iterable[object]) -> list[bool]: ...
+    def remove_many(self, keys: tp.Iterable[K]) -> list[tp.Optional[V]]: ...
     def nth(self, n: int) -> tp.Optional[tuple[K, V]]: ...
     # fn len(&self) -> usize
     def len(self) -> int: ...
     # fn is_empty(&self) -> bool
     def is_empty(self) -> bool: ...
-    # fn clear(&mut self)
+    # fn clear(&mut self) -> PyResult<()>
     def clear(self) -> None: ...
+    # fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes>
+    def to_bytes(self) -> bytes: ...
+    # fn from_bytes(_cls: &PyType, data: &[u8], aggregate: Option<AggregateKind>) -> PyResult<Self>
+    @classmethod
+    def from_bytes(
+        cls, data: bytes, aggregate: tp.Optional[Aggregate] = None
+    ) -> PyBTreeMap[K, V]: ...
+    # fn save(&self, path: PathBuf) -> PyResult<()>
+    def save(self, path: tp.Union[str, os.PathLike[str]]) -> None: ...
+    # fn load(_cls: &PyType, path: PathBuf, aggregate: Option<AggregateKind>) -> PyResult<Self>
+    @classmethod
+    def load(
+        cls,
+        path: tp.Union[str, os.PathLike[str]],
+        aggregate: tp.Optional[Aggregate] = None,
+    ) -> PyBTreeMap[K, V]: ...
+    # fn open(_cls: &PyType, path: PathBuf, cache_size: usize) -> PyResult<PyDiskBTreeMap>
+    @classmethod
+    def open(
+        cls, path: tp.Union[str, os.PathLike[str]], cache_size: int = 1024
+    ) -> PyDiskBTreeMap[K, V]: ...
+    # fn recover(_cls: &PyType, log_path: PathBuf, sync: SyncPolicy, compact_every: Option<usize>, aggregate: Option<AggregateKind>) -> PyResult<Self>
+    @classmethod
+    def recover(
+        cls,
+        log_path: tp.Union[str, os.PathLike[str]],
+        sync: tp.Union[str, float] = "always",
+        compact_every: tp.Optional[int] = None,
+        aggregate: tp.Optional[Aggregate] = None,
+    ) -> PyBTreeMap[K, V]: ...
+    # fn compact_log(&mut self) -> PyResult<()>
+    def compact_log(self) -> None: ...
+    # fn sync_log(&mut self) -> PyResult<()>
+    def sync_log(self) -> None: ...
+    # fn close_log(&mut self) -> PyResult<()>
+    def close_log(self) -> None: ...
+    # fn to_json(&self) -> PyResult<String>
+    def to_json(self) -> str: ...
+    # fn from_json(_cls: &PyType, data: &str, aggregate: Option<AggregateKind>) -> PyResult<Self>
+    @classmethod
+    def from_json(
+        cls, data: str, aggregate: tp.Optional[Aggregate] = None
+    ) -> PyBTreeMap[K, V]: ...
+    # fn save_json(&self, path: PathBuf) -> PyResult<()>
+    def save_json(self, path: tp.Union[str, os.PathLike[str]]) -> None: ...
+    # fn load_json(_cls: &PyType, path: PathBuf, aggregate: Option<AggregateKind>) -> PyResult<Self>
+    @classmethod
+    def load_json(
+        cls,
+        path: tp.Union[str, os.PathLike[str]],
+        aggregate: tp.Optional[Aggregate] = None,
+    ) -> PyBTreeMap[K, V]: ...
+    # fn freeze(&self, py: Python) -> PyFrozenBTreeMap
+    def freeze(self) -> PyFrozenBTreeMap[K, V]: ...
+    # fn snapshot(&self, py: Python) -> PyResult<PyBTreeMapSnapshot>
+    def snapshot(self) -> PyBTreeMapSnapshot[K, V]: ...
+    # fn range_aggregate(&self, py: Python, start: Option<PyObject>, stop: Option<PyObject>) -> PyResult<PyObject>
+    def range_aggregate(
+        self, start: tp.Optional[K] = None, stop: tp.Optional[K] = None
+    ) -> tp.Union[int, float, None]: ...
+    # fn prefix_aggregate(&self, py: Python, key: PyObject) -> PyResult<PyObject>
+    def prefix_aggregate(self, key: K) -> tp.Union[int, float, None]: ...
+    # fn keys(slf: PyRef<'_, Self>) -> PyBTreeMapKeys
+    def keys(self) -> tp.KeysView[K]: ...
+    # fn values(slf: PyRef<'_, Self>) -> PyBTreeMapValues
+    def values(self) -> tp.ValuesView[V]: ...
+    # fn items(slf: PyRef<'_, Self>) -> PyBTreeMapIter
+    def items(self) -> tp.ItemsView[K, V]: ...
+    # fn keys_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def keys_list(self) -> list[K]: ...
+    # fn values_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def values_list(self) -> list[V]: ...
+    # fn items_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def items_list(self) -> list[tuple[K, V]]: ...
+    # fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict>
+    def to_dict(self) -> dict[K, V]: ...
+    # fn split_off(slf: PyRefMut<'_, Self>, key: PyObject) -> PyResult<Self>
+    def split_off(self, key: K) -> PyBTreeMap[K, V]: ...
+    # fn append(slf: PyRefMut<'_, Self>, other: PyRefMut<'_, Self>) -> PyResult<()>
+    def append(self, other: PyBTreeMap[K, V]) -> None: ...
+    # fn merge(slf: PyRefMut<'_, Self>, other: PyRefMut<'_, Self>, on_conflict: OnConflict) -> PyResult<()>
+    def merge(
+        self,
+        other: PyBTreeMap[K, V],
+        on_conflict: tp.Union[
+            tp.Literal["replace", "keep"], tp.Callable[[K, V, V], V]
+        ] = "replace",
+    ) -> None: ...
+    def copy(self) -> PyBTreeMap[K, V]: ...
+    def __copy__(self) -> PyBTreeMap[K, V]: ...
+    def __deepcopy__(self, memo: dict[int, tp.Any]) -> PyBTreeMap[K, V]: ...
+    def __getstate__(self) -> tp.Any: ...
+    def __setstate__(self, state: tp.Any) -> None: ...
+    def __reduce__(self) -> tuple[tp.Any, ...]: ...
+
+# PyIntBTreeMap, PyFloatBTreeMap and PyStrBTreeMap share their methods, they
+# are generated by the `typed_btree_map!` macro
+class _PyTypedBTreeMap(tp.Generic[K, V]):
+    # fn new(input: Option<PyObject>, py: Python) -> PyResult<Self>
+    def __init__(
+        self,
+        other: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]], None] = None,
+    ) -> None: ...
+    # fn from_sorted(_cls: &Bound<'_, PyType>, input: PyObject, validate: bool, py: Python) -> PyResult<Self>
+    @classmethod
+    def from_sorted(
+        cls,
+        other: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]]],
+        validate: bool = True,
+    ) -> tp.Self: ...
+    def insert(self, key: K, value: V) -> tp.Optional[V]: ...
+    def get(self, key: K) -> tp.Optional[V]: ...
+    def remove(self, key: K) -> tp.Optional[V]: ...
+    def contains_key(self, key: K) -> bool: ...
+    def insert_many(
+        self, items: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]]]
+    ) -> list[tp.Optional[V]]: ...
+    def update_many(
+        self, items: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]]]
+    ) -> None: ...
+    def get_many(
+        self, keys: tp.Iterable[K], default: tp.Optional[T] = None
+    ) -> list[tp.Union[V, T, None]]: ...
+    def contains_many(self, keys: tp.Iterable[K]) -> list[bool]: ...
+    def remove_many(self, keys: tp.Iterable[K]) -> list[tp.Optional[V]]: ...
+    def nth(self, n: int) -> tp.Optional[tuple[K, V]]: ...
+    def len(self) -> int: ...
+    def is_empty(self) -> bool: ...
+    def clear(self) -> None: ...
+    def keys(self) -> tp.KeysView[K]: ...
+    def values(self) -> tp.ValuesView[V]: ...
+    def items(self) -> tp.ItemsView[K, V]: ...
+    def keys_list(self) -> list[K]: ...
+    def values_list(self) -> list[V]: ...
+    def items_list(self) -> list[tuple[K, V]]: ...
+    def to_dict(self) -> dict[K, V]: ...
+    def copy(self) -> tp.Self: ...
+    def __copy__(self) -> tp.Self: ...
+    def __deepcopy__(self, memo: dict[int, tp.Any]) -> tp.Self: ...
+    def __getstate__(self) -> tp.Any: ...
+    def __setstate__(self, state: tp.Any) -> None: ...
+    def __reduce__(self) -> tuple[tp.Any, ...]: ...
+
+class PyIntBTreeMap(_PyTypedBTreeMap[int, V]): ...
+class PyFloatBTreeMap(_PyTypedBTreeMap[float, V]): ...
+class PyStrBTreeMap(_PyTypedBTreeMap[str, V]): ...
+
+class PyDiskBTreeMap(tp.Generic[K, V]):
+    # pub fn get(&mut self, key: PyObject, py: Python) -> PyResult<Option<PyObject>>
+    def get(self, key: K) -> tp.Optional[V]: ...
+    # pub fn __getitem__(&mut self, key: PyObject, py: Python) -> PyResult<PyObject>
+    def __getitem__(self, key: K) -> V: ...
+    # pub fn contains_key(&mut self, key: PyObject, py: Python) -> PyResult<bool>
+    def contains_key(self, key: object) -> bool: ...
+    # pub fn insert(&mut self, key: PyObject, value: PyObject, py: Python) -> PyResult<Option<PyObject>>
+    def insert(self, key: K, value: V) -> tp.Optional[V]: ...
+    # pub fn remove(&mut self, key: PyObject, py: Python) -> PyResult<Option<PyObject>>
+    def remove(self, key: K) -> tp.Optional[V]: ...
+    # pub fn len(&mut self) -> PyResult<usize>
+    def len(self) -> int: ...
+    # pub fn is_empty(&mut self) -> PyResult<bool>
+    def is_empty(self) -> bool: ...
+    # pub fn range(slf: PyRef<'_, Self>, start: Option<PyObject>, stop: Option<PyObject>) -> PyResult<PyDiskBTreeMapRange>
+    def range(
+        self, start: tp.Optional[K] = None, stop: tp.Optional[K] = None
+    ) -> tp.Iterator[tuple[K, V]]: ...
+    # pub fn flush(&mut self) -> PyResult<()>
+    def flush(self) -> None: ...
+    # pub fn close(&mut self) -> PyResult<()>
+    def close(self) -> None: ...
+    # pub fn closed(&self) -> bool
+    @property
+    def closed(self) -> bool: ...
+
+class PyBTreeMapSnapshot(tp.Generic[K, V]):
+    def get(self, key: K) -> tp.Optional[V]: ...
+    def __getitem__(self, key: K) -> V: ...
+    def contains_key(self, key: object) -> bool: ...
+    # fn len(&self) -> usize
+    def len(self) -> int: ...
+    # fn is_empty(&self) -> bool
+    def is_empty(self) -> bool: ...
+    # fn keys_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def keys_list(self) -> list[K]: ...
+    # fn values_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def values_list(self) -> list[V]: ...
+    # fn items_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def items_list(self) -> list[tuple[K, V]]: ...
+    # fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict>
+    def to_dict(self) -> dict[K, V]: ...
+    # fn keys(&self) -> PyBTreeMapSnapshotIter
+    def keys(self) -> tp.Iterator[K]: ...
+    # fn values(&self) -> PyBTreeMapSnapshotIter
+    def values(self) -> tp.Iterator[V]: ...
+    # fn items(&self) -> PyBTreeMapSnapshotIter
+    def items(self) -> tp.Iterator[tuple[K, V]]: ...
+    def copy(self) -> PyBTreeMapSnapshot[K, V]: ...
+    def __copy__(self) -> PyBTreeMapSnapshot[K, V]: ...
+    def __deepcopy__(self, memo: dict[int, tp.Any]) -> PyBTreeMapSnapshot[K, V]: ...
+
+class PyFrozenBTreeMap(tp.Generic[K, V]):
+    def __init__(
+        self,
+        other: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]], None] = None,
+    ) -> None: ...
+    @classmethod
+    def from_sorted(
+        cls,
+        other: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]]],
+        validate: bool = True,
+    ) -> PyFrozenBTreeMap[K, V]: ...
+    def get(self, key: K) -> tp.Optional[V]: ...
+    def __getitem__(self, key: K) -> V: ...
+    def contains_key(self, key: object) -> bool: ...
+    def get_many(
+        self, keys: tp.Iterable[K], default: tp.Optional[T] = None
+    ) -> list[tp.Union[V, T, None]]: ...
+    def contains_many(self, keys: tp.Iterable[object]) -> list[bool]: ...
+    def nth(self, n: int) -> tp.Optional[tuple[K, V]]: ...
+    # fn len(&self) -> usize
+    def len(self) -> int: ...
+    # fn is_empty(&self) -> bool
+    def is_empty(self) -> bool: ...
+    # fn keys_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def keys_list(self) -> list[K]: ...
+    # fn values_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def values_list(self) -> list[V]: ...
+    # fn items_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def items_list(self) -> list[tuple[K, V]]: ...
+    # fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict>
+    def to_dict(self) -> dict[K, V]: ...
+    # fn thaw(&self, py: Python) -> PyResult<PyBTreeMap>
+    def thaw(self) -> PyBTreeMap[K, V]: ...
+    def __hash__(self) -> int: ...
+    def __eq__(self, other: object) -> bool: ...
     # fn keys(slf: PyRef<'_, Self>) -> PyBTreeMapKeys
     def keys(self) -> tp.KeysView[K]: ...
     # fn values(slf: PyRef<'_, Self>) -> PyBTreeMapValues
     def values(self) -> tp.ValuesView[V]: ...
     # fn items(slf: PyRef<'_, Self>) -> PyBTreeMapIter
     def items(self) -> tp.ItemsView[K, V]: ...
+    def copy(self) -> PyFrozenBTreeMap[K, V]: ...
+    def __copy__(self) -> PyFrozenBTreeMap[K, V]: ...
+    def __deepcopy__(self, memo: dict[int, tp.Any]) -> PyFrozenBTreeMap[K, V]: ...
+    def __getstate__(self) -> tp.Any: ...
+    def __reduce__(self) -> tuple[tp.Any, ...]: ...
 
 class PyBTreeSet(tp.Generic[K]):
 
     # pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self>
     def __init__(self, input: tp.Optional[tp.Iterable[K]] = None) -> None: ...
+    # pub fn from_sorted(_cls: &PyType, input: PyObject, validate: bool, py: Python) -> PyResult<Self>
+    @classmethod
+    def from_sorted(
+        cls, input: tp.Iterable[K], validate: bool = True
+    ) -> PyBTreeSet[K]: ...
     # pub fn insert(mut slf: PyRefMut<'_, Self>, key: PyObject) -> PyResult<bool>
     def insert(self, key: K) -> bool: ...
     # pub fn get(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<Option<PyObject>>
@@ -38,6 +314,18 @@ class PyBTreeSet(tp.Generic[K]):
     def remove(self, key: K) -> bool: ...
     # pub fn contains(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<bool>
     def contains(self, key: object) -> bool: ...
+    # pub fn insert_many(slf: PyRefMut<'_, Self>, input: PyObject) -> PyResult<Vec<bool>>
+    def insert_many(self, input: tp.Iterable[K]) -> list[bool]: ...
+    # pub fn update_many(slf: PyRefMut<'_, Self>, input: PyObject) -> PyResult<()>
+    def update_many(self, input: tp.Iterable[K]) -> None: ...
+    # pub fn get_many(slf: PyRef<'_, Self>, keys: PyObject, default: Option<PyObject>) -> PyResult<Vec<Option<PyObject>>>
+    def get_many(
+        self, keys: tp.Iterable[K], default: tp.Optional[T] = None
+    ) -> list[tp.Union[K, T, None]]: ...
+    # pub fn contains_many(slf: PyRef<'_, Self>, keys: PyObject) -> PyResult<Vec<bool>>
+    def contains_many(self, keys: tp.Iterable[object]) -> list[bool]: ...
+    # pub fn remove_many(slf: PyRefMut<'_, Self>, keys: PyObject) -> PyResult<Vec<bool>>
+    def remove_many(self, keys: tp.Iterable[K]) -> list[bool]: ...
     # pub fn nth(slf: PyRef<'_, Self>, n: i64) -> PyResult<Option<PyObject>>
     def nth(self, n: int) -> tp.Optional[K]: ...
     # pub fn len(&self) -> usize
@@ -47,12 +335,90 @@ class PyBTreeSet(tp.Generic[K]):
     # pub fn clear(&mut self)
     def clear(self) -> None: ...
     # pub fn iter(slf: PyRef<'_, Self>) -> PyBTreeSetIter
-    def iter(self) -> tp.Iterator[K]: ...
+    def iter(self) -> ChunkableIterator[K]: ...
+    # pub fn to_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def to_list(self) -> list[K]: ...
+    # pub fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes>
+    def to_bytes(self) -> bytes: ...
+    # pub fn from_bytes(_cls: &PyType, data: &[u8]) -> PyResult<Self>
+    @classmethod
+    def from_bytes(cls, data: bytes) -> PyBTreeSet[K]: ...
+    # pub fn save(&self, path: PathBuf) -> PyResult<()>
+    def save(self, path: tp.Union[str, os.PathLike[str]]) -> None: ...
+    # pub fn load(_cls: &PyType, path: PathBuf) -> PyResult<Self>
+    @classmethod
+    def load(cls, path: tp.Union[str, os.PathLike[str]]) -> PyBTreeSet[K]: ...
+    # pub fn to_json(&self) -> PyResult<String>
+    def to_json(self) -> str: ...
+    # pub fn from_json(_cls: &PyType, data: &str) -> PyResult<Self>
+    @classmethod
+    def from_json(cls, data: str) -> PyBTreeSet[K]: ...
+    # pub fn save_json(&self, path: PathBuf) -> PyResult<()>
+    def save_json(self, path: tp.Union[str, os.PathLike[str]]) -> None: ...
+    # pub fn load_json(_cls: &PyType, path: PathBuf) -> PyResult<Self>
+    @classmethod
+    def load_json(cls, path: tp.Union[str, os.PathLike[str]]) -> PyBTreeSet[K]: ...
+    # pub fn freeze(&self, py: Python) -> PyFrozenBTreeSet
+    def freeze(self) -> PyFrozenBTreeSet[K]: ...
+    # pub fn split_off(slf: PyRefMut<'_, Self>, key: PyObject) -> PyResult<Self>
+    def split_off(self, key: K) -> PyBTreeSet[K]: ...
+    # pub fn append(slf: PyRefMut<'_, Self>, other: PyRefMut<'_, Self>) -> PyResult<()>
+    def append(self, other: PyBTreeSet[K]) -> None: ...
+    # pub fn merge(slf: PyRefMut<'_, Self>, other: PyRefMut<'_, Self>, on_conflict: OnConflict) -> PyResult<()>
+    def merge(
+        self,
+        other: PyBTreeSet[K],
+        on_conflict: tp.Union[
+            tp.Literal["replace", "keep"], tp.Callable[[K, K], K]
+        ] = "replace",
+    ) -> None: ...
+    def copy(self) -> PyBTreeSet[K]: ...
+    def __copy__(self) -> PyBTreeSet[K]: ...
+    def __deepcopy__(self, memo: dict[int, tp.Any]) -> PyBTreeSet[K]: ...
+    def __getstate__(self) -> tp.Any: ...
+    def __setstate__(self, state: tp.Any) -> None: ...
+    def __reduce__(self) -> tuple[tp.Any, ...]: ...
+
+class PyFrozenBTreeSet(tp.Generic[K]):
+    def __init__(self, other: tp.Optional[tp.Iterable[K]] = None) -> None: ...
+    @classmethod
+    def from_sorted(
+        cls, other: tp.Iterable[K], validate: bool = True
+    ) -> PyFrozenBTreeSet[K]: ...
+    def get(self, key: K) -> tp.Optional[K]: ...
+    def contains(self, key: object) -> bool: ...
+    def get_many(
+        self, keys: tp.Iterable[K], default: tp.Optional[T] = None
+    ) -> list[tp.Union[K, T, None]]: ...
+    def contains_many(self, keys: tp.Iterable[object]) -> list[bool]: ...
+    def nth(self, n: int) -> tp.Optional[K]: ...
+    # pub fn len(&self) -> usize
+    def len(self) -> int: ...
+    # pub fn is_empty(&self) -> bool
+    def is_empty(self) -> bool: ...
+    # pub fn to_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def to_list(self) -> list[K]: ...
+    # pub fn thaw(&self, py: Python) -> PyBTreeSet
+    def thaw(self) -> PyBTreeSet[K]: ...
+    def __hash__(self) -> int: ...
+    def __eq__(self, other: object) -> bool: ...
+    # pub fn iter(slf: PyRef<'_, Self>) -> PyBTreeSetIter
+    def iter(self) -> ChunkableIterator[K]: ...
+    def copy(self) -> PyFrozenBTreeSet[K]: ...
+    def __copy__(self) -> PyFrozenBTreeSet[K]: ...
+    def __deepcopy__(self, memo: dict[int, tp.Any]) -> PyFrozenBTreeSet[K]: ...
+    def __getstate__(self) -> tp.Any: ...
+    def __reduce__(self) -> tuple[tp.Any, ...]: ...
 
 class PyBTreeSeq(tp.Generic[K]):
 
     # pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self>
     def __init__(self, input: tp.Optional[tp.Iterable[K]] = None) -> None: ...
+    # pub fn from_sorted(_cls: &PyType, input: PyObject, validate: bool, py: Python) -> PyResult<Self>
+    @classmethod
+    def from_sorted(
+        cls, input: tp.Iterable[K], validate: bool = True
+    ) -> PyBTreeSeq[K]: ...
     # pub fn insert(mut slf: PyRefMut<'_, Self>, key: PyObject) -> PyResult<bool>
     def insert(self, key: K) -> bool: ...
     # pub fn get(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<Option<PyObject>>
@@ -61,6 +427,18 @@ class PyBTreeSeq(tp.Generic[K]):
     def remove(self, key: K) -> bool: ...
     # pub fn contains(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<bool>
     def contains(self, key: object) -> bool: ...
+    # pub fn insert_many(slf: PyRefMut<'_, Self>, input: PyObject) -> PyResult<Vec<bool>>
+    def insert_many(self, input: tp.Iterable[K]) -> list[bool]: ...
+    # pub fn update_many(slf: PyRefMut<'_, Self>, input: PyObject) -> PyResult<()>
+    def update_many(self, input: tp.Iterable[K]) -> None: ...
+    # pub fn get_many(slf: PyRef<'_, Self>, keys: PyObject, default: Option<PyObject>) -> PyResult<Vec<Option<PyObject>>>
+    def get_many(
+        self, keys: tp.Iterable[K], default: tp.Optional[T] = None
+    ) -> list[tp.Union[K, T, None]]: ...
+    # pub fn contains_many(slf: PyRef<'_, Self>, keys: PyObject) -> PyResult<Vec<bool>>
+    def contains_many(self, keys: tp.Iterable[object]) -> list[bool]: ...
+    # pub fn remove_many(slf: PyRefMut<'_, Self>, keys: PyObject) -> PyResult<Vec<bool>>
+    def remove_many(self, keys: tp.Iterable[K]) -> list[bool]: ...
     # pub fn nth(slf: PyRef<'_, Self>, n: i64) -> PyResult<Option<PyObject>>
     def nth(self, n: int) -> tp.Optional[K]: ...
     # pub fn len(&self) -> usize
@@ -70,4 +448,140 @@ class PyBTreeSeq(tp.Generic[K]):
     # pub fn clear(&mut self)
     def clear(self) -> None: ...
     # pub fn iter(slf: PyRef<'_, Self>) -> PyBTreeSetIter
-    def iter(self) -> tp.Iterator[K]: ...
+    def iter(self) -> ChunkableIterator[K]: ...
+    # pub fn to_list<'py>(&self, py: Python<'py>) -> &'py PyList
+    def to_list(self) -> list[K]: ...
+    # pub fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes>
+    def to_bytes(self) -> bytes: ...
+    # pub fn from_bytes(_cls: &PyType, data: &[u8]) -> PyResult<Self>
+    @classmethod
+    def from_bytes(cls, data: bytes) -> PyBTreeSeq[K]: ...
+    # pub fn save(&self, path: PathBuf) -> PyResult<()>
+    def save(self, path: tp.Union[str, os.PathLike[str]]) -> None: ...
+    # pub fn load(_cls: &PyType, path: PathBuf) -> PyResult<Self>
+    @classmethod
+    def load(cls, path: tp.Union[str, os.PathLike[str]]) -> PyBTreeSeq[K]: ...
+    # pub fn to_json(&self) -> PyResult<String>
+    def to_json(self) -> str: ...
+    # pub fn from_json(_cls: &PyType, data: &str) -> PyResult<Self>
+    @classmethod
+    def from_json(cls, data: str) -> PyBTreeSeq[K]: ...
+    # pub fn save_json(&self, path: PathBuf) -> PyResult<()>
+    def save_json(self, path: tp.Union[str, os.PathLike[str]]) -> None: ...
+    # pub fn load_json(_cls: &PyType, path: PathBuf) -> PyResult<Self>
+    @classmethod
+    def load_json(cls, path: tp.Union[str, os.PathLike[str]]) -> PyBTreeSeq[K]: ...
+    # pub fn split_off(slf: PyRefMut<'_, Self>, key: PyObject) -> PyResult<Self>
+    def split_off(self, key: K) -> PyBTreeSeq[K]: ...
+    # pub fn append(slf: PyRefMut<'_, Self>, other: PyRefMut<'_, Self>) -> PyResult<()>
+    def append(self, other: PyBTreeSeq[K]) -> None: ...
+    # pub fn merge(slf: PyRefMut<'_, Self>, other: PyRefMut<'_, Self>, on_conflict: OnConflict) -> PyResult<()>
+    def merge(
+        self,
+        other: PyBTreeSeq[K],
+        on_conflict: tp.Union[
+            tp.Literal["replace", "keep"], tp.Callable[[K, K], K]
+        ] = "replace",
+    ) -> None: ...
+    def copy(self) -> PyBTreeSeq[K]: ...
+    def __copy__(self) -> PyBTreeSeq[K]: ...
+    def __deepcopy__(self, memo: dict[int, tp.Any]) -> PyBTreeSeq[K]: ...
+    def __getstate__(self) -> tp.Any: ...
+    def __setstate__(self, state: tp.Any) -> None: ...
+    def __reduce__(self) -> tuple[tp.Any, ...]: ...
+
+class PyBTreeMultiMap(tp.Generic[K, V]):
+
+    # pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self>
+    def __init__(
+        self,
+        input: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]], None] = None,
+    ) -> None: ...
+    # pub fn add(slf: PyRefMut<'_, Self>, key: PyObject, value: PyObject) -> PyResult<()>
+    def add(self, key: K, value: V) -> None: ...
+    # pub fn get_all<'py>(slf: PyRef<'py, Self>, key: PyObject) -> PyResult<&'py PyList>
+    def get_all(self, key: K) -> list[V]: ...
+    # pub fn count(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<usize>
+    def count(self, key: K) -> int: ...
+    # pub fn remove_one(slf: PyRefMut<'_, Self>, key: PyObject, value: PyObject) -> PyResult<bool>
+    def remove_one(self, key: K, value: V) -> bool: ...
+    # pub fn remove<'py>(slf: PyRefMut<'py, Self>, key: PyObject) -> PyResult<&'py PyList>
+    def remove(self, key: K) -> list[V]: ...
+    # pub fn contains_key(slf: PyRef<'_, Self>, key: PyObject) -> PyResult<bool>
+    def contains_key(self, key: object) -> bool: ...
+    # pub fn len(&self) -> usize
+    def len(self) -> int: ...
+    # pub fn is_empty(&self) -> bool
+    def is_empty(self) -> bool: ...
+    # pub fn clear(&mut self)
+    def clear(self) -> None: ...
+    # pub fn keys(slf: PyRef<'_, Self>) -> PyBTreeMultiMapKeys
+    def keys(self) -> tp.Iterator[K]: ...
+    # pub fn items(slf: PyRef<'_, Self>) -> PyBTreeMultiMapIter
+    def items(self) -> tp.Iterator[tuple[K, V]]: ...
+    # pub fn range(slf: PyRef<'_, Self>, start: Option<PyObject>, stop: Option<PyObject>) -> PyResult<PyBTreeMultiMapIter>
+    def range(
+        self, start: tp.Optional[K] = None, stop: tp.Optional[K] = None
+    ) -> tp.Iterator[tuple[K, V]]: ...
+    def copy(self) -> PyBTreeMultiMap[K, V]: ...
+    def __copy__(self) -> PyBTreeMultiMap[K, V]: ...
+    def __deepcopy__(self, memo: dict[int, tp.Any]) -> PyBTreeMultiMap[K, V]: ...
+    def __getstate__(self) -> tp.Any: ...
+    def __setstate__(self, state: tp.Any) -> None: ...
+    def __reduce__(self) -> tuple[tp.Any, ...]: ...
+
+class PyIntervalTree(tp.Generic[K, V]):
+
+    # pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self>
+    def __init__(self, input: tp.Optional[tp.Iterable[tuple[K, K, V]]] = None) -> None: ...
+    # pub fn add(slf: PyRefMut<'_, Self>, start: PyObject, end: PyObject, value: PyObject) -> PyResult<()>
+    def add(self, start: K, end: K, value: V) -> None: ...
+    # pub fn remove(slf: PyRefMut<'_, Self>, start: PyObject, end: PyObject) -> PyResult<Option<PyObject>>
+    def remove(self, start: K, end: K) -> tp.Optional[V]: ...
+    # pub fn overlap<'py>(slf: PyRef<'py, Self>, start: PyObject, end: PyObject) -> PyResult<&'py PyList>
+    def overlap(self, start: K, end: K) -> list[tuple[K, K, V]]: ...
+    # pub fn at<'py>(slf: PyRef<'py, Self>, point: PyObject) -> PyResult<&'py PyList>
+    def at(self, point: K) -> list[tuple[K, K, V]]: ...
+    # pub fn len(&self) -> usize
+    def len(self) -> int: ...
+    # pub fn is_empty(&self) -> bool
+    def is_empty(self) -> bool: ...
+    # pub fn clear(&mut self)
+    def clear(self) -> None: ...
+    # pub fn iter<'py>(&self, py: Python<'py>) -> PyResult<&'py PyIterator>
+    def iter(self) -> tp.Iterator[tuple[K, K, V]]: ...
+    def copy(self) -> PyIntervalTree[K, V]: ...
+    def __copy__(self) -> PyIntervalTree[K, V]: ...
+    def __deepcopy__(self, memo: dict[int, tp.Any]) -> PyIntervalTree[K, V]: ...
+    def __getstate__(self) -> tp.Any: ...
+    def __setstate__(self, state: tp.Any) -> None: ...
+    def __reduce__(self) -> tuple[tp.Any, ...]: ...
+
+class PyRangeMap(tp.Generic[K, V]):
+
+    # pub fn new(input: Option<PyObject>, py: Python) -> PyResult<Self>
+    def __init__(self, input: tp.Optional[tp.Iterable[tuple[K, K, V]]] = None) -> None: ...
+    # pub fn set_range(slf: PyRefMut<'_, Self>, start: PyObject, end: PyObject, value: PyObject) -> PyResult<()>
+    def set_range(self, start: K, end: K, value: V) -> None: ...
+    # pub fn remove_range(slf: PyRefMut<'_, Self>, start: PyObject, end: PyObject) -> PyResult<()>
+    def remove_range(self, start: K, end: K) -> None: ...
+    # pub fn get(slf: PyRef<'_, Self>, point: PyObject) -> PyResult<Option<PyObject>>
+    def get(self, point: K) -> tp.Optional[V]: ...
+    # pub fn __getitem__(slf: PyRef<'_, Self>, point: PyObject) -> PyResult<PyObject>
+    def __getitem__(self, point: K) -> V: ...
+    # pub fn gaps<'py>(slf: PyRef<'py, Self>, start: PyObject, end: PyObject) -> PyResult<&'py PyList>
+    def gaps(self, start: K, end: K) -> list[tuple[K, K]]: ...
+    # pub fn len(&self) -> usize
+    def len(self) -> int: ...
+    # pub fn is_empty(&self) -> bool
+    def is_empty(self) -> bool: ...
+    # pub fn clear(&mut self)
+    def clear(self) -> None: ...
+    # pub fn iter(slf: PyRef<'_, Self>) -> PyRangeMapIter
+    def iter(self) -> tp.Iterator[tuple[K, K, V]]: ...
+    def copy(self) -> PyRangeMap[K, V]: ...
+    def __copy__(self) -> PyRangeMap[K, V]: ...
+    def __deepcopy__(self, memo: dict[int, tp.Any]) -> PyRangeMap[K, V]: ...
+    def __getstate__(self) -> tp.Any: ...
+    def __setstate__(self, state: tp.Any) -> None: ...
+    def __reduce__(self) -> tuple[tp.Any, ...]: ...
@@ -1,10 +1,21 @@
-from tree_collections.tree_collections import PyBTreeMap
+from tree_collections.tree_collections import (
+    PyBTreeMap,
+    PyBTreeMapSnapshot,
+    PyDiskBTreeMap,
+    PyFloatBTreeMap,
+    PyFrozenBTreeMap,
+    PyIntBTreeMap,
+    PyStrBTreeMap,
+)
+import os
 import typing as tp
 
 K = tp.TypeVar("K")
 V = tp.TypeVar("V")
 T = tp.TypeVar("T")
 
+Aggregate = tp.Literal["sum", "min", "max", "count"]
+
 
 class SupportsKeysAndGetItem(tp.Protocol, tp.Generic[K, V]):
 
@@ -21,6 +32,17 @@ class Missing:
 
 MISSING = Missing()
 
+# maps with keys of a single type stored natively, see `TreeDict(key_type=...)`
+TYPED_MAPS = {int: PyIntBTreeMap, float: PyFloatBTreeMap, str: PyStrBTreeMap}
+
+
+def typed_map(key_type: type, aggregate: tp.Optional[Aggregate]):
+  if key_type not in TYPED_MAPS:
+    raise ValueError(f"key_type must be int, float or str, got {key_type!r}")
+  if aggregate is not None:
+    raise ValueError("aggregate is not supported together with key_type")
+  return TYPED_MAPS[key_type]
+
 
 class TreeDict(tp.MutableMapping[K, V]):
   if tp.TYPE_CHECKING:
@@ -29,11 +51,35 @@ class TreeDict(tp.MutableMapping[K, V]):
   def __init__(
       self,
       other: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]], None] = None,
+      aggregate: tp.Optional[Aggregate] = None,
+      key_type: tp.Optional[type] = None,
   ):
-    if other is not None:
-      self._tree = PyBTreeMap(other)
+    if key_type is None:
+      self._tree = PyBTreeMap(other, aggregate)
     else:
-      self._tree = PyBTreeMap()
+      self._tree = typed_map(key_type, aggregate)(other)
+
+  @classmethod
+  def _from_tree(cls, tree: "PyBTreeMap[K, V]") -> "TreeDict[K, V]":
+    output = cls.__new__(cls)
+    output._tree = tree
+    return output
+
+  @classmethod
+  def from_sorted(
+      cls,
+      other: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]]],
+      validate: bool = True,
+      aggregate: tp.Optional[Aggregate] = None,
+      key_type: tp.Optional[type] = None,
+  ) -> "TreeDict[K, V]":
+    if key_type is None:
+      return cls._from_tree(PyBTreeMap.from_sorted(other, validate, aggregate))
+    return cls._from_tree(typed_map(key_type, aggregate).from_sorted(other, validate))
+
+  @property
+  def aggregate(self) -> tp.Optional[Aggregate]:
+    return getattr(self._tree, "aggregate", None)
 
   def __getitem__(self, key: K) -> V:
     value = self._tree.get(key)
@@ -128,11 +174,348 @@ class TreeDict(tp.MutableMapping[K, V]):
   def values(self) -> tp.ValuesView[V]:
     return self._tree.values()
 
+  def keys_list(self) -> tp.List[K]:
+    return self._tree.keys_list()
+
+  def values_list(self) -> tp.List[V]:
+    return self._tree.values_list()
+
+  def items_list(self) -> tp.List[tp.Tuple[K, V]]:
+    return self._tree.items_list()
+
+  def to_dict(self) -> tp.Dict[K, V]:
+    return self._tree.to_dict()
+
   def nth(self, n: int) -> tuple[K, V]:
     output = self._tree.nth(n)
     if output is None:
       raise IndexError
     return output
 
+  def insert_many(
+      self, items: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]]]
+  ) -> tp.List[tp.Optional[V]]:
+    return self._tree.insert_many(items)
+
+  def update_many(
+      self, items: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]]]
+  ) -> None:
+    self._tree.update_many(items)
+
+  def get_many(
+      self, keys: tp.Iterable[K], default: tp.Optional[T] = None
+  ) -> tp.List[tp.Union[V, T, None]]:
+    return self._tree.get_many(keys, default)
+
+  def contains_many(self, keys: tp.Iterable[K]) -> tp.List[bool]:
+    return self._tree.contains_many(keys)
+
+  def remove_many(self, keys: tp.Iterable[K]) -> tp.List[tp.Optional[V]]:
+    return self._tree.remove_many(keys)
+
+  def copy(self) -> "TreeDict[K, V]":
+    return TreeDict._from_tree(self._tree.copy())
+
+  def __copy__(self) -> "TreeDict[K, V]":
+    return self.copy()
+
+  def __deepcopy__(self, memo: tp.Dict[int, tp.Any]) -> "TreeDict[K, V]":
+    return TreeDict._from_tree(self._tree.__deepcopy__(memo))
+
+  def to_bytes(self) -> bytes:
+    return self._tree.to_bytes()
+
+  @classmethod
+  def from_bytes(
+      cls, data: bytes, aggregate: tp.Optional[Aggregate] = None
+  ) -> "TreeDict[K, V]":
+    return cls._from_tree(PyBTreeMap.from_bytes(data, aggregate))
+
+  def save(self, path: tp.Union[str, "os.PathLike[str]"]) -> None:
+    self._tree.save(path)
+
+  @classmethod
+  def load(
+      cls,
+      path: tp.Union[str, "os.PathLike[str]"],
+      aggregate: tp.Optional[Aggregate] = None,
+  ) -> "TreeDict[K, V]":
+    return cls._from_tree(PyBTreeMap.load(path, aggregate))
+
+  @classmethod
+  def open(
+      cls, path: tp.Union[str, "os.PathLike[str]"], cache_size: int = 1024
+  ) -> "DiskTreeDict[K, V]":
+    """Opens a dict stored in the page file at `path`, creating it if missing.
+
+    Keys and values must be ints, floats, strs, tuples, lists or None. Updates
+    are durable once `flush()` or `close()` returns.
+    """
+    return DiskTreeDict(PyBTreeMap.open(path, cache_size))
+
+  @classmethod
+  def recover(
+      cls,
+      log_path: tp.Union[str, "os.PathLike[str]"],
+      sync: tp.Union[str, float] = "always",
+      compact_every: tp.Optional[int] = None,
+      aggregate: tp.Optional[Aggregate] = None,
+  ) -> "TreeDict[K, V]":
+    """Rebuilds a dict from the log at `log_path`, creating it if missing.
+
+    Every later insert, removal and clear is appended to the log, and keys and
+    values must be ints, floats, strs, tuples, lists or None. `sync` is
+    "always", "never" or the seconds between fsyncs. After `compact_every`
+    records the dict is saved to `<log_path>.snapshot` and the log is emptied.
+    """
+    return cls._from_tree(
+        PyBTreeMap.recover(log_path, sync, compact_every, aggregate)
+    )
+
+  def compact_log(self) -> None:
+    self._tree.compact_log()
+
+  def sync_log(self) -> None:
+    self._tree.sync_log()
+
+  def close_log(self) -> None:
+    self._tree.close_log()
+
+  def to_json(self) -> str:
+    return self._tree.to_json()
+
+  @classmethod
+  def from_json(
+      cls, data: str, aggregate: tp.Optional[Aggregate] = None
+  ) -> "TreeDict[K, V]":
+    return cls._from_tree(PyBTreeMap.from_json(data, aggregate))
+
+  def save_json(self, path: tp.Union[str, "os.PathLike[str]"]) -> None:
+    self._tree.save_json(path)
+
+  @classmethod
+  def load_json(
+      cls,
+      path: tp.Union[str, "os.PathLike[str]"],
+      aggregate: tp.Optional[Aggregate] = None,
+  ) -> "TreeDict[K, V]":
+    return cls._from_tree(PyBTreeMap.load_json(path, aggregate))
+
+  def freeze(self) -> "FrozenTreeDict[K, V]":
+    return FrozenTreeDict._from_tree(self._tree.freeze())
+
+  def snapshot(self) -> "TreeDictSnapshot[K, V]":
+    """Returns a read-only view that later writes to this dict don't affect.
+
+    Taking a snapshot is O(1), the snapshot shares the dict's nodes.
+    """
+    return TreeDictSnapshot(self._tree.snapshot())
+
+  def range_aggregate(
+      self, start: tp.Optional[K] = None, stop: tp.Optional[K] = None
+  ) -> tp.Union[int, float, None]:
+    """Aggregates the values whose keys are in `[start, stop)`."""
+    return self._tree.range_aggregate(start, stop)
+
+  def prefix_aggregate(self, key: K) -> tp.Union[int, float, None]:
+    """Aggregates the values whose keys are less than `key`."""
+    return self._tree.prefix_aggregate(key)
+
+  def split_off(self, key: K) -> "TreeDict[K, V]":
+    return TreeDict._from_tree(self._tree.split_off(key))
+
+  def append(self, other: "TreeDict[K, V]") -> None:
+    self._tree.append(other._tree)
+
+  def merge(
+      self,
+      other: "TreeDict[K, V]",
+      on_conflict: tp.Union[
+          tp.Literal["replace", "keep"], tp.Callable[[K, V, V], V]
+      ] = "replace",
+  ) -> None:
+    self._tree.merge(other._tree, on_conflict)
+
   def __eq__(self, __other: object) -> bool:
     return self._tree == __other
+
+
+class DiskTreeDict(tp.MutableMapping[K, V]):
+  if tp.TYPE_CHECKING:
+    _tree: PyDiskBTreeMap[K, V]
+
+  def __init__(self, tree: "PyDiskBTreeMap[K, V]"):
+    self._tree = tree
+
+  def __getitem__(self, key: K) -> V:
+    return self._tree[key]
+
+  def __setitem__(self, key: K, value: V) -> None:
+    self._tree.insert(key, value)
+
+  def __delitem__(self, key: K) -> None:
+    if self._tree.remove(key) is None:
+      raise KeyError(key)
+
+  def __iter__(self) -> tp.Iterator[K]:
+    return (key for key, _ in self._tree.range())
+
+  def __len__(self) -> int:
+    return self._tree.len()
+
+  def __contains__(self, key: object) -> bool:
+    return self._tree.contains_key(key)
+
+  def __enter__(self) -> "DiskTreeDict[K, V]":
+    return self
+
+  def __exit__(self, *args: object) -> None:
+    self.close()
+
+  def range(
+      self, start: tp.Optional[K] = None, stop: tp.Optional[K] = None
+  ) -> tp.Iterator[tp.Tuple[K, V]]:
+    return self._tree.range(start, stop)
+
+  def flush(self) -> None:
+    self._tree.flush()
+
+  def close(self) -> None:
+    self._tree.close()
+
+  @property
+  def closed(self) -> bool:
+    return self._tree.closed
+
+
+class TreeDictSnapshot(tp.Mapping[K, V]):
+  if tp.TYPE_CHECKING:
+    _tree: PyBTreeMapSnapshot[K, V]
+
+  def __init__(self, tree: "PyBTreeMapSnapshot[K, V]"):
+    self._tree = tree
+
+  def __getitem__(self, key: K) -> V:
+    return self._tree[key]
+
+  def __iter__(self) -> tp.Iterator[K]:
+    return iter(self._tree.keys())
+
+  def __len__(self) -> int:
+    return self._tree.len()
+
+  def __contains__(self, key: object) -> bool:
+    return self._tree.contains_key(key)
+
+  def copy(self) -> "TreeDictSnapshot[K, V]":
+    return self
+
+  def __copy__(self) -> "TreeDictSnapshot[K, V]":
+    return self
+
+  def __deepcopy__(self, memo: tp.Dict[int, tp.Any]) -> "TreeDictSnapshot[K, V]":
+    return TreeDictSnapshot(self._tree.__deepcopy__(memo))
+
+  def keys_list(self) -> tp.List[K]:
+    return self._tree.keys_list()
+
+  def values_list(self) -> tp.List[V]:
+    return self._tree.values_list()
+
+  def items_list(self) -> tp.List[tp.Tuple[K, V]]:
+    return self._tree.items_list()
+
+  def to_dict(self) -> tp.Dict[K, V]:
+    return self._tree.to_dict()
+
+
+class FrozenTreeDict(tp.Mapping[K, V], tp.Hashable):
+  if tp.TYPE_CHECKING:
+    _tree: PyFrozenBTreeMap[K, V]
+
+  def __init__(
+      self,
+      other: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]], None] = None,
+  ):
+    self._tree = PyFrozenBTreeMap(other)
+
+  @classmethod
+  def _from_tree(cls, tree: "PyFrozenBTreeMap[K, V]") -> "FrozenTreeDict[K, V]":
+    output = cls.__new__(cls)
+    output._tree = tree
+    return output
+
+  @classmethod
+  def from_sorted(
+      cls,
+      other: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]]],
+      validate: bool = True,
+  ) -> "FrozenTreeDict[K, V]":
+    return cls._from_tree(PyFrozenBTreeMap.from_sorted(other, validate))
+
+  def copy(self) -> "FrozenTreeDict[K, V]":
+    return self
+
+  def __copy__(self) -> "FrozenTreeDict[K, V]":
+    return self.copy()
+
+  def __deepcopy__(self, memo: tp.Dict[int, tp.Any]) -> "FrozenTreeDict[K, V]":
+    return FrozenTreeDict._from_tree(self._tree.__deepcopy__(memo))
+
+  def thaw(self) -> TreeDict[K, V]:
+    return TreeDict._from_tree(self._tree.thaw())
+
+  def __getitem__(self, key: K) -> V:
+    return self._tree[key]
+
+  def __iter__(self) -> tp.Iterator[K]:
+    return iter(self._tree.keys())
+
+  def __len__(self) -> int:
+    return self._tree.len()
+
+  def __contains__(self, key: object) -> bool:
+    return self._tree.contains_key(key)
+
+  def __hash__(self) -> int:
+    return hash(self._tree)
+
+  def __eq__(self, other: object) -> bool:
+    if not isinstance(other, FrozenTreeDict):
+      return NotImplemented
+    return self._tree == other._tree
+
+  def items(self) -> tp.ItemsView[K, V]:
+    return self._tree.items()
+
+  def keys(self) -> tp.KeysView[K]:
+    return self._tree.keys()
+
+  def values(self) -> tp.ValuesView[V]:
+    return self._tree.values()
+
+  def keys_list(self) -> tp.List[K]:
+    return self._tree.keys_list()
+
+  def values_list(self) -> tp.List[V]:
+    return self._tree.values_list()
+
+  def items_list(self) -> tp.List[tp.Tuple[K, V]]:
+    return self._tree.items_list()
+
+  def to_dict(self) -> tp.Dict[K, V]:
+    return self._tree.to_dict()
+
+  def nth(self, n: int) -> tuple[K, V]:
+    output = self._tree.nth(n)
+    if output is None:
+      raise IndexError
+    return output
+
+  def get_many(
+      self, keys: tp.Iterable[K], default: tp.Optional[T] = None
+  ) -> tp.List[tp.Union[V, T, None]]:
+    return self._tree.get_many(keys, default)
+
+  def contains_many(self, keys: tp.Iterable[K]) -> tp.List[bool]:
+    return self._tree.contains_many(keys)
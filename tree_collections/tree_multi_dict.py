@@ -0,0 +1,83 @@
+from tree_collections.tree_collections import PyBTreeMultiMap
+import typing as tp
+
+K = tp.TypeVar("K")
+V = tp.TypeVar("V")
+
+
+class TreeMultiDict(tp.Generic[K, V]):
+  if tp.TYPE_CHECKING:
+    _tree: PyBTreeMultiMap[K, V]
+
+  def __init__(
+      self,
+      other: tp.Union[tp.Mapping[K, V], tp.Iterable[tp.Tuple[K, V]], None] = None,
+  ):
+    if other is not None:
+      self._tree = PyBTreeMultiMap(other)
+    else:
+      self._tree = PyBTreeMultiMap()
+
+  @classmethod
+  def _from_tree(cls, tree: "PyBTreeMultiMap[K, V]") -> "TreeMultiDict[K, V]":
+    output = cls.__new__(cls)
+    output._tree = tree
+    return output
+
+  def copy(self) -> "TreeMultiDict[K, V]":
+    return TreeMultiDict._from_tree(self._tree.copy())
+
+  def __copy__(self) -> "TreeMultiDict[K, V]":
+    return self.copy()
+
+  def __deepcopy__(self, memo: tp.Dict[int, tp.Any]) -> "TreeMultiDict[K, V]":
+    return TreeMultiDict._from_tree(self._tree.__deepcopy__(memo))
+
+  def __getitem__(self, key: K) -> tp.List[V]:
+    values = self._tree.get_all(key)
+    if not values:
+      raise KeyError(key)
+    return values
+
+  def __delitem__(self, key: K) -> None:
+    values = self._tree.remove(key)
+    if not values:
+      raise KeyError(key)
+
+  def __iter__(self) -> tp.Iterator[K]:
+    return iter(self._tree.keys())
+
+  def __len__(self) -> int:
+    return self._tree.len()
+
+  def __contains__(self, key: object) -> bool:
+    return self._tree.contains_key(key)
+
+  def add(self, key: K, value: V) -> None:
+    self._tree.add(key, value)
+
+  def get_all(self, key: K) -> tp.List[V]:
+    return self._tree.get_all(key)
+
+  def remove_one(self, key: K, value: V) -> bool:
+    return self._tree.remove_one(key, value)
+
+  def count(self, key: K) -> int:
+    return self._tree.count(key)
+
+  def clear(self) -> None:
+    self._tree.clear()
+
+  def keys(self) -> tp.Iterator[K]:
+    return self._tree.keys()
+
+  def values(self) -> tp.Iterator[V]:
+    return (value for _, value in self._tree.items())
+
+  def items(self) -> tp.Iterator[tp.Tuple[K, V]]:
+    return self._tree.items()
+
+  def range(
+      self, start: tp.Optional[K] = None, stop: tp.Optional[K] = None
+  ) -> tp.Iterator[tp.Tuple[K, V]]:
+    return self._tree.range(start, stop)
@@ -1,4 +1,5 @@
 from tree_collections.tree_collections import PyBTreeSeq
+import os
 import typing as tp
 
 K = tp.TypeVar("K")
@@ -18,6 +19,18 @@ class TreeSeq(tp.Generic[K]):
         else:
             self._tree = PyBTreeSeq()
 
+    @classmethod
+    def _from_tree(cls, tree: "PyBTreeSeq[K]") -> "TreeSeq[K]":
+        output = cls.__new__(cls)
+        output._tree = tree
+        return output
+
+    @classmethod
+    def from_sorted(
+        cls, __input: tp.Iterable[K], /, validate: bool = True
+    ) -> "TreeSeq[K]":
+        return cls._from_tree(PyBTreeSeq.from_sorted(__input, validate))
+
     def __iter__(self) -> tp.Iterator[K]:
         return iter(self._tree.iter())
 
@@ -26,3 +39,78 @@ class TreeSeq(tp.Generic[K]):
         if output is None:
             raise IndexError(idx)
         return output
+
+    def to_list(self) -> tp.List[K]:
+        return self._tree.to_list()
+
+    def chunks(self, n: int) -> tp.Iterator[tp.List[K]]:
+        return self._tree.iter().chunks(n)
+
+    def insert_many(self, values: tp.Iterable[K]) -> tp.List[bool]:
+        return self._tree.insert_many(values)
+
+    def update_many(self, values: tp.Iterable[K]) -> None:
+        self._tree.update_many(values)
+
+    def get_many(
+        self, values: tp.Iterable[K], default: tp.Optional[B] = None
+    ) -> tp.List[tp.Union[K, B, None]]:
+        return self._tree.get_many(values, default)
+
+    def contains_many(self, values: tp.Iterable[K]) -> tp.List[bool]:
+        return self._tree.contains_many(values)
+
+    def remove_many(self, values: tp.Iterable[K]) -> tp.List[bool]:
+        return self._tree.remove_many(values)
+
+    def copy(self) -> "TreeSeq[K]":
+        return TreeSeq._from_tree(self._tree.copy())
+
+    def __copy__(self) -> "TreeSeq[K]":
+        return self.copy()
+
+    def __deepcopy__(self, memo: tp.Dict[int, tp.Any]) -> "TreeSeq[K]":
+        return TreeSeq._from_tree(self._tree.__deepcopy__(memo))
+
+    def to_bytes(self) -> bytes:
+        return self._tree.to_bytes()
+
+    @classmethod
+    def from_bytes(cls, data: bytes) -> "TreeSeq[K]":
+        return cls._from_tree(PyBTreeSeq.from_bytes(data))
+
+    def save(self, path: tp.Union[str, "os.PathLike[str]"]) -> None:
+        self._tree.save(path)
+
+    @classmethod
+    def load(cls, path: tp.Union[str, "os.PathLike[str]"]) -> "TreeSeq[K]":
+        return cls._from_tree(PyBTreeSeq.load(path))
+
+    def to_json(self) -> str:
+        return self._tree.to_json()
+
+    @classmethod
+    def from_json(cls, data: str) -> "TreeSeq[K]":
+        return cls._from_tree(PyBTreeSeq.from_json(data))
+
+    def save_json(self, path: tp.Union[str, "os.PathLike[str]"]) -> None:
+        self._tree.save_json(path)
+
+    @classmethod
+    def load_json(cls, path: tp.Union[str, "os.PathLike[str]"]) -> "TreeSeq[K]":
+        return cls._from_tree(PyBTreeSeq.load_json(path))
+
+    def split_off(self, value: K) -> "TreeSeq[K]":
+        return TreeSeq._from_tree(self._tree.split_off(value))
+
+    def append(self, other: "TreeSeq[K]") -> None:
+        self._tree.append(other._tree)
+
+    def merge(
+        self,
+        other: "TreeSeq[K]",
+        on_conflict: tp.Union[
+            tp.Literal["replace", "keep"], tp.Callable[[K, K], K]
+        ] = "replace",
+    ) -> None:
+        self._tree.merge(other._tree, on_conflict)
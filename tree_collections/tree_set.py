@@ -1,4 +1,5 @@
-from tree_collections.tree_collections import PyBTreeSet
+from tree_collections.tree_collections import PyBTreeSet, PyFrozenBTreeSet
+import os
 import typing as tp
 
 K = tp.TypeVar("K")
@@ -16,6 +17,18 @@ class TreeSet(tp.MutableSet[K]):
         else:
             self._tree = PyBTreeSet()
 
+    @classmethod
+    def _from_tree(cls, tree: "PyBTreeSet[K]") -> "TreeSet[K]":
+        output = cls.__new__(cls)
+        output._tree = tree
+        return output
+
+    @classmethod
+    def from_sorted(
+        cls, __input: tp.Iterable[K], /, validate: bool = True
+    ) -> "TreeSet[K]":
+        return cls._from_tree(PyBTreeSet.from_sorted(__input, validate))
+
     def __iter__(self) -> tp.Iterator[K]:
         return iter(self._tree.iter())
 
@@ -25,6 +38,84 @@ class TreeSet(tp.MutableSet[K]):
     def discard(self, value: K) -> None:
         self._tree.remove(value)
 
+    def to_list(self) -> tp.List[K]:
+        return self._tree.to_list()
+
+    def chunks(self, n: int) -> tp.Iterator[tp.List[K]]:
+        return self._tree.iter().chunks(n)
+
+    def insert_many(self, values: tp.Iterable[K]) -> tp.List[bool]:
+        return self._tree.insert_many(values)
+
+    def update_many(self, values: tp.Iterable[K]) -> None:
+        self._tree.update_many(values)
+
+    def get_many(
+        self, values: tp.Iterable[K], default: tp.Optional[B] = None
+    ) -> tp.List[tp.Union[K, B, None]]:
+        return self._tree.get_many(values, default)
+
+    def contains_many(self, values: tp.Iterable[K]) -> tp.List[bool]:
+        return self._tree.contains_many(values)
+
+    def remove_many(self, values: tp.Iterable[K]) -> tp.List[bool]:
+        return self._tree.remove_many(values)
+
+    def copy(self) -> "TreeSet[K]":
+        return TreeSet._from_tree(self._tree.copy())
+
+    def __copy__(self) -> "TreeSet[K]":
+        return self.copy()
+
+    def __deepcopy__(self, memo: tp.Dict[int, tp.Any]) -> "TreeSet[K]":
+        return TreeSet._from_tree(self._tree.__deepcopy__(memo))
+
+    def to_bytes(self) -> bytes:
+        return self._tree.to_bytes()
+
+    @classmethod
+    def from_bytes(cls, data: bytes) -> "TreeSet[K]":
+        return cls._from_tree(PyBTreeSet.from_bytes(data))
+
+    def save(self, path: tp.Union[str, "os.PathLike[str]"]) -> None:
+        self._tree.save(path)
+
+    @classmethod
+    def load(cls, path: tp.Union[str, "os.PathLike[str]"]) -> "TreeSet[K]":
+        return cls._from_tree(PyBTreeSet.load(path))
+
+    def to_json(self) -> str:
+        return self._tree.to_json()
+
+    @classmethod
+    def from_json(cls, data: str) -> "TreeSet[K]":
+        return cls._from_tree(PyBTreeSet.from_json(data))
+
+    def save_json(self, path: tp.Union[str, "os.PathLike[str]"]) -> None:
+        self._tree.save_json(path)
+
+    @classmethod
+    def load_json(cls, path: tp.Union[str, "os.PathLike[str]"]) -> "TreeSet[K]":
+        return cls._from_tree(PyBTreeSet.load_json(path))
+
+    def freeze(self) -> "FrozenTreeSet[K]":
+        return FrozenTreeSet._from_tree(self._tree.freeze())
+
+    def split_off(self, value: K) -> "TreeSet[K]":
+        return TreeSet._from_tree(self._tree.split_off(value))
+
+    def append(self, other: "TreeSet[K]") -> None:
+        self._tree.append(other._tree)
+
+    def merge(
+        self,
+        other: "TreeSet[K]",
+        on_conflict: tp.Union[
+            tp.Literal["replace", "keep"], tp.Callable[[K, K], K]
+        ] = "replace",
+    ) -> None:
+        self._tree.merge(other._tree, on_conflict)
+
     # Mixin methods
     def clear(self) -> None:
         self._tree.clear()
@@ -86,3 +177,72 @@ class TreeSet(tp.MutableSet[K]):
 
     def isdisjoint(self, other: tp.Iterable[tp.Any]) -> bool:
         raise NotImplementedError
+
+
+class FrozenTreeSet(tp.AbstractSet[K], tp.Hashable):
+    if tp.TYPE_CHECKING:
+        _tree: PyFrozenBTreeSet[K]
+
+    def __init__(self, __input: tp.Optional[tp.Iterable[K]] = None, /):
+        self._tree = PyFrozenBTreeSet(__input)
+
+    @classmethod
+    def _from_tree(cls, tree: "PyFrozenBTreeSet[K]") -> "FrozenTreeSet[K]":
+        output = cls.__new__(cls)
+        output._tree = tree
+        return output
+
+    @classmethod
+    def from_sorted(
+        cls, __input: tp.Iterable[K], /, validate: bool = True
+    ) -> "FrozenTreeSet[K]":
+        return cls._from_tree(PyFrozenBTreeSet.from_sorted(__input, validate))
+
+    def copy(self) -> "FrozenTreeSet[K]":
+        return self
+
+    def __copy__(self) -> "FrozenTreeSet[K]":
+        return self.copy()
+
+    def __deepcopy__(self, memo: tp.Dict[int, tp.Any]) -> "FrozenTreeSet[K]":
+        return FrozenTreeSet._from_tree(self._tree.__deepcopy__(memo))
+
+    def thaw(self) -> TreeSet[K]:
+        return TreeSet._from_tree(self._tree.thaw())
+
+    def __iter__(self) -> tp.Iterator[K]:
+        return iter(self._tree.iter())
+
+    def __len__(self) -> int:
+        return self._tree.len()
+
+    def __contains__(self, __x: object) -> bool:
+        return self._tree.contains(__x)
+
+    def __hash__(self) -> int:
+        return hash(self._tree)
+
+    def __eq__(self, other: object) -> bool:
+        if not isinstance(other, FrozenTreeSet):
+            return NotImplemented
+        return self._tree == other._tree
+
+    def to_list(self) -> tp.List[K]:
+        return self._tree.to_list()
+
+    def chunks(self, n: int) -> tp.Iterator[tp.List[K]]:
+        return self._tree.iter().chunks(n)
+
+    def nth(self, n: int) -> K:
+        output = self._tree.nth(n)
+        if output is None:
+            raise IndexError
+        return output
+
+    def get_many(
+        self, values: tp.Iterable[K], default: tp.Optional[B] = None
+    ) -> tp.List[tp.Union[K, B, None]]:
+        return self._tree.get_many(values, default)
+
+    def contains_many(self, values: tp.Iterable[K]) -> tp.List[bool]:
+        return self._tree.contains_many(values)